@@ -0,0 +1,6 @@
+use fil_actor_example::state::State;
+use fil_actor_example::Actor;
+
+fn main() {
+    fil_actors_runtime::test_utils::run::main::<Actor, State>(|_| {});
+}
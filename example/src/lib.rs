@@ -1,28 +1,21 @@
-mod state;
+pub mod state;
 
 use crate::state::{State, UserPersistParam};
-use fil_actors_runtime::runtime::{ActorCode, Runtime};
-use fil_actors_runtime::{
-    actor_dispatch, actor_error, restrict_internal_api, runtime, ActorDowncast, ActorError,
-    INIT_ACTOR_ADDR,
-};
-use fvm_shared::error::ExitCode;
-use fvm_shared::{MethodNum, METHOD_CONSTRUCTOR};
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
+use fil_actors_runtime::{runtime, INIT_ACTOR_ADDR};
+use fvm_utils::prelude::*;
 
 #[no_mangle]
 pub fn invoke(param: u32) -> u32 {
     runtime::fvm::trampoline::<Actor>(param)
 }
 
-/// SCA actor methods available
-#[derive(FromPrimitive)]
-#[repr(u64)]
-pub enum Method {
-    /// Constructor for Storage Power Actor
-    Constructor = METHOD_CONSTRUCTOR,
-    Persist = frc42_dispatch::method_hash!("Persist"),
+actor_methods! {
+    /// SCA actor methods available
+    pub enum Method {
+        /// Constructor for Storage Power Actor
+        Constructor = METHOD_CONSTRUCTOR,
+        Persist = frc42_dispatch::method_hash!("Persist"),
+    }
 }
 
 pub struct Actor;
@@ -76,6 +69,13 @@ mod test {
     use fvm_shared::address::Address;
     use fvm_shared::MethodNum;
 
+    #[allow(dead_code)]
+    mod wasm {
+        include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+    }
+
+    fil_actors_runtime::wasm_size_report!(wasm::WASM_BINARY, "wasm_size_budget.json");
+
     #[test]
     fn constructor_works() {
         let mut rt = new_runtime();
@@ -1,9 +1,4 @@
-use cid::Cid;
-use fvm_ipld_blockstore::Blockstore;
-use fvm_ipld_hamt::BytesKey;
-use fvm_shared::address::Address;
-use primitives::{TCid, THamt};
-use serde::{Deserialize, Serialize};
+use fvm_utils::prelude::*;
 
 /// Sample struct for user persistence
 #[derive(Serialize, Deserialize)]
@@ -19,7 +14,7 @@ pub struct User {
 }
 
 /// The state storage struct, persisted in BlockStore
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct State {
     pub call_count: usize,
     pub typed_hamt: TCid<THamt<Cid, User>>,
@@ -0,0 +1,185 @@
+//! Compares the storage footprint of the typed collections for different layouts.
+//!
+//! For every layout and collection size it inserts the same records, flushes,
+//! then reads them all back from a freshly loaded root, and reports the number of
+//! blockstore operations and bytes written as one JSON object per line, e.g.
+//!
+//! ```text
+//! {"layout":"hamt-5","entries":1000,"puts":..,"bytes_written":..,"gets":..,"millis":..}
+//! ```
+//!
+//! Run with `cargo bench -p primitives --bench layouts`. The sizes can be overridden
+//! with a comma separated `LAYOUT_BENCH_SIZES` environment variable.
+use std::cell::Cell;
+use std::time::Instant;
+
+use anyhow::Result;
+use cid::multihash::Code;
+use cid::Cid;
+use fil_actors_runtime::u64_key;
+use fvm_ipld_blockstore::{Block, Blockstore, MemoryBlockstore};
+use fvm_ipld_encoding::tuple::*;
+use primitives::{TAmt, TCid, THamt};
+
+const DEFAULT_SIZES: &[u64] = &[1_000, 10_000, 100_000, 1_000_000];
+
+/// A representative record, roughly the size of a balance table entry.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, PartialEq)]
+struct Record {
+    owner: u64,
+    amount: u64,
+    payload: Vec<u8>,
+}
+
+impl Record {
+    fn new(i: u64) -> Self {
+        Self {
+            owner: i,
+            amount: i * 1_000,
+            payload: i.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// Blockstore counting the operations performed against it.
+#[derive(Default)]
+struct CountingBlockstore {
+    inner: MemoryBlockstore,
+    gets: Cell<u64>,
+    puts: Cell<u64>,
+    bytes_written: Cell<u64>,
+}
+
+impl CountingBlockstore {
+    fn reset_counts(&self) {
+        self.gets.set(0);
+        self.puts.set(0);
+        self.bytes_written.set(0);
+    }
+}
+
+impl Blockstore for CountingBlockstore {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        self.gets.set(self.gets.get() + 1);
+        self.inner.get(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.puts.set(self.puts.get() + 1);
+        self.bytes_written
+            .set(self.bytes_written.get() + block.len() as u64);
+        self.inner.put_keyed(k, block)
+    }
+
+    fn put<D>(&self, mh_code: Code, block: &Block<D>) -> Result<Cid>
+    where
+        D: AsRef<[u8]>,
+    {
+        self.puts.set(self.puts.get() + 1);
+        self.bytes_written
+            .set(self.bytes_written.get() + block.data.as_ref().len() as u64);
+        self.inner.put(mh_code, block)
+    }
+}
+
+struct Measurement {
+    layout: &'static str,
+    entries: u64,
+    puts: u64,
+    bytes_written: u64,
+    gets: u64,
+    millis: u128,
+}
+
+impl Measurement {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"layout\":\"{}\",\"entries\":{},\"puts\":{},\"bytes_written\":{},\"gets\":{},\"millis\":{}}}",
+            self.layout, self.entries, self.puts, self.bytes_written, self.gets, self.millis
+        )
+    }
+}
+
+fn bench_hamt<const W: u32>(layout: &'static str, entries: u64) -> Result<Measurement> {
+    let store = CountingBlockstore::default();
+    let start = Instant::now();
+
+    let mut root: TCid<THamt<u64, Record, W>> = TCid::new_hamt(&store)?;
+    root.update(&store, |map| {
+        for i in 0..entries {
+            map.set(u64_key(i), Record::new(i))?;
+        }
+        Ok(())
+    })?;
+    let (puts, bytes_written) = (store.puts.get(), store.bytes_written.get());
+
+    store.reset_counts();
+    let map = root.load(&store)?;
+    for i in 0..entries {
+        map.get(&u64_key(i))?;
+    }
+
+    Ok(Measurement {
+        layout,
+        entries,
+        puts,
+        bytes_written,
+        gets: store.gets.get(),
+        millis: start.elapsed().as_millis(),
+    })
+}
+
+fn bench_amt<const W: u32>(layout: &'static str, entries: u64) -> Result<Measurement> {
+    let store = CountingBlockstore::default();
+    let start = Instant::now();
+
+    let mut root: TCid<TAmt<Record, W>> = TCid::new_amt(&store)?;
+    root.update(&store, |arr| {
+        for i in 0..entries {
+            arr.set(i, Record::new(i))?;
+        }
+        Ok(())
+    })?;
+    let (puts, bytes_written) = (store.puts.get(), store.bytes_written.get());
+
+    store.reset_counts();
+    let arr = root.load(&store)?;
+    for i in 0..entries {
+        arr.get(i)?;
+    }
+
+    Ok(Measurement {
+        layout,
+        entries,
+        puts,
+        bytes_written,
+        gets: store.gets.get(),
+        millis: start.elapsed().as_millis(),
+    })
+}
+
+fn sizes() -> Vec<u64> {
+    match std::env::var("LAYOUT_BENCH_SIZES") {
+        Ok(sizes) => sizes
+            .split(',')
+            .map(|s| s.trim().parse().expect("invalid LAYOUT_BENCH_SIZES"))
+            .collect(),
+        Err(_) => DEFAULT_SIZES.to_vec(),
+    }
+}
+
+fn main() -> Result<()> {
+    for entries in sizes() {
+        let measurements = [
+            bench_hamt::<3>("hamt-3", entries)?,
+            bench_hamt::<5>("hamt-5", entries)?,
+            bench_hamt::<8>("hamt-8", entries)?,
+            bench_amt::<3>("amt-3", entries)?,
+            bench_amt::<5>("amt-5", entries)?,
+        ];
+        for m in measurements {
+            println!("{}", m.to_json());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+/// Items used by the code generated by `ipc_actor_state!`, so that crates using
+/// it don't need to depend on them directly.
+#[doc(hidden)]
+pub mod macro_support {
+    pub use anyhow::Result;
+    pub use fil_actors_runtime::fvm_ipld_amt::Amt;
+    pub use fil_actors_runtime::MessageAccumulator;
+    pub use fvm_ipld_blockstore::Blockstore;
+    pub use fvm_ipld_hamt::Hamt;
+}
+
+/// Declare the state of an actor following our conventions: plain fields first, then the
+/// HAMTs and the AMTs as typed CIDs, serialized as a tuple in that order.
+///
+/// Besides the struct, it generates:
+/// * `new`, taking the plain fields and creating the collections empty;
+/// * an accessor per collection, named after it, loading it for reading; it's modified
+///   through the field itself, e.g. with `update`;
+/// * `check_collections`, adding a message to a `MessageAccumulator` for every collection
+///   that can't be loaded, as a start for the state invariants of the actor.
+///
+/// The tuple serialization derives have to be in scope, i.e. `use fvm_ipld_encoding::tuple::*;`.
+///
+/// # Example
+/// ```
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_ipld_encoding::tuple::*;
+/// use fvm_ipld_hamt::BytesKey;
+/// use fvm_shared::address::Address;
+/// use primitives::ipc_actor_state;
+///
+/// ipc_actor_state! {
+///     /// The state of the subnet actor.
+///     #[derive(Debug, Clone)]
+///     pub struct State {
+///         pub name: String,
+///     }
+///     hamts {
+///         pub stakes: Address => u64,
+///     }
+///     amts {
+///         pub checkpoints: Vec<u8>,
+///     }
+/// }
+///
+/// let store = MemoryBlockstore::new();
+/// let mut st = State::new(&store, "subnet".to_string()).unwrap();
+/// st.stakes
+///     .update(&store, |stakes| {
+///         stakes.set(BytesKey::from(Address::new_id(100).to_bytes()), 10)?;
+///         Ok(())
+///     })
+///     .unwrap();
+///
+/// let key = BytesKey::from(Address::new_id(100).to_bytes());
+/// assert_eq!(st.stakes(&store).unwrap().get(&key).unwrap(), Some(&10));
+/// ```
+#[macro_export]
+macro_rules! ipc_actor_state {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident : $ty:ty),* $(,)?
+        }
+        $(hamts {
+            $($(#[$hmeta:meta])* $hvis:vis $hamt:ident : $key:ty => $value:ty),* $(,)?
+        })?
+        $(amts {
+            $($(#[$ameta:meta])* $avis:vis $amt:ident : $item:ty),* $(,)?
+        })?
+    ) => {
+        $(#[$meta])*
+        #[derive(Serialize_tuple, Deserialize_tuple)]
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty,)*
+            $($($(#[$hmeta])* $hvis $hamt: $crate::TCid<$crate::THamt<$key, $value>>,)*)?
+            $($($(#[$ameta])* $avis $amt: $crate::TCid<$crate::TAmt<$item>>,)*)?
+        }
+
+        impl $name {
+            /// Create the state from its plain fields, with empty collections.
+            #[allow(clippy::too_many_arguments)]
+            pub fn new<S: $crate::macro_support::Blockstore>(
+                store: &S,
+                $($field: $ty),*
+            ) -> $crate::macro_support::Result<Self> {
+                let _ = store;
+                Ok(Self {
+                    $($field,)*
+                    $($($hamt: $crate::TCid::new_hamt(store)?,)*)?
+                    $($($amt: $crate::TCid::new_amt(store)?,)*)?
+                })
+            }
+
+            $($(
+                #[doc = concat!("Load the `", stringify!($hamt), "` map for reading.")]
+                $hvis fn $hamt<'s, S: $crate::macro_support::Blockstore>(
+                    &self,
+                    store: &'s S,
+                ) -> $crate::macro_support::Result<$crate::macro_support::Hamt<&'s S, $value>> {
+                    self.$hamt.load(store)
+                }
+            )*)?
+
+            $($(
+                #[doc = concat!("Load the `", stringify!($amt), "` array for reading.")]
+                $avis fn $amt<'s, S: $crate::macro_support::Blockstore>(
+                    &self,
+                    store: &'s S,
+                ) -> $crate::macro_support::Result<$crate::macro_support::Amt<$item, &'s S>> {
+                    self.$amt.load(store)
+                }
+            )*)?
+
+            /// Check that every collection of the state can be loaded from the store.
+            pub fn check_collections<S: $crate::macro_support::Blockstore>(
+                &self,
+                store: &S,
+                acc: &$crate::macro_support::MessageAccumulator,
+            ) {
+                let _ = (store, acc);
+                $($(
+                    acc.require_no_error(
+                        self.$hamt.load(store),
+                        concat!("failed to load ", stringify!($hamt)),
+                    );
+                )*)?
+                $($(
+                    acc.require_no_error(
+                        self.$amt.load(store),
+                        concat!("failed to load ", stringify!($amt)),
+                    );
+                )*)?
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use fil_actors_runtime::MessageAccumulator;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_shared::econ::TokenAmount;
+
+    use crate::TCid;
+
+    ipc_actor_state! {
+        #[derive(Debug, PartialEq)]
+        struct State {
+            name: String,
+            total: TokenAmount,
+        }
+        hamts {
+            balances: String => TokenAmount,
+        }
+        amts {
+            log: u64,
+        }
+    }
+
+    #[test]
+    fn generated_state() {
+        let store = MemoryBlockstore::new();
+        let mut st = State::new(&store, "subnet".into(), TokenAmount::from_atto(5)).unwrap();
+        st.log
+            .update(&store, |log| {
+                log.set(0, 1)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(st.log(&store).unwrap().count(), 1);
+        assert!(st.balances(&store).unwrap().is_empty());
+
+        // Plain fields first, then the collections.
+        let decoded: (String, TokenAmount, Cid, Cid) = from_slice(&to_vec(&st).unwrap()).unwrap();
+        assert_eq!(decoded.2, st.balances.cid());
+        assert_eq!(from_slice::<State>(&to_vec(&st).unwrap()).unwrap(), st);
+
+        let acc = MessageAccumulator::default();
+        st.check_collections(&store, &acc);
+        acc.assert_empty();
+
+        st.balances = TCid::from(Cid::default());
+        st.check_collections(&store, &acc);
+        assert_eq!(acc.len(), 1);
+        assert!(acc.messages()[0].starts_with("failed to load balances"));
+    }
+}
@@ -0,0 +1,383 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+use fil_actors_runtime::{actor_error, phantom_wrapper, ActorError};
+use fvm_shared::bigint::bigint_ser::{BigIntDe, BigIntSer};
+use fvm_shared::bigint::{BigInt, Integer};
+use fvm_shared::econ::TokenAmount;
+use num_traits::{Signed, Zero};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Marker for the asset an [`Amount`] is denominated in.
+///
+/// Actors handling more than one FRC-46 token should declare a marker for each of them,
+/// rather than using [`Frc46`] for all.
+pub trait Asset {
+    /// Shown after the amount, e.g. `1.5 FIL`.
+    const SYMBOL: &'static str;
+}
+
+/// The native token of the chain the actor is deployed on.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Fil;
+
+/// The native token of a child subnet, as accounted for in its parent.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct SubnetNative;
+
+/// An FRC-46 fungible token held in a token actor.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Frc46;
+
+impl Asset for Fil {
+    const SYMBOL: &'static str = "FIL";
+}
+
+impl Asset for SubnetNative {
+    const SYMBOL: &'static str = "SUBNET";
+}
+
+impl Asset for Frc46 {
+    const SYMBOL: &'static str = "FRC46";
+}
+
+/// A `TokenAmount` tagged with the asset it is denominated in, so that amounts of different
+/// assets can't be added, compared or sent in place of each other by mistake.
+///
+/// It is encoded exactly like a `TokenAmount`, so fields can be changed to it without a
+/// migration. Only `Amount<Fil>` converts to and from a `TokenAmount`, which is what
+/// `Runtime::send` takes as the value; amounts of other assets have to go through a [`Rate`],
+/// or explicitly through [`Amount::as_token_amount`] when encoding them for a token actor.
+///
+/// # Example
+/// ```
+/// use primitives::{Amount, Fil, Rate, SubnetNative};
+///
+/// let fee: Amount<Fil> = Amount::from_whole(2);
+/// let total = fee.clone() + Amount::from_atto(500);
+/// assert!(total > fee);
+///
+/// // Two subnet tokens for every FIL.
+/// let rate: Rate<Fil, SubnetNative> = Rate::new(2, 1).unwrap();
+/// let minted: Amount<SubnetNative> = rate.convert(&fee);
+/// assert_eq!(minted, Amount::from_whole(4));
+/// assert_eq!(minted.to_string(), "4.0 SUBNET");
+/// ```
+pub struct Amount<A> {
+    amount: TokenAmount,
+    _asset: PhantomData<A>,
+}
+
+impl<A> Amount<A> {
+    pub fn zero() -> Self {
+        Self::tagged(TokenAmount::zero())
+    }
+
+    pub fn from_atto(atto: impl Into<BigInt>) -> Self {
+        Self::tagged(TokenAmount::from_atto(atto))
+    }
+
+    pub fn from_whole(tokens: impl Into<BigInt>) -> Self {
+        Self::tagged(TokenAmount::from_whole(tokens))
+    }
+
+    pub fn atto(&self) -> &BigInt {
+        self.amount.atto()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.amount.is_positive()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_negative()
+    }
+
+    /// The untagged amount, e.g. to encode in the parameters of an FRC-46 transfer.
+    ///
+    /// Not to be used as the value of a send unless the asset is `Fil`, in which case
+    /// converting with `into` says so.
+    pub fn as_token_amount(&self) -> &TokenAmount {
+        &self.amount
+    }
+
+    // Private, so that only `Fil` converts from a `TokenAmount`, e.g. the value received;
+    // amounts of other assets are built from numbers, with `from_atto` or `from_whole`.
+    fn tagged(amount: TokenAmount) -> Self {
+        Self {
+            amount,
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl From<TokenAmount> for Amount<Fil> {
+    fn from(amount: TokenAmount) -> Self {
+        Self::tagged(amount)
+    }
+}
+
+impl From<Amount<Fil>> for TokenAmount {
+    fn from(amount: Amount<Fil>) -> Self {
+        amount.amount
+    }
+}
+
+impl<A> PartialOrd for Amount<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<A> Ord for Amount<A> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.amount.cmp(&other.amount)
+    }
+}
+
+impl<A> Default for Amount<A> {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+/// Shows whole tokens and the symbol of the asset, e.g. `1.5 FIL`.
+impl<A: Asset> Display for Amount<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, A::SYMBOL)
+    }
+}
+
+// Serializes exactly as its underlying `TokenAmount`.
+phantom_wrapper!(Amount<A> { amount: TokenAmount, _asset });
+
+impl<A> Add for Amount<A> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::tagged(self.amount + rhs.amount)
+    }
+}
+
+impl<'a, A> Add<&'a Amount<A>> for Amount<A> {
+    type Output = Self;
+
+    fn add(self, rhs: &'a Self) -> Self {
+        Self::tagged(self.amount + &rhs.amount)
+    }
+}
+
+impl<A> AddAssign for Amount<A> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.amount += rhs.amount;
+    }
+}
+
+impl<'a, A> AddAssign<&'a Amount<A>> for Amount<A> {
+    fn add_assign(&mut self, rhs: &'a Self) {
+        self.amount += &rhs.amount;
+    }
+}
+
+impl<A> Sub for Amount<A> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::tagged(self.amount - rhs.amount)
+    }
+}
+
+impl<'a, A> Sub<&'a Amount<A>> for Amount<A> {
+    type Output = Self;
+
+    fn sub(self, rhs: &'a Self) -> Self {
+        Self::tagged(self.amount - &rhs.amount)
+    }
+}
+
+impl<A> SubAssign for Amount<A> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.amount -= rhs.amount;
+    }
+}
+
+impl<'a, A> SubAssign<&'a Amount<A>> for Amount<A> {
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        self.amount -= &rhs.amount;
+    }
+}
+
+impl<A> Neg for Amount<A> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::tagged(-self.amount)
+    }
+}
+
+impl<A> Sum for Amount<A> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self::tagged(iter.map(|a| a.amount).sum())
+    }
+}
+
+impl<'a, A> Sum<&'a Amount<A>> for Amount<A> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        Self::tagged(iter.map(|a| &a.amount).sum())
+    }
+}
+
+/// The exchange rate between two assets, as `numerator` atto `To` for every `denominator`
+/// atto `From`, which is the only way to turn an [`Amount`] of one into the other.
+///
+/// It is encoded as a tuple of the numerator and the denominator.
+pub struct Rate<From, To> {
+    numerator: BigInt,
+    denominator: BigInt,
+    _assets: PhantomData<(From, To)>,
+}
+
+impl<From, To> Rate<From, To> {
+    /// Fails with `USR_ILLEGAL_ARGUMENT` unless both sides are positive.
+    pub fn new(
+        numerator: impl Into<BigInt>,
+        denominator: impl Into<BigInt>,
+    ) -> Result<Self, ActorError> {
+        let (numerator, denominator) = (numerator.into(), denominator.into());
+        if !numerator.is_positive() || !denominator.is_positive() {
+            return Err(actor_error!(illegal_argument;
+                "invalid rate {}/{}, both sides must be positive", numerator, denominator));
+        }
+        Ok(Self {
+            numerator,
+            denominator,
+            _assets: PhantomData,
+        })
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    /// Convert an amount, rounding down to whole atto, so the result is never worth more
+    /// than what was given for it.
+    pub fn convert(&self, amount: &Amount<From>) -> Amount<To> {
+        let atto = (amount.atto() * &self.numerator).div_floor(&self.denominator);
+        Amount::from_atto(atto)
+    }
+
+    /// The rate of converting back.
+    pub fn inverse(&self) -> Rate<To, From> {
+        Rate {
+            numerator: self.denominator.clone(),
+            denominator: self.numerator.clone(),
+            _assets: PhantomData,
+        }
+    }
+}
+
+impl<From, To> Clone for Rate<From, To> {
+    fn clone(&self) -> Self {
+        Self {
+            numerator: self.numerator.clone(),
+            denominator: self.denominator.clone(),
+            _assets: PhantomData,
+        }
+    }
+}
+
+impl<From, To> PartialEq for Rate<From, To> {
+    fn eq(&self, other: &Self) -> bool {
+        self.numerator == other.numerator && self.denominator == other.denominator
+    }
+}
+
+impl<From, To> Eq for Rate<From, To> {}
+
+impl<From: Asset, To: Asset> Debug for Rate<From, To> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rate<{}, {}>({}/{})",
+            From::SYMBOL,
+            To::SYMBOL,
+            self.numerator,
+            self.denominator
+        )
+    }
+}
+
+impl<From, To> Serialize for Rate<From, To> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (BigIntSer(&self.numerator), BigIntSer(&self.denominator)).serialize(serializer)
+    }
+}
+
+/// Fails unless both sides are positive, like `Rate::new`.
+impl<'de, From, To> Deserialize<'de> for Rate<From, To> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (BigIntDe(numerator), BigIntDe(denominator)) = Deserialize::deserialize(deserializer)?;
+        Self::new(numerator, denominator).map_err(|e| serde::de::Error::custom(e.msg()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_shared::bigint::bigint_ser::BigIntSer;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Amount, Fil, Frc46, Rate, SubnetNative};
+
+    #[test]
+    fn arithmetic_and_encoding() {
+        let a: Amount<Fil> = Amount::from_whole(1);
+        let b = Amount::from_atto(500_000_000_000_000_000u64);
+        let mut total = a.clone() + &b;
+        assert_eq!(total.to_string(), "1.5 FIL");
+        total -= &a;
+        assert_eq!(total, b);
+        assert_eq!([a.clone(), b.clone()].iter().sum::<Amount<Fil>>(), a + b);
+        assert!((-total).is_negative());
+
+        let value: TokenAmount = Amount::<Fil>::from_atto(7).into();
+        assert_eq!(value, TokenAmount::from_atto(7));
+
+        let tokens: Amount<Frc46> = Amount::from_atto(7);
+        let bytes = to_vec(&tokens).unwrap();
+        assert_eq!(bytes, to_vec(&TokenAmount::from_atto(7)).unwrap());
+        assert_eq!(from_slice::<Amount<Frc46>>(&bytes).unwrap(), tokens);
+    }
+
+    #[test]
+    fn conversion_rounds_down() {
+        // 3 atto subnet tokens for every 2 atto FIL.
+        let rate: Rate<Fil, SubnetNative> = Rate::new(3, 2).unwrap();
+        assert_eq!(rate.convert(&Amount::from_atto(5)), Amount::from_atto(7));
+        assert_eq!(
+            rate.inverse().convert(&Amount::from_atto(7)),
+            Amount::from_atto(4)
+        );
+
+        let bytes = to_vec(&rate).unwrap();
+        assert_eq!(from_slice::<Rate<Fil, SubnetNative>>(&bytes).unwrap(), rate);
+
+        let err = Rate::<Fil, Frc46>::new(1, 0).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        let zero = to_vec(&(BigIntSer(&0.into()), BigIntSer(&1.into()))).unwrap();
+        let err = from_slice::<Rate<Fil, Frc46>>(&zero).unwrap_err();
+        assert!(err.to_string().contains("both sides must be positive"));
+    }
+}
@@ -3,11 +3,14 @@ use std::marker::PhantomData;
 
 use crate::tcid_ops;
 
-use super::{TCid, TCidContent};
+use super::{short_type_name, DeepEq, TCid, TCidContent};
 use anyhow::{anyhow, Result};
+use cid::Cid;
 use fil_actors_runtime::fvm_ipld_amt::Amt;
 use fil_actors_runtime::fvm_ipld_amt::Error as AmtError;
+use fil_actors_runtime::{actor_error, ActorDowncast, ActorError};
 use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use fvm_shared::error::ExitCode;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
@@ -48,6 +51,26 @@ pub struct TAmt<V, const W: u32 = AMT_BIT_WIDTH> {
 
 impl<V, const W: u32> TCidContent for TAmt<V, W> {}
 
+/// Arrays are equal if they have equal values at the same indices.
+impl<V, const W: u32> DeepEq for TAmt<V, W>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    fn content_eq<S: Blockstore>(a: &Cid, b: &Cid, store: &S) -> Result<bool> {
+        let a = Amt::<V, _>::load(a, store)?;
+        let b = Amt::<V, _>::load(b, store)?;
+        if a.count() != b.count() {
+            return Ok(false);
+        }
+        let mut eq = true;
+        a.for_each_while(|i, v| {
+            eq = b.get(i)? == Some(v);
+            Ok(eq)
+        })?;
+        Ok(eq)
+    }
+}
+
 impl<V, const W: u32> TCid<TAmt<V, W>>
 where
     V: Serialize + DeserializeOwned,
@@ -81,6 +104,141 @@ where
 
 tcid_ops!(TAmt<V : Serialize + DeserializeOwned, W const: u32> => Amt<V, &'s S>);
 
+impl<V, const W: u32> TCid<TAmt<V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    /// Visit the entries in ascending order of their indices, failing if the array yields
+    /// them in any other order, so that nothing relies on an order it doesn't get.
+    ///
+    /// Unlike a HAMT, an AMT iterates in the order of its keys by construction, so this
+    /// costs nothing over `Amt::for_each`.
+    pub fn for_each_by_index<S: Blockstore>(
+        &self,
+        store: &S,
+        mut f: impl FnMut(u64, &V) -> Result<()>,
+    ) -> Result<()> {
+        let array = self.load(store)?;
+        let mut last = None;
+        array.for_each(|i, v| {
+            if matches!(last, Some(last) if i <= last) {
+                return Err(anyhow!("index {} visited after {:?}", i, last));
+            }
+            last = Some(i);
+            f(i, v)
+        })?;
+        Ok(())
+    }
+}
+
+/// Array-like operations, each loading the array and, if it changes, flushing it, failing
+/// with `ActorError`s which say which array and which index an operation failed on.
+///
+/// Several changes in a row are cheaper done in one `modify`.
+impl<V, const W: u32> TCid<TAmt<V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    pub fn get<S: Blockstore>(
+        &self,
+        store: &S,
+        index: u64,
+    ) -> std::result::Result<Option<V>, ActorError>
+    where
+        V: Clone,
+    {
+        let array = self.load_typed(store)?;
+        let value = array
+            .get(index)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, Self::at("get", index)))?;
+        Ok(value.cloned())
+    }
+
+    /// Insert or replace the value at the index.
+    pub fn set<S: Blockstore>(
+        &mut self,
+        store: &S,
+        index: u64,
+        value: V,
+    ) -> std::result::Result<(), ActorError> {
+        let mut array = self.load_typed(store)?;
+        array
+            .set(index, value)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, Self::at("set", index)))?;
+        self.flush_typed(array)
+    }
+
+    /// Append the value at the index equal to the number of values, returning that index.
+    ///
+    /// That is the end of arrays which are only ever appended to, as logs of e.g. checkpoints
+    /// or cross messages are; on an array with gaps the index may be taken, which is an error.
+    pub fn push<S: Blockstore>(
+        &mut self,
+        store: &S,
+        value: V,
+    ) -> std::result::Result<u64, ActorError> {
+        let mut array = self.load_typed(store)?;
+        let index = array.count();
+        let taken = array
+            .get(index)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, Self::at("push", index)))?
+            .is_some();
+        if taken {
+            return Err(actor_error!(illegal_state;
+                "failed to push to {}: index {} is taken, the array has gaps", Self::name(), index));
+        }
+        array.set(index, value).map_err(|e| {
+            e.downcast_default(ExitCode::USR_ILLEGAL_STATE, Self::at("push", index))
+        })?;
+        self.flush_typed(array)?;
+        Ok(index)
+    }
+
+    /// Visit the values in ascending order of their indices, for as long as `f` returns `true`.
+    pub fn for_each_while<S: Blockstore>(
+        &self,
+        store: &S,
+        mut f: impl FnMut(u64, &V) -> std::result::Result<bool, ActorError>,
+    ) -> std::result::Result<(), ActorError> {
+        let array = self.load_typed(store)?;
+        array.for_each_while(|i, v| Ok(f(i, v)?)).map_err(|e| {
+            e.downcast_default(
+                ExitCode::USR_ILLEGAL_STATE,
+                format!("failed to iterate {}", Self::name()),
+            )
+        })
+    }
+
+    fn load_typed<'s, S: Blockstore>(
+        &self,
+        store: &'s S,
+    ) -> std::result::Result<Amt<V, &'s S>, ActorError> {
+        Amt::load(&self.cid, store).map_err(|e| {
+            e.downcast_default(
+                ExitCode::USR_ILLEGAL_STATE,
+                format!("failed to load {}", Self::name()),
+            )
+        })
+    }
+
+    fn flush_typed<S: Blockstore>(
+        &mut self,
+        array: Amt<V, &S>,
+    ) -> std::result::Result<(), ActorError> {
+        self.flush(array)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to flush"))?;
+        Ok(())
+    }
+
+    fn name() -> String {
+        short_type_name(type_name::<TAmt<V, W>>())
+    }
+
+    fn at(op: &str, index: u64) -> String {
+        format!("failed to {} {}[{}]", op, Self::name(), index)
+    }
+}
+
 /// This `Default` implementation is unsound in that while it
 /// creates `TAmt` instances with a correct `Cid` value, this value
 /// is not stored anywhere, so there is no guarantee that any retrieval
@@ -96,3 +254,71 @@ where
         Self::new_amt(&MemoryBlockstore::new()).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::actor_error;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::error::ExitCode;
+
+    use crate::{TAmt, TCid};
+
+    #[test]
+    fn iterates_by_index() {
+        let store = MemoryBlockstore::new();
+        let mut array: TCid<TAmt<u64>> = TCid::new_amt(&store).unwrap();
+        let indices = [1000, 3, 64, 0, 9];
+        array
+            .update(&store, |a| {
+                for i in indices {
+                    a.set(i, i * 2)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let mut visited = Vec::new();
+        array
+            .for_each_by_index(&store, |i, v| {
+                visited.push((i, *v));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            visited,
+            vec![(0, 0), (3, 6), (9, 18), (64, 128), (1000, 2000)]
+        );
+    }
+
+    #[test]
+    fn typed_array_operations() {
+        let store = MemoryBlockstore::new();
+        let mut array: TCid<TAmt<String>> = TCid::new_amt(&store).unwrap();
+
+        assert_eq!(array.push(&store, "a".into()).unwrap(), 0);
+        assert_eq!(array.push(&store, "b".into()).unwrap(), 1);
+        array.set(&store, 1, "c".into()).unwrap();
+        assert_eq!(array.get(&store, 1).unwrap(), Some("c".to_string()));
+        assert_eq!(array.get(&store, 2).unwrap(), None);
+
+        let mut visited = Vec::new();
+        array
+            .for_each_while(&store, |i, v| {
+                visited.push((i, v.clone()));
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(visited, vec![(0, "a".to_string())]);
+
+        // With a gap, the number of values is an index which is taken.
+        array.set(&store, 3, "d".into()).unwrap();
+        let err = array.push(&store, "e".into()).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        assert!(err.msg().contains("TAmt<String, 3>"), "{}", err.msg());
+
+        let err = array
+            .for_each_while(&store, |_, _| Err(actor_error!(forbidden; "stop")))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+    }
+}
@@ -0,0 +1,197 @@
+use std::ops::{Bound, Range, RangeBounds};
+
+use anyhow::{anyhow, Result};
+use fil_actors_runtime::fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+/// Find the first entry of an AMT for which `pred` holds, given that it doesn't hold for the
+/// entries before it and holds for all the ones after, e.g. `|c| c.epoch >= epoch` on entries
+/// sorted by epoch. Only `O(log n)` entries are looked up, rather than scanning the array.
+///
+/// The entries have to occupy consecutive indices, as when they are only appended and
+/// pruned from the front. Landing on a gap is an error, but not every gap is noticed.
+///
+/// # Example
+/// ```
+/// use fil_actors_runtime::fvm_ipld_amt::Amt;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use primitives::{find_first_where, find_last_where};
+///
+/// let store = MemoryBlockstore::new();
+/// let mut epochs = Amt::new(&store);
+/// epochs.batch_set([10i64, 20, 30]).unwrap();
+///
+/// assert_eq!(find_first_where(&epochs, |e| *e >= 15).unwrap(), Some((1, &20)));
+/// // The latest entry at or before epoch 25.
+/// assert_eq!(find_last_where(&epochs, |e| *e <= 25).unwrap(), Some((1, &20)));
+/// ```
+pub fn find_first_where<V, BS>(
+    amt: &Amt<V, BS>,
+    mut pred: impl FnMut(&V) -> bool,
+) -> Result<Option<(u64, &V)>>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    let range = entry_range(amt)?;
+    let end = range.end;
+    let i = partition_point(amt, range, |v| !pred(v))?;
+    if i == end {
+        return Ok(None);
+    }
+    Ok(Some((i, get(amt, i)?)))
+}
+
+/// Find the last entry of an AMT for which `pred` holds, given that it holds for the entries
+/// before it and doesn't hold for any after, e.g. `|c| c.epoch <= epoch` for the latest entry
+/// at or before an epoch. Same requirements as `find_first_where`.
+pub fn find_last_where<V, BS>(
+    amt: &Amt<V, BS>,
+    pred: impl FnMut(&V) -> bool,
+) -> Result<Option<(u64, &V)>>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    let range = entry_range(amt)?;
+    let start = range.start;
+    let i = partition_point(amt, range, pred)?;
+    if i == start {
+        return Ok(None);
+    }
+    Ok(Some((i - 1, get(amt, i - 1)?)))
+}
+
+/// Collect the entries of an AMT sorted by `key` whose key is in `keys`, in order.
+/// Same requirements as `find_first_where`.
+pub fn range_by_key<V, BS, K>(
+    amt: &Amt<V, BS>,
+    key: impl Fn(&V) -> K,
+    keys: impl RangeBounds<K>,
+) -> Result<Vec<(u64, &V)>>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+    K: Ord,
+{
+    let range = entry_range(amt)?;
+    let start = partition_point(amt, range.clone(), |v| match keys.start_bound() {
+        Bound::Included(k) => key(v) < *k,
+        Bound::Excluded(k) => key(v) <= *k,
+        Bound::Unbounded => false,
+    })?;
+    let end = partition_point(amt, start..range.end, |v| match keys.end_bound() {
+        Bound::Included(k) => key(v) <= *k,
+        Bound::Excluded(k) => key(v) < *k,
+        Bound::Unbounded => true,
+    })?;
+    (start..end).map(|i| Ok((i, get(amt, i)?))).collect()
+}
+
+/// The indices occupied by the entries, assuming they are consecutive.
+fn entry_range<V, BS>(amt: &Amt<V, BS>) -> Result<Range<u64>>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    let mut first = 0;
+    amt.for_each_while(|i, _| {
+        first = i;
+        Ok(false)
+    })
+    .map_err(|e| anyhow!("failed to iterate array: {}", e))?;
+    Ok(first..first + amt.count())
+}
+
+/// The first index in `range` for which `pred` doesn't hold, or its end.
+fn partition_point<V, BS>(
+    amt: &Amt<V, BS>,
+    range: Range<u64>,
+    mut pred: impl FnMut(&V) -> bool,
+) -> Result<u64>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    let (mut lo, mut hi) = (range.start, range.end);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(get(amt, mid)?) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    Ok(lo)
+}
+
+fn get<V, BS>(amt: &Amt<V, BS>, i: u64) -> Result<&V>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    amt.get(i)
+        .map_err(|e| anyhow!("failed to get array entry {}: {}", i, e))?
+        .ok_or_else(|| anyhow!("array entries are not consecutive, {} is missing", i))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeBounds;
+
+    use fil_actors_runtime::fvm_ipld_amt::Amt;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+
+    use super::{find_first_where, find_last_where, range_by_key};
+
+    fn keys(amt: &Amt<i64, &MemoryBlockstore>, range: impl RangeBounds<i64>) -> Vec<i64> {
+        let found = range_by_key(amt, |e| *e, range).unwrap();
+        found.into_iter().map(|(_, e)| *e).collect()
+    }
+
+    #[test]
+    fn search_pruned_array() {
+        let store = MemoryBlockstore::new();
+        let mut amt = Amt::new(&store);
+        for (i, epoch) in [5i64, 10, 10, 20, 30, 40].into_iter().enumerate() {
+            amt.set(i as u64, epoch).unwrap();
+        }
+        // Pruned from the front, as by `prune_expired`.
+        amt.delete(0).unwrap();
+
+        assert_eq!(
+            find_first_where(&amt, |e| *e >= 10).unwrap(),
+            Some((1, &10))
+        );
+        assert_eq!(
+            find_first_where(&amt, |e| *e >= 25).unwrap(),
+            Some((4, &30))
+        );
+        assert_eq!(find_first_where(&amt, |e| *e > 40).unwrap(), None);
+        assert_eq!(find_last_where(&amt, |e| *e <= 10).unwrap(), Some((2, &10)));
+        assert_eq!(find_last_where(&amt, |e| *e <= 9).unwrap(), None);
+        assert_eq!(
+            find_last_where(&amt, |e| *e <= 100).unwrap(),
+            Some((5, &40))
+        );
+
+        assert_eq!(keys(&amt, 10..30), vec![10, 10, 20]);
+        assert_eq!(keys(&amt, 11..=30), vec![20, 30]);
+        assert_eq!(keys(&amt, 35..), vec![40]);
+        assert_eq!(keys(&amt, 50..60), Vec::<i64>::new());
+
+        let empty: Amt<i64, _> = Amt::new(&store);
+        assert_eq!(find_first_where(&empty, |_| true).unwrap(), None);
+    }
+
+    #[test]
+    fn gaps_are_errors() {
+        let store = MemoryBlockstore::new();
+        let mut amt = Amt::new(&store);
+        amt.batch_set([1u64, 2, 3, 4]).unwrap();
+        amt.delete(2).unwrap();
+        assert!(find_first_where(&amt, |v| *v >= 4).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use fil_actors_runtime::runtime::{GasCharge, Runtime};
+use fil_actors_runtime::{ActorDowncast, ActorError};
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::error::ExitCode;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::{TAmt, TCid};
+
+/// Position of a `process_until` over an array, to be persisted in the actor state
+/// so that the next call can resume where the last one stopped.
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ProcessCursor {
+    next_index: u64,
+}
+
+impl ProcessCursor {
+    /// Start processing at the given index.
+    pub fn new(from: u64) -> Self {
+        Self { next_index: from }
+    }
+
+    /// The first index that hasn't been processed yet.
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+}
+
+/// Outcome of a single `process_until` call.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Processed {
+    /// Number of entries processed.
+    pub processed: u64,
+    /// Whether every entry from the cursor on has been processed,
+    /// or the budget ran out and the processing has to be resumed.
+    pub done: bool,
+}
+
+/// Apply `f` to the entries of an array from the `cursor` on, in index order, as long as
+/// the estimated gas allows, so that e.g. a cron method stays within the block gas limit.
+///
+/// Every entry is estimated to cost `per_item`, e.g. `rt.policy().gas.cross_msg`, which is
+/// charged before `f` is applied to it, so at most `budget / per_item.compute` entries are
+/// processed. Whatever `f` charges on top of that isn't counted against the budget.
+///
+/// The `cursor` is advanced past the entries processed. If the result isn't `done`, calling
+/// it again with the same cursor, e.g. from the next cron tick, carries on. The array isn't
+/// modified; `f` gets a copy of every entry. The entries before the cursor are still visited,
+/// so removing the processed ones keeps the iteration short.
+pub fn process_until<RT, V, F>(
+    rt: &mut RT,
+    budget: i64,
+    per_item: GasCharge,
+    items: &TCid<TAmt<V>>,
+    cursor: &mut ProcessCursor,
+    mut f: F,
+) -> Result<Processed, ActorError>
+where
+    RT: Runtime,
+    V: Serialize + DeserializeOwned + Clone,
+    F: FnMut(&mut RT, u64, V) -> Result<(), ActorError>,
+{
+    let max_items = match per_item.compute {
+        compute if compute > 0 => (budget / compute).max(0) as u64,
+        _ => u64::MAX,
+    };
+
+    // Take one more entry than can be processed, to know whether any is left.
+    let mut batch = Vec::new();
+    let start = cursor.next_index;
+    items
+        .load(rt.store())
+        .and_then(|arr| {
+            arr.for_each_while(|i, v| {
+                if i < start {
+                    return Ok(true);
+                }
+                batch.push((i, v.clone()));
+                Ok((batch.len() as u64) <= max_items)
+            })?;
+            Ok(())
+        })
+        .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to load entries"))?;
+
+    let done = batch.len() as u64 <= max_items;
+    let mut processed = 0;
+    for (i, v) in batch.into_iter().take(max_items as usize) {
+        rt.charge(per_item);
+        f(rt, i, v)?;
+        processed += 1;
+        cursor.next_index = i + 1;
+    }
+    Ok(Processed { processed, done })
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::runtime::GasCharge;
+    use fil_actors_runtime::test_utils::MockRuntime;
+
+    use super::{process_until, ProcessCursor, Processed};
+    use crate::{TAmt, TCid};
+
+    const PER_ITEM: GasCharge = GasCharge::new("process_item", 100);
+
+    /// Process within the budget, expecting `charges` entries to be charged for,
+    /// and return the outcome with the entries seen.
+    fn process(
+        rt: &mut MockRuntime,
+        items: &TCid<TAmt<u64>>,
+        cursor: &mut ProcessCursor,
+        budget: i64,
+        charges: usize,
+    ) -> (Processed, Vec<(u64, u64)>) {
+        if !cfg!(feature = "no-gas-charges") {
+            for _ in 0..charges {
+                rt.expect_named_gas_charge(PER_ITEM.name, PER_ITEM.compute);
+            }
+        }
+        let mut seen = Vec::new();
+        let processed = rt
+            .call_fn(|rt| {
+                Ok(process_until(
+                    rt,
+                    budget,
+                    PER_ITEM,
+                    items,
+                    cursor,
+                    |_, i, v| {
+                        seen.push((i, v));
+                        Ok(())
+                    },
+                )?)
+            })
+            .unwrap();
+        rt.verify();
+        (processed, seen)
+    }
+
+    #[test]
+    fn resume_within_budget() {
+        let mut rt = MockRuntime::default();
+        let store = rt.store.clone();
+        let mut items: TCid<TAmt<u64>> = TCid::new_amt(&store).unwrap();
+        items
+            .update(&store, |arr| {
+                // With a gap, which takes no budget.
+                for i in [0, 1, 2, 10, 11] {
+                    arr.set(i, i * 2)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        let mut cursor = ProcessCursor::default();
+        let outcome = |processed, done| Processed { processed, done };
+
+        // A budget too small for even one entry makes no progress.
+        let (res, seen) = process(&mut rt, &items, &mut cursor, 99, 0);
+        assert_eq!((res, seen), (outcome(0, false), vec![]));
+
+        let (res, seen) = process(&mut rt, &items, &mut cursor, 250, 2);
+        assert_eq!((res, seen), (outcome(2, false), vec![(0, 0), (1, 2)]));
+        assert_eq!(cursor.next_index(), 2);
+
+        // Exactly enough for the rest.
+        let (res, seen) = process(&mut rt, &items, &mut cursor, 300, 3);
+        assert_eq!(
+            (res, seen),
+            (outcome(3, true), vec![(2, 4), (10, 20), (11, 22)])
+        );
+        assert_eq!(cursor.next_index(), 12);
+
+        let (res, _) = process(&mut rt, &items, &mut cursor, 1000, 0);
+        assert_eq!(res, outcome(0, true));
+    }
+}
@@ -0,0 +1,95 @@
+use fvm_ipld_encoding::to_vec;
+use serde::ser::Serialize;
+
+/// Sort keys in canonical CBOR order, i.e. by their encoded bytes as in the keys of a
+/// canonical map, and remove the duplicates, keeping the first of each.
+///
+/// Builders and verifiers of batches and commitments have to agree on the order of the
+/// entries, and this is the one `check_canonical` enforces on maps. For byte strings, such
+/// as addresses, it amounts to shorter first, then bytewise.
+///
+/// Note that it is not the order a HAMT iterates in, which follows the hashes of the keys.
+///
+/// # Example
+/// ```
+/// use primitives::sort_cbor_keys;
+/// use fvm_shared::address::Address;
+///
+/// let secp = Address::new_secp256k1(&[1u8; 65]).unwrap();
+/// let mut keys = vec![secp, Address::new_id(1000), Address::new_id(1), secp];
+/// sort_cbor_keys(&mut keys).unwrap();
+///
+/// assert_eq!(keys, vec![Address::new_id(1), Address::new_id(1000), secp]);
+/// ```
+pub fn sort_cbor_keys<T: Serialize>(keys: &mut Vec<T>) -> anyhow::Result<()> {
+    let mut encoded = keys
+        .drain(..)
+        .map(|k| Ok((to_vec(&k)?, k)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    encoded.sort_by(|(a, _), (b, _)| a.cmp(b));
+    encoded.dedup_by(|(a, _), (b, _)| a == b);
+    keys.extend(encoded.into_iter().map(|(_, k)| k));
+    Ok(())
+}
+
+/// Check that keys are in canonical CBOR order without duplicates, as `sort_cbor_keys` leaves them.
+pub fn is_cbor_sorted<T: Serialize>(keys: &[T]) -> anyhow::Result<bool> {
+    let encoded = keys.iter().map(to_vec).collect::<Result<Vec<_>, _>>()?;
+    Ok(encoded.windows(2).all(|w| w[0] < w[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fvm_ipld_encoding::to_vec;
+    use indexmap::IndexMap;
+
+    use super::{is_cbor_sorted, sort_cbor_keys};
+
+    /// A deterministic xorshift generator, so failures can be reproduced.
+    fn next(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn order_matches_canonical_maps() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..200 {
+            let n = next(&mut seed) % 20;
+            let mut keys: Vec<String> = (0..n)
+                .map(|_| {
+                    // Lengths from 0 to 29 and a small alphabet, for duplicates and shared prefixes.
+                    let len = next(&mut seed) % 30;
+                    (0..len)
+                        .map(|_| (b'a' + (next(&mut seed) % 3) as u8) as char)
+                        .collect()
+                })
+                .collect();
+
+            let map: BTreeMap<String, ()> = keys.iter().map(|k| (k.clone(), ())).collect();
+            let encoded: IndexMap<String, ()> =
+                fvm_ipld_encoding::from_slice(&to_vec(&map).unwrap()).unwrap();
+            let expected: Vec<String> = encoded.into_keys().collect();
+
+            sort_cbor_keys(&mut keys).unwrap();
+            assert_eq!(keys, expected);
+            assert!(is_cbor_sorted(&keys).unwrap());
+            // Sorting is idempotent.
+            let mut again = keys.clone();
+            sort_cbor_keys(&mut again).unwrap();
+            assert_eq!(again, keys);
+        }
+    }
+
+    #[test]
+    fn unsorted_or_duplicated() {
+        assert!(!is_cbor_sorted(&["b", "a"]).unwrap());
+        assert!(!is_cbor_sorted(&["a", "a"]).unwrap());
+        assert!(!is_cbor_sorted(&["aa", "b"]).unwrap());
+        assert!(is_cbor_sorted(&["b", "aa"]).unwrap());
+    }
+}
@@ -0,0 +1,81 @@
+//! Computing, parsing and checking CIDs, so that actors and tests don't each assemble them
+//! with `Cid::new_v1` and a hasher of their choosing.
+use cid::multibase::Base;
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::{to_vec, DAG_CBOR};
+use serde::Serialize;
+
+/// The CID of arbitrary bytes, e.g. `cid_of(IPLD_RAW, Code::Sha2_256, data)`.
+pub fn cid_of(codec: u64, hasher: Code, data: &[u8]) -> Cid {
+    Cid::new_v1(codec, hasher.digest(data))
+}
+
+/// The CID under which the blockstore keeps a value put with `put_cbor(value, Blake2b256)`,
+/// which `TCid` links and state roots use, without having to store it.
+pub fn cbor_cid<T: Serialize>(value: &T) -> Result<Cid, ActorError> {
+    let bytes = to_vec(value)?;
+    Ok(cid_of(DAG_CBOR, Code::Blake2b256, &bytes))
+}
+
+/// Parse a CID in any multibase, e.g. base32 `bafy...` or base58 `Qm...` for a CIDv0.
+pub fn parse_cid(s: &str) -> Result<Cid, ActorError> {
+    Cid::try_from(s).map_err(|e| actor_error!(illegal_argument; "invalid CID {}: {}", s, e))
+}
+
+/// Format a CID in the given multibase; a CIDv0 can only be formatted in base58.
+pub fn format_cid(cid: &Cid, base: Base) -> Result<String, ActorError> {
+    cid.to_string_of_base(base)
+        .map_err(|e| actor_error!(illegal_argument; "cannot format {} in {:?}: {}", cid, base, e))
+}
+
+/// Check that a CID, e.g. received in parameters, has the codec and hash function expected
+/// of it, so that a link to content of another kind isn't taken for one to be loaded.
+pub fn check_cid(cid: &Cid, codec: u64, hasher: Code) -> Result<(), ActorError> {
+    if cid.codec() != codec {
+        return Err(actor_error!(illegal_argument;
+            "CID {} has codec {:#x}, expected {:#x}", cid, cid.codec(), codec));
+    }
+    let code = u64::from(hasher);
+    if cid.hash().code() != code {
+        return Err(actor_error!(illegal_argument;
+            "CID {} is hashed with {:#x}, expected {:#x}", cid, cid.hash().code(), code));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multibase::Base;
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{CborStore, DAG_CBOR, IPLD_RAW};
+    use fvm_shared::error::ExitCode;
+
+    use super::{cbor_cid, check_cid, cid_of, format_cid, parse_cid};
+
+    #[test]
+    fn compute_format_and_check() {
+        let store = MemoryBlockstore::new();
+        let value = ("state", 1u64);
+        let stored = store.put_cbor(&value, Code::Blake2b256).unwrap();
+        assert_eq!(cbor_cid(&value).unwrap(), stored);
+        check_cid(&stored, DAG_CBOR, Code::Blake2b256).unwrap();
+
+        let raw = cid_of(IPLD_RAW, Code::Sha2_256, b"foo");
+        for base in [Base::Base32Lower, Base::Base58Btc, Base::Base64] {
+            let s = format_cid(&raw, base).unwrap();
+            assert_eq!(parse_cid(&s).unwrap(), raw);
+        }
+        assert_eq!(
+            format_cid(&raw, Base::Base64).unwrap(),
+            "mAVUSICwmtGto/8aP+ZtFPB0wQTQTQi1wZIO/oPmKXohiZueu"
+        );
+        assert!(parse_cid("not a cid").is_err());
+
+        let err = check_cid(&raw, DAG_CBOR, Code::Sha2_256).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert!(check_cid(&raw, IPLD_RAW, Code::Blake2b256).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::tuple::*;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+/// Counts the consecutive failures of an operation, to be embedded in the actor state,
+/// and stops attempting it once they reach a threshold, until it's manually reset.
+///
+/// Meant for operations retried periodically, e.g. submitting a checkpoint to the parent
+/// from cron, where a persistent failure would otherwise burn gas every epoch.
+///
+/// # Example
+/// ```
+/// use primitives::CircuitBreaker;
+///
+/// let mut breaker = CircuitBreaker::new(2);
+/// breaker.record(false);
+/// assert!(!breaker.is_open());
+/// breaker.record(false);
+/// assert!(breaker.is_open());
+///
+/// breaker.reset();
+/// assert!(!breaker.is_open());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize_tuple, Deserialize_tuple)]
+pub struct CircuitBreaker {
+    threshold: u64,
+    failures: u64,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker that opens after `threshold` consecutive failures.
+    pub fn new(threshold: u64) -> Self {
+        Self {
+            threshold,
+            failures: 0,
+        }
+    }
+
+    /// Number of failures since the last success or reset.
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Whether the operation should no longer be attempted.
+    pub fn is_open(&self) -> bool {
+        self.threshold > 0 && self.failures >= self.threshold
+    }
+
+    /// Record the outcome of an attempt.
+    pub fn record(&mut self, success: bool) {
+        if success {
+            self.failures = 0;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+        }
+    }
+
+    /// Close the breaker, allowing the operation to be attempted again.
+    /// Who is allowed to do this is up to the actor.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Call a method from `ActorCode::around_method` unless the breaker selected from the
+    /// state `T` is open, and record its outcome in the state, so that a method is guarded
+    /// by naming it there rather than wrapping the operation in it.
+    ///
+    /// An open breaker aborts the call. A failed call is recorded and reported as a debug
+    /// event, but succeeds with no return value, as aborting would revert the state,
+    /// including the recorded failure.
+    ///
+    /// The method is called outside of a state transaction, so it can send messages.
+    ///
+    /// ```ignore
+    /// fn around_method<RT: Runtime>(rt: &mut RT, method: MethodNum, call: impl FnOnce(&mut RT)
+    ///     -> Result<Option<IpldBlock>, ActorError>) -> Result<Option<IpldBlock>, ActorError> {
+    ///     match Method::from_u64(method) {
+    ///         Some(Method::SubmitCheckpoint) => CircuitBreaker::around_method(
+    ///             rt, "submit", |st: &mut State| &mut st.submit, call),
+    ///         _ => call(rt),
+    ///     }
+    /// }
+    /// ```
+    pub fn around_method<T, RT>(
+        rt: &mut RT,
+        name: &str,
+        breaker: impl Fn(&mut T) -> &mut CircuitBreaker,
+        call: impl FnOnce(&mut RT) -> Result<Option<IpldBlock>, ActorError>,
+    ) -> Result<Option<IpldBlock>, ActorError>
+    where
+        T: Serialize + DeserializeOwned,
+        RT: Runtime,
+    {
+        let mut st: T = rt.state()?;
+        let current = breaker(&mut st);
+        if current.is_open() {
+            return Err(actor_error!(forbidden;
+                "circuit breaker of {} is open after {} failures", name, current.failures));
+        }
+
+        let res = call(rt);
+
+        let failures = rt.transaction(|st: &mut T, _| {
+            let current = breaker(st);
+            current.record(res.is_ok());
+            Ok(current.failures)
+        })?;
+        res.or_else(|e| {
+            rt.debug_event(
+                "circuit-breaker",
+                &[("name", &name), ("failures", &failures), ("error", &e)],
+            );
+            Ok(None)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::runtime::{ActorCode, Runtime};
+    use fil_actors_runtime::test_utils::{MockRuntime, ACCOUNT_ACTOR_CODE_ID};
+    use fil_actors_runtime::{actor_dispatch, actor_error, actor_methods, ActorError};
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::MethodNum;
+    use num_traits::FromPrimitive;
+
+    use super::CircuitBreaker;
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct State {
+        submit: CircuitBreaker,
+    }
+
+    actor_methods! {
+        enum Method {
+            Constructor = fvm_shared::METHOD_CONSTRUCTOR,
+            Submit = fil_actors_runtime::FIRST_EXPORTED_METHOD_NUMBER,
+        }
+    }
+
+    struct Actor;
+
+    impl Actor {
+        fn constructor(_: &mut impl Runtime) -> Result<(), ActorError> {
+            Ok(())
+        }
+
+        fn submit(rt: &mut impl Runtime, ok: bool) -> Result<u64, ActorError> {
+            rt.validate_immediate_caller_accept_any()?;
+            if ok {
+                Ok(1)
+            } else {
+                Err(actor_error!(illegal_state; "parent unreachable"))
+            }
+        }
+    }
+
+    impl ActorCode for Actor {
+        type Methods = Method;
+
+        fn around_method<RT: Runtime>(
+            rt: &mut RT,
+            method: MethodNum,
+            call: impl FnOnce(&mut RT) -> Result<Option<IpldBlock>, ActorError>,
+        ) -> Result<Option<IpldBlock>, ActorError> {
+            match Method::from_u64(method) {
+                Some(Method::Submit) => CircuitBreaker::around_method(
+                    rt,
+                    "submit",
+                    |st: &mut State| &mut st.submit,
+                    call,
+                ),
+                _ => call(rt),
+            }
+        }
+
+        actor_dispatch! {
+            Constructor => constructor,
+            Submit => submit,
+        }
+    }
+
+    fn submit(rt: &mut MockRuntime, ok: bool) -> Result<Option<IpldBlock>, ActorError> {
+        rt.expect_validate_caller_any();
+        let params = IpldBlock::serialize_cbor(&ok).unwrap();
+        let res = rt.call::<Actor>(Method::Submit as MethodNum, params);
+        rt.verify();
+        res
+    }
+
+    #[test]
+    fn trips_after_consecutive_failures() {
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(101));
+        rt.replace_state(&State {
+            submit: CircuitBreaker::new(2),
+        });
+
+        // Failures are recorded, rather than aborting the call.
+        assert_eq!(submit(&mut rt, false).unwrap(), None);
+        // A success resets the count, and returns the value of the method.
+        assert!(submit(&mut rt, true).unwrap().is_some());
+        assert_eq!(submit(&mut rt, false).unwrap(), None);
+        assert_eq!(submit(&mut rt, false).unwrap(), None);
+
+        let err = rt
+            .call::<Actor>(Method::Submit as MethodNum, None)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+
+        let mut st: State = rt.get_state();
+        assert_eq!(st.submit.failures(), 2);
+        st.submit.reset();
+        rt.replace_state(&st);
+        assert!(submit(&mut rt, true).unwrap().is_some());
+    }
+}
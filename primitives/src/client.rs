@@ -0,0 +1,138 @@
+//! Reading the state of actors from outside of the FVM, e.g. in the IPC agent or an explorer,
+//! with the same types the actors use.
+//!
+//! The blocks are fetched from a node on demand through a [`BlockFetcher`], typically with the
+//! `Filecoin.ChainReadObj` RPC method, so that loading a HAMT or an AMT only fetches the nodes
+//! it actually visits.
+
+use std::convert::TryFrom;
+
+use anyhow::anyhow;
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+use fvm_ipld_encoding::CborStore;
+use serde::de::DeserializeOwned;
+
+/// Fetches blocks by CID from a node.
+pub trait BlockFetcher {
+    /// Fetch the block with the given CID, if the node has it.
+    fn fetch(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>>;
+}
+
+impl<F> BlockFetcher for F
+where
+    F: Fn(&Cid) -> anyhow::Result<Option<Vec<u8>>>,
+{
+    fn fetch(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self(cid)
+    }
+}
+
+/// A read-through `Blockstore` over a `BlockFetcher`, keeping the blocks it fetched,
+/// and the ones put into it, in memory.
+///
+/// Fetched blocks are checked against their CID, so the node doesn't have to be trusted
+/// with the content, only with the state root.
+pub struct FetchingBlockstore<F> {
+    fetcher: F,
+    cache: MemoryBlockstore,
+}
+
+impl<F: BlockFetcher> FetchingBlockstore<F> {
+    pub fn new(fetcher: F) -> Self {
+        Self {
+            fetcher,
+            cache: MemoryBlockstore::new(),
+        }
+    }
+}
+
+impl<F: BlockFetcher> Blockstore for FetchingBlockstore<F> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(data) = self.cache.get(k)? {
+            return Ok(Some(data));
+        }
+        let Some(data) = self.fetcher.fetch(k)? else {
+            return Ok(None);
+        };
+        let code = Code::try_from(k.hash().code())?;
+        if code.digest(&data) != *k.hash() {
+            return Err(anyhow!("fetched block does not match its CID {}", k));
+        }
+        self.cache.put_keyed(k, &data)?;
+        Ok(Some(data))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.cache.put_keyed(k, block)
+    }
+}
+
+/// Load the state of an actor from its root CID, e.g. the `Head` of the actor from
+/// `Filecoin.StateGetActor`.
+pub fn load_state<S, BS>(store: &BS, head: &Cid) -> anyhow::Result<S>
+where
+    S: DeserializeOwned,
+    BS: Blockstore,
+{
+    store
+        .get_cbor(head)?
+        .ok_or_else(|| anyhow!("state {} not found", head))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use cid::multihash::Code;
+    use cid::Cid;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::CborStore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+
+    use super::{load_state, FetchingBlockstore};
+    use crate::{Deposits, Withdrawals};
+
+    #[test]
+    fn read_state_through_fetcher() {
+        let node = MemoryBlockstore::new();
+        let owner = Address::new_id(100);
+        let mut deposits = Deposits::new(&node, TokenAmount::from_atto(10)).unwrap();
+        deposits
+            .lock(&node, &owner, &TokenAmount::from_atto(10))
+            .unwrap();
+        let head = node.put_cbor(&deposits, Code::Blake2b256).unwrap();
+
+        let fetches = Cell::new(0);
+        let store = FetchingBlockstore::new(|cid: &Cid| {
+            fetches.set(fetches.get() + 1);
+            node.get(cid)
+        });
+
+        let mut loaded: Deposits = load_state(&store, &head).unwrap();
+        assert_eq!(loaded, deposits);
+        let mut withdrawals = Withdrawals::new(&store).unwrap();
+        loaded.release(&store, &owner, &mut withdrawals).unwrap();
+        assert_eq!(
+            withdrawals.credit_of(&store, &owner).unwrap(),
+            TokenAmount::from_atto(10)
+        );
+
+        // Blocks already fetched are not fetched again.
+        let count = fetches.get();
+        let _: Deposits = load_state(&store, &head).unwrap();
+        assert_eq!(fetches.get(), count);
+    }
+
+    #[test]
+    fn rejects_tampered_blocks() {
+        let node = MemoryBlockstore::new();
+        let head = node.put_cbor(&1u64, Code::Blake2b256).unwrap();
+        let store = FetchingBlockstore::new(|_: &Cid| Ok(Some(vec![0x02])));
+
+        assert!(load_state::<u64, _>(&store, &head).is_err());
+        assert!(load_state::<u64, _>(&node, &head).is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+use cid::Cid;
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorError, EventBuilder};
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+
+/// Type of the event emitted whenever a code CID is allowed or disallowed by a `CodePolicy`.
+pub const CODE_POLICY_UPDATED_EVENT: &str = "code-policy-updated";
+
+/// The code CIDs an actor accepts for the actors it creates, e.g. child subnet actors,
+/// to be embedded in its state.
+///
+/// Checking the code with [`CodePolicy::require_allowed`] before `create_actor` or an
+/// `init_exec` keeps an actor from spawning children running code nobody vetted. Only the
+/// admin can change the allowlist, and every change emits an event.
+///
+/// # Example
+/// ```
+/// use primitives::CodePolicy;
+/// use fvm_shared::address::Address;
+/// use cid::Cid;
+///
+/// let policy = CodePolicy::new(Address::new_id(100), [Cid::default()]);
+///
+/// assert!(policy.require_allowed(&Cid::default()).is_ok());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct CodePolicy {
+    admin: Address,
+    /// Sorted, so that the same allowlist always has the same encoding.
+    allowed: Vec<Cid>,
+}
+
+impl CodePolicy {
+    pub fn new(admin: Address, allowed: impl IntoIterator<Item = Cid>) -> Self {
+        let mut allowed: Vec<Cid> = allowed.into_iter().collect();
+        allowed.sort();
+        allowed.dedup();
+        Self { admin, allowed }
+    }
+
+    /// The address allowed to change the policy.
+    pub fn admin(&self) -> &Address {
+        &self.admin
+    }
+
+    /// The code CIDs allowed, in ascending order.
+    pub fn allowed(&self) -> &[Cid] {
+        &self.allowed
+    }
+
+    pub fn is_allowed(&self, code_cid: &Cid) -> bool {
+        self.allowed.binary_search(code_cid).is_ok()
+    }
+
+    /// Fail with `USR_FORBIDDEN` unless the code CID is in the allowlist.
+    pub fn require_allowed(&self, code_cid: &Cid) -> Result<(), ActorError> {
+        if !self.is_allowed(code_cid) {
+            return Err(actor_error!(forbidden; "code {} is not allowed", code_cid));
+        }
+        Ok(())
+    }
+
+    /// Add a code CID to the allowlist, provided the immediate caller is the admin,
+    /// returning whether it wasn't allowed yet.
+    ///
+    /// This does not count as the caller validation of the method,
+    /// which still has to be done separately.
+    pub fn allow<RT: Runtime>(&mut self, rt: &RT, code_cid: Cid) -> Result<bool, ActorError> {
+        self.require_admin(rt)?;
+        match self.allowed.binary_search(&code_cid) {
+            Ok(_) => Ok(false),
+            Err(i) => {
+                self.allowed.insert(i, code_cid);
+                emit_updated(rt, &code_cid, true)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Remove a code CID from the allowlist, provided the immediate caller is the admin,
+    /// returning whether it was allowed. Actors already created with it are unaffected.
+    pub fn disallow<RT: Runtime>(&mut self, rt: &RT, code_cid: &Cid) -> Result<bool, ActorError> {
+        self.require_admin(rt)?;
+        match self.allowed.binary_search(code_cid) {
+            Ok(i) => {
+                self.allowed.remove(i);
+                emit_updated(rt, code_cid, false)?;
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Hand over the admin role, provided the immediate caller is the current admin.
+    pub fn set_admin<RT: Runtime>(&mut self, rt: &RT, admin: Address) -> Result<(), ActorError> {
+        self.require_admin(rt)?;
+        self.admin = admin;
+        Ok(())
+    }
+
+    fn require_admin<RT: Runtime>(&self, rt: &RT) -> Result<(), ActorError> {
+        let caller = rt.message().caller();
+        if rt.resolve_address(&self.admin) != Some(caller) {
+            return Err(actor_error!(forbidden;
+                "caller {} is not the code policy admin {}", caller, self.admin));
+        }
+        Ok(())
+    }
+}
+
+fn emit_updated<RT: Runtime>(rt: &RT, code_cid: &Cid, allowed: bool) -> Result<(), ActorError> {
+    let event = EventBuilder::new()
+        .typ(CODE_POLICY_UPDATED_EVENT)
+        .field_indexed("code", code_cid)?
+        .field("allowed", &allowed)?
+        .build();
+    rt.emit_event(&event)
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use fil_actors_runtime::test_utils::{
+        MockRuntime, ACCOUNT_ACTOR_CODE_ID, MULTISIG_ACTOR_CODE_ID,
+    };
+    use fil_actors_runtime::EventBuilder;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use super::{CodePolicy, CODE_POLICY_UPDATED_EVENT};
+
+    fn updated_event(code: &Cid, allowed: bool) -> fvm_shared::event::ActorEvent {
+        EventBuilder::new()
+            .typ(CODE_POLICY_UPDATED_EVENT)
+            .field_indexed("code", code)
+            .unwrap()
+            .field("allowed", &allowed)
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn admin_maintains_allowlist() {
+        let admin = Address::new_id(100);
+        let mut rt = MockRuntime {
+            caller: admin,
+            ..Default::default()
+        };
+        let (account, multisig) = (*ACCOUNT_ACTOR_CODE_ID, *MULTISIG_ACTOR_CODE_ID);
+        let mut policy = CodePolicy::new(admin, [account, account]);
+        assert_eq!(policy.allowed(), &[account]);
+        let err = policy.require_allowed(&multisig).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+
+        rt.expect_emitted_event(updated_event(&multisig, true));
+        rt.expect_emitted_event(updated_event(&account, false));
+        rt.call_fn(|rt| {
+            assert!(policy.allow(rt, multisig)?);
+            assert!(!policy.allow(rt, multisig)?);
+            assert!(policy.disallow(rt, &account)?);
+            assert!(!policy.disallow(rt, &account)?);
+            Ok(())
+        })
+        .unwrap();
+        rt.verify();
+        policy.require_allowed(&multisig).unwrap();
+        assert!(policy.require_allowed(&account).is_err());
+
+        rt.caller = Address::new_id(101);
+        rt.in_call = true;
+        let err = policy.allow(&rt, account).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        assert!(!policy.is_allowed(&account));
+    }
+}
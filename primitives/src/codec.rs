@@ -0,0 +1,194 @@
+//! Encoding parameters and return values as JSON for off-chain interfaces, e.g. for the IPC
+//! agent to show users what they are about to sign, with the same serde derives as on chain.
+//!
+//! Only CBOR ever goes on chain: JSON is only produced as text, and the functions handing out
+//! parameters to send or sign, such as [`params_from_json`], always encode them as CBOR.
+//!
+//! Note that the derives decide the shape of the JSON: tuple structs become arrays, and types
+//! encoded as bytes become arrays of numbers, unless their fields use the serde helpers here,
+//! as [`address`] and [`token_amount`] do to render them with their `Display` forms.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use fil_actors_runtime::parse_fil;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An encoding of parameters and return values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// The encoding used on chain.
+    Cbor,
+    /// Pretty printed JSON, for off-chain interfaces only.
+    Json,
+}
+
+impl Codec {
+    pub fn encode<T: Serialize>(self, value: &T) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Codec::Cbor => Ok(fvm_ipld_encoding::to_vec(value)?),
+            Codec::Json => Ok(serde_json::to_vec_pretty(value)?),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> anyhow::Result<T> {
+        match self {
+            Codec::Cbor => Ok(fvm_ipld_encoding::from_slice(bytes)?),
+            Codec::Json => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+
+    /// Re-encode a value of type `T` in another codec.
+    pub fn transcode<T>(self, to: Codec, bytes: &[u8]) -> anyhow::Result<Vec<u8>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        to.encode(&self.decode::<T>(bytes)?)
+    }
+}
+
+/// The CBOR parameters of a message as JSON, for users to review before signing them.
+///
+/// Fails unless the JSON describes exactly the bytes that will be signed, i.e. unless encoding
+/// the decoded parameters gives back the same CBOR, as it wouldn't with e.g. trailing data.
+pub fn preview_params<T>(params: &RawBytes) -> anyhow::Result<String>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let value: T = Codec::Cbor
+        .decode(params.bytes())
+        .context("failed to decode params")?;
+    if Codec::Cbor.encode(&value)? != params.bytes() {
+        return Err(anyhow!(
+            "params are not in the canonical encoding of their type"
+        ));
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// The CBOR parameters of a message, from JSON e.g. written or edited by a user.
+pub fn params_from_json<T>(json: &str) -> anyhow::Result<RawBytes>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let value: T = serde_json::from_str(json).context("failed to parse params")?;
+    Ok(RawBytes::new(Codec::Cbor.encode(&value)?))
+}
+
+/// Serde helpers for an `Address` field, e.g. `#[serde(with = "primitives::codec::address")]`,
+/// rendering it as e.g. `"f0100"` in JSON, while its CBOR encoding is left as is.
+pub mod address {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(address: &Address, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(address)
+        } else {
+            address.serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Address, D::Error> {
+        if d.is_human_readable() {
+            Address::from_str(&String::deserialize(d)?).map_err(de::Error::custom)
+        } else {
+            Address::deserialize(d)
+        }
+    }
+}
+
+/// Serde helpers for a `TokenAmount` field, rendering it in FIL as e.g. `"1.5"` in JSON, while
+/// its CBOR encoding is left as is.
+pub mod token_amount {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(amount: &TokenAmount, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(amount)
+        } else {
+            amount.serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<TokenAmount, D::Error> {
+        if d.is_human_readable() {
+            parse_fil(&String::deserialize(d)?).map_err(de::Error::custom)
+        } else {
+            TokenAmount::deserialize(d)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+
+    use super::{params_from_json, preview_params, Codec};
+
+    #[derive(Serialize_tuple, Deserialize_tuple, Debug, PartialEq)]
+    struct Params {
+        name: String,
+        #[serde(with = "super::address")]
+        to: Address,
+        #[serde(with = "super::token_amount")]
+        value: TokenAmount,
+        amounts: Vec<u64>,
+        memo: Option<String>,
+    }
+
+    fn params() -> Params {
+        Params {
+            name: "transfer".to_string(),
+            to: Address::new_id(100),
+            value: TokenAmount::from_nano(1_500_000_000),
+            amounts: vec![1, 2],
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn json_and_cbor() {
+        let cbor = Codec::Cbor.encode(&params()).unwrap();
+        assert_eq!(
+            cbor,
+            fvm_ipld_encoding::to_vec(&(
+                "transfer",
+                Address::new_id(100),
+                TokenAmount::from_nano(1_500_000_000),
+                [1, 2],
+                None::<String>
+            ))
+            .unwrap()
+        );
+        let json = Codec::Cbor.transcode::<Params>(Codec::Json, &cbor).unwrap();
+        let compact: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(
+            compact,
+            serde_json::json!(["transfer", "f0100", "1.5", [1, 2], null])
+        );
+        assert_eq!(Codec::Json.decode::<Params>(&json).unwrap(), params());
+        assert_eq!(
+            Codec::Json.transcode::<Params>(Codec::Cbor, &json).unwrap(),
+            cbor
+        );
+    }
+
+    #[test]
+    fn preview_is_what_gets_signed() {
+        let params = RawBytes::serialize(params()).unwrap();
+        let preview = preview_params::<Params>(&params).unwrap();
+        assert_eq!(params_from_json::<Params>(&preview).unwrap(), params);
+
+        let mut trailing = params.to_vec();
+        trailing.push(0);
+        assert!(preview_params::<Params>(&RawBytes::new(trailing)).is_err());
+        assert!(params_from_json::<Params>(r#"["transfer"]"#).is_err());
+    }
+}
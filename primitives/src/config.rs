@@ -0,0 +1,177 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorDowncast, ActorError, EventBuilder};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::error::ExitCode;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::{StoreContent, TCid, TLink};
+
+/// Type of the event emitted whenever a `Config` is updated.
+pub const CONFIG_UPDATED_EVENT: &str = "config-updated";
+
+/// Versioned configuration of an actor, to be embedded in its state.
+///
+/// The configuration value itself is stored as a separate block, and can only be
+/// changed by the admin. Every change bumps the version and emits an event.
+///
+/// # Example
+/// ```
+/// use primitives::Config;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+///
+/// let store = MemoryBlockstore::new();
+/// let config = Config::new(&store, Address::new_id(100), &10u64).unwrap();
+///
+/// assert_eq!(0, config.version());
+/// assert_eq!(10, *config.get(&store).unwrap());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+#[serde(bound = "")]
+pub struct Config<T> {
+    admin: Address,
+    version: u64,
+    value: TCid<TLink<T>>,
+}
+
+impl<T> Config<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Store the initial configuration value, at version 0.
+    pub fn new<S: Blockstore>(store: &S, admin: Address, value: &T) -> anyhow::Result<Self> {
+        Ok(Self {
+            admin,
+            version: 0,
+            value: TCid::new_link(store, value)?,
+        })
+    }
+
+    /// The address allowed to change the configuration.
+    pub fn admin(&self) -> &Address {
+        &self.admin
+    }
+
+    /// Number of updates applied since the configuration was created.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Load the current configuration value.
+    pub fn get<'s, S: Blockstore>(&self, store: &'s S) -> anyhow::Result<StoreContent<'s, S, T>> {
+        self.value.load(store)
+    }
+
+    /// Modify the configuration value, provided the immediate caller is the admin.
+    ///
+    /// This does not count as the caller validation of the method,
+    /// which still has to be done separately.
+    pub fn update<RT, R>(
+        &mut self,
+        rt: &RT,
+        f: impl FnOnce(&mut T) -> Result<R, ActorError>,
+    ) -> Result<R, ActorError>
+    where
+        RT: Runtime,
+    {
+        self.require_admin(rt)?;
+
+        let mut value = self.value.load(rt.store()).map_err(|e| {
+            e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to load config")
+        })?;
+        let result = f(&mut value)?;
+        self.value.flush(value).map_err(|e| {
+            e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to flush config")
+        })?;
+
+        self.version += 1;
+        self.emit_updated(rt)?;
+        Ok(result)
+    }
+
+    /// Hand over the admin role, provided the immediate caller is the current admin.
+    pub fn set_admin<RT: Runtime>(&mut self, rt: &RT, admin: Address) -> Result<(), ActorError> {
+        self.require_admin(rt)?;
+        self.admin = admin;
+        self.version += 1;
+        self.emit_updated(rt)
+    }
+
+    fn require_admin<RT: Runtime>(&self, rt: &RT) -> Result<(), ActorError> {
+        let caller = rt.message().caller();
+        if rt.resolve_address(&self.admin) != Some(caller) {
+            return Err(actor_error!(forbidden;
+                "caller {} is not the config admin {}", caller, self.admin));
+        }
+        Ok(())
+    }
+
+    fn emit_updated<RT: Runtime>(&self, rt: &RT) -> Result<(), ActorError> {
+        let event = EventBuilder::new()
+            .typ(CONFIG_UPDATED_EVENT)
+            .field_indexed("version", &self.version)?
+            .field("admin", &self.admin)?
+            .build();
+        rt.emit_event(&event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::MockRuntime;
+    use fil_actors_runtime::EventBuilder;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Config, CONFIG_UPDATED_EVENT};
+
+    fn updated_event(version: u64, admin: &Address) -> fvm_shared::event::ActorEvent {
+        EventBuilder::new()
+            .typ(CONFIG_UPDATED_EVENT)
+            .field_indexed("version", &version)
+            .unwrap()
+            .field("admin", admin)
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn admin_can_update() {
+        let admin = Address::new_id(100);
+        let mut rt = MockRuntime {
+            caller: admin,
+            ..Default::default()
+        };
+        let mut config = Config::new(rt.store.as_ref(), admin, &10u64).unwrap();
+
+        rt.expect_emitted_event(updated_event(1, &admin));
+        rt.call_fn(|rt| {
+            Ok(config.update(rt, |v| {
+                *v += 1;
+                Ok(())
+            })?)
+        })
+        .unwrap();
+        rt.verify();
+
+        assert_eq!(config.version(), 1);
+        assert_eq!(*config.get(rt.store.as_ref()).unwrap(), 11);
+    }
+
+    #[test]
+    fn non_admin_cannot_update() {
+        let mut rt = MockRuntime {
+            caller: Address::new_id(101),
+            ..Default::default()
+        };
+        let mut config = Config::new(rt.store.as_ref(), Address::new_id(100), &10u64).unwrap();
+
+        rt.in_call = true;
+        let err = config.update(&rt, |_| Ok(())).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        assert_eq!(config.version(), 0);
+    }
+}
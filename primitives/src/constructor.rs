@@ -0,0 +1,260 @@
+use std::iter;
+
+use fil_actors_runtime::runtime::{Policy, Runtime};
+use fil_actors_runtime::{actor_error, ActorDowncast, ActorError, INIT_ACTOR_ADDR};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use serde::de::{Deserialize, DeserializeOwned};
+use serde::ser::Serialize;
+
+use crate::{Config, StoreContent};
+
+/// The parameters most constructors take: the owner of the actor, the name of the network
+/// it's deployed to, the overrides of the network's policy for this actor and the initial
+/// value of its configuration, where actors keep the parameters they let the owner tune.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct StdConstructorParams<T> {
+    pub owner: Address,
+    pub network_name: String,
+    pub policy: PolicyOverrides,
+    pub config: T,
+}
+
+// By hand, as for `Claim`, so that the struct itself doesn't need bounds on `T`.
+impl<T: Serialize> Serialize for StdConstructorParams<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.owner, &self.network_name, &self.policy, &self.config).serialize(serializer)
+    }
+}
+
+impl<'d, T: DeserializeOwned> Deserialize<'d> for StdConstructorParams<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'d>,
+    {
+        let (owner, network_name, policy, config) = Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            owner,
+            network_name,
+            policy,
+            config,
+        })
+    }
+}
+
+/// Economic parameters of the network's `Policy` set differently for one actor, e.g. a higher
+/// collateral for the validators of a subnet. Those left unset are taken from the policy.
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize_tuple, Deserialize_tuple)]
+pub struct PolicyOverrides {
+    pub min_validator_collateral: Option<TokenAmount>,
+    pub cross_msg_fee: Option<TokenAmount>,
+    pub checkpoint_reward: Option<TokenAmount>,
+}
+
+impl PolicyOverrides {
+    /// The policy with the overrides applied.
+    pub fn apply(&self, mut policy: Policy) -> Policy {
+        if let Some(amount) = &self.min_validator_collateral {
+            policy = policy.with_min_validator_collateral(amount.clone());
+        }
+        if let Some(amount) = &self.cross_msg_fee {
+            policy = policy.with_cross_msg_fee(amount.clone());
+        }
+        if let Some(amount) = &self.checkpoint_reward {
+            policy = policy.with_checkpoint_reward(amount.clone());
+        }
+        policy
+    }
+
+    fn validate(&self) -> Result<(), ActorError> {
+        let amounts = [
+            ("min validator collateral", &self.min_validator_collateral),
+            ("cross message fee", &self.cross_msg_fee),
+            ("checkpoint reward", &self.checkpoint_reward),
+        ];
+        for (what, amount) in amounts {
+            if let Some(amount) = amount.as_ref().filter(|a| a.is_negative()) {
+                return Err(actor_error!(illegal_argument; "negative {} {}", what, amount));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The part of the state set up from `StdConstructorParams`, to be embedded in the state.
+///
+/// The owner is the admin of the configuration, so ownership is transferred with
+/// `config_mut().set_admin`.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+#[serde(bound = "")]
+pub struct StdState<T> {
+    network_name: String,
+    policy: PolicyOverrides,
+    config: Config<T>,
+}
+
+impl<T> StdState<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// The name of the network the actor was deployed to.
+    pub fn network_name(&self) -> &str {
+        &self.network_name
+    }
+
+    /// The policy of the network the actor runs on, with the overrides it was constructed with.
+    pub fn policy(&self, rt: &impl Runtime) -> Policy {
+        self.policy.apply(rt.policy().clone())
+    }
+
+    /// The ID address of the owner.
+    pub fn owner(&self) -> &Address {
+        self.config.admin()
+    }
+
+    pub fn config(&self) -> &Config<T> {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config<T> {
+        &mut self.config
+    }
+
+    /// Load the current configuration value.
+    pub fn get_config<'s, S: Blockstore>(
+        &self,
+        store: &'s S,
+    ) -> anyhow::Result<StoreContent<'s, S, T>> {
+        self.config.get(store)
+    }
+}
+
+/// Do what constructors have in common: check that the actor is being constructed by the
+/// init actor, validate the parameters and create the `StdState`, with the owner resolved
+/// to its ID address, failing for negative amounts in the policy overrides.
+///
+/// # Example
+/// ```ignore
+/// fn constructor(rt: &mut impl Runtime, params: StdConstructorParams<Settings>) -> Result<(), ActorError> {
+///     let std = init_standard_state(rt, params)?;
+///     rt.create(&State { std, .. })
+/// }
+/// ```
+pub fn init_standard_state<T, RT>(
+    rt: &mut RT,
+    params: StdConstructorParams<T>,
+) -> Result<StdState<T>, ActorError>
+where
+    T: Serialize + DeserializeOwned,
+    RT: Runtime,
+{
+    rt.validate_immediate_caller_is(iter::once(&INIT_ACTOR_ADDR))?;
+
+    if params.network_name.is_empty() {
+        return Err(actor_error!(illegal_argument; "network name must not be empty"));
+    }
+    params.policy.validate()?;
+    let owner = rt.resolve_address(&params.owner).ok_or_else(
+        || actor_error!(illegal_argument; "failed to resolve owner {}", params.owner),
+    )?;
+    let config = Config::new(rt.store(), owner, &params.config)
+        .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to store config"))?;
+
+    Ok(StdState {
+        network_name: params.network_name,
+        policy: params.policy,
+        config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::runtime::Policy;
+    use fil_actors_runtime::test_utils::{MockRuntime, INIT_ACTOR_CODE_ID};
+    use fil_actors_runtime::INIT_ACTOR_ADDR;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{init_standard_state, PolicyOverrides, StdConstructorParams};
+
+    fn params(owner: Address, network_name: &str) -> StdConstructorParams<u64> {
+        StdConstructorParams {
+            owner,
+            network_name: network_name.into(),
+            policy: PolicyOverrides::default(),
+            config: 10,
+        }
+    }
+
+    #[test]
+    fn init_from_params() {
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*INIT_ACTOR_CODE_ID, INIT_ACTOR_ADDR);
+        let key = Address::new_secp256k1(&[1; 65]).unwrap();
+        rt.add_id_address(key, Address::new_id(100));
+        rt.in_call = true;
+
+        rt.expect_validate_caller_addr(vec![INIT_ACTOR_ADDR]);
+        let st = init_standard_state(&mut rt, params(key, "/r123")).unwrap();
+        rt.verify();
+
+        assert_eq!(st.network_name(), "/r123");
+        assert_eq!(st.owner(), &Address::new_id(100));
+        assert_eq!(*st.get_config(rt.store.as_ref()).unwrap(), 10);
+        assert_eq!(st.config().version(), 0);
+        assert_eq!(st.policy(&rt), Policy::default());
+
+        let bytes = fvm_ipld_encoding::to_vec(&params(key, "/r123")).unwrap();
+        let decoded: StdConstructorParams<u64> = fvm_ipld_encoding::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, params(key, "/r123"));
+    }
+
+    #[test]
+    fn policy_overrides() {
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*INIT_ACTOR_CODE_ID, INIT_ACTOR_ADDR);
+        rt.in_call = true;
+
+        let collateral = TokenAmount::from_whole(100);
+        let mut p = params(Address::new_id(100), "/r123");
+        p.policy.min_validator_collateral = Some(collateral.clone());
+        rt.expect_validate_caller_addr(vec![INIT_ACTOR_ADDR]);
+        let st = init_standard_state(&mut rt, p).unwrap();
+
+        let policy = st.policy(&rt);
+        assert_eq!(policy.economics.min_validator_collateral, collateral);
+        assert_eq!(
+            policy.economics.cross_msg_fee,
+            rt.policy.economics.cross_msg_fee
+        );
+
+        let mut p = params(Address::new_id(100), "/r123");
+        p.policy.cross_msg_fee = Some(TokenAmount::from_atto(-1));
+        rt.expect_validate_caller_addr(vec![INIT_ACTOR_ADDR]);
+        let err = init_standard_state(&mut rt, p).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+    }
+
+    #[test]
+    fn invalid_params() {
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*INIT_ACTOR_CODE_ID, INIT_ACTOR_ADDR);
+        rt.in_call = true;
+
+        for p in [
+            params(Address::new_id(100), ""),
+            params(Address::new_secp256k1(&[1; 65]).unwrap(), "/r123"),
+        ] {
+            rt.expect_validate_caller_addr(vec![INIT_ACTOR_ADDR]);
+            let err = init_standard_state(&mut rt, p).unwrap_err();
+            assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Deref;
+
+use anyhow::Result;
+use fil_actors_runtime::fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::{HamtError, TAmt, TCid, TCidContent, THamt};
+
+/// A typed HAMT or AMT along with the number of entries in it, so that `len` doesn't
+/// have to traverse the data structure. Serialized as `[root, len]`.
+///
+/// The count is kept up to date by `modify` and `update`, which is why the root
+/// is only exposed for reading.
+///
+/// # Example
+/// ```
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_ipld_hamt::BytesKey;
+/// use primitives::{Counted, THamt};
+///
+/// let store = MemoryBlockstore::new();
+/// let mut validators: Counted<THamt<String, u64>> = Counted::new_hamt(&store).unwrap();
+///
+/// validators.update(&store, |map| {
+///     map.set(BytesKey::from("alice"), 1)?;
+///     map.set(BytesKey::from("bob"), 2)?;
+///     map.set(BytesKey::from("alice"), 3)?;
+///     Ok(())
+/// }).unwrap();
+///
+/// assert_eq!(validators.len(), 2);
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Counted<T: TCidContent> {
+    root: TCid<T>,
+    len: u64,
+}
+
+impl<T: TCidContent> Counted<T> {
+    pub fn root(&self) -> &TCid<T> {
+        &self.root
+    }
+
+    /// Number of entries in the collection.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<K, V, const W: u32> Counted<THamt<K, V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    /// Initialize an empty map, flush it to the store and capture the `Cid`.
+    pub fn new_hamt<S: Blockstore>(store: &S) -> Result<Self> {
+        Ok(Self {
+            root: TCid::new_hamt(store)?,
+            len: 0,
+        })
+    }
+
+    /// Load the map for reading; changes made to it are not flushed.
+    pub fn load<'s, S: Blockstore>(&self, store: &'s S) -> Result<Hamt<&'s S, V>> {
+        self.root.load(store)
+    }
+
+    /// Load, modify and flush the map, counting the entries inserted and deleted.
+    pub fn modify<'s, S: Blockstore, R>(
+        &mut self,
+        store: &'s S,
+        f: impl FnOnce(&mut CountingHamt<'s, S, V>) -> Result<R>,
+    ) -> Result<R> {
+        let mut map = CountingHamt {
+            hamt: self.root.load(store)?,
+            len: self.len,
+        };
+        let result = f(&mut map)?;
+        self.root.flush(map.hamt)?;
+        self.len = map.len;
+        Ok(result)
+    }
+
+    /// Load, modify and flush the map.
+    pub fn update<'s, S: Blockstore>(
+        &mut self,
+        store: &'s S,
+        f: impl FnOnce(&mut CountingHamt<'s, S, V>) -> Result<()>,
+    ) -> Result<()> {
+        self.modify(store, f)
+    }
+}
+
+impl<V, const W: u32> Counted<TAmt<V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    /// Initialize an empty array, flush it to the store and capture the `Cid`.
+    pub fn new_amt<S: Blockstore>(store: &S) -> Result<Self> {
+        Ok(Self {
+            root: TCid::new_amt(store)?,
+            len: 0,
+        })
+    }
+
+    /// Load the array for reading; changes made to it are not flushed.
+    pub fn load<'s, S: Blockstore>(&self, store: &'s S) -> Result<Amt<V, &'s S>> {
+        self.root.load(store)
+    }
+
+    /// Load, modify and flush the array. The AMT maintains its own count,
+    /// which is copied here so it can be read without loading the root.
+    pub fn modify<'s, S: Blockstore, R>(
+        &mut self,
+        store: &'s S,
+        f: impl FnOnce(&mut Amt<V, &'s S>) -> Result<R>,
+    ) -> Result<R> {
+        let mut amt = self.root.load(store)?;
+        let result = f(&mut amt)?;
+        let amt = self.root.flush(amt)?;
+        self.len = amt.count();
+        Ok(result)
+    }
+
+    /// Load, modify and flush the array.
+    pub fn update<'s, S: Blockstore>(
+        &mut self,
+        store: &'s S,
+        f: impl FnOnce(&mut Amt<V, &'s S>) -> Result<()>,
+    ) -> Result<()> {
+        self.modify(store, f)
+    }
+}
+
+/// A loaded HAMT that counts its entries as they are inserted and deleted.
+///
+/// The read operations of the underlying `Hamt` are available through `Deref`.
+pub struct CountingHamt<'s, S: Blockstore, V> {
+    hamt: Hamt<&'s S, V>,
+    len: u64,
+}
+
+impl<'s, S: Blockstore, V> CountingHamt<'s, S, V>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Set a value, returning the previous one if there was any.
+    pub fn set(&mut self, key: BytesKey, value: V) -> Result<Option<V>, HamtError> {
+        let previous = self.hamt.set(key, value)?;
+        if previous.is_none() {
+            self.len += 1;
+        }
+        Ok(previous)
+    }
+
+    /// Set a value unless the key is already present, returning whether it was set.
+    pub fn set_if_absent(&mut self, key: BytesKey, value: V) -> Result<bool, HamtError> {
+        let inserted = self.hamt.set_if_absent(key, value)?;
+        if inserted {
+            self.len += 1;
+        }
+        Ok(inserted)
+    }
+
+    /// Delete a key, returning the removed entry if there was any.
+    pub fn delete(&mut self, key: &BytesKey) -> Result<Option<(BytesKey, V)>, HamtError> {
+        let removed = self.hamt.delete(key)?;
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        Ok(removed)
+    }
+}
+
+impl<'s, S: Blockstore, V> Deref for CountingHamt<'s, S, V> {
+    type Target = Hamt<&'s S, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.hamt
+    }
+}
+
+/// Serializes as `[root, len]`.
+impl<T: TCidContent> serde::Serialize for Counted<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.root, self.len).serialize(serializer)
+    }
+}
+
+impl<'d, T: TCidContent> serde::Deserialize<'d> for Counted<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'d>,
+    {
+        let (root, len) = <(TCid<T>, u64)>::deserialize(deserializer)?;
+        Ok(Self { root, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_ipld_hamt::BytesKey;
+
+    use super::Counted;
+    use crate::{TAmt, THamt};
+
+    #[test]
+    fn hamt_len_follows_modifications() {
+        let store = MemoryBlockstore::new();
+        let mut users: Counted<THamt<String, u64>> = Counted::new_hamt(&store).unwrap();
+        assert!(users.is_empty());
+
+        users
+            .update(&store, |map| {
+                map.set(BytesKey::from("alice"), 1)?;
+                map.set(BytesKey::from("alice"), 2)?;
+                assert!(map.set_if_absent(BytesKey::from("bob"), 1)?);
+                assert!(!map.set_if_absent(BytesKey::from("bob"), 2)?);
+                map.set(BytesKey::from("carol"), 1)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(users.len(), 3);
+
+        users
+            .update(&store, |map| {
+                assert!(map.delete(&BytesKey::from("alice"))?.is_some());
+                assert!(map.delete(&BytesKey::from("dave"))?.is_none());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(users.len(), 2);
+
+        // A failed modification leaves the count alone.
+        let res = users.update(&store, |map| {
+            map.set(BytesKey::from("erin"), 1)?;
+            Err(anyhow::anyhow!("abort"))
+        });
+        assert!(res.is_err());
+        assert_eq!(users.len(), 2);
+
+        let decoded: Counted<THamt<String, u64>> = from_slice(&to_vec(&users).unwrap()).unwrap();
+        assert_eq!(decoded, users);
+    }
+
+    #[test]
+    fn amt_len_follows_modifications() {
+        let store = MemoryBlockstore::new();
+        let mut items: Counted<TAmt<u64>> = Counted::new_amt(&store).unwrap();
+
+        items
+            .update(&store, |amt| {
+                amt.set(0, 10)?;
+                amt.set(5, 15)?;
+                amt.delete(0)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items.load(&store).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,274 @@
+use fil_actors_runtime::cbor::block_from_raw_bytes;
+use fil_actors_runtime::runtime::{Policy, Runtime};
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::MethodNum;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::exit_codes::{
+    USR_CROSS_MSG_BATCH_TOO_LARGE, USR_CROSS_MSG_TOO_LARGE, USR_SUBNET_TOO_DEEP,
+    USR_UNEXPECTED_NONCE,
+};
+use crate::{IPCAddress, SubnetID};
+
+/// A message sent between addresses in different subnets.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct CrossMsg {
+    pub from: IPCAddress,
+    pub to: IPCAddress,
+    pub method: MethodNum,
+    pub params: RawBytes,
+    pub value: TokenAmount,
+    pub nonce: u64,
+}
+
+impl CrossMsg {
+    /// Check the message against the limits of the policy.
+    pub fn check(&self, policy: &Policy) -> Result<(), ActorError> {
+        let size = self.params.len() as u64;
+        if size > policy.max_cross_msg_params_size {
+            return Err(ActorError::unchecked(
+                USR_CROSS_MSG_TOO_LARGE,
+                format!(
+                    "cross message {} has {} bytes of params, more than the limit of {}",
+                    self.nonce, size, policy.max_cross_msg_params_size
+                ),
+            ));
+        }
+        check_subnet_depth(self.from.subnet(), policy)?;
+        check_subnet_depth(self.to.subnet(), policy)
+    }
+}
+
+/// Check a batch of cross messages, and each message in it, against the limits of the policy.
+pub fn check_cross_msgs(msgs: &[CrossMsg], policy: &Policy) -> Result<(), ActorError> {
+    if msgs.len() as u64 > policy.max_cross_msgs_per_batch {
+        return Err(ActorError::unchecked(
+            USR_CROSS_MSG_BATCH_TOO_LARGE,
+            format!(
+                "batch of {} cross messages exceeds the limit of {}",
+                msgs.len(),
+                policy.max_cross_msgs_per_batch
+            ),
+        ));
+    }
+    msgs.iter().try_for_each(|msg| msg.check(policy))
+}
+
+fn check_subnet_depth(subnet_id: &SubnetID, policy: &Policy) -> Result<(), ActorError> {
+    let depth = subnet_id.children().len() as u64;
+    if depth > policy.max_subnet_depth {
+        return Err(ActorError::unchecked(
+            USR_SUBNET_TOO_DEEP,
+            format!(
+                "subnet {} is {} levels deep, more than the limit of {}",
+                subnet_id, depth, policy.max_subnet_depth
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// What `apply_cross_msgs` did with each message of a batch, by nonce.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct CrossMsgReport {
+    /// Messages delivered successfully.
+    pub applied: Vec<u64>,
+    /// Duplicates, and messages applied by an earlier batch.
+    pub skipped: Vec<u64>,
+    /// Messages whose delivery failed with the exit code. Their nonce is consumed all the same.
+    pub failed: Vec<(u64, ExitCode)>,
+    /// Messages left for a later batch once the budget was used up.
+    pub pending: Vec<u64>,
+    /// The nonce expected of the next message.
+    pub next_nonce: u64,
+}
+
+/// Apply a batch of cross messages destined to this subnet, in nonce order, sending each
+/// message to its recipient, and keep the nonce selected from the actor state `T` up to date.
+///
+/// The batch is checked against the policy, sorted and deduplicated first. Messages with a
+/// nonce lower than the expected one are skipped as already applied, while a gap in the
+/// sequence fails the whole batch with `USR_UNEXPECTED_NONCE` before anything is sent.
+///
+/// At most `max_msgs` messages are delivered, bounding the gas spent by a single call; the rest
+/// are reported as pending. The runtime doesn't expose the gas left, so the budget is in messages.
+///
+/// The nonce is consumed in a transaction before each send, so a reentrant call can't
+/// apply the same message twice. A failed send is reported and doesn't abort the batch.
+pub fn apply_cross_msgs<T, RT>(
+    rt: &mut RT,
+    mut msgs: Vec<CrossMsg>,
+    max_msgs: usize,
+    nonce: impl Fn(&mut T) -> &mut u64,
+) -> Result<CrossMsgReport, ActorError>
+where
+    T: Serialize + DeserializeOwned,
+    RT: Runtime,
+{
+    check_cross_msgs(&msgs, rt.policy())?;
+    msgs.sort_by_key(|msg| msg.nonce);
+
+    let mut st: T = rt.state()?;
+    let mut report = CrossMsgReport {
+        next_nonce: *nonce(&mut st),
+        ..Default::default()
+    };
+
+    let mut expected = report.next_nonce;
+    let mut sequence = Vec::new();
+    for msg in msgs {
+        if msg.nonce < expected {
+            report.skipped.push(msg.nonce);
+            continue;
+        }
+        if msg.nonce > expected {
+            return Err(ActorError::unchecked(
+                USR_UNEXPECTED_NONCE,
+                format!(
+                    "expected cross message nonce {}, got {}",
+                    expected, msg.nonce
+                ),
+            ));
+        }
+        expected += 1;
+        sequence.push(msg);
+    }
+
+    for msg in sequence {
+        if report.applied.len() + report.failed.len() >= max_msgs {
+            report.pending.push(msg.nonce);
+            continue;
+        }
+
+        rt.transaction(|st: &mut T, _| {
+            let next = nonce(st);
+            if *next != msg.nonce {
+                return Err(actor_error!(illegal_state;
+                    "nonce changed to {} while applying cross message {}", next, msg.nonce));
+            }
+            *next += 1;
+            Ok(())
+        })?;
+        report.next_nonce = msg.nonce + 1;
+
+        let to = msg.to.raw_addr();
+        let params = block_from_raw_bytes(msg.params);
+        match rt.send(to, msg.method, params, msg.value) {
+            Ok(_) => report.applied.push(msg.nonce),
+            Err(e) => report.failed.push((msg.nonce, e.exit_code())),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::runtime::Policy;
+    use fil_actors_runtime::test_utils::MockRuntime;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+
+    use super::*;
+
+    fn msg(to: &SubnetID, params: Vec<u8>) -> CrossMsg {
+        CrossMsg {
+            from: IPCAddress::new(&SubnetID::default(), &Address::new_id(100)),
+            to: IPCAddress::new(to, &Address::new_id(101)),
+            method: 2,
+            params: RawBytes::new(params),
+            value: TokenAmount::from_atto(1),
+            nonce: 0,
+        }
+    }
+
+    #[test]
+    fn check_limits() {
+        let policy = Policy {
+            max_cross_msgs_per_batch: 2,
+            max_cross_msg_params_size: 4,
+            max_subnet_depth: 1,
+            ..Default::default()
+        };
+        let child = SubnetID::new_from_parent(&SubnetID::default(), Address::new_id(1000));
+        let grandchild = SubnetID::new_from_parent(&child, Address::new_id(1001));
+
+        assert!(check_cross_msgs(&[msg(&child, vec![0; 4])], &policy).is_ok());
+
+        let err = check_cross_msgs(&vec![msg(&child, vec![]); 3], &policy).unwrap_err();
+        assert_eq!(err.exit_code(), USR_CROSS_MSG_BATCH_TOO_LARGE);
+
+        let err = check_cross_msgs(&[msg(&child, vec![0; 5])], &policy).unwrap_err();
+        assert_eq!(err.exit_code(), USR_CROSS_MSG_TOO_LARGE);
+
+        let err = check_cross_msgs(&[msg(&grandchild, vec![])], &policy).unwrap_err();
+        assert_eq!(err.exit_code(), USR_SUBNET_TOO_DEEP);
+    }
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct State {
+        applied_nonce: u64,
+    }
+
+    fn apply(rt: &mut MockRuntime, msgs: Vec<CrossMsg>) -> Result<CrossMsgReport, ActorError> {
+        rt.in_call = true;
+        let res = apply_cross_msgs(rt, msgs, 2, |st: &mut State| &mut st.applied_nonce);
+        rt.in_call = false;
+        rt.verify();
+        res
+    }
+
+    fn nonced(nonce: u64) -> CrossMsg {
+        CrossMsg {
+            nonce,
+            ..msg(&SubnetID::default(), vec![])
+        }
+    }
+
+    #[test]
+    fn apply_in_order_within_budget() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&State { applied_nonce: 1 });
+        rt.set_balance(TokenAmount::from_atto(10));
+
+        let to = Address::new_id(101);
+        rt.expect_send(to, 2, None, TokenAmount::from_atto(1), None, ExitCode::OK);
+        rt.expect_send(
+            to,
+            2,
+            None,
+            TokenAmount::from_atto(1),
+            None,
+            ExitCode::USR_FORBIDDEN,
+        );
+
+        let msgs = vec![nonced(3), nonced(0), nonced(1), nonced(2), nonced(1)];
+        let report = apply(&mut rt, msgs).unwrap();
+        assert_eq!(
+            report,
+            CrossMsgReport {
+                applied: vec![1],
+                skipped: vec![0, 1],
+                failed: vec![(2, ExitCode::USR_FORBIDDEN)],
+                pending: vec![3],
+                next_nonce: 3,
+            }
+        );
+        assert_eq!(rt.get_state::<State>().applied_nonce, 3);
+    }
+
+    #[test]
+    fn apply_rejects_gaps() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&State { applied_nonce: 0 });
+
+        let err = apply(&mut rt, vec![nonced(0), nonced(2)]).unwrap_err();
+        assert_eq!(err.exit_code(), USR_UNEXPECTED_NONCE);
+        assert_eq!(rt.get_state::<State>().applied_nonce, 0);
+    }
+}
@@ -0,0 +1,210 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use num_traits::Zero;
+
+use crate::{TCid, THamt, Withdrawals};
+
+/// Deposits locked for every entry an address adds to the state of an actor, to be
+/// embedded in the state, so that growing the state has a cost and can't be used to spam it.
+///
+/// The deposit is taken from the value sent with the message adding the entry, and when
+/// the entry is deleted it's credited back to the owner in [`Withdrawals`].
+///
+/// # Example
+/// ```
+/// use primitives::{Deposits, Withdrawals};
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut deposits = Deposits::new(&store, TokenAmount::from_atto(10)).unwrap();
+/// let mut withdrawals = Withdrawals::new(&store).unwrap();
+///
+/// let owner = Address::new_id(100);
+/// let excess = deposits.lock(&store, &owner, &TokenAmount::from_atto(15)).unwrap();
+/// assert_eq!(excess, TokenAmount::from_atto(5));
+///
+/// deposits.release(&store, &owner, &mut withdrawals).unwrap();
+/// assert_eq!(TokenAmount::from_atto(10), withdrawals.credit_of(&store, &owner).unwrap());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Deposits {
+    per_entry: TokenAmount,
+    held: TCid<THamt<Address, TokenAmount>>,
+    total: TokenAmount,
+}
+
+impl Deposits {
+    /// Create an empty table requiring `per_entry` for every entry added.
+    ///
+    /// The amount can't be changed afterwards, so that every release refunds what was locked.
+    pub fn new<S: Blockstore>(store: &S, per_entry: TokenAmount) -> anyhow::Result<Self> {
+        Ok(Self {
+            per_entry,
+            held: TCid::new_hamt(store)?,
+            total: TokenAmount::zero(),
+        })
+    }
+
+    /// The deposit required for every entry.
+    pub fn per_entry(&self) -> &TokenAmount {
+        &self.per_entry
+    }
+
+    /// Sum of all the deposits held, which the actor has to keep in its balance.
+    pub fn total(&self) -> &TokenAmount {
+        &self.total
+    }
+
+    /// The deposits currently held for the entries of an address.
+    pub fn held_by<S: Blockstore>(
+        &self,
+        store: &S,
+        owner: &Address,
+    ) -> anyhow::Result<TokenAmount> {
+        let held = self.held.load(store)?;
+        let amount = held.get(&BytesKey::from(owner.to_bytes()))?;
+        Ok(amount.cloned().unwrap_or_default())
+    }
+
+    /// Lock the deposit of a new entry of `owner` out of `paid`, usually the value received
+    /// with the message, failing with `USR_INSUFFICIENT_FUNDS` if it doesn't cover it.
+    ///
+    /// Returns the excess paid, for the actor to refund or keep.
+    pub fn lock<S: Blockstore>(
+        &mut self,
+        store: &S,
+        owner: &Address,
+        paid: &TokenAmount,
+    ) -> anyhow::Result<TokenAmount> {
+        if paid < &self.per_entry {
+            return Err(actor_error!(insufficient_funds;
+                "deposit of {} required for a new entry of {}, got {}", self.per_entry, owner, paid)
+            .into());
+        }
+        if !self.per_entry.is_zero() {
+            let per_entry = &self.per_entry;
+            self.held.update(store, |held| {
+                let key = BytesKey::from(owner.to_bytes());
+                let amount = held.get(&key)?.cloned().unwrap_or_default();
+                held.set(key, amount + per_entry)?;
+                Ok(())
+            })?;
+            self.total += &self.per_entry;
+        }
+        Ok(paid - &self.per_entry)
+    }
+
+    /// Release the deposit of a deleted entry of `owner`, crediting it to them in `withdrawals`.
+    pub fn release<S: Blockstore>(
+        &mut self,
+        store: &S,
+        owner: &Address,
+        withdrawals: &mut Withdrawals,
+    ) -> anyhow::Result<()> {
+        if self.per_entry.is_zero() {
+            return Ok(());
+        }
+        let per_entry = &self.per_entry;
+        self.held.update(store, |held| {
+            let key = BytesKey::from(owner.to_bytes());
+            let amount = held.get(&key)?.cloned().unwrap_or_default();
+            if &amount < per_entry {
+                return Err(actor_error!(illegal_state;
+                    "no deposit of {} held for an entry of {}", per_entry, owner)
+                .into());
+            }
+            let rest = amount - per_entry;
+            if rest.is_zero() {
+                held.delete(&key)?;
+            } else {
+                held.set(key, rest)?;
+            }
+            Ok(())
+        })?;
+        self.total -= &self.per_entry;
+        withdrawals.credit(store, owner, &self.per_entry)
+    }
+}
+
+/// Check that the balance of the actor is at least `min_balance`, failing with
+/// `USR_INSUFFICIENT_FUNDS` otherwise, for actors that guard their state-growing
+/// operations with a minimum balance rather than per entry deposits.
+pub fn check_min_balance<RT: Runtime>(
+    rt: &RT,
+    min_balance: &TokenAmount,
+) -> Result<(), ActorError> {
+    let balance = rt.current_balance();
+    if &balance < min_balance {
+        return Err(actor_error!(insufficient_funds;
+            "balance {} is below the minimum of {}", balance, min_balance));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::MockRuntime;
+    use fil_actors_runtime::{ActorDowncast, ActorError};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{check_min_balance, Deposits};
+    use crate::Withdrawals;
+
+    fn exit_code(e: anyhow::Error) -> ExitCode {
+        e.downcast_default(ExitCode::USR_UNSPECIFIED, "")
+            .exit_code()
+    }
+
+    #[test]
+    fn lock_and_release() {
+        let store = MemoryBlockstore::new();
+        let mut deposits = Deposits::new(&store, TokenAmount::from_atto(10)).unwrap();
+        let mut withdrawals = Withdrawals::new(&store).unwrap();
+        let owner = Address::new_id(100);
+
+        let err = deposits
+            .lock(&store, &owner, &TokenAmount::from_atto(9))
+            .unwrap_err();
+        assert_eq!(exit_code(err), ExitCode::USR_INSUFFICIENT_FUNDS);
+
+        deposits
+            .lock(&store, &owner, &TokenAmount::from_atto(10))
+            .unwrap();
+        deposits
+            .lock(&store, &owner, &TokenAmount::from_atto(10))
+            .unwrap();
+        assert_eq!(deposits.total(), &TokenAmount::from_atto(20));
+
+        deposits.release(&store, &owner, &mut withdrawals).unwrap();
+        deposits.release(&store, &owner, &mut withdrawals).unwrap();
+        assert!(deposits.total().is_zero());
+        assert!(deposits.held_by(&store, &owner).unwrap().is_zero());
+        assert_eq!(withdrawals.total(), &TokenAmount::from_atto(20));
+
+        let err = deposits
+            .release(&store, &owner, &mut withdrawals)
+            .unwrap_err();
+        assert_eq!(exit_code(err), ExitCode::USR_ILLEGAL_STATE);
+    }
+
+    #[test]
+    fn min_balance() {
+        let mut rt = MockRuntime::default();
+        rt.set_balance(TokenAmount::from_atto(10));
+        rt.in_call = true;
+
+        check_min_balance(&rt, &TokenAmount::from_atto(10)).unwrap();
+        let err: ActorError = check_min_balance(&rt, &TokenAmount::from_atto(11)).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_INSUFFICIENT_FUNDS);
+    }
+}
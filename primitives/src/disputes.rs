@@ -0,0 +1,406 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, u64_key, ActorDowncast, ActorError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use serde::de::{Deserialize, DeserializeOwned};
+use serde::ser::Serialize;
+
+use crate::{TCid, THamt, Withdrawals};
+
+/// A claim submitted to `Disputes`, e.g. a checkpoint of a child subnet.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Claim<T> {
+    pub submitter: Address,
+    pub bond: TokenAmount,
+    pub content: T,
+    pub submitted_at: ChainEpoch,
+    pub challenge: Option<Challenge>,
+}
+
+// Serializes as a tuple, as `Serialize_tuple` would. The derives only carry the where clause
+// of the struct over to their impls, so deriving them here would need `T: Serialize +
+// DeserializeOwned` on `Claim` itself, and through it on `Disputes`.
+impl<T: Serialize> Serialize for Claim<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (
+            &self.submitter,
+            &self.bond,
+            &self.content,
+            &self.submitted_at,
+            &self.challenge,
+        )
+            .serialize(serializer)
+    }
+}
+
+impl<'d, T: DeserializeOwned> Deserialize<'d> for Claim<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'d>,
+    {
+        let (submitter, bond, content, submitted_at, challenge) =
+            Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            submitter,
+            bond,
+            content,
+            submitted_at,
+            challenge,
+        })
+    }
+}
+
+/// A challenge of a `Claim`, to be resolved by verifying the claim.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Challenge {
+    pub challenger: Address,
+    pub bond: TokenAmount,
+    pub challenged_at: ChainEpoch,
+}
+
+/// How a claim was settled by `Disputes`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Settlement<T> {
+    /// The claim stands, either unchallenged or verified, and the challenger, if any, was
+    /// slashed in favour of the submitter.
+    Accepted(T),
+    /// The claim failed verification and the submitter was slashed in favour of the challenger.
+    Rejected(T),
+}
+
+/// Claims that are accepted optimistically unless challenged within a window of epochs, as
+/// in fraud proof flows between subnets and their parents, to be embedded in the state.
+///
+/// Submitting and challenging a claim both take a bond. An unchallenged claim is accepted
+/// once the window has passed. A challenged one is settled by a verification supplied by
+/// the actor, e.g. checking a fraud proof, and whoever was wrong loses their bond to the
+/// other. Bonds are paid out as credits in [`Withdrawals`].
+///
+/// # Example
+/// ```
+/// use primitives::Disputes;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let store = MemoryBlockstore::new();
+/// let bond = TokenAmount::from_atto(10);
+/// let disputes: Disputes<u64> = Disputes::new(&store, 100, bond.clone(), bond).unwrap();
+///
+/// assert_eq!(100, disputes.window());
+/// assert!(disputes.get(&store, 0).unwrap().is_none());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+#[serde(bound = "")]
+pub struct Disputes<T> {
+    window: ChainEpoch,
+    submit_bond: TokenAmount,
+    challenge_bond: TokenAmount,
+    next_id: u64,
+    claims: TCid<THamt<u64, Claim<T>>>,
+}
+
+impl<T> Disputes<T>
+where
+    T: Serialize + DeserializeOwned + Clone + PartialEq,
+{
+    /// Create an empty set of claims, each of which can be challenged for `window` epochs,
+    /// failing for a negative window.
+    pub fn new<S: Blockstore>(
+        store: &S,
+        window: ChainEpoch,
+        submit_bond: TokenAmount,
+        challenge_bond: TokenAmount,
+    ) -> anyhow::Result<Self> {
+        if window < 0 {
+            return Err(
+                actor_error!(illegal_argument; "negative challenge window {}", window).into(),
+            );
+        }
+        Ok(Self {
+            window,
+            submit_bond,
+            challenge_bond,
+            next_id: 0,
+            claims: TCid::new_hamt(store)?,
+        })
+    }
+
+    /// Number of epochs a claim can be challenged for after it was submitted.
+    pub fn window(&self) -> ChainEpoch {
+        self.window
+    }
+
+    /// Look up a pending claim by ID.
+    pub fn get<S: Blockstore>(&self, store: &S, id: u64) -> anyhow::Result<Option<Claim<T>>> {
+        let claims = self.claims.load(store)?;
+        Ok(claims.get(&u64_key(id))?.cloned())
+    }
+
+    /// Submit a claim on behalf of the immediate caller, bonding `bond`, usually the value
+    /// received, which has to cover the submission bond. Returns the ID of the claim.
+    pub fn submit<RT: Runtime>(
+        &mut self,
+        rt: &RT,
+        content: T,
+        bond: TokenAmount,
+    ) -> Result<u64, ActorError> {
+        if bond < self.submit_bond {
+            return Err(actor_error!(insufficient_funds;
+                "bond of {} required to submit a claim, got {}", self.submit_bond, bond));
+        }
+        let id = self.next_id;
+        let claim = Claim {
+            submitter: rt.message().caller(),
+            bond,
+            content,
+            submitted_at: rt.curr_epoch(),
+            challenge: None,
+        };
+        self.put(rt.store(), id, claim)?;
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    /// Challenge a claim on behalf of the immediate caller, bonding `bond`, within the window.
+    /// A claim can only be challenged once.
+    pub fn challenge<RT: Runtime>(
+        &mut self,
+        rt: &RT,
+        id: u64,
+        bond: TokenAmount,
+    ) -> Result<(), ActorError> {
+        let mut claim = self.load(rt.store(), id)?;
+        let epoch = rt.curr_epoch();
+        let closes_at = self.window_end(id, &claim)?;
+        if epoch >= closes_at {
+            return Err(actor_error!(forbidden;
+                "challenge window of claim {} closed at epoch {}, now {}", id, closes_at, epoch));
+        }
+        if claim.challenge.is_some() {
+            return Err(actor_error!(illegal_argument; "claim {} is already challenged", id));
+        }
+        if bond < self.challenge_bond {
+            return Err(actor_error!(insufficient_funds;
+                "bond of {} required to challenge a claim, got {}", self.challenge_bond, bond));
+        }
+        claim.challenge = Some(Challenge {
+            challenger: rt.message().caller(),
+            bond,
+            challenged_at: epoch,
+        });
+        self.put(rt.store(), id, claim)
+    }
+
+    /// Settle a claim: an unchallenged one is accepted once its window has passed, and a
+    /// challenged one is accepted if `verify` holds for it, and rejected otherwise.
+    ///
+    /// The loser's bond is credited to the winner in `withdrawals`, along with their own.
+    pub fn settle<RT, F>(
+        &mut self,
+        rt: &RT,
+        id: u64,
+        withdrawals: &mut Withdrawals,
+        verify: F,
+    ) -> Result<Settlement<T>, ActorError>
+    where
+        RT: Runtime,
+        F: FnOnce(&T) -> Result<bool, ActorError>,
+    {
+        let claim = self.load(rt.store(), id)?;
+        let (winner, bonds, settlement) = match claim.challenge {
+            None => {
+                let epoch = rt.curr_epoch();
+                let closes_at = self.window_end(id, &claim)?;
+                if epoch < closes_at {
+                    return Err(actor_error!(forbidden;
+                        "claim {} can be challenged until epoch {}, now {}", id, closes_at, epoch));
+                }
+                (
+                    claim.submitter,
+                    claim.bond,
+                    Settlement::Accepted(claim.content),
+                )
+            }
+            Some(challenge) => {
+                let bonds = claim.bond + challenge.bond;
+                if verify(&claim.content)? {
+                    (claim.submitter, bonds, Settlement::Accepted(claim.content))
+                } else {
+                    (
+                        challenge.challenger,
+                        bonds,
+                        Settlement::Rejected(claim.content),
+                    )
+                }
+            }
+        };
+
+        self.claims
+            .update(rt.store(), |claims| {
+                claims.delete(&u64_key(id))?;
+                Ok(())
+            })
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to remove claim")
+            })?;
+        withdrawals
+            .credit(rt.store(), &winner, &bonds)
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to pay out bonds")
+            })?;
+        Ok(settlement)
+    }
+
+    /// The first epoch at which the claim can no longer be challenged.
+    fn window_end(&self, id: u64, claim: &Claim<T>) -> Result<ChainEpoch, ActorError> {
+        claim.submitted_at.checked_add(self.window).ok_or_else(|| {
+            actor_error!(illegal_state;
+                "challenge window of claim {} overflows the epochs", id)
+        })
+    }
+
+    fn load<S: Blockstore>(&self, store: &S, id: u64) -> Result<Claim<T>, ActorError> {
+        self.get(store, id)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to load claim"))?
+            .ok_or_else(|| actor_error!(not_found; "no claim {} pending", id))
+    }
+
+    fn put<S: Blockstore>(
+        &mut self,
+        store: &S,
+        id: u64,
+        claim: Claim<T>,
+    ) -> Result<(), ActorError> {
+        self.claims
+            .update(store, |claims| {
+                claims.set(u64_key(id), claim)?;
+                Ok(())
+            })
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to store claim"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::{MockRuntime, ACCOUNT_ACTOR_CODE_ID};
+    use fil_actors_runtime::ActorError;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Disputes, Settlement};
+    use crate::Withdrawals;
+
+    #[test]
+    fn unchallenged_claims_are_accepted() {
+        let submitter = Address::new_id(100);
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, submitter);
+        rt.set_epoch(10);
+        rt.in_call = true;
+        let bond = TokenAmount::from_atto(10);
+        let mut disputes = Disputes::new(&*rt.store, 5, bond.clone(), bond.clone()).unwrap();
+        let mut withdrawals = Withdrawals::new(&*rt.store).unwrap();
+
+        let err = disputes
+            .submit(&rt, 7u64, TokenAmount::from_atto(9))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_INSUFFICIENT_FUNDS);
+        let id = disputes.submit(&rt, 7u64, bond.clone()).unwrap();
+
+        rt.set_epoch(14);
+        let err = disputes
+            .settle(&rt, id, &mut withdrawals, |_| Ok(false))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+
+        rt.set_epoch(15);
+        let err = disputes.challenge(&rt, id, bond.clone()).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        let settled = disputes
+            .settle(&rt, id, &mut withdrawals, |_| unreachable!())
+            .unwrap();
+        assert_eq!(settled, Settlement::Accepted(7));
+        assert_eq!(withdrawals.credit_of(&*rt.store, &submitter).unwrap(), bond);
+        assert!(disputes.get(&*rt.store, id).unwrap().is_none());
+    }
+
+    #[test]
+    fn challenged_claims_are_verified() {
+        let submitter = Address::new_id(100);
+        let challenger = Address::new_id(101);
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, submitter);
+        rt.in_call = true;
+        let bond = TokenAmount::from_atto(10);
+        let mut disputes = Disputes::new(&*rt.store, 5, bond.clone(), bond.clone()).unwrap();
+        let mut withdrawals = Withdrawals::new(&*rt.store).unwrap();
+
+        let valid = disputes.submit(&rt, 1u64, bond.clone()).unwrap();
+        let invalid = disputes.submit(&rt, 2u64, bond.clone()).unwrap();
+
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, challenger);
+        let err = disputes
+            .challenge(&rt, valid, TokenAmount::from_atto(1))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_INSUFFICIENT_FUNDS);
+        disputes.challenge(&rt, valid, bond.clone()).unwrap();
+        let err = disputes.challenge(&rt, valid, bond.clone()).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        disputes.challenge(&rt, invalid, bond.clone()).unwrap();
+
+        // Challenged claims are settled by verification, without waiting for the window.
+        let verify = |n: &u64| Ok(*n == 1);
+        let settled = disputes
+            .settle(&rt, valid, &mut withdrawals, verify)
+            .unwrap();
+        assert_eq!(settled, Settlement::Accepted(1));
+        let settled = disputes
+            .settle(&rt, invalid, &mut withdrawals, verify)
+            .unwrap();
+        assert_eq!(settled, Settlement::Rejected(2));
+
+        let both = TokenAmount::from_atto(20);
+        assert_eq!(withdrawals.credit_of(&*rt.store, &submitter).unwrap(), both);
+        assert_eq!(
+            withdrawals.credit_of(&*rt.store, &challenger).unwrap(),
+            both
+        );
+
+        let err = disputes
+            .settle(&rt, valid, &mut withdrawals, verify)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_NOT_FOUND);
+    }
+
+    #[test]
+    fn rejects_bad_windows() {
+        let mut rt = MockRuntime::default();
+        let bond = TokenAmount::from_atto(10);
+        let err = Disputes::<u64>::new(&*rt.store, -1, bond.clone(), bond.clone()).unwrap_err();
+        assert_eq!(
+            err.downcast::<ActorError>().unwrap().exit_code(),
+            ExitCode::USR_ILLEGAL_ARGUMENT
+        );
+
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(100));
+        rt.set_epoch(1);
+        rt.in_call = true;
+        let mut disputes = Disputes::new(&*rt.store, i64::MAX, bond.clone(), bond.clone()).unwrap();
+        let mut withdrawals = Withdrawals::new(&*rt.store).unwrap();
+        let id = disputes.submit(&rt, 7u64, bond.clone()).unwrap();
+        let err = disputes.challenge(&rt, id, bond).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        let err = disputes
+            .settle(&rt, id, &mut withdrawals, |_| unreachable!())
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+    }
+}
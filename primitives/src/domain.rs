@@ -0,0 +1,112 @@
+//! Domain separation tags of the IPC family of actors, prefixed by `domain_hash` to the data
+//! it hashes, so that a digest computed for one purpose can never be passed off as one for
+//! another. The `Runtime` doesn't expose the randomness syscalls, so the tags only separate
+//! the digests the actors compute themselves.
+//!
+//! They are allocated from `FIRST_IPC_DOMAIN_TAG..=LAST_IPC_DOMAIN_TAG`, away from the tags of
+//! the built-in actors, and never reused for another purpose. Defining two tags with the same
+//! value fails to compile.
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::ActorError;
+use fvm_ipld_encoding::to_vec;
+use serde::Serialize;
+
+/// First domain separation tag reserved for the IPC actors; the built-in actors use the
+/// ones from 1, e.g. `DomainSeparationTag::TicketProduction`.
+pub const FIRST_IPC_DOMAIN_TAG: i64 = 1000;
+
+/// Last domain separation tag reserved for the IPC actors.
+pub const LAST_IPC_DOMAIN_TAG: i64 = 1999;
+
+/// Define the tag constants along with their names, checking that they are distinct.
+macro_rules! ipc_domain_tags {
+    ($($(#[$doc:meta])* $name:ident = $tag:literal,)+) => {
+        $(
+        $(#[$doc])*
+        pub const $name: i64 = $tag;
+        )+
+
+        /// Every IPC domain separation tag with its name.
+        pub const IPC_DOMAIN_TAGS: &[(&str, i64)] = &[$((stringify!($name), $tag)),+];
+
+        const _: () = check_tags(IPC_DOMAIN_TAGS);
+    };
+}
+
+ipc_domain_tags! {
+    /// Digests of checkpoints, as signed by the validators of a subnet.
+    CHECKPOINT_DOMAIN_TAG = 1000,
+    /// Digests seeding the election of the validators of a subnet.
+    VALIDATOR_ELECTION_DOMAIN_TAG = 1001,
+    /// Digests of cross messages, which bind them to their nonce.
+    CROSS_MSG_NONCE_DOMAIN_TAG = 1002,
+}
+
+const fn check_tags(tags: &[(&str, i64)]) {
+    let mut i = 0;
+    while i < tags.len() {
+        let tag = tags[i].1;
+        assert!(
+            tag >= FIRST_IPC_DOMAIN_TAG && tag <= LAST_IPC_DOMAIN_TAG,
+            "domain separation tag out of the IPC range"
+        );
+        let mut j = i + 1;
+        while j < tags.len() {
+            assert!(tag != tags[j].1, "domain separation tag defined twice");
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// The name of an IPC domain separation tag, e.g. `"CHECKPOINT_DOMAIN_TAG"`.
+pub fn domain_tag_name(tag: i64) -> Option<&'static str> {
+    IPC_DOMAIN_TAGS
+        .iter()
+        .find(|(_, t)| *t == tag)
+        .map(|(name, _)| *name)
+}
+
+/// The canonical data to pass to `domain_hash`: the CBOR encoding of `parts`, e.g. a tuple
+/// of the subnet ID and the epoch, rather than bytes concatenated by hand, which can be
+/// ambiguous.
+pub fn entropy<T: Serialize>(parts: &T) -> Result<Vec<u8>, ActorError> {
+    to_vec(parts)
+        .map_err(|e| ActorError::serialization(format!("failed to serialize entropy: {e}")))
+}
+
+/// The blake2b digest of `data` in the domain of `tag`: the tag as 8 big-endian bytes
+/// followed by the data.
+pub fn domain_hash<RT: Runtime>(rt: &RT, tag: i64, data: &[u8]) -> [u8; 32] {
+    let mut input = Vec::with_capacity(8 + data.len());
+    input.extend_from_slice(&tag.to_be_bytes());
+    input.extend_from_slice(data);
+    rt.hash_blake2b(&input)
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::MockRuntime;
+
+    use super::*;
+
+    #[test]
+    fn tags_are_named_and_separate_digests() {
+        assert_eq!(
+            domain_tag_name(CHECKPOINT_DOMAIN_TAG),
+            Some("CHECKPOINT_DOMAIN_TAG")
+        );
+        assert_eq!(domain_tag_name(1), None);
+
+        let rt = MockRuntime::default();
+        let data = entropy(&("/r314", 10u64)).unwrap();
+        assert_eq!(data, to_vec(&("/r314", 10u64)).unwrap());
+        let digests: Vec<_> = IPC_DOMAIN_TAGS
+            .iter()
+            .map(|(_, tag)| domain_hash(&rt, *tag, &data))
+            .collect();
+        for (i, digest) in digests.iter().enumerate() {
+            assert!(!digests[i + 1..].contains(digest));
+        }
+    }
+}
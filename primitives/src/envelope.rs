@@ -0,0 +1,125 @@
+use fil_actors_runtime::runtime::fvm::resolve_secp_bls;
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::{to_vec, RawBytes};
+use fvm_shared::address::Address;
+use fvm_shared::chainid::ChainID;
+use fvm_shared::crypto::signature::Signature;
+
+/// Operation signed off-chain by `signer` and submitted to an actor by someone else,
+/// e.g. a cross message forwarded by a relayer.
+///
+/// The signature covers the canonical payload produced by [`signing_payload`], which binds
+/// the parameters to a domain, a chain and a nonce, so that it can't be replayed in
+/// another context. Checking that the nonce hasn't been used before is up to the actor.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SignedEnvelope {
+    pub signer: Address,
+    pub nonce: u64,
+    pub params: RawBytes,
+    pub signature: Signature,
+}
+
+/// The canonical bytes to sign: the CBOR encoding of `[domain, chain_id, nonce, params]`.
+///
+/// The `domain` should be unique to the actor and the operation, e.g. `"ipc/gateway/propagate"`.
+pub fn signing_payload(
+    domain: &str,
+    chain_id: ChainID,
+    nonce: u64,
+    params: &RawBytes,
+) -> Result<Vec<u8>, ActorError> {
+    to_vec(&(domain, u64::from(chain_id), nonce, params))
+        .map_err(|e| ActorError::serialization(format!("failed to serialize signing payload: {e}")))
+}
+
+impl SignedEnvelope {
+    /// The digest the signer is expected to have signed for this envelope on the current chain.
+    pub fn digest<RT: Runtime>(&self, rt: &RT, domain: &str) -> Result<[u8; 32], ActorError> {
+        let payload = signing_payload(domain, rt.chain_id(), self.nonce, &self.params)?;
+        Ok(rt.hash_blake2b(&payload))
+    }
+
+    /// Verify the signature over the digest against the public key of the signer,
+    /// resolving it through the account actor if the signer is given as an ID address.
+    pub fn verify<RT: Runtime>(&self, rt: &mut RT, domain: &str) -> Result<(), ActorError> {
+        let digest = self.digest(rt, domain)?;
+        let key = resolve_secp_bls(rt, &self.signer)
+            .map_err(|e| e.wrap(format!("failed to resolve key of signer {}", self.signer)))?;
+
+        rt.verify_signature(&self.signature, &key, &digest).map_err(
+            |e| actor_error!(illegal_argument; "invalid signature of {}: {}", self.signer, e),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::{ExpectedVerifySig, MockRuntime};
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::chainid::ChainID;
+    use fvm_shared::crypto::signature::Signature;
+    use fvm_shared::error::ExitCode;
+
+    use super::{signing_payload, SignedEnvelope};
+
+    const DOMAIN: &str = "test/envelope";
+
+    fn envelope() -> SignedEnvelope {
+        SignedEnvelope {
+            signer: Address::new_secp256k1(&[1u8; 65]).unwrap(),
+            nonce: 7,
+            params: RawBytes::serialize("params").unwrap(),
+            signature: Signature::new_secp256k1(vec![2u8; 65]),
+        }
+    }
+
+    #[test]
+    fn payload_binds_chain_and_nonce() {
+        let params = RawBytes::serialize("params").unwrap();
+        let payload = signing_payload(DOMAIN, ChainID::from(1), 7, &params).unwrap();
+        assert_ne!(
+            payload,
+            signing_payload(DOMAIN, ChainID::from(2), 7, &params).unwrap()
+        );
+        assert_ne!(
+            payload,
+            signing_payload(DOMAIN, ChainID::from(1), 8, &params).unwrap()
+        );
+        assert_ne!(
+            payload,
+            signing_payload("other", ChainID::from(1), 7, &params).unwrap()
+        );
+    }
+
+    #[test]
+    fn verify_checks_signature_over_digest() {
+        let mut rt = MockRuntime {
+            chain_id: ChainID::from(314),
+            ..Default::default()
+        };
+        let env = envelope();
+        let digest = env.digest(&rt, DOMAIN).unwrap();
+
+        rt.in_call = true;
+        rt.expect_verify_signature(ExpectedVerifySig {
+            sig: env.signature.clone(),
+            signer: env.signer,
+            plaintext: digest.to_vec(),
+            result: Ok(()),
+        });
+        env.verify(&mut rt, DOMAIN).unwrap();
+
+        rt.expect_verify_signature(ExpectedVerifySig {
+            sig: env.signature.clone(),
+            signer: env.signer,
+            plaintext: digest.to_vec(),
+            result: Err(anyhow::anyhow!("bad signature")),
+        });
+        let err = env.verify(&mut rt, DOMAIN).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        rt.verify();
+    }
+}
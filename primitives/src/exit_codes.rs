@@ -0,0 +1,210 @@
+//! Exit codes shared by the IPC family of actors, so that all of them and their clients
+//! interpret failures the same way.
+//!
+//! They are allocated from `FIRST_IPC_EXIT_CODE..=LAST_IPC_EXIT_CODE`, i.e. 32 to 63, and
+//! never reused for another meaning. The FVM leaves codes from 32 up to each actor, so the
+//! same values may mean something else when returned by an actor outside this library: the
+//! range is reserved for the IPC actors by convention only, and a code should be read with
+//! the actor that returned it in mind. New codes are allocated here and nowhere else.
+//!
+//! Codes aborting with a typed payload are registered here along with the `TypedError` they
+//! carry, so that a payload is only ever decoded as the type it is.
+#[cfg(test)]
+use fil_actors_runtime::TypedError;
+use fvm_shared::error::ExitCode;
+
+#[cfg(test)]
+use crate::{QueueFull, UnexpectedNonces};
+
+/// First exit code reserved for the IPC actors. The ones below are the FVM's own, up to 15,
+/// and the common codes of the built-in actors, from `ExitCode::FIRST_USER_EXIT_CODE` to 31.
+pub const FIRST_IPC_EXIT_CODE: u32 = 32;
+
+/// Last exit code reserved for the IPC actors.
+pub const LAST_IPC_EXIT_CODE: u32 = 63;
+
+/// Define the exit code constants along with their names and the types of their payloads.
+macro_rules! ipc_exit_codes {
+    ($($(#[$doc:meta])* $name:ident = $code:literal $(=> $payload:ty)?,)+) => {
+        $(
+        $(#[$doc])*
+        pub const $name: ExitCode = ExitCode::new($code);
+        )+
+
+        /// The name of an IPC exit code, e.g. `"USR_SUBNET_NOT_ACTIVE"`.
+        pub fn ipc_exit_code_name(code: ExitCode) -> Option<&'static str> {
+            match code.value() {
+                $($code => Some(stringify!($name)),)+
+                _ => None,
+            }
+        }
+
+        /// The name of the `TypedError` an IPC exit code carries as payload, if any, e.g.
+        /// `"QueueFull"` for `USR_QUEUE_FULL`.
+        pub fn ipc_exit_code_payload(code: ExitCode) -> Option<&'static str> {
+            match code.value() {
+                $($code => None $(.or(Some(stringify!($payload))))?,)+
+                _ => None,
+            }
+        }
+
+        /// The registered payloads, with the exit codes each code's type claims.
+        #[cfg(test)]
+        fn registered_payloads() -> Vec<(ExitCode, &'static str, &'static [ExitCode])> {
+            vec![$($(
+                ($name, stringify!($payload), <$payload as TypedError>::EXIT_CODES),
+            )?)+]
+        }
+    };
+}
+
+ipc_exit_codes! {
+    /// A batch holds more cross messages than `Policy::max_cross_msgs_per_batch`.
+    USR_CROSS_MSG_BATCH_TOO_LARGE = 32,
+    /// The parameters of a cross message exceed `Policy::max_cross_msg_params_size`.
+    USR_CROSS_MSG_TOO_LARGE = 33,
+    /// A subnet is nested deeper than `Policy::max_subnet_depth`.
+    USR_SUBNET_TOO_DEEP = 34,
+    /// No checkpoint exists for the requested epoch.
+    USR_CHECKPOINT_NOT_FOUND = 35,
+    /// The checkpoint is malformed, or doesn't follow the previous one.
+    USR_INVALID_CHECKPOINT = 36,
+    /// The subnet isn't registered.
+    USR_SUBNET_NOT_FOUND = 37,
+    /// The subnet is registered, but not active, e.g. for lack of collateral.
+    USR_SUBNET_NOT_ACTIVE = 38,
+    /// A message carries a nonce other than the next one expected.
+    USR_UNEXPECTED_NONCE = 39 => UnexpectedNonces,
+    /// A queue is at its maximum length and can't take more items until some are processed.
+    USR_QUEUE_FULL = 40 => QueueFull,
+}
+
+/// Human readable form of any exit code, for tooling, e.g. `USR_SUBNET_NOT_ACTIVE (38)`.
+///
+/// # Example
+/// ```
+/// use fvm_shared::error::ExitCode;
+/// use primitives::{exit_code_to_string, USR_SUBNET_NOT_ACTIVE};
+///
+/// assert_eq!(exit_code_to_string(USR_SUBNET_NOT_ACTIVE), "USR_SUBNET_NOT_ACTIVE (38)");
+/// assert_eq!(exit_code_to_string(ExitCode::USR_FORBIDDEN), "USR_FORBIDDEN (18)");
+/// ```
+pub fn exit_code_to_string(code: ExitCode) -> String {
+    let name = ipc_exit_code_name(code).or_else(|| fvm_exit_code_name(code));
+    match name {
+        Some(name) => format!("{} ({})", name, code.value()),
+        None => format!("exit code {}", code.value()),
+    }
+}
+
+/// Whether the code is in the range reserved for the IPC actors.
+pub fn is_ipc_exit_code(code: ExitCode) -> bool {
+    (FIRST_IPC_EXIT_CODE..=LAST_IPC_EXIT_CODE).contains(&code.value())
+}
+
+fn fvm_exit_code_name(code: ExitCode) -> Option<&'static str> {
+    let name = match code {
+        ExitCode::OK => "OK",
+        ExitCode::SYS_SENDER_INVALID => "SYS_SENDER_INVALID",
+        ExitCode::SYS_SENDER_STATE_INVALID => "SYS_SENDER_STATE_INVALID",
+        ExitCode::SYS_ILLEGAL_INSTRUCTION => "SYS_ILLEGAL_INSTRUCTION",
+        ExitCode::SYS_INVALID_RECEIVER => "SYS_INVALID_RECEIVER",
+        ExitCode::SYS_INSUFFICIENT_FUNDS => "SYS_INSUFFICIENT_FUNDS",
+        ExitCode::SYS_OUT_OF_GAS => "SYS_OUT_OF_GAS",
+        ExitCode::SYS_ILLEGAL_EXIT_CODE => "SYS_ILLEGAL_EXIT_CODE",
+        ExitCode::SYS_ASSERTION_FAILED => "SYS_ASSERTION_FAILED",
+        ExitCode::SYS_MISSING_RETURN => "SYS_MISSING_RETURN",
+        ExitCode::USR_ILLEGAL_ARGUMENT => "USR_ILLEGAL_ARGUMENT",
+        ExitCode::USR_NOT_FOUND => "USR_NOT_FOUND",
+        ExitCode::USR_FORBIDDEN => "USR_FORBIDDEN",
+        ExitCode::USR_INSUFFICIENT_FUNDS => "USR_INSUFFICIENT_FUNDS",
+        ExitCode::USR_ILLEGAL_STATE => "USR_ILLEGAL_STATE",
+        ExitCode::USR_SERIALIZATION => "USR_SERIALIZATION",
+        ExitCode::USR_UNHANDLED_MESSAGE => "USR_UNHANDLED_MESSAGE",
+        ExitCode::USR_UNSPECIFIED => "USR_UNSPECIFIED",
+        ExitCode::USR_ASSERTION_FAILED => "USR_ASSERTION_FAILED",
+        ExitCode::USR_READ_ONLY => "USR_READ_ONLY",
+        ExitCode::USR_NOT_PAYABLE => "USR_NOT_PAYABLE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::runtime::Runtime;
+    use fil_actors_runtime::test_utils::MockRuntime;
+    use fil_actors_runtime::ActorError;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use num_traits::Zero;
+
+    use super::*;
+    use crate::QueueMetrics;
+
+    #[test]
+    fn codes_are_in_reserved_range() {
+        for value in 0..=u8::MAX as u32 {
+            let code = ExitCode::new(value);
+            if ipc_exit_code_name(code).is_some() {
+                assert!(is_ipc_exit_code(code), "{value} out of the IPC range");
+                assert!(fvm_exit_code_name(code).is_none());
+            }
+        }
+        assert_eq!(
+            exit_code_to_string(ExitCode::new(LAST_IPC_EXIT_CODE)),
+            "exit code 63"
+        );
+    }
+
+    #[test]
+    fn payloads_are_registered() {
+        for (code, payload, codes) in registered_payloads() {
+            assert!(codes.contains(&code), "{payload} doesn't abort with {code}");
+            for code in codes {
+                assert_eq!(ipc_exit_code_payload(*code), Some(payload));
+            }
+        }
+        assert_eq!(ipc_exit_code_payload(USR_QUEUE_FULL), Some("QueueFull"));
+        assert_eq!(ipc_exit_code_payload(USR_SUBNET_NOT_FOUND), None);
+    }
+
+    #[test]
+    fn typed_errors_across_sends() {
+        let mut rt = MockRuntime::default();
+        let to = Address::new_id(100);
+        let full = QueueFull {
+            metrics: QueueMetrics {
+                len: 3,
+                max_len: 3,
+                pushed: 5,
+                popped: 2,
+            },
+        };
+        let mut abort = ActorError::typed(&full);
+        assert_eq!(abort.exit_code(), USR_QUEUE_FULL);
+        assert_eq!(abort.msg(), "queue full with 3 of at most 3 items");
+        rt.expect_send(
+            to,
+            2,
+            None,
+            TokenAmount::zero(),
+            abort.take_data(),
+            abort.exit_code(),
+        );
+
+        rt.in_call = true;
+        let err = rt.send(&to, 2, None, TokenAmount::zero()).unwrap_err();
+        assert_eq!(err.to_typed::<QueueFull>(), Some(full));
+        assert_eq!(err.to_typed::<UnexpectedNonces>(), None);
+        rt.verify();
+
+        // Only decoded from the exit codes of the type.
+        let other = ActorError::unchecked_with_data(
+            USR_UNEXPECTED_NONCE,
+            "unexpected".to_string(),
+            err.data().cloned(),
+        );
+        assert_eq!(other.to_typed::<QueueFull>(), None);
+    }
+}
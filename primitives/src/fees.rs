@@ -0,0 +1,335 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{
+    actor_error, ActorDowncast, ActorError, EventBuilder, BURNT_FUNDS_ACTOR_ADDR,
+};
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::METHOD_SEND;
+use num_traits::Zero;
+
+use crate::{RewardPool, Withdrawals};
+
+/// Type of the event emitted whenever `Fees::charge` splits a fee.
+pub const FEE_CHARGED_EVENT: &str = "fee-charged";
+
+/// The whole of a fee in a `FeeSplit`, which is expressed in basis points.
+pub const BASIS_POINTS: u16 = 10_000;
+
+/// How a fee is shared, in basis points adding up to `BASIS_POINTS`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct FeeSplit {
+    burn: u16,
+    relayer: u16,
+    validators: u16,
+}
+
+impl FeeSplit {
+    pub fn new(burn: u16, relayer: u16, validators: u16) -> Result<Self, ActorError> {
+        let total = burn as u32 + relayer as u32 + validators as u32;
+        if total != BASIS_POINTS as u32 {
+            return Err(actor_error!(illegal_argument;
+                "fee split adds up to {} basis points rather than {}", total, BASIS_POINTS));
+        }
+        Ok(Self {
+            burn,
+            relayer,
+            validators,
+        })
+    }
+
+    pub fn burn(&self) -> u16 {
+        self.burn
+    }
+
+    pub fn relayer(&self) -> u16 {
+        self.relayer
+    }
+
+    pub fn validators(&self) -> u16 {
+        self.validators
+    }
+
+    /// Split a fee into its shares. The relayer and validator shares are rounded down and
+    /// the burn takes what's left, so the shares always add up to exactly the fee.
+    pub fn split(&self, fee: &TokenAmount) -> FeeShares {
+        let share = |bp: u16| TokenAmount::from_atto(fee.atto() * bp / BASIS_POINTS);
+        let relayer = share(self.relayer);
+        let validators = share(self.validators);
+        FeeShares {
+            burn: fee - &relayer - &validators,
+            relayer,
+            validators,
+        }
+    }
+}
+
+/// The shares of a fee, as split by a `FeeSplit`.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct FeeShares {
+    pub burn: TokenAmount,
+    pub relayer: TokenAmount,
+    pub validators: TokenAmount,
+}
+
+/// Fees for executing cross messages, to be embedded in the state of the gateway, so that
+/// every version of it splits them the same way.
+///
+/// The relayer share is credited to the relayer in the `Withdrawals` of the actor, the
+/// validator share accrues to the stakers of a `RewardPool`, or is burnt while the pool has
+/// no stake, and the burnt share is kept until `burn` sends it to the burnt funds actor.
+///
+/// # Example
+/// ```
+/// use primitives::{FeeSplit, Fees};
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let fees = Fees::new(FeeSplit::new(2_000, 5_000, 3_000).unwrap());
+/// let shares = fees.split().split(&TokenAmount::from_atto(10));
+///
+/// assert_eq!(shares.relayer, TokenAmount::from_atto(5));
+/// assert_eq!(shares.validators, TokenAmount::from_atto(3));
+/// assert_eq!(shares.burn, TokenAmount::from_atto(2));
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Fees {
+    split: FeeSplit,
+    /// Burnt shares not yet sent to the burnt funds actor.
+    to_burn: TokenAmount,
+}
+
+impl Fees {
+    pub fn new(split: FeeSplit) -> Self {
+        Self {
+            split,
+            to_burn: TokenAmount::zero(),
+        }
+    }
+
+    pub fn split(&self) -> &FeeSplit {
+        &self.split
+    }
+
+    /// Change how future fees are split; the shares of past fees are left as they are.
+    pub fn set_split(&mut self, split: FeeSplit) {
+        self.split = split;
+    }
+
+    /// Burnt shares still held by the actor, which it has to keep in its balance.
+    pub fn to_burn(&self) -> &TokenAmount {
+        &self.to_burn
+    }
+
+    /// Split a fee paid by a cross message executed by `relayer`, and emit an event,
+    /// returning the shares as accounted for.
+    ///
+    /// While the pool of validators has no stake, nobody could ever claim their share, so it
+    /// is burnt along with the burnt share instead.
+    ///
+    /// Either every share is accounted for or, on error, none is: the event is emitted and
+    /// the relayer credited, the only steps which can fail, before anything else changes.
+    pub fn charge<RT: Runtime>(
+        &mut self,
+        rt: &RT,
+        relayer: &Address,
+        fee: &TokenAmount,
+        withdrawals: &mut Withdrawals,
+        validators: &mut RewardPool,
+    ) -> Result<FeeShares, ActorError> {
+        if fee.is_negative() {
+            return Err(actor_error!(illegal_argument; "negative fee {}", fee));
+        }
+        let mut shares = self.split.split(fee);
+        if validators.total_stake().is_zero() {
+            shares.burn += std::mem::take(&mut shares.validators);
+        }
+
+        let event = EventBuilder::new()
+            .typ(FEE_CHARGED_EVENT)
+            .field_indexed("relayer", relayer)?
+            .field("fee", fee)?
+            .field("burn", &shares.burn)?
+            .field("relayer_share", &shares.relayer)?
+            .field("validators_share", &shares.validators)?
+            .build();
+        rt.emit_event(&event)?;
+        withdrawals
+            .credit(rt.store(), relayer, &shares.relayer)
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to credit relayer")
+            })?;
+        // Only fails on negative amounts, which no share is.
+        validators
+            .accrue(&shares.validators)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to accrue fee"))?;
+        self.to_burn += &shares.burn;
+        Ok(shares)
+    }
+
+    /// Send the burnt shares to the burnt funds actor, returning the amount burnt.
+    ///
+    /// Sends aren't allowed within a state transaction, so it has to be called on the state
+    /// as loaded with `rt.state()`, which is then saved with a transaction, e.g.
+    ///
+    /// ```ignore
+    /// let mut st: State = rt.state()?;
+    /// st.fees.burn(rt)?;
+    /// rt.transaction(|saved: &mut State, _| {
+    ///     saved.fees = st.fees;
+    ///     Ok(())
+    /// })?;
+    /// ```
+    ///
+    /// If the send fails, so does the method, and the state is left as it was.
+    pub fn burn<RT: Runtime>(&mut self, rt: &RT) -> Result<TokenAmount, ActorError> {
+        let amount = std::mem::take(&mut self.to_burn);
+        if !amount.is_zero() {
+            rt.send(&BURNT_FUNDS_ACTOR_ADDR, METHOD_SEND, None, amount.clone())
+                .map_err(|e| e.wrap("failed to send fees to the burnt funds actor"))?;
+        }
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::MockRuntime;
+    use fil_actors_runtime::{EventBuilder, BURNT_FUNDS_ACTOR_ADDR};
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::METHOD_SEND;
+
+    use super::{FeeSplit, Fees, BASIS_POINTS, FEE_CHARGED_EVENT};
+    use crate::{RewardPool, Withdrawals};
+
+    fn atto(n: u64) -> TokenAmount {
+        TokenAmount::from_atto(n)
+    }
+
+    #[test]
+    fn invalid_splits() {
+        assert!(FeeSplit::new(0, 0, BASIS_POINTS).is_ok());
+        let err = FeeSplit::new(5_000, 5_000, 1).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert!(FeeSplit::new(u16::MAX, u16::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn rounding_of_shares() {
+        let splits = [
+            (BASIS_POINTS, 0, 0),
+            (0, BASIS_POINTS, 0),
+            (0, 0, BASIS_POINTS),
+            (3_333, 3_333, 3_334),
+            (1, 4_999, 5_000),
+            (9_998, 1, 1),
+        ];
+        for (burn, relayer, validators) in splits {
+            let split = FeeSplit::new(burn, relayer, validators).unwrap();
+            for fee in 0..=2 * BASIS_POINTS as u64 + 7 {
+                let shares = split.split(&atto(fee));
+                assert_eq!(
+                    &shares.burn + &shares.relayer + &shares.validators,
+                    atto(fee),
+                    "shares of {fee} with {split:?}"
+                );
+                // Every share is its exact proportion rounded down, with the
+                // burn absorbing the up to two atto of remainders.
+                let floor = |bp: u16| atto(fee * bp as u64 / BASIS_POINTS as u64);
+                assert_eq!(shares.relayer, floor(relayer));
+                assert_eq!(shares.validators, floor(validators));
+                assert!(shares.burn >= floor(burn) && shares.burn <= &floor(burn) + atto(2));
+            }
+        }
+    }
+
+    #[test]
+    fn charge_and_burn() {
+        let mut rt = MockRuntime::default();
+        rt.set_balance(atto(100));
+        let store = rt.store.clone();
+        let (relayer, validator) = (Address::new_id(100), Address::new_id(101));
+        let mut withdrawals = Withdrawals::new(&store).unwrap();
+        let mut pool = RewardPool::new(&store).unwrap();
+        pool.deposit(&store, &validator, &atto(1)).unwrap();
+        let mut fees = Fees::new(FeeSplit::new(2_000, 5_000, 3_000).unwrap());
+
+        rt.expect_emitted_event(
+            EventBuilder::new()
+                .typ(FEE_CHARGED_EVENT)
+                .field_indexed("relayer", &relayer)
+                .unwrap()
+                .field("fee", &atto(99))
+                .unwrap()
+                .field("burn", &atto(21))
+                .unwrap()
+                .field("relayer_share", &atto(49))
+                .unwrap()
+                .field("validators_share", &atto(29))
+                .unwrap()
+                .build(),
+        );
+        rt.expect_send(
+            BURNT_FUNDS_ACTOR_ADDR,
+            METHOD_SEND,
+            None,
+            atto(21),
+            None,
+            ExitCode::OK,
+        );
+        rt.call_fn(|rt| {
+            fees.charge(rt, &relayer, &atto(99), &mut withdrawals, &mut pool)?;
+            assert!(fees
+                .charge(rt, &relayer, &-atto(99), &mut withdrawals, &mut pool)
+                .is_err());
+            assert_eq!(fees.burn(rt)?, atto(21));
+            Ok(())
+        })
+        .unwrap();
+        rt.verify();
+
+        assert_eq!(withdrawals.credit_of(&store, &relayer).unwrap(), atto(49));
+        assert_eq!(pool.pending(&store, &validator).unwrap(), atto(29));
+        assert_eq!(fees.to_burn(), &atto(0));
+    }
+
+    #[test]
+    fn burns_validator_share_without_stake() {
+        let mut rt = MockRuntime::default();
+        let store = rt.store.clone();
+        let relayer = Address::new_id(100);
+        let mut withdrawals = Withdrawals::new(&store).unwrap();
+        let mut pool = RewardPool::new(&store).unwrap();
+        let mut fees = Fees::new(FeeSplit::new(2_000, 5_000, 3_000).unwrap());
+
+        rt.expect_emitted_event(
+            EventBuilder::new()
+                .typ(FEE_CHARGED_EVENT)
+                .field_indexed("relayer", &relayer)
+                .unwrap()
+                .field("fee", &atto(10))
+                .unwrap()
+                .field("burn", &atto(5))
+                .unwrap()
+                .field("relayer_share", &atto(5))
+                .unwrap()
+                .field("validators_share", &atto(0))
+                .unwrap()
+                .build(),
+        );
+        let shares = rt
+            .call_fn(|rt| Ok(fees.charge(rt, &relayer, &atto(10), &mut withdrawals, &mut pool)?))
+            .unwrap();
+        rt.verify();
+        assert_eq!(shares.validators, atto(0));
+        assert_eq!(fees.to_burn(), &atto(5));
+
+        // A later staker doesn't get the share accrued before it.
+        let validator = Address::new_id(101);
+        pool.deposit(&store, &validator, &atto(1)).unwrap();
+        pool.accrue(&atto(0)).unwrap();
+        assert_eq!(pool.pending(&store, &validator).unwrap(), atto(0));
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::{serde, strict_bytes};
+
+/// Bytes of a fixed length, such as a commitment, a hash or a public key, for parameters
+/// and state which would otherwise hold them in a `Vec<u8>` and check the length by hand.
+///
+/// It is encoded as CBOR bytes, exactly like a `Vec<u8>` with `strict_bytes`, so fields can be
+/// changed to it without a migration, but decoding fails unless there are exactly `N` bytes.
+/// Unlike a `[u8; N]` field with `#[serde(with = "strict_bytes")]`, it also works inside an
+/// `Option`, a `Vec` or a map.
+#[derive(
+    serde::Deserialize, serde::Serialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy,
+)]
+#[serde(transparent)]
+pub struct FixedBytes<const N: usize>(#[serde(with = "strict_bytes")] pub [u8; N]);
+
+/// A 32 byte hash or commitment, e.g. a blake2b-256 or keccak-256 digest.
+pub type Bytes32 = FixedBytes<32>;
+
+impl<const N: usize> FixedBytes<N> {
+    pub const fn new(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for FixedBytes<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> From<FixedBytes<N>> for [u8; N] {
+    fn from(bytes: FixedBytes<N>) -> Self {
+        bytes.0
+    }
+}
+
+/// Fails with `USR_ILLEGAL_ARGUMENT` unless there are exactly `N` bytes, e.g. when checking
+/// bytes from a `Vec<u8>` parameter.
+impl<const N: usize> TryFrom<&[u8]> for FixedBytes<N> {
+    type Error = ActorError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes = bytes.try_into().map_err(
+            |_| actor_error!(illegal_argument; "expected {} bytes, got {}", N, bytes.len()),
+        )?;
+        Ok(Self(bytes))
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for FixedBytes<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Debug for FixedBytes<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl<const N: usize> Display for FixedBytes<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+/// Parses hex, with or without a `0x` prefix.
+impl<const N: usize> FromStr for FixedBytes<N> {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; N];
+        hex::decode_to_slice(s.strip_prefix("0x").unwrap_or(s), &mut bytes)?;
+        Ok(Self(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_ipld_encoding::{from_slice, strict_bytes, to_vec};
+    use fvm_shared::error::ExitCode;
+
+    use super::{Bytes32, FixedBytes};
+
+    #[derive(Serialize_tuple, Deserialize_tuple, Debug, PartialEq)]
+    struct LegacyParams {
+        #[serde(with = "strict_bytes")]
+        commitment: Vec<u8>,
+        keys: Vec<strict_bytes::ByteBuf>,
+    }
+
+    #[derive(Serialize_tuple, Deserialize_tuple, Debug, PartialEq)]
+    struct Params {
+        commitment: Bytes32,
+        keys: Vec<FixedBytes<4>>,
+    }
+
+    #[test]
+    fn fixed_length_params() {
+        let legacy = LegacyParams {
+            commitment: vec![7; 32],
+            keys: vec![strict_bytes::ByteBuf(vec![1, 2, 3, 4])],
+        };
+        let bytes = to_vec(&legacy).unwrap();
+        let params: Params = from_slice(&bytes).unwrap();
+        assert_eq!(params.commitment, FixedBytes([7; 32]));
+        assert_eq!(params.keys, vec![FixedBytes([1, 2, 3, 4])]);
+        assert_eq!(to_vec(&params).unwrap(), bytes);
+
+        let short = LegacyParams {
+            commitment: vec![7; 32],
+            keys: vec![strict_bytes::ByteBuf(vec![1, 2, 3])],
+        };
+        assert!(from_slice::<Params>(&to_vec(&short).unwrap()).is_err());
+
+        let err = Bytes32::try_from(&[0u8; 31][..]).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert_eq!(err.msg(), "expected 32 bytes, got 31");
+    }
+
+    #[test]
+    fn fixed_bytes_hex() {
+        let key = FixedBytes::<4>::from_str("0x0102abcd").unwrap();
+        assert_eq!(key, FixedBytes([1, 2, 0xab, 0xcd]));
+        assert_eq!(key.to_string(), "0x0102abcd");
+        assert_eq!(format!("{key:?}"), "0102abcd");
+        assert_eq!(FixedBytes::<4>::from_str("0102abcd").unwrap(), key);
+        assert!(FixedBytes::<4>::from_str("0102ab").is_err());
+    }
+}
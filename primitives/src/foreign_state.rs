@@ -0,0 +1,110 @@
+use std::marker::PhantomData;
+
+use anyhow::anyhow;
+use cid::Cid;
+use fil_actors_runtime::phantom_wrapper;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use fvm_shared::address::Address;
+use serde::de::DeserializeOwned;
+
+/// The address of another actor along with the type of its state, for protocols where an
+/// actor's state is inspected by others, e.g. the IPC agent reading the state of a subnet.
+///
+/// Actors can't read the state of other actors on chain, so the state root has to come from
+/// outside: from a node, with `fetch` and the `client` feature, or from wherever the root is
+/// known, with `load`, e.g. a state proof once those can be verified on chain.
+/// It serializes as the bare address, and can be kept in the state.
+///
+/// # Example
+/// ```ignore
+/// let subnet: ForeignStateRef<subnet::State> = ForeignStateRef::new(subnet_addr);
+/// let st = subnet.fetch(&store, |addr| Ok(Some(lotus.state_get_actor(addr)?.head)))?;
+/// ```
+pub struct ForeignStateRef<T> {
+    address: Address,
+    _state: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> ForeignStateRef<T> {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            _state: PhantomData,
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Decode the state of the actor from its root, which has to be in the store.
+    pub fn load<BS: Blockstore>(&self, store: &BS, head: &Cid) -> anyhow::Result<T> {
+        store
+            .get_cbor(head)?
+            .ok_or_else(|| anyhow!("state {} of actor {} not found", head, self.address))
+    }
+
+    /// Look up the state root of the actor with `head_of`, e.g. with `Filecoin.StateGetActor`,
+    /// and decode the state from it, e.g. from a `FetchingBlockstore`.
+    #[cfg(feature = "client")]
+    pub fn fetch<BS: Blockstore>(
+        &self,
+        store: &BS,
+        head_of: impl FnOnce(&Address) -> anyhow::Result<Option<Cid>>,
+    ) -> anyhow::Result<T> {
+        let head =
+            head_of(&self.address)?.ok_or_else(|| anyhow!("actor {} not found", self.address))?;
+        self.load(store, &head)
+    }
+}
+
+// Serializes exactly as its address.
+phantom_wrapper!(ForeignStateRef<T> { address: Address, _state });
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{from_slice, to_vec, CborStore};
+    use fvm_shared::address::Address;
+
+    use super::ForeignStateRef;
+    use crate::CircuitBreaker;
+
+    #[test]
+    fn load_foreign_state() {
+        let store = MemoryBlockstore::new();
+        let state = CircuitBreaker::new(3);
+        let head = store.put_cbor(&state, Code::Blake2b256).unwrap();
+
+        let foreign: ForeignStateRef<CircuitBreaker> = ForeignStateRef::new(Address::new_id(100));
+        assert_eq!(foreign.load(&store, &head).unwrap(), state);
+        assert!(foreign
+            .load(&MemoryBlockstore::new(), &head)
+            .unwrap_err()
+            .to_string()
+            .contains("f0100"));
+
+        let bytes = to_vec(&foreign).unwrap();
+        assert_eq!(bytes, to_vec(&Address::new_id(100)).unwrap());
+        assert_eq!(
+            from_slice::<ForeignStateRef<CircuitBreaker>>(&bytes).unwrap(),
+            foreign
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn fetch_foreign_state() {
+        let store = MemoryBlockstore::new();
+        let head = store
+            .put_cbor(&CircuitBreaker::new(3), Code::Blake2b256)
+            .unwrap();
+
+        let foreign: ForeignStateRef<CircuitBreaker> = ForeignStateRef::new(Address::new_id(100));
+        let st = foreign.fetch(&store, |_| Ok(Some(head))).unwrap();
+        assert_eq!(st, CircuitBreaker::new(3));
+        assert!(foreign.fetch(&store, |_| Ok(None)).is_err());
+    }
+}
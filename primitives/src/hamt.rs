@@ -1,17 +1,26 @@
 use std::any::type_name;
+use std::fmt::Display;
 use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 
 use crate::tcid_ops;
 use anyhow::{anyhow, Result};
-use fil_actors_runtime::{make_empty_map, make_map_with_root_and_bitwidth};
+use cid::Cid;
+use fil_actors_runtime::{
+    make_empty_map, make_map_with_root_and_bitwidth, parse_uint_key, u64_key, ActorDowncast,
+    ActorError, BatchReturn, BatchReturnGen,
+};
 use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
-use fvm_ipld_hamt::Error as HamtError;
-use fvm_ipld_hamt::Hamt;
+pub use fvm_ipld_hamt::Error as HamtError;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::address::Address;
+use fvm_shared::error::ExitCode;
 use fvm_shared::HAMT_BIT_WIDTH;
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
 
-use super::{TCid, TCidContent};
+use super::{short_type_name, DeepEq, SubnetID, TCid, TCidContent};
 
 /// Static typing information for HAMT fields, a.k.a. `Map`.
 ///
@@ -51,6 +60,30 @@ pub struct THamt<K, V, const W: u32 = HAMT_BIT_WIDTH> {
 
 impl<K, V, const W: u32> TCidContent for THamt<K, V, W> {}
 
+/// Maps are equal if they have the same keys with equal values.
+impl<K, V, const W: u32> DeepEq for THamt<K, V, W>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    fn content_eq<S: Blockstore>(a: &Cid, b: &Cid, store: &S) -> Result<bool> {
+        let a = make_map_with_root_and_bitwidth::<S, V>(a, store, W)?;
+        let b = make_map_with_root_and_bitwidth::<S, V>(b, store, W)?;
+        let mut a_len = 0u64;
+        let mut eq = true;
+        a.for_each(|k, v| {
+            a_len += 1;
+            eq = eq && b.get(k)? == Some(v);
+            Ok(())
+        })?;
+        let mut b_len = 0u64;
+        b.for_each(|_, _| {
+            b_len += 1;
+            Ok(())
+        })?;
+        Ok(eq && a_len == b_len)
+    }
+}
+
 impl<K, V, const W: u32> TCid<THamt<K, V, W>>
 where
     V: Serialize + DeserializeOwned,
@@ -88,6 +121,373 @@ where
 
 tcid_ops!(THamt<K, V : Serialize + DeserializeOwned, W const: u32> => Hamt<&'s S, V>);
 
+impl<K, V, const W: u32> TCid<THamt<K, V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    /// Load the map under a name describing it, e.g. `"balances"`,
+    /// which will be included in the errors of the operations on it.
+    pub fn load_named<'s, S: Blockstore>(
+        &self,
+        store: &'s S,
+        name: impl Into<String>,
+    ) -> std::result::Result<NamedHamt<'s, S, V>, ActorError> {
+        let name = name.into();
+        let hamt = make_map_with_root_and_bitwidth::<S, V>(&self.cid, store, W)
+            .map_err(|e| hamt_error(e, &name, format_args!("failed to load root {}", self.cid)))?;
+        Ok(NamedHamt { name, hamt })
+    }
+}
+
+/// Iteration in a defined order.
+///
+/// `Hamt::for_each` visits the entries in the order of the hashes of their keys, which stays
+/// the same only as long as the hash function, the bit width and the node layout do. Anything
+/// whose outcome depends on the order, e.g. which entries a bounded batch gets to or the order
+/// of entries returned to callers, should iterate in the order of the key bytes instead.
+impl<K, V, const W: u32> TCid<THamt<K, V, W>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    /// The keys in the map, sorted by their bytes.
+    pub fn keys_by_bytes<S: Blockstore>(&self, store: &S) -> Result<Vec<BytesKey>> {
+        sorted_keys(&self.load(store)?)
+    }
+
+    /// Visit the entries in the order of the bytes of their keys.
+    ///
+    /// All the keys are loaded up front, so this costs memory in proportion to the size of
+    /// the map; use it in messages only on maps whose size is bounded.
+    pub fn for_each_by_key<S: Blockstore>(
+        &self,
+        store: &S,
+        mut f: impl FnMut(&BytesKey, &V) -> Result<()>,
+    ) -> Result<()> {
+        let map = self.load(store)?;
+        for key in sorted_keys(&map)? {
+            let value = map
+                .get(&key)?
+                .ok_or_else(|| anyhow!("{} vanished while iterating", fmt_key(&key)))?;
+            f(&key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Types that can be the keys of a `THamt`, for the map-like operations on its `TCid`.
+///
+/// They are encoded the way the actors in this repository already encode them: addresses as
+/// their bytes, integers as varints, strings and subnet IDs as UTF-8 text and CIDs as their
+/// bytes, so existing maps can be used through the typed API.
+pub trait HamtKey: Sized {
+    fn to_key(&self) -> BytesKey;
+    fn from_key(key: &BytesKey) -> Result<Self>;
+}
+
+impl HamtKey for BytesKey {
+    fn to_key(&self) -> BytesKey {
+        self.clone()
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        Ok(key.clone())
+    }
+}
+
+impl HamtKey for Address {
+    fn to_key(&self) -> BytesKey {
+        BytesKey::from(self.to_bytes())
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        Ok(Address::from_bytes(&key.0)?)
+    }
+}
+
+impl HamtKey for u64 {
+    fn to_key(&self) -> BytesKey {
+        u64_key(*self)
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        Ok(parse_uint_key(&key.0)?)
+    }
+}
+
+impl HamtKey for String {
+    fn to_key(&self) -> BytesKey {
+        BytesKey::from(self.as_bytes())
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        Ok(String::from_utf8(key.0.clone())?)
+    }
+}
+
+impl HamtKey for SubnetID {
+    fn to_key(&self) -> BytesKey {
+        BytesKey::from(self.to_string().into_bytes())
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        SubnetID::from_str(std::str::from_utf8(&key.0)?)
+    }
+}
+
+impl HamtKey for Cid {
+    fn to_key(&self) -> BytesKey {
+        BytesKey::from(self.to_bytes())
+    }
+
+    fn from_key(key: &BytesKey) -> Result<Self> {
+        Ok(Cid::try_from(key.0.as_slice())?)
+    }
+}
+
+/// Map-like operations with typed keys, each loading the map and, if it changes, flushing it,
+/// failing with `ActorError`s which say which map and which key an operation failed on.
+///
+/// Several changes in a row are cheaper done in one `modify`.
+impl<K, V, const W: u32> TCid<THamt<K, V, W>>
+where
+    K: HamtKey,
+    V: Serialize + DeserializeOwned,
+{
+    pub fn get<S: Blockstore>(
+        &self,
+        store: &S,
+        key: &K,
+    ) -> std::result::Result<Option<V>, ActorError>
+    where
+        V: Clone,
+    {
+        Ok(self.named(store)?.get(&key.to_key())?.cloned())
+    }
+
+    pub fn contains_key<S: Blockstore>(
+        &self,
+        store: &S,
+        key: &K,
+    ) -> std::result::Result<bool, ActorError> {
+        self.named(store)?.contains_key(&key.to_key())
+    }
+
+    /// Insert or replace the value under the key, returning the previous one, if any.
+    pub fn set<S: Blockstore>(
+        &mut self,
+        store: &S,
+        key: &K,
+        value: V,
+    ) -> std::result::Result<Option<V>, ActorError>
+    where
+        V: PartialEq,
+    {
+        let mut map = self.named(store)?;
+        let previous = map.set(key.to_key(), value)?;
+        self.flush_named(map)?;
+        Ok(previous)
+    }
+
+    /// Remove the value under the key, returning it, if any.
+    pub fn delete<S: Blockstore>(
+        &mut self,
+        store: &S,
+        key: &K,
+    ) -> std::result::Result<Option<V>, ActorError> {
+        let mut map = self.named(store)?;
+        let removed = map.delete(&key.to_key())?;
+        if removed.is_some() {
+            self.flush_named(map)?;
+        }
+        Ok(removed)
+    }
+
+    /// Visit the entries in the order of the hashes of their keys; see `for_each_by_key`
+    /// for a defined order.
+    pub fn for_each<S: Blockstore>(
+        &self,
+        store: &S,
+        mut f: impl FnMut(K, &V) -> std::result::Result<(), ActorError>,
+    ) -> std::result::Result<(), ActorError> {
+        let map = self.named(store)?;
+        map.for_each(|k, v| {
+            let key = K::from_key(k)
+                .map_err(|e| anyhow!("{}: invalid {}: {}", map.name(), fmt_key(k), e))?;
+            Ok(f(key, v)?)
+        })
+        .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to iterate"))
+    }
+
+    fn named<'s, S: Blockstore>(
+        &self,
+        store: &'s S,
+    ) -> std::result::Result<NamedHamt<'s, S, V>, ActorError> {
+        self.load_named(store, short_type_name(type_name::<THamt<K, V, W>>()))
+    }
+
+    fn flush_named<S: Blockstore>(
+        &mut self,
+        map: NamedHamt<S, V>,
+    ) -> std::result::Result<(), ActorError> {
+        let name = map.name().to_string();
+        self.flush(map.into_inner())
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, name))?;
+        Ok(())
+    }
+}
+
+fn sorted_keys<S: Blockstore, V>(map: &Hamt<S, V>) -> Result<Vec<BytesKey>>
+where
+    V: Serialize + DeserializeOwned,
+{
+    let mut keys = Vec::new();
+    map.for_each(|k, _| {
+        keys.push(k.clone());
+        Ok(())
+    })?;
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(keys)
+}
+
+/// A loaded HAMT that knows what it is called, so that its errors can tell
+/// which map and which key an operation failed on.
+///
+/// The other operations of the underlying `Hamt` are available through `Deref`,
+/// and `into_inner` gives it back to be flushed.
+pub struct NamedHamt<'s, S: Blockstore, V> {
+    name: String,
+    hamt: Hamt<&'s S, V>,
+}
+
+impl<'s, S: Blockstore, V> NamedHamt<'s, S, V>
+where
+    V: Serialize + DeserializeOwned,
+{
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self, key: &BytesKey) -> std::result::Result<Option<&V>, ActorError> {
+        self.hamt.get(key).map_err(|e| {
+            hamt_error(
+                e,
+                &self.name,
+                format_args!("failed to get {}", fmt_key(key)),
+            )
+        })
+    }
+
+    pub fn set(&mut self, key: BytesKey, value: V) -> std::result::Result<Option<V>, ActorError>
+    where
+        V: PartialEq,
+    {
+        let context = format!("failed to set {}", fmt_key(&key));
+        self.hamt
+            .set(key, value)
+            .map_err(|e| hamt_error(e, &self.name, context))
+    }
+
+    pub fn delete(&mut self, key: &BytesKey) -> std::result::Result<Option<V>, ActorError> {
+        self.hamt
+            .delete(key)
+            .map(|removed| removed.map(|(_, v)| v))
+            .map_err(|e| {
+                hamt_error(
+                    e,
+                    &self.name,
+                    format_args!("failed to delete {}", fmt_key(key)),
+                )
+            })
+    }
+
+    pub fn contains_key(&self, key: &BytesKey) -> std::result::Result<bool, ActorError> {
+        self.hamt.contains_key(key).map_err(|e| {
+            hamt_error(
+                e,
+                &self.name,
+                format_args!("failed to find {}", fmt_key(key)),
+            )
+        })
+    }
+
+    /// Insert the entries whose key isn't in the map yet, reporting the others as failed
+    /// with `USR_ILLEGAL_ARGUMENT`, rather than failing the whole batch.
+    pub fn set_if_absent_batch(
+        &mut self,
+        entries: impl IntoIterator<Item = (BytesKey, V)>,
+    ) -> std::result::Result<BatchReturn, ActorError>
+    where
+        V: PartialEq,
+    {
+        let entries: Vec<_> = entries.into_iter().collect();
+        let mut ret = BatchReturnGen::new(entries.len());
+        for (key, value) in entries {
+            let context = format!("failed to set {}", fmt_key(&key));
+            let inserted = self
+                .hamt
+                .set_if_absent(key, value)
+                .map_err(|e| hamt_error(e, &self.name, context))?;
+            if inserted {
+                ret.add_success();
+            } else {
+                ret.add_fail(ExitCode::USR_ILLEGAL_ARGUMENT);
+            }
+        }
+        Ok(ret.gen())
+    }
+
+    /// Delete the given keys, reporting the ones not in the map as failed with `USR_NOT_FOUND`,
+    /// rather than failing the whole batch.
+    pub fn delete_batch<'k>(
+        &mut self,
+        keys: impl IntoIterator<Item = &'k BytesKey>,
+    ) -> std::result::Result<BatchReturn, ActorError> {
+        let keys: Vec<_> = keys.into_iter().collect();
+        let mut ret = BatchReturnGen::new(keys.len());
+        for key in keys {
+            match self.delete(key)? {
+                Some(_) => ret.add_success(),
+                None => ret.add_fail(ExitCode::USR_NOT_FOUND),
+            };
+        }
+        Ok(ret.gen())
+    }
+
+    pub fn into_inner(self) -> Hamt<&'s S, V> {
+        self.hamt
+    }
+}
+
+impl<'s, S: Blockstore, V> Deref for NamedHamt<'s, S, V> {
+    type Target = Hamt<&'s S, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.hamt
+    }
+}
+
+impl<'s, S: Blockstore, V> DerefMut for NamedHamt<'s, S, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.hamt
+    }
+}
+
+/// Turn a HAMT error into an illegal state `ActorError` saying which map failed doing what.
+pub fn hamt_error(err: HamtError, name: &str, context: impl Display) -> ActorError {
+    let msg = format!("{name}: {context}: {err}");
+    ActorError::illegal_state(msg).with_source(err)
+}
+
+/// Show keys that are printable text as such, e.g. `"alice"`, and other keys as hex, e.g. `0x01f4`.
+fn fmt_key(key: &BytesKey) -> String {
+    match std::str::from_utf8(&key.0) {
+        Ok(s) if !s.is_empty() && s.chars().all(|c| c.is_ascii_graphic() || c == ' ') => {
+            format!("key {s:?}")
+        }
+        _ => format!("key 0x{}", hex::encode(&key.0)),
+    }
+}
+
 /// This `Default` implementation is unsound in that while it
 /// creates `TCid` instances with a correct `Cid` value, this value
 /// is not stored anywhere, so there is no guarantee that any retrieval
@@ -103,3 +503,167 @@ where
         Self::new_hamt(&MemoryBlockstore::new()).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::actor_error;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_hamt::BytesKey;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use crate::{TCid, THamt};
+
+    #[test]
+    fn named_errors_include_name_and_key() {
+        let store = MemoryBlockstore::new();
+        let mut map: TCid<THamt<String, u64>> = TCid::new_hamt(&store).unwrap();
+        map.update(&store, |m| {
+            for i in 0..100u64 {
+                m.set(BytesKey::from(format!("key{i}").as_str()), i)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let named = map.load_named(&store, "balances").unwrap();
+        assert_eq!(named.get(&BytesKey::from("key1")).unwrap(), Some(&1));
+
+        // Lose the child nodes, so lookups beyond the root fail.
+        let empty = MemoryBlockstore::new();
+        let root = store.get(&map.cid()).unwrap().unwrap();
+        empty.put_keyed(&map.cid(), &root).unwrap();
+
+        let named = map.load_named(&empty, "balances").unwrap();
+        let err = (0..100u64)
+            .find_map(|i| named.get(&BytesKey::from(format!("key{i}").as_str())).err())
+            .expect("some key should be in a lost node");
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        assert!(err.msg().starts_with("balances: failed to get key \"key"));
+
+        let err = TCid::<THamt<String, u64>>::default()
+            .load_named(&empty, "balances")
+            .err()
+            .unwrap();
+        assert!(err.msg().starts_with("balances: failed to load root"));
+    }
+
+    #[test]
+    fn batch_operations_report_each_item() {
+        let store = MemoryBlockstore::new();
+        let map: TCid<THamt<String, u64>> = TCid::new_hamt(&store).unwrap();
+        let mut named = map.load_named(&store, "balances").unwrap();
+        named.set(BytesKey::from("b"), 0).unwrap();
+
+        let ret = named
+            .set_if_absent_batch(["a", "b", "c"].map(|k| (BytesKey::from(k), 1)))
+            .unwrap();
+        assert_eq!(
+            ret.codes().unwrap(),
+            vec![ExitCode::OK, ExitCode::USR_ILLEGAL_ARGUMENT, ExitCode::OK]
+        );
+        assert_eq!(named.get(&BytesKey::from("b")).unwrap(), Some(&0));
+
+        let keys = ["a", "d"].map(BytesKey::from);
+        let ret = named.delete_batch(&keys).unwrap();
+        assert_eq!(
+            ret.codes().unwrap(),
+            vec![ExitCode::OK, ExitCode::USR_NOT_FOUND]
+        );
+        assert_eq!(ret.successes(&keys).unwrap(), vec![BytesKey::from("a")]);
+    }
+
+    /// The order in which a map of `k0..k39` is iterated, which must not change across
+    /// versions of the HAMT, or actors iterating in it would diverge from their past selves.
+    const GOLDEN_HASH_ORDER: [u64; 40] = [
+        2, 30, 11, 19, 6, 34, 3, 31, 18, 14, 22, 10, 32, 24, 12, 15, 8, 13, 25, 1, 17, 16, 5, 29,
+        4, 28, 26, 36, 38, 27, 9, 20, 0, 21, 35, 37, 23, 39, 33, 7,
+    ];
+    const GOLDEN_ROOT: &str = "bafy2bzaceblk4x3d4qyzq7wglea6t35msmfweyfirrwa62utt4lgrxle3tp2a";
+
+    #[test]
+    fn iteration_order_is_stable() {
+        let store = MemoryBlockstore::new();
+        let mut maps = Vec::new();
+        // The order of insertion makes no difference.
+        for reverse in [false, true] {
+            let mut map: TCid<THamt<String, u64>> = TCid::new_hamt(&store).unwrap();
+            let mut values: Vec<u64> = (0..40).collect();
+            if reverse {
+                values.reverse();
+            }
+            map.update(&store, |m| {
+                for i in values {
+                    m.set(BytesKey::from(format!("k{i}").as_str()), i)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(map.cid().to_string(), GOLDEN_ROOT);
+            maps.push(map);
+        }
+
+        let map = &maps[0];
+        let mut hash_order = Vec::new();
+        map.load(&store)
+            .unwrap()
+            .for_each(|_, v| {
+                hash_order.push(*v);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(hash_order, GOLDEN_HASH_ORDER);
+
+        let mut key_order = Vec::new();
+        map.for_each_by_key(&store, |k, v| {
+            key_order.push((String::from_utf8(k.0.clone())?, *v));
+            Ok(())
+        })
+        .unwrap();
+        let mut expected: Vec<_> = (0..40).map(|i| (format!("k{i}"), i)).collect();
+        expected.sort();
+        assert_eq!(key_order, expected);
+        assert_eq!(
+            &key_order[..3],
+            &[("k0".into(), 0), ("k1".into(), 1), ("k10".into(), 10)]
+        );
+
+        let keys = map.keys_by_bytes(&store).unwrap();
+        assert_eq!(keys.len(), 40);
+        assert!(keys.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn typed_map_operations() {
+        let store = MemoryBlockstore::new();
+        let mut balances: TCid<THamt<Address, u64>> = TCid::new_hamt(&store).unwrap();
+        let (a, b) = (Address::new_id(100), Address::new_id(101));
+
+        assert_eq!(balances.set(&store, &a, 1).unwrap(), None);
+        assert_eq!(balances.set(&store, &b, 2).unwrap(), None);
+        assert_eq!(balances.set(&store, &a, 3).unwrap(), Some(1));
+        assert_eq!(balances.get(&store, &a).unwrap(), Some(3));
+        assert!(balances.contains_key(&store, &b).unwrap());
+
+        let mut seen = Vec::new();
+        balances
+            .for_each(&store, |k, v| {
+                seen.push((k, *v));
+                Ok(())
+            })
+            .unwrap();
+        seen.sort();
+        assert_eq!(seen, vec![(a, 3), (b, 2)]);
+
+        let before = balances.cid();
+        assert_eq!(balances.delete(&store, &b).unwrap(), Some(2));
+        assert_ne!(balances.cid(), before);
+        assert_eq!(balances.delete(&store, &b).unwrap(), None);
+        assert!(!balances.contains_key(&store, &b).unwrap());
+
+        let err = balances
+            .for_each(&store, |_, _| Err(actor_error!(forbidden; "stop")))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use fil_actors_runtime::runtime::Runtime;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+
+use crate::SubnetID;
+
+/// Separates the subnet from the raw address in the string form of an `IPCAddress`.
+const IPC_ADDRESS_SEPARATOR: char = ':';
+
+/// An address qualified with the subnet it lives in.
+///
+/// The canonical string form is `<subnet>:<address>`, e.g. `/root/f0100:f1abc...`,
+/// and it is CBOR encoded as the tuple `[subnet_id, raw_address]`.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct IPCAddress {
+    subnet_id: SubnetID,
+    raw_address: Address,
+}
+
+impl IPCAddress {
+    pub fn new(subnet_id: &SubnetID, raw_address: &Address) -> Self {
+        Self {
+            subnet_id: subnet_id.clone(),
+            raw_address: *raw_address,
+        }
+    }
+
+    /// The subnet the address lives in.
+    pub fn subnet(&self) -> &SubnetID {
+        &self.subnet_id
+    }
+
+    /// The address within its subnet.
+    pub fn raw_addr(&self) -> &Address {
+        &self.raw_address
+    }
+
+    /// Whether the address lives in `subnet_id`.
+    pub fn is_in(&self, subnet_id: &SubnetID) -> bool {
+        self.subnet_id == *subnet_id
+    }
+
+    /// Resolves the raw address to its ID form through the runtime.
+    ///
+    /// This is only meaningful for addresses in the subnet the runtime executes in,
+    /// which is expected to be `current`; `None` is returned for any other subnet or
+    /// if the address cannot be resolved.
+    pub fn resolve_id(&self, rt: &impl Runtime, current: &SubnetID) -> Option<IPCAddress> {
+        if !self.is_in(current) {
+            return None;
+        }
+        let id = rt.resolve_address(&self.raw_address)?;
+        Some(Self::new(&self.subnet_id, &id))
+    }
+}
+
+impl Display for IPCAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.subnet_id, IPC_ADDRESS_SEPARATOR, self.raw_address
+        )
+    }
+}
+
+impl FromStr for IPCAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (subnet_id, raw_address) = s.rsplit_once(IPC_ADDRESS_SEPARATOR).ok_or_else(|| {
+            anyhow!("ipc address is missing the '{IPC_ADDRESS_SEPARATOR}' separator: {s}")
+        })?;
+
+        let subnet_id = SubnetID::from_str(subnet_id)?;
+        let raw_address = Address::from_str(raw_address)
+            .map_err(|e| anyhow!("invalid raw address {raw_address} in {s}: {e}"))?;
+
+        Ok(Self::new(&subnet_id, &raw_address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+
+    use super::IPCAddress;
+    use crate::SubnetID;
+
+    #[test]
+    fn ipc_address_string_roundtrip() {
+        let subnet = SubnetID::from_str("/root/f0100").unwrap();
+        let raw = Address::new_id(1001);
+        let addr = IPCAddress::new(&subnet, &raw);
+
+        assert_eq!(addr.to_string(), "/root/f0100:f01001");
+        assert_eq!(IPCAddress::from_str("/root/f0100:f01001").unwrap(), addr);
+        assert!(IPCAddress::from_str("/root/f0100").is_err());
+    }
+
+    #[test]
+    fn ipc_address_cbor_roundtrip() {
+        let subnet = SubnetID::from_str("/root/f0100/f0101").unwrap();
+        let addr = IPCAddress::new(&subnet, &Address::new_id(7));
+
+        let encoded = RawBytes::serialize(&addr).unwrap();
+        let decoded: IPCAddress = encoded.deserialize().unwrap();
+        assert_eq!(decoded, addr);
+    }
+}
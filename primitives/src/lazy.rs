@@ -0,0 +1,248 @@
+use std::any::type_name;
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+
+use cid::multihash::Code;
+use cid::Cid;
+use fil_actors_runtime::actor_error;
+use fil_actors_runtime::runtime::ActorBlockstore;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::CborStore;
+use once_cell::unsync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A link to content in the store which dereferences to the content itself, loading it the
+/// first time it is accessed and writing it back when the link is serialized, if it was
+/// accessed mutably, so that state with nested links needs no explicit loads and flushes.
+///
+/// Unlike a `TCid<TLink<T>>`, it carries its store, which is why it is meant for actors, where
+/// the default `ActorBlockstore` is the same global store in every instance. Links decoded as
+/// part of a state are attached to `S::default()`, so stores which can't be shared that way,
+/// e.g. a `MemoryBlockstore` in a test, only work with links created by `new` and `load`.
+///
+/// Content that is missing or doesn't decode aborts the call with `USR_ILLEGAL_STATE`, like
+/// `TCid::load_or_abort`, and so does failing to write it back when serializing.
+///
+/// # Example
+/// ```
+/// use primitives::LazyLink;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut names = LazyLink::new(&store, vec!["foo".to_string()]);
+/// names.push("bar".to_string());
+/// let cid = names.flush().unwrap();
+///
+/// let names: LazyLink<Vec<String>, _> = LazyLink::load(&store, cid);
+/// assert_eq!(names.len(), 2);
+/// ```
+pub struct LazyLink<T, S = ActorBlockstore> {
+    store: S,
+    /// The content as of the last load or flush, unless it is dirty.
+    cid: Cell<Option<Cid>>,
+    value: OnceCell<T>,
+    dirty: Cell<bool>,
+}
+
+impl<T, S> LazyLink<T, S>
+where
+    T: Serialize + DeserializeOwned,
+    S: Blockstore,
+{
+    /// Link to new content, which is written to the store when flushed or serialized.
+    pub fn new(store: S, value: T) -> Self {
+        Self {
+            store,
+            cid: Cell::new(None),
+            value: OnceCell::from(value),
+            dirty: Cell::new(true),
+        }
+    }
+
+    /// Link to content already in the store, which is only read once accessed.
+    pub fn load(store: S, cid: Cid) -> Self {
+        Self {
+            store,
+            cid: Cell::new(Some(cid)),
+            value: OnceCell::new(),
+            dirty: Cell::new(false),
+        }
+    }
+
+    /// Whether the content was accessed mutably since it was last written.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.get()
+    }
+
+    /// Write the content to the store if it was accessed mutably, returning its `Cid`.
+    pub fn flush(&self) -> anyhow::Result<Cid> {
+        if let (Some(cid), false) = (self.cid.get(), self.dirty.get()) {
+            return Ok(cid);
+        }
+        let value = self.value.get().expect("dirty content is loaded");
+        let cid = self.store.put_cbor(value, Code::Blake2b256)?;
+        self.cid.set(Some(cid));
+        self.dirty.set(false);
+        Ok(cid)
+    }
+
+    fn get(&self) -> &T {
+        self.value.get_or_init(|| {
+            let cid = self.cid.get().expect("a link has either a cid or a value");
+            let value = self
+                .store
+                .get_cbor(&cid)
+                .and_then(|value| value.ok_or_else(|| anyhow::anyhow!("not found")));
+            value.unwrap_or_else(|e| {
+                actor_error!(illegal_state;
+                    "failed to load {} {}: {}", type_name::<T>(), cid, e)
+                .abort()
+            })
+        })
+    }
+}
+
+impl<T, S> Deref for LazyLink<T, S>
+where
+    T: Serialize + DeserializeOwned,
+    S: Blockstore,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T, S> DerefMut for LazyLink<T, S>
+where
+    T: Serialize + DeserializeOwned,
+    S: Blockstore,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.get();
+        self.dirty.set(true);
+        self.value.get_mut().expect("loaded above")
+    }
+}
+
+/// Serializes exactly as the `Cid` of its content, writing the content first if it is dirty.
+impl<T, S> Serialize for LazyLink<T, S>
+where
+    T: Serialize + DeserializeOwned,
+    S: Blockstore,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let cid = self.flush().unwrap_or_else(|e| {
+            actor_error!(illegal_state; "failed to flush {}: {}", type_name::<T>(), e).abort()
+        });
+        cid.serialize(serializer)
+    }
+}
+
+/// Deserializes from a `Cid`, attached to the default store, without loading anything yet.
+impl<'de, T, S> Deserialize<'de> for LazyLink<T, S>
+where
+    T: Serialize + DeserializeOwned,
+    S: Blockstore + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let cid = Cid::deserialize(deserializer)?;
+        Ok(Self::load(S::default(), cid))
+    }
+}
+
+impl<T: Clone, S: Clone> Clone for LazyLink<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            cid: self.cid.clone(),
+            value: self.value.clone(),
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+/// Shows the `Cid` and the content, if it was loaded.
+impl<T: Debug, S> Debug for LazyLink<T, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyLink")
+            .field("cid", &self.cid.get())
+            .field("value", &self.value.get())
+            .field("dirty", &self.dirty.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use cid::Cid;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+
+    use super::LazyLink;
+
+    thread_local! {
+        static STORE: Rc<MemoryBlockstore> = Rc::new(MemoryBlockstore::new());
+    }
+
+    /// A store every default instance of which is the same, like the `ActorBlockstore`.
+    #[derive(Default, Clone)]
+    struct SharedStore;
+
+    impl Blockstore for SharedStore {
+        fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+            STORE.with(|s| s.get(k))
+        }
+
+        fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+            STORE.with(|s| s.put_keyed(k, block))
+        }
+    }
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct State {
+        count: u64,
+        names: LazyLink<Vec<String>, SharedStore>,
+    }
+
+    #[test]
+    fn loads_on_access_and_flushes_on_serialize() {
+        let st = State {
+            count: 0,
+            names: LazyLink::new(SharedStore, vec![]),
+        };
+        let bytes = to_vec(&st).unwrap();
+        let empty = st.names.flush().unwrap();
+
+        let mut st: State = from_slice(&bytes).unwrap();
+        assert!(st.names.value.get().is_none());
+        assert!(st.names.is_empty());
+        assert!(!st.names.is_dirty());
+        assert_eq!(to_vec(&st).unwrap(), bytes);
+
+        st.count += 1;
+        st.names.push("foo".into());
+        assert!(st.names.is_dirty());
+        let bytes = to_vec(&st).unwrap();
+        assert!(!st.names.is_dirty());
+        assert_ne!(st.names.flush().unwrap(), empty);
+
+        let st: State = from_slice(&bytes).unwrap();
+        assert_eq!(*st.names, vec!["foo".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "aborted with exit code 20: failed to load")]
+    fn aborts_on_decode_failure() {
+        let store = MemoryBlockstore::new();
+        let cid = LazyLink::new(&store, 1u64).flush().unwrap();
+        let wrong: LazyLink<String, _> = LazyLink::load(&store, cid);
+        let _ = wrong.len();
+    }
+}
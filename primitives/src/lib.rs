@@ -1,19 +1,90 @@
-use std::{fmt::Display, marker::PhantomData};
+use std::any::type_name;
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
 
 use cid::{multihash::Code, Cid};
+use fvm_ipld_blockstore::Blockstore;
 
+mod actor_state;
+mod amount;
 mod amt;
+mod amt_search;
+mod budget;
+mod cbor_order;
+mod cids;
+mod circuit_breaker;
+#[cfg(feature = "client")]
+pub mod client;
+mod code_policy;
+#[cfg(feature = "json")]
+pub mod codec;
+mod config;
+mod constructor;
+mod counted;
+mod crossmsg;
+mod deposits;
+mod disputes;
+mod domain;
+mod envelope;
 mod ethaddr;
+mod exit_codes;
+mod fees;
+mod fixed_bytes;
+mod foreign_state;
 mod hamt;
+mod ipc_address;
+mod lazy;
 mod link;
+mod nonces;
+mod prune;
+mod queue;
+mod quorum;
+mod rewards;
+mod subnet_id;
+mod subnet_registry;
 mod taddress;
+mod timelock;
 mod uints;
+mod versioned;
+mod withdrawals;
 
+pub use actor_state::macro_support;
+pub use amount::{Amount, Asset, Fil, Frc46, Rate, SubnetNative};
 pub use amt::TAmt;
+pub use amt_search::{find_first_where, find_last_where, range_by_key};
+pub use budget::{process_until, ProcessCursor, Processed};
+pub use cbor_order::{is_cbor_sorted, sort_cbor_keys};
+pub use cids::{cbor_cid, check_cid, cid_of, format_cid, parse_cid};
+pub use circuit_breaker::CircuitBreaker;
+pub use code_policy::{CodePolicy, CODE_POLICY_UPDATED_EVENT};
+pub use config::{Config, CONFIG_UPDATED_EVENT};
+pub use constructor::{init_standard_state, PolicyOverrides, StdConstructorParams, StdState};
+pub use counted::{Counted, CountingHamt};
+pub use crossmsg::*;
+pub use deposits::{check_min_balance, Deposits};
+pub use disputes::{Challenge, Claim, Disputes, Settlement};
+pub use domain::*;
+pub use envelope::{signing_payload, SignedEnvelope};
 pub use ethaddr::*;
-pub use hamt::THamt;
-pub use link::TLink;
+pub use exit_codes::*;
+pub use fees::{FeeShares, FeeSplit, Fees, BASIS_POINTS, FEE_CHARGED_EVENT};
+pub use fixed_bytes::{Bytes32, FixedBytes};
+pub use foreign_state::ForeignStateRef;
+pub use hamt::{hamt_error, HamtError, HamtKey, NamedHamt, THamt};
+pub use ipc_address::IPCAddress;
+pub use lazy::LazyLink;
+pub use link::{StoreContent, TLink};
+pub use nonces::{Direction, NextNonces, NonceTracker, UnexpectedNonces};
+pub use prune::{prune_expired, EpochKeyed, PruneCursor, Pruned};
+pub use queue::{Queue, QueueFull, QueueMetrics};
+pub use quorum::{verify_quorum, QuorumProof, Validator, ValidatorSet};
+pub use rewards::RewardPool;
+pub use subnet_id::{SubnetID, ROOTNET_ID};
+pub use subnet_registry::{SubnetInfo, SubnetRegistry, SubnetStatus};
 pub use taddress::*;
+pub use timelock::{Timelock, TimelockedOp};
+pub use versioned::{next_state, unknown_version, StateVersion, VersionedState};
+pub use withdrawals::{withdraw, Withdrawals};
 
 /// Helper type to be able to define `Code` as a generic parameter.
 pub trait CodeType {
@@ -24,7 +95,7 @@ pub trait CodeType {
 pub trait TCidContent {}
 
 /// `TCid` is typed content, represented by a `Cid`.
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone)]
 pub struct TCid<T: TCidContent, C = codes::Blake2b256> {
     cid: Cid,
     _phantom_t: PhantomData<T>,
@@ -73,10 +144,77 @@ impl<'d, T: TCidContent, C> serde::Deserialize<'d> for TCid<T, C> {
 
 impl<T: TCidContent, C> Display for TCid<T, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.cid.fmt(f)
+        Display::fmt(&self.cid, f)
     }
 }
 
+/// Shows the type of the content, the codec and hash function of the `Cid`, and the `Cid`
+/// itself abbreviated, e.g. `TCid<THamt<String, u64, 5>>(dag-cbor blake2b-256 bafy2bza..nwq4ky)`.
+impl<T: TCidContent, C> Debug for TCid<T, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cid = self.cid.to_string();
+        let cid = match cid.len() {
+            0..=20 => cid,
+            n => format!("{}..{}", &cid[..8], &cid[n - 6..]),
+        };
+        let codec = match self.cid.codec() {
+            fvm_ipld_encoding::DAG_CBOR => "dag-cbor".to_string(),
+            fvm_ipld_encoding::CBOR => "cbor".to_string(),
+            fvm_ipld_encoding::IPLD_RAW => "raw".to_string(),
+            other => format!("{other:#x}"),
+        };
+        let hash = match self.cid.hash().code() {
+            0x00 => "identity".to_string(),
+            0x12 => "sha2-256".to_string(),
+            0x1b => "keccak-256".to_string(),
+            0xb220 => "blake2b-256".to_string(),
+            other => format!("{other:#x}"),
+        };
+        write!(
+            f,
+            "TCid<{}>({} {} {})",
+            short_type_name(type_name::<T>()),
+            codec,
+            hash,
+            cid
+        )
+    }
+}
+
+/// A type name without the paths of the types in it, e.g. `TLink<Vec<u8>>`.
+fn short_type_name(name: &str) -> String {
+    let is_delimiter = |c: char| "<>,;()[]& ".contains(c);
+    name.split_inclusive(is_delimiter)
+        .map(|part| {
+            let (path, delimiter) = match part.char_indices().last() {
+                Some((i, c)) if is_delimiter(c) => part.split_at(i),
+                _ => (part, ""),
+            };
+            format!("{}{}", path.rsplit("::").next().unwrap_or(path), delimiter)
+        })
+        .collect()
+}
+
+/// Content that can be compared by value, rather than by `Cid`.
+pub trait DeepEq: TCidContent {
+    /// Whether the content under two roots in the store is the same.
+    fn content_eq<S: Blockstore>(a: &Cid, b: &Cid, store: &S) -> anyhow::Result<bool>;
+}
+
+/// Whether two typed links point at the same content, even if their `Cid`s differ, e.g.
+/// because one was written by an implementation that doesn't encode canonically.
+/// Links with the same `Cid` are equal without loading anything.
+pub fn deep_eq<T, C1, C2, S>(a: &TCid<T, C1>, b: &TCid<T, C2>, store: &S) -> anyhow::Result<bool>
+where
+    T: DeepEq,
+    S: Blockstore,
+{
+    if a.cid == b.cid {
+        return Ok(true);
+    }
+    T::content_eq(&a.cid, &b.cid, store)
+}
+
 /// Assuming that the type implements `maybe_load` and `flush`,
 /// implement some convenience methods.
 ///
@@ -121,6 +259,23 @@ macro_rules! tcid_ops {
                 }
             }
 
+            /// Like `load`, but aborting the call with `USR_ILLEGAL_STATE` when the content is
+            /// missing or doesn't decode, rather than returning an error for every call site to
+            /// map. Only for actors, which can't recover from broken state anyway; libraries
+            /// should stick to `load`.
+            pub fn load_or_abort<'s, S: fvm_ipld_blockstore::Blockstore>(&self, store: &'s S) -> $item {
+                self.load(store).unwrap_or_else(|e| {
+                    fil_actors_runtime::actor_error!(
+                        illegal_state;
+                        "failed to load {} {}: {}",
+                        type_name::<Self>(),
+                        self.cid,
+                        e
+                    )
+                    .abort()
+                })
+            }
+
             /// Load, modify and flush a value, returning something as a result.
             pub fn modify<'s, S: fvm_ipld_blockstore::Blockstore, R>(
                 &mut self,
@@ -226,6 +381,18 @@ mod test {
         assert!(cid_typed.load(&store).is_err());
     }
 
+    #[test]
+    #[should_panic(expected = "aborted with exit code 20: failed to load")]
+    fn load_or_abort_on_decode_failure() {
+        let store = MemoryBlockstore::new();
+        let record: TCid<TLink<TestRecord>> =
+            TCid::new_link(&store, &TestRecord::default()).unwrap();
+        assert_eq!(record.load_or_abort(&store).foo, 0);
+
+        let wrong: TCid<TLink<u64>> = TCid::from(record.cid());
+        wrong.load_or_abort(&store);
+    }
+
     #[test]
     fn ref_modify() {
         let store = MemoryBlockstore::new();
@@ -241,6 +408,61 @@ mod test {
         assert_eq!(r.load(&store).unwrap().foo, 1);
     }
 
+    #[test]
+    fn debug_output() {
+        let store = MemoryBlockstore::new();
+        let map: TCid<THamt<String, TestRecord>> = TCid::new_hamt(&store).unwrap();
+        let debug = format!("{map:?}");
+        assert!(
+            debug.starts_with("TCid<THamt<String, TestRecord, 5>>(dag-cbor blake2b-256 bafy2bza.."),
+            "{debug}"
+        );
+        assert_eq!(short_type_name("a::B<c::D, [e::F; 2]>"), "B<D, [F; 2]>");
+    }
+
+    #[test]
+    fn deep_equality() {
+        let store = MemoryBlockstore::new();
+        let record = TestRecord {
+            foo: 1,
+            bar: vec![2],
+        };
+        let a: TCid<TLink<TestRecord>> = TCid::new_link(&store, &record).unwrap();
+        // The same record, with `foo` not encoded in the fewest bytes.
+        let mut bytes = vec![0x82, 0x18, 0x01];
+        bytes.extend_from_slice(&fvm_ipld_encoding::to_vec(&record).unwrap()[2..]);
+        let b: TCid<TLink<TestRecord>> = TCid::from(
+            store
+                .put(
+                    Code::Blake2b256,
+                    &fvm_ipld_blockstore::Block::new(fvm_ipld_encoding::DAG_CBOR, &bytes),
+                )
+                .unwrap(),
+        );
+        assert_ne!(a, b);
+        assert!(deep_eq(&a, &b, &store).unwrap());
+        let c: TCid<TLink<TestRecord>> = TCid::new_link(&store, &TestRecord::default()).unwrap();
+        assert!(!deep_eq(&a, &c, &store).unwrap());
+
+        let mut m1: TCid<THamt<String, TestRecord>> = TCid::new_hamt(&store).unwrap();
+        let mut m2: TCid<THamt<String, TestRecord>> = TCid::from(m1.cid());
+        m1.update(&store, |m| {
+            Ok(m.set("spam".into(), TestRecord::default()).map(|_| ())?)
+        })
+        .unwrap();
+        assert!(!deep_eq(&m1, &m2, &store).unwrap());
+        m2.update(&store, |m| {
+            Ok(m.set("spam".into(), TestRecord::default()).map(|_| ())?)
+        })
+        .unwrap();
+        assert!(deep_eq(&m1, &m2, &store).unwrap());
+
+        let a1: TCid<TAmt<u64>> = TCid::new_amt(&store).unwrap();
+        let mut a2: TCid<TAmt<u64>> = TCid::from(a1.cid());
+        a2.update(&store, |a| Ok(a.set(0, 1)?)).unwrap();
+        assert!(!deep_eq(&a1, &a2, &store).unwrap());
+    }
+
     #[test]
     fn hamt_modify() {
         let store = MemoryBlockstore::new();
@@ -1,9 +1,10 @@
 use std::any::type_name;
 use std::marker::PhantomData;
 
-use super::{CodeType, TCid, TCidContent};
+use super::{CodeType, DeepEq, TCid, TCidContent};
 use crate::tcid_ops;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 use serde::de::DeserializeOwned;
@@ -43,6 +44,21 @@ pub struct TLink<T> {
 
 impl<T> TCidContent for TLink<T> {}
 
+/// Links are equal if their values decode as equal.
+impl<T> DeepEq for TLink<T>
+where
+    T: DeserializeOwned + PartialEq,
+{
+    fn content_eq<S: Blockstore>(a: &Cid, b: &Cid, store: &S) -> Result<bool> {
+        let load = |cid| {
+            store
+                .get_cbor::<T>(cid)?
+                .ok_or_else(|| anyhow!("error loading {}: {} not found", type_name::<T>(), cid))
+        };
+        Ok(load(a)? == load(b)?)
+    }
+}
+
 pub struct StoreContent<'s, S: Blockstore, T> {
     store: &'s S,
     content: T,
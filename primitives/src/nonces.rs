@@ -0,0 +1,294 @@
+use std::fmt::Display;
+
+use fil_actors_runtime::{ActorError, TypedError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::error::ExitCode;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::{HamtKey, SubnetID, TCid, THamt, USR_UNEXPECTED_NONCE};
+
+/// Which way cross messages travel between a subnet and its parent.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Direction {
+    /// From the parent down to the child subnet.
+    TopDown = 0,
+    /// From the child subnet up to the parent.
+    BottomUp = 1,
+}
+
+/// The error of a batch of nonces which doesn't continue the sequence, aborting with
+/// `USR_UNEXPECTED_NONCE` and decodable with `ActorError::to_typed`, so that relayers can
+/// tell which messages to resend.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct UnexpectedNonces {
+    pub subnet: SubnetID,
+    pub direction: Direction,
+    /// The nonce the batch should have started with.
+    pub expected: u64,
+    /// The nonces skipped by the batch, as inclusive ranges in ascending order.
+    pub missing: Vec<(u64, u64)>,
+    /// The nonces in the batch that were already accepted, or that it holds more than once,
+    /// in ascending order.
+    pub replayed: Vec<u64>,
+    /// Whether the batch holds `u64::MAX`, after which the sequence couldn't continue.
+    pub exhausted: bool,
+}
+
+impl UnexpectedNonces {
+    /// Check that a batch of nonces, in any order, continues the sequence at `expected`
+    /// without gaps or replays, returning the nonce expected after it.
+    pub fn check(
+        subnet: &SubnetID,
+        direction: Direction,
+        expected: u64,
+        nonces: &[u64],
+    ) -> Result<u64, Self> {
+        let mut nonces = nonces.to_vec();
+        nonces.sort_unstable();
+
+        let (mut missing, mut replayed) = (Vec::new(), Vec::new());
+        let mut exhausted = false;
+        let mut next = expected;
+        for nonce in nonces {
+            if nonce < next {
+                if replayed.last() != Some(&nonce) {
+                    replayed.push(nonce);
+                }
+                continue;
+            }
+            if nonce > next {
+                missing.push((next, nonce - 1));
+            }
+            match nonce.checked_add(1) {
+                Some(after) => next = after,
+                None => {
+                    // Nothing but more copies of it can follow in sorted order.
+                    exhausted = true;
+                    break;
+                }
+            }
+        }
+        if missing.is_empty() && replayed.is_empty() && !exhausted {
+            return Ok(next);
+        }
+        Err(Self {
+            subnet: subnet.clone(),
+            direction,
+            expected,
+            missing,
+            replayed,
+            exhausted,
+        })
+    }
+}
+
+impl Display for UnexpectedNonces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unexpected {:?} nonces for subnet {} from {}",
+            self.direction, self.subnet, self.expected
+        )?;
+        if !self.missing.is_empty() {
+            let missing: Vec<String> = self
+                .missing
+                .iter()
+                .map(|(from, to)| {
+                    if from == to {
+                        from.to_string()
+                    } else {
+                        format!("{from}..={to}")
+                    }
+                })
+                .collect();
+            write!(f, ", missing {}", missing.join(", "))?;
+        }
+        if !self.replayed.is_empty() {
+            write!(f, ", replayed {:?}", self.replayed)?;
+        }
+        if self.exhausted {
+            write!(f, ", exhausting the sequence at {}", u64::MAX)?;
+        }
+        Ok(())
+    }
+}
+
+impl TypedError for UnexpectedNonces {
+    const EXIT_CODES: &'static [ExitCode] = &[USR_UNEXPECTED_NONCE];
+
+    fn exit_code(&self) -> ExitCode {
+        USR_UNEXPECTED_NONCE
+    }
+}
+
+/// The nonces expected next from a subnet, in both directions.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize_tuple, Deserialize_tuple)]
+pub struct NextNonces {
+    pub top_down: u64,
+    pub bottom_up: u64,
+}
+
+impl NextNonces {
+    pub fn get(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::TopDown => self.top_down,
+            Direction::BottomUp => self.bottom_up,
+        }
+    }
+
+    fn get_mut(&mut self, direction: Direction) -> &mut u64 {
+        match direction {
+            Direction::TopDown => &mut self.top_down,
+            Direction::BottomUp => &mut self.bottom_up,
+        }
+    }
+}
+
+/// The nonces expected next of the cross messages from and to each subnet, to be embedded
+/// in the state, so that the top-down and bottom-up paths detect gaps and replays the same way.
+///
+/// # Example
+/// ```
+/// use primitives::{Direction, NonceTracker, SubnetID};
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut nonces = NonceTracker::new(&store).unwrap();
+/// let subnet = SubnetID::default();
+///
+/// assert_eq!(nonces.accept(&store, &subnet, Direction::TopDown, &[1, 0]).unwrap(), 2);
+/// assert!(nonces.accept(&store, &subnet, Direction::TopDown, &[3]).is_err());
+/// assert_eq!(nonces.next_nonce(&store, &subnet, Direction::BottomUp).unwrap(), 0);
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct NonceTracker {
+    next: TCid<THamt<SubnetID, NextNonces>>,
+}
+
+impl NonceTracker {
+    pub fn new<S: Blockstore>(store: &S) -> anyhow::Result<Self> {
+        Ok(Self {
+            next: TCid::new_hamt(store)?,
+        })
+    }
+
+    /// The nonce expected of the next message, 0 for a subnet not seen before.
+    pub fn next_nonce<S: Blockstore>(
+        &self,
+        store: &S,
+        subnet: &SubnetID,
+        direction: Direction,
+    ) -> anyhow::Result<u64> {
+        let next = self.next.load(store)?;
+        Ok(next
+            .get(&subnet.to_key())?
+            .map(|n| n.get(direction))
+            .unwrap_or_default())
+    }
+
+    /// Accept a batch of nonces, in any order, if it continues the sequence without gaps or
+    /// replays, returning the nonce expected next, or failing with `UnexpectedNonces`
+    /// otherwise, in which case nothing changes.
+    pub fn accept<S: Blockstore>(
+        &mut self,
+        store: &S,
+        subnet: &SubnetID,
+        direction: Direction,
+        nonces: &[u64],
+    ) -> anyhow::Result<u64> {
+        self.next.modify(store, |next| {
+            let mut nonces_of_subnet = next.get(&subnet.to_key())?.copied().unwrap_or_default();
+            let expected = nonces_of_subnet.get(direction);
+            let after = UnexpectedNonces::check(subnet, direction, expected, nonces)
+                .map_err(|e| ActorError::typed(&e))?;
+            if after != expected {
+                *nonces_of_subnet.get_mut(direction) = after;
+                next.set(subnet.to_key(), nonces_of_subnet)?;
+            }
+            Ok(after)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::{ActorDowncast, ActorError};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Direction, NonceTracker, UnexpectedNonces};
+    use crate::{SubnetID, USR_UNEXPECTED_NONCE};
+
+    #[test]
+    fn gaps_and_replays() {
+        let subnet = SubnetID::default();
+        let check = |expected, nonces: &[u64]| {
+            UnexpectedNonces::check(&subnet, Direction::BottomUp, expected, nonces)
+        };
+        assert_eq!(check(3, &[]), Ok(3));
+        assert_eq!(check(3, &[4, 3, 5]), Ok(6));
+
+        let err = check(3, &[1, 4, 4, 5, 9, 11, 2]).unwrap_err();
+        assert_eq!(err.missing, vec![(3, 3), (6, 8), (10, 10)]);
+        assert_eq!(err.replayed, vec![1, 2, 4]);
+        assert_eq!(
+            err.to_string(),
+            "unexpected BottomUp nonces for subnet /root from 3, \
+             missing 3, 6..=8, 10, replayed [1, 2, 4]"
+        );
+
+        // The last nonce is rejected, rather than wrapping the sequence around to 0.
+        let err = check(u64::MAX - 1, &[u64::MAX, u64::MAX - 1]).unwrap_err();
+        assert!(err.exhausted);
+        assert!(err.missing.is_empty() && err.replayed.is_empty());
+        let err = check(u64::MAX, &[u64::MAX]).unwrap_err();
+        assert!(err.exhausted);
+        assert_eq!(check(u64::MAX - 2, &[u64::MAX - 2]), Ok(u64::MAX - 1));
+    }
+
+    #[test]
+    fn tracks_subnets_and_directions_apart() {
+        let store = MemoryBlockstore::new();
+        let mut tracker = NonceTracker::new(&store).unwrap();
+        let (a, b) = (
+            SubnetID::default(),
+            SubnetID::new_from_parent(&SubnetID::default(), Address::new_id(100)),
+        );
+
+        assert_eq!(
+            tracker
+                .accept(&store, &a, Direction::TopDown, &[0, 1])
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            tracker
+                .accept(&store, &a, Direction::BottomUp, &[0])
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            tracker
+                .accept(&store, &b, Direction::TopDown, &[0])
+                .unwrap(),
+            1
+        );
+
+        let err: ActorError = tracker
+            .accept(&store, &a, Direction::TopDown, &[1, 3])
+            .unwrap_err()
+            .downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to accept nonces");
+        assert_eq!(err.exit_code(), USR_UNEXPECTED_NONCE);
+        let err = err.to_typed::<UnexpectedNonces>().unwrap();
+        assert_eq!(err.expected, 2);
+        assert_eq!(err.missing, vec![(2, 2)]);
+        assert_eq!(err.replayed, vec![1]);
+        assert_eq!(
+            tracker.next_nonce(&store, &a, Direction::TopDown).unwrap(),
+            2
+        );
+    }
+}
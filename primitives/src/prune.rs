@@ -0,0 +1,253 @@
+use anyhow::Result;
+use fil_actors_runtime::fvm_ipld_amt::Amt;
+use fil_actors_runtime::u64_key;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::Hamt;
+use fvm_shared::clock::ChainEpoch;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+/// Position of a pruning process over an epoch-keyed collection,
+/// to be persisted in the actor state so that the next call can resume where the last one stopped.
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct PruneCursor {
+    next_epoch: ChainEpoch,
+}
+
+impl PruneCursor {
+    /// Start pruning at the given epoch, e.g. the epoch the collection was created at.
+    pub fn new(from: ChainEpoch) -> Self {
+        Self { next_epoch: from }
+    }
+
+    /// The first epoch that hasn't been pruned yet.
+    pub fn next_epoch(&self) -> ChainEpoch {
+        self.next_epoch
+    }
+}
+
+/// Outcome of a single `prune_expired` call.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Pruned {
+    /// Number of entries removed.
+    pub removed: u64,
+    /// Whether all the entries before the watermark are gone,
+    /// or the call ran out of steps and has to be resumed.
+    pub done: bool,
+}
+
+/// Collections keyed by epoch, that can be pruned in bounded chunks.
+pub trait EpochKeyed {
+    /// Remove entries keyed by an epoch lower than `watermark`, starting at the cursor,
+    /// taking at most `max_steps` steps, and advance the cursor past what was handled.
+    fn prune(
+        &mut self,
+        cursor: &mut PruneCursor,
+        watermark: ChainEpoch,
+        max_steps: u64,
+    ) -> Result<Pruned>;
+}
+
+/// Remove the entries of an epoch-keyed collection that are older than the `watermark`,
+/// doing at most `max_steps` units of work, so that the gas used by a single call is bounded.
+///
+/// The `cursor` is advanced past the epochs that have been handled. If the result isn't
+/// `done`, calling it again with the same cursor, e.g. from the next cron tick, carries on.
+///
+/// For an `Amt` indexed by epoch a step is an entry visited, each of which is removed, even
+/// if it is behind the cursor; for a `Hamt` keyed by `u64_key(epoch)` a step is an epoch
+/// looked up, as the keys can't be iterated in order, and entries behind the cursor are left.
+///
+/// # Example
+/// ```
+/// use primitives::{prune_expired, PruneCursor, TAmt, TCid};
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut expiries: TCid<TAmt<String>> = TCid::new_amt(&store).unwrap();
+/// expiries.update(&store, |arr| {
+///     for epoch in 0..10 {
+///         arr.set(epoch, format!("expires at {epoch}"))?;
+///     }
+///     Ok(())
+/// }).unwrap();
+///
+/// let mut cursor = PruneCursor::default();
+/// let pruned = expiries.modify(&store, |arr| prune_expired(arr, &mut cursor, 8, 5)).unwrap();
+/// assert_eq!(pruned.removed, 5);
+/// assert!(!pruned.done);
+///
+/// let pruned = expiries.modify(&store, |arr| prune_expired(arr, &mut cursor, 8, 5)).unwrap();
+/// assert_eq!(pruned.removed, 3);
+/// assert!(pruned.done);
+/// assert_eq!(expiries.load(&store).unwrap().count(), 2);
+/// ```
+pub fn prune_expired<C: EpochKeyed>(
+    collection: &mut C,
+    cursor: &mut PruneCursor,
+    watermark: ChainEpoch,
+    max_steps: u64,
+) -> Result<Pruned> {
+    collection.prune(cursor, watermark, max_steps)
+}
+
+impl<V, BS> EpochKeyed for Amt<V, BS>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    fn prune(
+        &mut self,
+        cursor: &mut PruneCursor,
+        watermark: ChainEpoch,
+        max_steps: u64,
+    ) -> Result<Pruned> {
+        let end = watermark.max(0) as u64;
+
+        // The AMT can only be iterated from the beginning, so rather than skipping entries
+        // before the cursor, e.g. ones set after it passed them, which would take unbounded
+        // work, every entry visited counts as a step and is removed, being expired too.
+        let mut expired = Vec::new();
+        let mut done = true;
+        self.for_each_while(|i, _| {
+            if i >= end {
+                return Ok(false);
+            }
+            if expired.len() as u64 == max_steps {
+                done = false;
+                return Ok(false);
+            }
+            expired.push(i);
+            Ok(true)
+        })?;
+
+        let removed = expired.len() as u64;
+        cursor.next_epoch = match (done, expired.last()) {
+            (false, Some(last)) => cursor.next_epoch.max(*last as ChainEpoch + 1),
+            (false, None) => cursor.next_epoch,
+            (true, _) => cursor.next_epoch.max(watermark),
+        };
+        self.batch_delete(expired, true)?;
+
+        Ok(Pruned { removed, done })
+    }
+}
+
+impl<BS, V> EpochKeyed for Hamt<BS, V>
+where
+    V: Serialize + DeserializeOwned,
+    BS: Blockstore,
+{
+    fn prune(
+        &mut self,
+        cursor: &mut PruneCursor,
+        watermark: ChainEpoch,
+        max_steps: u64,
+    ) -> Result<Pruned> {
+        let mut removed = 0;
+        let mut steps = 0;
+        while cursor.next_epoch < watermark {
+            if steps == max_steps {
+                return Ok(Pruned {
+                    removed,
+                    done: false,
+                });
+            }
+            if cursor.next_epoch >= 0 && self.delete(&u64_key(cursor.next_epoch as u64))?.is_some()
+            {
+                removed += 1;
+            }
+            cursor.next_epoch += 1;
+            steps += 1;
+        }
+        Ok(Pruned {
+            removed,
+            done: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::u64_key;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+
+    use crate::{prune_expired, PruneCursor, TAmt, TCid, THamt};
+
+    #[test]
+    fn amt_counts_entries_behind_cursor() {
+        let store = MemoryBlockstore::new();
+        let mut arr: TCid<TAmt<u64>> = TCid::new_amt(&store).unwrap();
+        arr.update(&store, |arr| {
+            for epoch in 0..6 {
+                arr.set(epoch, epoch)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let mut cursor = PruneCursor::default();
+        let pruned = arr
+            .modify(&store, |arr| prune_expired(arr, &mut cursor, 10, 3))
+            .unwrap();
+        assert_eq!(pruned.removed, 3);
+        assert_eq!(cursor.next_epoch(), 3);
+
+        // Set behind the cursor: visited, so it takes a step, and removed.
+        arr.update(&store, |arr| arr.set(1, 1).map_err(Into::into))
+            .unwrap();
+        let pruned = arr
+            .modify(&store, |arr| prune_expired(arr, &mut cursor, 10, 3))
+            .unwrap();
+        assert_eq!(pruned.removed, 3);
+        assert!(!pruned.done);
+        assert_eq!(cursor.next_epoch(), 5);
+
+        let pruned = arr
+            .modify(&store, |arr| prune_expired(arr, &mut cursor, 10, 3))
+            .unwrap();
+        assert_eq!(pruned.removed, 1);
+        assert!(pruned.done);
+        assert_eq!(cursor.next_epoch(), 10);
+        assert_eq!(arr.load(&store).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn hamt_resumes_from_cursor() {
+        let store = MemoryBlockstore::new();
+        let mut map: TCid<THamt<u64, u64>> = TCid::new_hamt(&store).unwrap();
+        map.update(&store, |map| {
+            for epoch in [10u64, 12, 15, 30] {
+                map.set(u64_key(epoch), epoch)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let mut cursor = PruneCursor::new(10);
+        let pruned = map
+            .modify(&store, |map| prune_expired(map, &mut cursor, 20, 4))
+            .unwrap();
+        assert_eq!(pruned.removed, 2);
+        assert!(!pruned.done);
+        assert_eq!(cursor.next_epoch(), 14);
+
+        let pruned = map
+            .modify(&store, |map| prune_expired(map, &mut cursor, 20, 4))
+            .unwrap();
+        assert_eq!(pruned.removed, 1);
+        assert!(!pruned.done);
+
+        let pruned = map
+            .modify(&store, |map| prune_expired(map, &mut cursor, 20, 4))
+            .unwrap();
+        assert_eq!(pruned.removed, 0);
+        assert!(pruned.done);
+        assert_eq!(cursor.next_epoch(), 20);
+
+        let map = map.load(&store).unwrap();
+        assert!(map.get(&u64_key(15)).unwrap().is_none());
+        assert_eq!(map.get(&u64_key(30)).unwrap(), Some(&30));
+    }
+}
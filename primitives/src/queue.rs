@@ -0,0 +1,223 @@
+use std::fmt::Display;
+
+use fil_actors_runtime::{ActorError, TypedError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::error::ExitCode;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::{TAmt, TCid, USR_QUEUE_FULL};
+
+/// A first in, first out queue of items, e.g. cross messages waiting to be relayed, to be
+/// embedded in the state.
+///
+/// Items are numbered in the order they were pushed, from 0, and the numbers are never reused.
+/// The length is bounded for producers pushing with [`Queue::push_with_backpressure`], who get a
+/// [`QueueFull`] error to pass on to their callers once the queue is at its maximum length,
+/// rather than the state growing without bound while the consumer lags behind.
+///
+/// # Example
+/// ```
+/// use primitives::Queue;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut queue = Queue::new(&store, 2).unwrap();
+///
+/// assert_eq!(queue.push_with_backpressure(&store, "foo".to_string()).unwrap(), 0);
+/// assert_eq!(queue.push_with_backpressure(&store, "bar".to_string()).unwrap(), 1);
+/// assert!(queue.push_with_backpressure(&store, "baz".to_string()).is_err());
+///
+/// assert_eq!(queue.pop(&store).unwrap(), Some("foo".to_string()));
+/// assert_eq!(queue.metrics().len, 1);
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+#[serde(bound = "")]
+pub struct Queue<T> {
+    items: TCid<TAmt<T>>,
+    /// Number of the next item to pop.
+    head: u64,
+    /// Number of the next item to push.
+    tail: u64,
+    max_len: u64,
+}
+
+/// The fill level of a `Queue` and its throughput since it was created.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QueueMetrics {
+    pub len: u64,
+    pub max_len: u64,
+    pub pushed: u64,
+    pub popped: u64,
+}
+
+/// The error of pushing to a `Queue` at its maximum length, aborting with `USR_QUEUE_FULL`
+/// and decodable with `ActorError::to_typed`, so that callers can back off and retry.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QueueFull {
+    pub metrics: QueueMetrics,
+}
+
+impl Display for QueueFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "queue full with {} of at most {} items",
+            self.metrics.len, self.metrics.max_len
+        )
+    }
+}
+
+impl TypedError for QueueFull {
+    const EXIT_CODES: &'static [ExitCode] = &[USR_QUEUE_FULL];
+
+    fn exit_code(&self) -> ExitCode {
+        USR_QUEUE_FULL
+    }
+}
+
+impl<T> Queue<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Create an empty queue, bounded to `max_len` items for producers that respect it.
+    pub fn new<S: Blockstore>(store: &S, max_len: u64) -> anyhow::Result<Self> {
+        Ok(Self {
+            items: TCid::new_amt(store)?,
+            head: 0,
+            tail: 0,
+            max_len,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.tail - self.head
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_len(&self) -> u64 {
+        self.max_len
+    }
+
+    /// Change the maximum length; items beyond a lower one stay queued, but nothing more can
+    /// be pushed with backpressure until the queue has drained below it.
+    pub fn set_max_len(&mut self, max_len: u64) {
+        self.max_len = max_len;
+    }
+
+    pub fn metrics(&self) -> QueueMetrics {
+        QueueMetrics {
+            len: self.len(),
+            max_len: self.max_len,
+            pushed: self.tail,
+            popped: self.head,
+        }
+    }
+
+    /// Push an item regardless of the maximum length, e.g. one the actor generates itself and
+    /// can't refuse, returning its number.
+    pub fn push<S: Blockstore>(&mut self, store: &S, item: T) -> anyhow::Result<u64> {
+        let n = self.tail;
+        self.items.update(store, |items| Ok(items.set(n, item)?))?;
+        self.tail += 1;
+        Ok(n)
+    }
+
+    /// Push an item from a producer that has to be held back when the queue is full,
+    /// returning its number, or failing with the `QueueFull` error otherwise.
+    pub fn push_with_backpressure<S: Blockstore>(
+        &mut self,
+        store: &S,
+        item: T,
+    ) -> anyhow::Result<u64> {
+        if self.len() >= self.max_len {
+            let full = QueueFull {
+                metrics: self.metrics(),
+            };
+            return Err(ActorError::typed(&full).into());
+        }
+        self.push(store, item)
+    }
+
+    /// The next item to pop, if any.
+    pub fn peek<S: Blockstore>(&self, store: &S) -> anyhow::Result<Option<T>>
+    where
+        T: Clone,
+    {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let items = self.items.load(store)?;
+        Ok(items.get(self.head)?.cloned())
+    }
+
+    /// Remove and return the oldest item, if any.
+    pub fn pop<S: Blockstore>(&mut self, store: &S) -> anyhow::Result<Option<T>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let head = self.head;
+        let item = self.items.modify(store, |items| Ok(items.delete(head)?))?;
+        self.head += 1;
+        Ok(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::{ActorDowncast, ActorError};
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::error::ExitCode;
+
+    use super::{Queue, QueueFull, QueueMetrics};
+    use crate::USR_QUEUE_FULL;
+
+    #[test]
+    fn backpressure_when_full() {
+        let store = MemoryBlockstore::new();
+        let mut queue: Queue<u64> = Queue::new(&store, 2).unwrap();
+        queue.push_with_backpressure(&store, 10).unwrap();
+        queue.push_with_backpressure(&store, 11).unwrap();
+
+        let err: ActorError = queue
+            .push_with_backpressure(&store, 12)
+            .unwrap_err()
+            .downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to queue");
+        assert_eq!(err.exit_code(), USR_QUEUE_FULL);
+        let full = QueueMetrics {
+            len: 2,
+            max_len: 2,
+            pushed: 2,
+            popped: 0,
+        };
+        assert_eq!(
+            err.to_typed::<QueueFull>(),
+            Some(QueueFull { metrics: full })
+        );
+
+        // Items that can't be refused still go in.
+        assert_eq!(queue.push(&store, 12).unwrap(), 2);
+        assert_eq!(queue.peek(&store).unwrap(), Some(10));
+        assert_eq!(queue.pop(&store).unwrap(), Some(10));
+        assert!(queue.push_with_backpressure(&store, 13).is_err());
+        assert_eq!(queue.pop(&store).unwrap(), Some(11));
+        assert_eq!(queue.push_with_backpressure(&store, 13).unwrap(), 3);
+
+        assert_eq!(queue.pop(&store).unwrap(), Some(12));
+        assert_eq!(queue.pop(&store).unwrap(), Some(13));
+        assert_eq!(queue.pop(&store).unwrap(), None);
+        assert_eq!(
+            queue.metrics(),
+            QueueMetrics {
+                len: 0,
+                max_len: 2,
+                pushed: 4,
+                popped: 4,
+            }
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use cid::Cid;
+use fil_actors_runtime::runtime::fvm::resolve_secp_bls;
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_encoding::strict_bytes;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::Signature;
+use fvm_shared::econ::TokenAmount;
+use num_traits::Zero;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{cbor_cid, USR_INVALID_CHECKPOINT};
+
+/// A validator with its voting weight, e.g. its stake in the subnet.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Validator {
+    pub addr: Address,
+    pub weight: TokenAmount,
+}
+
+/// The validators whose signatures make a checkpoint valid, in a fixed order which the
+/// bitmap of a `QuorumProof` refers to.
+///
+/// Only built by `new`, and checked the same way when decoded.
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize_tuple)]
+pub struct ValidatorSet {
+    validators: Vec<Validator>,
+}
+
+impl ValidatorSet {
+    /// Fails with `USR_ILLEGAL_ARGUMENT` if an address is listed twice, which would let one
+    /// signature count for every copy, or a weight is negative.
+    pub fn new(validators: Vec<Validator>) -> Result<Self, ActorError> {
+        for (i, v) in validators.iter().enumerate() {
+            if v.weight.is_negative() {
+                return Err(actor_error!(illegal_argument;
+                    "validator {} has negative weight {}", v.addr, v.weight));
+            }
+            if validators[..i].iter().any(|other| other.addr == v.addr) {
+                return Err(actor_error!(illegal_argument; "validator {} is listed twice", v.addr));
+            }
+        }
+        Ok(Self { validators })
+    }
+
+    pub fn validators(&self) -> &[Validator] {
+        &self.validators
+    }
+
+    pub fn total_weight(&self) -> TokenAmount {
+        self.validators.iter().map(|v| &v.weight).sum()
+    }
+
+    /// The CID of the set, as it would be stored with `TCid::new_link`, which proofs use to
+    /// refer to the snapshot of the weights they were signed with.
+    pub fn cid(&self) -> Result<Cid, ActorError> {
+        cbor_cid(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValidatorSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (validators,) = Deserialize::deserialize(deserializer)?;
+        Self::new(validators).map_err(de::Error::custom)
+    }
+}
+
+/// The signatures of a quorum of validators over a checkpoint.
+///
+/// Bit `i` of the bitmap, counting from the lowest bit of the first byte, is set if the
+/// validator at index `i` in the set has signed, and the signatures are in the same order
+/// as the set bits. BLS signatures are verified one by one, like the others, as there is no
+/// syscall to verify an aggregate over several keys in this version of the FVM.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QuorumProof {
+    #[serde(with = "strict_bytes")]
+    pub signers: Vec<u8>,
+    pub signatures: Vec<Signature>,
+    /// The CID of the `ValidatorSet` the signers are taken from.
+    pub validators: Cid,
+}
+
+impl QuorumProof {
+    /// Build a proof from the signatures of the validators at the given indices in the set,
+    /// e.g. as collected by a relayer or in tests.
+    pub fn new(
+        validators: &ValidatorSet,
+        signatures: impl IntoIterator<Item = (usize, Signature)>,
+    ) -> Result<Self, ActorError> {
+        let mut signatures: Vec<_> = signatures.into_iter().collect();
+        signatures.sort_by_key(|(i, _)| *i);
+        let mut signers = vec![0u8; (validators.validators.len() + 7) / 8];
+        for (i, _) in &signatures {
+            if *i >= validators.validators.len() {
+                return Err(actor_error!(illegal_argument; "no validator at index {}", i));
+            }
+            if signers[i / 8] & (1 << (i % 8)) != 0 {
+                return Err(
+                    actor_error!(illegal_argument; "duplicate signature of validator {}", i),
+                );
+            }
+            signers[i / 8] |= 1 << (i % 8);
+        }
+        Ok(Self {
+            signers,
+            signatures: signatures.into_iter().map(|(_, s)| s).collect(),
+            validators: validators.cid()?,
+        })
+    }
+
+    /// Indices of the validators who signed, in ascending order.
+    pub fn signer_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.signers.len() * 8).filter(|i| self.signers[i / 8] & (1 << (i % 8)) != 0)
+    }
+}
+
+/// Verify that validators holding more than two thirds of the weight of `validators` have
+/// signed the bytes of `checkpoint`, returning the weight that signed.
+///
+/// Fails with `USR_INVALID_CHECKPOINT` if the proof refers to another set, is malformed,
+/// carries an invalid signature, counts a key twice, e.g. for a validator listed by both its
+/// ID and its key address, or falls short of the quorum.
+pub fn verify_quorum<RT: Runtime>(
+    rt: &mut RT,
+    proof: &QuorumProof,
+    checkpoint: &Cid,
+    validators: &ValidatorSet,
+) -> Result<TokenAmount, ActorError> {
+    let invalid = |msg: String| ActorError::unchecked(USR_INVALID_CHECKPOINT, msg);
+
+    if proof.validators != validators.cid()? {
+        return Err(invalid(format!(
+            "proof is for validator set {}, not {}",
+            proof.validators,
+            validators.cid()?
+        )));
+    }
+    let count = validators.validators.len();
+    if proof.signers.len() != (count + 7) / 8 {
+        return Err(invalid(format!(
+            "bitmap of {} bytes for {} validators",
+            proof.signers.len(),
+            count
+        )));
+    }
+    let signers: Vec<usize> = proof.signer_indices().collect();
+    if let Some(i) = signers.iter().find(|i| **i >= count) {
+        return Err(invalid(format!("no validator at index {i}")));
+    }
+    if signers.len() != proof.signatures.len() {
+        return Err(invalid(format!(
+            "{} signers with {} signatures",
+            signers.len(),
+            proof.signatures.len()
+        )));
+    }
+
+    let plaintext = checkpoint.to_bytes();
+    let mut signed = TokenAmount::zero();
+    let mut keys = Vec::with_capacity(proof.signatures.len());
+    for (i, signature) in signers.into_iter().zip(&proof.signatures) {
+        let validator = &validators.validators[i];
+        let key = resolve_secp_bls(rt, &validator.addr).map_err(|e| {
+            e.wrap(format!(
+                "failed to resolve key of validator {}",
+                validator.addr
+            ))
+        })?;
+        if keys.contains(&key) {
+            return Err(invalid(format!(
+                "validator {} signed with key {}, which was already counted",
+                validator.addr, key
+            )));
+        }
+        rt.verify_signature(signature, &key, &plaintext)
+            .map_err(|e| invalid(format!("invalid signature of {}: {}", validator.addr, e)))?;
+        signed += &validator.weight;
+        keys.push(key);
+    }
+
+    let total = validators.total_weight();
+    if &signed * 3 <= &total * 2 {
+        return Err(invalid(format!(
+            "signed by {signed} out of {total}, which is no more than two thirds"
+        )));
+    }
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::Cid;
+    use fil_actors_runtime::runtime::fvm::PUBLIC_RESOLVE_ADDRESS_METHOD;
+    use fil_actors_runtime::test_utils::{ExpectedVerifySig, MockRuntime};
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_shared::address::Address;
+    use fvm_shared::crypto::signature::Signature;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use num_traits::Zero;
+
+    use super::{verify_quorum, QuorumProof, Validator, ValidatorSet};
+    use crate::USR_INVALID_CHECKPOINT;
+
+    fn validators() -> ValidatorSet {
+        ValidatorSet::new(
+            [40, 30, 20, 10]
+                .into_iter()
+                .enumerate()
+                .map(|(i, weight)| Validator {
+                    addr: Address::new_secp256k1(&[i as u8; 65]).unwrap(),
+                    weight: TokenAmount::from_atto(weight),
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    fn signature(i: usize) -> Signature {
+        Signature::new_secp256k1(vec![i as u8; 65])
+    }
+
+    /// Verify a proof signed by the validators at `signers`, all signatures being valid.
+    fn verify(signers: &[usize]) -> Result<TokenAmount, fil_actors_runtime::ActorError> {
+        let mut rt = MockRuntime::default();
+        let set = validators();
+        let checkpoint = Cid::default();
+        // Verified in the order of the validators, whatever the order they signed in.
+        let mut sorted = signers.to_vec();
+        sorted.sort();
+        for i in &sorted {
+            rt.expect_verify_signature(ExpectedVerifySig {
+                sig: signature(*i),
+                signer: set.validators[*i].addr,
+                plaintext: checkpoint.to_bytes(),
+                result: Ok(()),
+            });
+        }
+        let proof = QuorumProof::new(&set, signers.iter().map(|i| (*i, signature(*i)))).unwrap();
+        rt.in_call = true;
+        let res = verify_quorum(&mut rt, &proof, &checkpoint, &set);
+        rt.verify();
+        res
+    }
+
+    #[test]
+    fn quorum_of_weight() {
+        assert_eq!(verify(&[2, 0, 1]).unwrap(), TokenAmount::from_atto(90));
+        assert_eq!(verify(&[0, 1, 3]).unwrap(), TokenAmount::from_atto(80));
+        // Exactly two thirds isn't enough, however many validators signed.
+        let err = verify(&[1, 2, 3]).unwrap_err();
+        assert_eq!(err.exit_code(), USR_INVALID_CHECKPOINT);
+    }
+
+    #[test]
+    fn malformed_proofs() {
+        let mut rt = MockRuntime::default();
+        let set = validators();
+        let checkpoint = Cid::default();
+        let proof = QuorumProof::new(&set, [(0, signature(0)), (1, signature(1))]).unwrap();
+        assert_eq!(proof.signers, vec![0b11]);
+        assert_eq!(proof.signer_indices().collect::<Vec<_>>(), vec![0, 1]);
+        assert!(QuorumProof::new(&set, [(4, signature(4))]).is_err());
+        assert!(QuorumProof::new(&set, [(1, signature(1)), (1, signature(1))]).is_err());
+
+        rt.in_call = true;
+        let mut other = set.clone();
+        other.validators.pop();
+        let mut bad = vec![QuorumProof::new(&other, []).unwrap()];
+        let mut p = proof.clone();
+        p.signatures.pop();
+        bad.push(p);
+        let mut p = proof.clone();
+        p.signers[0] |= 1 << 5;
+        bad.push(p);
+        let mut p = proof;
+        p.signers.push(0);
+        bad.push(p);
+        for proof in bad {
+            let err = verify_quorum(&mut rt, &proof, &checkpoint, &set).unwrap_err();
+            assert_eq!(err.exit_code(), USR_INVALID_CHECKPOINT, "{proof:?}");
+        }
+        rt.verify();
+    }
+
+    #[test]
+    fn rejects_duplicate_validators() {
+        let set = validators();
+        assert_eq!(
+            from_slice::<ValidatorSet>(&to_vec(&set).unwrap()).unwrap(),
+            set
+        );
+
+        let mut validators = set.validators;
+        validators.push(validators[1].clone());
+        let bytes = to_vec(&(validators.clone(),)).unwrap();
+        let err = ValidatorSet::new(validators).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert!(from_slice::<ValidatorSet>(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_key_signing_twice() {
+        // The validator at index 0, listed again by its ID address.
+        let mut validators = validators().validators;
+        let key = validators[0].addr;
+        let id = Address::new_id(100);
+        validators.push(Validator {
+            addr: id,
+            weight: TokenAmount::from_atto(40),
+        });
+        let set = ValidatorSet::new(validators).unwrap();
+        let checkpoint = Cid::default();
+
+        let mut rt = MockRuntime::default();
+        rt.expect_verify_signature(ExpectedVerifySig {
+            sig: signature(0),
+            signer: key,
+            plaintext: checkpoint.to_bytes(),
+            result: Ok(()),
+        });
+        rt.expect_send(
+            id,
+            PUBLIC_RESOLVE_ADDRESS_METHOD,
+            None,
+            TokenAmount::zero(),
+            IpldBlock::serialize_cbor(&key).unwrap(),
+            ExitCode::OK,
+        );
+        let proof = QuorumProof::new(&set, [(0, signature(0)), (4, signature(0))]).unwrap();
+        rt.in_call = true;
+        let err = verify_quorum(&mut rt, &proof, &checkpoint, &set).unwrap_err();
+        assert_eq!(err.exit_code(), USR_INVALID_CHECKPOINT);
+        rt.verify();
+    }
+
+    #[test]
+    fn rejects_negative_weights() {
+        let mut validators = validators().validators;
+        validators[2].weight = TokenAmount::from_atto(-1);
+        let bytes = to_vec(&(validators.clone(),)).unwrap();
+        let err = ValidatorSet::new(validators).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert!(from_slice::<ValidatorSet>(&bytes).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use fil_actors_runtime::actor_error;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::{bigint_ser, BigInt};
+use fvm_shared::econ::TokenAmount;
+use num_traits::Zero;
+
+use crate::{TCid, THamt};
+
+/// Fixed point scale of the accumulated reward per unit of stake.
+const PRECISION: u64 = 1_000_000_000_000_000_000;
+
+/// Rewards shared among stakers in proportion to their stake, to be embedded in the state,
+/// using the accumulated rewards per share pattern: accruing rewards and settling a staker
+/// are both O(1), regardless of the number of stakers.
+///
+/// All amounts are kept exact in fixed point, and only rounded down when paid out, with the
+/// fraction carried over, so the total claimed never exceeds the total accrued, no matter how
+/// stakes are split or how often rewards are claimed.
+///
+/// # Example
+/// ```
+/// use primitives::RewardPool;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut pool = RewardPool::new(&store).unwrap();
+/// let (alice, bob) = (Address::new_id(100), Address::new_id(101));
+///
+/// pool.deposit(&store, &alice, &TokenAmount::from_atto(1)).unwrap();
+/// pool.deposit(&store, &bob, &TokenAmount::from_atto(3)).unwrap();
+/// pool.accrue(&TokenAmount::from_atto(100)).unwrap();
+///
+/// assert_eq!(pool.claim(&store, &alice).unwrap(), TokenAmount::from_atto(25));
+/// assert_eq!(pool.claim(&store, &bob).unwrap(), TokenAmount::from_atto(75));
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct RewardPool {
+    stakes: TCid<THamt<Address, Stake>>,
+    total_stake: TokenAmount,
+    /// Rewards per atto of stake since the pool was created, scaled by `PRECISION`.
+    #[serde(with = "bigint_ser")]
+    acc_per_share: BigInt,
+    /// Scaled rewards accrued but not yet reflected in `acc_per_share`, either
+    /// for lack of stake or because they were less than one unit of it.
+    #[serde(with = "bigint_ser")]
+    remainder: BigInt,
+}
+
+/// The stake of an address and its rewards, scaled by `PRECISION`.
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize_tuple, Deserialize_tuple)]
+struct Stake {
+    amount: TokenAmount,
+    /// `amount * acc_per_share` when the stake was last settled.
+    #[serde(with = "bigint_ser")]
+    debt: BigInt,
+    /// Rewards settled but not claimed yet.
+    #[serde(with = "bigint_ser")]
+    unclaimed: BigInt,
+}
+
+impl Stake {
+    /// Move the rewards accumulated since the last settlement to `unclaimed`.
+    fn settle(&mut self, acc_per_share: &BigInt) {
+        let accumulated = self.amount.atto() * acc_per_share;
+        self.unclaimed += &accumulated - &self.debt;
+        self.debt = accumulated;
+    }
+
+    /// The whole units of `unclaimed`, without taking them.
+    fn claimable(&self) -> TokenAmount {
+        TokenAmount::from_atto(&self.unclaimed / PRECISION)
+    }
+}
+
+impl RewardPool {
+    pub fn new<S: Blockstore>(store: &S) -> anyhow::Result<Self> {
+        Ok(Self {
+            stakes: TCid::new_hamt(store)?,
+            total_stake: TokenAmount::zero(),
+            acc_per_share: BigInt::zero(),
+            remainder: BigInt::zero(),
+        })
+    }
+
+    pub fn total_stake(&self) -> &TokenAmount {
+        &self.total_stake
+    }
+
+    /// The stake of an address.
+    pub fn stake_of<S: Blockstore>(
+        &self,
+        store: &S,
+        addr: &Address,
+    ) -> anyhow::Result<TokenAmount> {
+        Ok(self.get(store, addr)?.amount)
+    }
+
+    /// The rewards an address could claim now.
+    pub fn pending<S: Blockstore>(&self, store: &S, addr: &Address) -> anyhow::Result<TokenAmount> {
+        let mut stake = self.get(store, addr)?;
+        stake.settle(&self.acc_per_share);
+        Ok(stake.claimable())
+    }
+
+    /// Distribute rewards among the current stakers.
+    ///
+    /// Rewards accrued while there is no stake go to the first stakers.
+    pub fn accrue(&mut self, reward: &TokenAmount) -> anyhow::Result<()> {
+        check_amount(reward, "reward")?;
+        self.remainder += reward.atto() * PRECISION;
+        if self.total_stake.is_positive() {
+            let increment = &self.remainder / self.total_stake.atto();
+            self.remainder -= &increment * self.total_stake.atto();
+            self.acc_per_share += increment;
+        }
+        Ok(())
+    }
+
+    /// Add to the stake of an address. Its rewards so far are kept for it to claim.
+    pub fn deposit<S: Blockstore>(
+        &mut self,
+        store: &S,
+        addr: &Address,
+        amount: &TokenAmount,
+    ) -> anyhow::Result<()> {
+        check_amount(amount, "deposit")?;
+        self.update(store, addr, |stake| {
+            stake.amount += amount;
+            Ok(())
+        })?;
+        self.total_stake += amount;
+        Ok(())
+    }
+
+    /// Take from the stake of an address. Its rewards so far are kept for it to claim.
+    pub fn withdraw<S: Blockstore>(
+        &mut self,
+        store: &S,
+        addr: &Address,
+        amount: &TokenAmount,
+    ) -> anyhow::Result<()> {
+        check_amount(amount, "withdrawal")?;
+        self.update(store, addr, |stake| {
+            if &stake.amount < amount {
+                return Err(actor_error!(insufficient_funds;
+                    "can't withdraw {} from stake {} of {}", amount, stake.amount, addr)
+                .into());
+            }
+            stake.amount -= amount;
+            Ok(())
+        })?;
+        self.total_stake -= amount;
+        Ok(())
+    }
+
+    /// Take the rewards of an address, returning the amount to pay it.
+    pub fn claim<S: Blockstore>(
+        &mut self,
+        store: &S,
+        addr: &Address,
+    ) -> anyhow::Result<TokenAmount> {
+        let mut claimed = TokenAmount::zero();
+        self.update(store, addr, |stake| {
+            claimed = stake.claimable();
+            stake.unclaimed -= claimed.atto() * PRECISION;
+            Ok(())
+        })?;
+        Ok(claimed)
+    }
+
+    fn get<S: Blockstore>(&self, store: &S, addr: &Address) -> anyhow::Result<Stake> {
+        let stakes = self.stakes.load(store)?;
+        let stake = stakes.get(&BytesKey::from(addr.to_bytes()))?;
+        Ok(stake.cloned().unwrap_or_default())
+    }
+
+    /// Settle the stake of an address, apply `f` to it, and store it back,
+    /// deleting it once there is neither stake nor rewards left.
+    fn update<S: Blockstore>(
+        &mut self,
+        store: &S,
+        addr: &Address,
+        f: impl FnOnce(&mut Stake) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let acc_per_share = &self.acc_per_share;
+        self.stakes.update(store, |stakes| {
+            let key = BytesKey::from(addr.to_bytes());
+            let mut stake = stakes.get(&key)?.cloned().unwrap_or_default();
+            stake.settle(acc_per_share);
+            f(&mut stake)?;
+            stake.debt = stake.amount.atto() * acc_per_share;
+            if stake.amount.is_zero() && stake.unclaimed.is_zero() {
+                stakes.delete(&key)?;
+            } else {
+                stakes.set(key, stake)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn check_amount(amount: &TokenAmount, what: &str) -> anyhow::Result<()> {
+    if amount.is_negative() {
+        return Err(actor_error!(illegal_argument; "negative {} {}", what, amount).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use num_traits::Zero;
+
+    use super::RewardPool;
+
+    fn atto(n: u64) -> TokenAmount {
+        TokenAmount::from_atto(n)
+    }
+
+    #[test]
+    fn rewards_follow_stake_over_time() {
+        let store = MemoryBlockstore::new();
+        let mut pool = RewardPool::new(&store).unwrap();
+        let (alice, bob) = (Address::new_id(100), Address::new_id(101));
+
+        // Accrued before anyone stakes, kept for the first stakers.
+        pool.accrue(&atto(10)).unwrap();
+        pool.deposit(&store, &alice, &atto(10)).unwrap();
+        pool.accrue(&atto(10)).unwrap();
+        assert_eq!(pool.pending(&store, &alice).unwrap(), atto(20));
+
+        pool.deposit(&store, &bob, &atto(30)).unwrap();
+        pool.accrue(&atto(40)).unwrap();
+        assert_eq!(pool.pending(&store, &alice).unwrap(), atto(30));
+        assert_eq!(pool.pending(&store, &bob).unwrap(), atto(30));
+
+        // Withdrawing the stake keeps the rewards earned so far.
+        pool.withdraw(&store, &alice, &atto(10)).unwrap();
+        pool.accrue(&atto(30)).unwrap();
+        assert_eq!(pool.claim(&store, &alice).unwrap(), atto(30));
+        assert_eq!(pool.claim(&store, &bob).unwrap(), atto(60));
+        assert!(pool.claim(&store, &alice).unwrap().is_zero());
+
+        assert!(pool.withdraw(&store, &bob, &atto(31)).is_err());
+    }
+
+    #[test]
+    fn rounding_never_pays_more_than_accrued() {
+        let store = MemoryBlockstore::new();
+        let mut pool = RewardPool::new(&store).unwrap();
+        let stakers: Vec<Address> = (100..107).map(Address::new_id).collect();
+        for (i, addr) in stakers.iter().enumerate() {
+            pool.deposit(&store, addr, &atto(1 + i as u64 * 3)).unwrap();
+        }
+
+        let mut accrued = TokenAmount::zero();
+        let mut claimed = TokenAmount::zero();
+        for round in 0..200u64 {
+            // Amounts that don't split evenly, claimed after every accrual.
+            let reward = atto(1 + round % 5);
+            pool.accrue(&reward).unwrap();
+            accrued += reward;
+            for addr in &stakers {
+                claimed += pool.claim(&store, addr).unwrap();
+            }
+            assert!(claimed <= accrued, "claimed {claimed} of {accrued} accrued");
+        }
+        // The fractions left behind add up to less than one atto per staker, and one in the pool.
+        assert!(&accrued - &claimed <= atto(stakers.len() as u64));
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::address::Address;
+
+/// Name of the root network used when none is specified.
+pub const ROOTNET_ID: &str = "root";
+
+/// Identifies a subnet by the name of its root network and the path of
+/// subnet actor addresses leading from the root to the subnet.
+///
+/// The canonical string form is `/<root>/<actor>/<actor>...`, e.g. `/root/f0100`.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SubnetID {
+    root: String,
+    children: Vec<Address>,
+}
+
+impl SubnetID {
+    pub fn new(root: impl Into<String>, children: Vec<Address>) -> Self {
+        Self {
+            root: root.into(),
+            children,
+        }
+    }
+
+    /// Creates the ID of the root network.
+    pub fn new_root(root: impl Into<String>) -> Self {
+        Self::new(root, Vec::new())
+    }
+
+    /// Creates the ID of a child subnet governed by `subnet_actor` in `parent`.
+    pub fn new_from_parent(parent: &SubnetID, subnet_actor: Address) -> Self {
+        let mut children = parent.children.clone();
+        children.push(subnet_actor);
+        Self::new(parent.root.clone(), children)
+    }
+
+    /// Name of the root network this subnet descends from.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Addresses of the subnet actors on the path from the root to this subnet.
+    pub fn children(&self) -> &[Address] {
+        &self.children
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// The ID of the root network this subnet descends from.
+    pub fn root_id(&self) -> SubnetID {
+        Self::new_root(self.root.clone())
+    }
+
+    /// The parent subnet, or `None` for the root network.
+    pub fn parent(&self) -> Option<SubnetID> {
+        let (_, ancestors) = self.children.split_last()?;
+        Some(Self::new(self.root.clone(), ancestors.to_vec()))
+    }
+
+    /// The address of the subnet actor governing this subnet in its parent,
+    /// or `None` for the root network.
+    pub fn subnet_actor(&self) -> Option<Address> {
+        self.children.last().copied()
+    }
+}
+
+impl Default for SubnetID {
+    fn default() -> Self {
+        Self::new_root(ROOTNET_ID)
+    }
+}
+
+impl Display for SubnetID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/{}", self.root)?;
+        for child in &self.children {
+            write!(f, "/{child}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SubnetID {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = s
+            .strip_prefix('/')
+            .ok_or_else(|| anyhow!("subnet id must start with '/': {s}"))?
+            .split('/');
+
+        let root = match segments.next() {
+            Some(root) if !root.is_empty() => root.to_string(),
+            _ => return Err(anyhow!("subnet id is missing the root network: {s}")),
+        };
+
+        let children = segments
+            .map(|child| {
+                Address::from_str(child)
+                    .map_err(|e| anyhow!("invalid subnet actor address {child} in {s}: {e}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self::new(root, children))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use fvm_shared::address::Address;
+
+    use super::SubnetID;
+
+    #[test]
+    fn subnet_id_string_roundtrip() {
+        let root = SubnetID::default();
+        assert_eq!(root.to_string(), "/root");
+
+        let child = SubnetID::new_from_parent(&root, Address::new_id(100));
+        assert_eq!(child.to_string(), "/root/f0100");
+        assert_eq!(SubnetID::from_str("/root/f0100").unwrap(), child);
+        assert_eq!(child.parent(), Some(root.clone()));
+        assert_eq!(child.subnet_actor(), Some(Address::new_id(100)));
+        assert_eq!(root.parent(), None);
+
+        assert!(SubnetID::from_str("root/f0100").is_err());
+        assert!(SubnetID::from_str("/").is_err());
+        assert!(SubnetID::from_str("/root/not-an-address").is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use fil_actors_runtime::{actor_error, ActorError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::econ::TokenAmount;
+use num_traits::Zero;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::{SubnetID, TCid, THamt, USR_SUBNET_NOT_ACTIVE, USR_SUBNET_NOT_FOUND};
+
+/// The stage of its lifecycle a subnet is in.
+///
+/// The only transitions are:
+/// * `Registered` -> `Active`, once its collateral reaches the minimum;
+/// * `Active` -> `Inactive`, when its collateral falls below the minimum;
+/// * `Inactive` -> `Active`, when it's topped up again;
+/// * `Registered` or `Inactive` -> `Killed`, which is final.
+///
+/// An active subnet has to be deactivated before it can be killed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum SubnetStatus {
+    Registered = 0,
+    Active = 1,
+    Inactive = 2,
+    Killed = 3,
+}
+
+impl SubnetStatus {
+    /// Whether a subnet in this status can move to `next`.
+    pub fn can_transition_to(self, next: SubnetStatus) -> bool {
+        use SubnetStatus::*;
+        matches!(
+            (self, next),
+            (Registered, Active)
+                | (Active, Inactive)
+                | (Inactive, Active)
+                | (Registered, Killed)
+                | (Inactive, Killed)
+        )
+    }
+}
+
+/// A subnet in a `SubnetRegistry`.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SubnetInfo {
+    pub id: SubnetID,
+    pub status: SubnetStatus,
+    pub collateral: TokenAmount,
+}
+
+/// The child subnets known to an actor and their collateral, to be embedded in the state of
+/// both the gateway and the registry actors, so that they agree on when a subnet is active.
+///
+/// The status of a subnet follows its collateral: it's active while it holds at least the
+/// minimum, and inactive once it falls below it. Killed subnets are kept, so that their ID
+/// can't be registered again.
+///
+/// # Example
+/// ```
+/// use primitives::{SubnetID, SubnetRegistry, SubnetStatus};
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut registry = SubnetRegistry::new(&store, TokenAmount::from_atto(10)).unwrap();
+/// let id = SubnetID::new_from_parent(&SubnetID::default(), Address::new_id(1000));
+///
+/// let status = registry.register(&store, &id, &TokenAmount::from_atto(5)).unwrap();
+/// assert_eq!(status, SubnetStatus::Registered);
+/// let status = registry.add_collateral(&store, &id, &TokenAmount::from_atto(5)).unwrap();
+/// assert_eq!(status, SubnetStatus::Active);
+/// assert!(registry.require_active(&store, &id).is_ok());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SubnetRegistry {
+    min_collateral: TokenAmount,
+    subnets: TCid<THamt<SubnetID, SubnetInfo>>,
+    total_collateral: TokenAmount,
+}
+
+impl SubnetRegistry {
+    /// Create an empty registry, where subnets need `min_collateral` to be active.
+    pub fn new<S: Blockstore>(store: &S, min_collateral: TokenAmount) -> anyhow::Result<Self> {
+        Ok(Self {
+            min_collateral,
+            subnets: TCid::new_hamt(store)?,
+            total_collateral: TokenAmount::zero(),
+        })
+    }
+
+    /// The collateral a subnet needs to be active.
+    pub fn min_collateral(&self) -> &TokenAmount {
+        &self.min_collateral
+    }
+
+    /// Sum of the collateral of all subnets, which the actor has to keep in its balance.
+    pub fn total_collateral(&self) -> &TokenAmount {
+        &self.total_collateral
+    }
+
+    /// The subnet with the given ID, in whatever status.
+    pub fn get<S: Blockstore>(
+        &self,
+        store: &S,
+        id: &SubnetID,
+    ) -> anyhow::Result<Option<SubnetInfo>> {
+        let subnets = self.subnets.load(store)?;
+        Ok(subnets.get(&key(id))?.cloned())
+    }
+
+    /// The subnet with the given ID, failing with `USR_SUBNET_NOT_FOUND` if it isn't
+    /// registered and `USR_SUBNET_NOT_ACTIVE` if it isn't active, e.g. before accepting
+    /// cross messages or checkpoints of it.
+    pub fn require_active<S: Blockstore>(
+        &self,
+        store: &S,
+        id: &SubnetID,
+    ) -> anyhow::Result<SubnetInfo> {
+        let info = self.get(store, id)?.ok_or_else(|| not_found(id))?;
+        if info.status != SubnetStatus::Active {
+            return Err(ActorError::unchecked(
+                USR_SUBNET_NOT_ACTIVE,
+                format!("subnet {} is {:?}", id, info.status),
+            )
+            .into());
+        }
+        Ok(info)
+    }
+
+    /// Register a new subnet with its initial collateral, returning its status, which is
+    /// already `Active` if the collateral covers the minimum.
+    pub fn register<S: Blockstore>(
+        &mut self,
+        store: &S,
+        id: &SubnetID,
+        collateral: &TokenAmount,
+    ) -> anyhow::Result<SubnetStatus> {
+        check_amount(collateral)?;
+        let mut info = SubnetInfo {
+            id: id.clone(),
+            status: SubnetStatus::Registered,
+            collateral: collateral.clone(),
+        };
+        if info.collateral >= self.min_collateral {
+            info.status = SubnetStatus::Active;
+        }
+        let status = info.status;
+        self.subnets.update(store, |subnets| {
+            if !subnets.set_if_absent(key(id), info)? {
+                return Err(
+                    actor_error!(illegal_argument; "subnet {} already registered", id).into(),
+                );
+            }
+            Ok(())
+        })?;
+        self.total_collateral += collateral;
+        Ok(status)
+    }
+
+    /// Add to the collateral of a subnet, activating it if it now covers the minimum,
+    /// and returning its status.
+    pub fn add_collateral<S: Blockstore>(
+        &mut self,
+        store: &S,
+        id: &SubnetID,
+        amount: &TokenAmount,
+    ) -> anyhow::Result<SubnetStatus> {
+        check_amount(amount)?;
+        let min_collateral = &self.min_collateral.clone();
+        let status = self.modify(store, id, |info| {
+            if info.status == SubnetStatus::Killed {
+                return Err(actor_error!(forbidden; "subnet {} has been killed", id).into());
+            }
+            info.collateral += amount;
+            if info.status != SubnetStatus::Active && &info.collateral >= min_collateral {
+                transition(info, SubnetStatus::Active)?;
+            }
+            Ok(info.status)
+        })?;
+        self.total_collateral += amount;
+        Ok(status)
+    }
+
+    /// Take from the collateral of a subnet, e.g. for a validator leaving it or to slash it,
+    /// deactivating it if it no longer covers the minimum, and returning its status.
+    ///
+    /// Fails with `USR_INSUFFICIENT_FUNDS` if the subnet doesn't hold that much.
+    pub fn release_collateral<S: Blockstore>(
+        &mut self,
+        store: &S,
+        id: &SubnetID,
+        amount: &TokenAmount,
+    ) -> anyhow::Result<SubnetStatus> {
+        check_amount(amount)?;
+        let min_collateral = &self.min_collateral.clone();
+        let status = self.modify(store, id, |info| {
+            if &info.collateral < amount {
+                return Err(actor_error!(insufficient_funds;
+                    "subnet {} holds {} of collateral, not {}", id, info.collateral, amount)
+                .into());
+            }
+            info.collateral -= amount;
+            if info.status == SubnetStatus::Active && &info.collateral < min_collateral {
+                transition(info, SubnetStatus::Inactive)?;
+            }
+            Ok(info.status)
+        })?;
+        self.total_collateral -= amount;
+        Ok(status)
+    }
+
+    /// Kill a subnet which isn't active, returning the collateral it still held, which
+    /// is released for the actor to pay back.
+    pub fn kill<S: Blockstore>(&mut self, store: &S, id: &SubnetID) -> anyhow::Result<TokenAmount> {
+        let released = self.modify(store, id, |info| {
+            transition(info, SubnetStatus::Killed)?;
+            Ok(std::mem::take(&mut info.collateral))
+        })?;
+        self.total_collateral -= &released;
+        Ok(released)
+    }
+
+    /// Load, modify and store back a registered subnet.
+    fn modify<S: Blockstore, R>(
+        &mut self,
+        store: &S,
+        id: &SubnetID,
+        f: impl FnOnce(&mut SubnetInfo) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        self.subnets.modify(store, |subnets| {
+            let mut info = subnets
+                .get(&key(id))?
+                .cloned()
+                .ok_or_else(|| not_found(id))?;
+            let result = f(&mut info)?;
+            subnets.set(key(id), info)?;
+            Ok(result)
+        })
+    }
+}
+
+/// Move a subnet to `next`, failing if that's not one of the allowed transitions.
+fn transition(info: &mut SubnetInfo, next: SubnetStatus) -> Result<(), ActorError> {
+    if !info.status.can_transition_to(next) {
+        return Err(actor_error!(forbidden;
+            "subnet {} can't go from {:?} to {:?}", info.id, info.status, next));
+    }
+    info.status = next;
+    Ok(())
+}
+
+fn check_amount(amount: &TokenAmount) -> Result<(), ActorError> {
+    if amount.is_negative() {
+        return Err(actor_error!(illegal_argument; "negative collateral {}", amount));
+    }
+    Ok(())
+}
+
+fn not_found(id: &SubnetID) -> ActorError {
+    ActorError::unchecked(USR_SUBNET_NOT_FOUND, format!("subnet {id} not registered"))
+}
+
+fn key(id: &SubnetID) -> BytesKey {
+    BytesKey::from(id.to_string().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::ActorError;
+    use fvm_ipld_blockstore::MemoryBlockstore;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{SubnetRegistry, SubnetStatus};
+    use crate::{SubnetID, USR_SUBNET_NOT_ACTIVE, USR_SUBNET_NOT_FOUND};
+
+    fn code(err: anyhow::Error) -> ExitCode {
+        err.downcast::<ActorError>().unwrap().exit_code()
+    }
+
+    #[test]
+    fn status_follows_collateral() {
+        use SubnetStatus::*;
+        let store = MemoryBlockstore::new();
+        let mut registry = SubnetRegistry::new(&store, TokenAmount::from_atto(10)).unwrap();
+        let id = SubnetID::new_from_parent(&SubnetID::default(), Address::new_id(1000));
+        let atto = TokenAmount::from_atto;
+
+        let err = registry.require_active(&store, &id).unwrap_err();
+        assert_eq!(code(err), USR_SUBNET_NOT_FOUND);
+        assert_eq!(
+            registry.register(&store, &id, &atto(4)).unwrap(),
+            Registered
+        );
+        assert!(registry.register(&store, &id, &atto(10)).is_err());
+        let err = registry.require_active(&store, &id).unwrap_err();
+        assert_eq!(code(err), USR_SUBNET_NOT_ACTIVE);
+
+        assert_eq!(
+            registry.add_collateral(&store, &id, &atto(6)).unwrap(),
+            Active
+        );
+        registry.require_active(&store, &id).unwrap();
+        // Active subnets must be deactivated before being killed.
+        assert_eq!(
+            code(registry.kill(&store, &id).unwrap_err()),
+            ExitCode::USR_FORBIDDEN
+        );
+
+        assert_eq!(
+            registry.release_collateral(&store, &id, &atto(1)).unwrap(),
+            Inactive
+        );
+        let err = registry
+            .release_collateral(&store, &id, &atto(10))
+            .unwrap_err();
+        assert_eq!(code(err), ExitCode::USR_INSUFFICIENT_FUNDS);
+        assert_eq!(
+            registry.add_collateral(&store, &id, &atto(2)).unwrap(),
+            Active
+        );
+        assert_eq!(
+            registry.release_collateral(&store, &id, &atto(3)).unwrap(),
+            Inactive
+        );
+        assert_eq!(registry.total_collateral(), &atto(8));
+
+        assert_eq!(registry.kill(&store, &id).unwrap(), atto(8));
+        assert!(registry.total_collateral().is_zero());
+        let info = registry.get(&store, &id).unwrap().unwrap();
+        assert_eq!(info.status, Killed);
+        assert!(registry.add_collateral(&store, &id, &atto(10)).is_err());
+        assert!(registry.register(&store, &id, &atto(10)).is_err());
+
+        let other = SubnetID::new_from_parent(&SubnetID::default(), Address::new_id(1001));
+        assert_eq!(
+            registry.register(&store, &other, &atto(10)).unwrap(),
+            Active
+        );
+        assert_eq!(registry.total_collateral(), &atto(10));
+    }
+
+    #[test]
+    fn transitions() {
+        use SubnetStatus::*;
+        let all = [Registered, Active, Inactive, Killed];
+        let allowed = [
+            (Registered, Active),
+            (Active, Inactive),
+            (Inactive, Active),
+            (Registered, Killed),
+            (Inactive, Killed),
+        ];
+        for from in all {
+            for to in all {
+                assert_eq!(from.can_transition_to(to), allowed.contains(&(from, to)));
+            }
+            let bytes = to_vec(&from).unwrap();
+            assert_eq!(from_slice::<SubnetStatus>(&bytes).unwrap(), from);
+        }
+        assert!(from_slice::<SubnetStatus>(&to_vec(&4u8).unwrap()).is_err());
+    }
+}
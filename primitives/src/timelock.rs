@@ -0,0 +1,249 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{actor_error, u64_key, ActorDowncast, ActorError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_encoding::{strict_bytes, RawBytes};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::error::ExitCode;
+use fvm_shared::MethodNum;
+
+use crate::{TCid, THamt};
+
+/// An operation waiting in a `Timelock`.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TimelockedOp {
+    pub target: Address,
+    pub method: MethodNum,
+    /// Blake2b-256 hash of the parameters, which are only provided again on execution.
+    #[serde(with = "strict_bytes")]
+    pub params_hash: [u8; 32],
+    /// The first epoch at which the operation can be executed.
+    pub ready_at: ChainEpoch,
+}
+
+/// Operations that can only be executed a fixed number of epochs after they were scheduled,
+/// to be embedded in the state of an actor, e.g. for governance actions on a subnet, so that
+/// participants have time to react to them. Until then, the admin can cancel them.
+///
+/// Who may schedule and execute operations is up to the actor; `execute` only checks the
+/// operation and the delay, and the actor sends the message itself, outside of the
+/// transaction updating its state.
+///
+/// # Example
+/// ```
+/// use primitives::Timelock;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+///
+/// let store = MemoryBlockstore::new();
+/// let timelock = Timelock::new(&store, Address::new_id(100), 10).unwrap();
+///
+/// assert_eq!(10, timelock.delay());
+/// assert!(timelock.get(&store, 0).unwrap().is_none());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Timelock {
+    admin: Address,
+    delay: ChainEpoch,
+    next_id: u64,
+    ops: TCid<THamt<u64, TimelockedOp>>,
+}
+
+impl Timelock {
+    /// Create an empty timelock enforcing `delay` epochs between scheduling and execution,
+    /// failing for a negative delay.
+    pub fn new<S: Blockstore>(
+        store: &S,
+        admin: Address,
+        delay: ChainEpoch,
+    ) -> anyhow::Result<Self> {
+        if delay < 0 {
+            return Err(actor_error!(illegal_argument; "negative timelock delay {}", delay).into());
+        }
+        Ok(Self {
+            admin,
+            delay,
+            next_id: 0,
+            ops: TCid::new_hamt(store)?,
+        })
+    }
+
+    /// The address allowed to cancel operations.
+    pub fn admin(&self) -> &Address {
+        &self.admin
+    }
+
+    /// Number of epochs an operation has to wait before it can be executed.
+    pub fn delay(&self) -> ChainEpoch {
+        self.delay
+    }
+
+    /// Look up a scheduled operation by ID.
+    pub fn get<S: Blockstore>(&self, store: &S, id: u64) -> anyhow::Result<Option<TimelockedOp>> {
+        let ops = self.ops.load(store)?;
+        Ok(ops.get(&u64_key(id))?.cloned())
+    }
+
+    /// Schedule sending `params` to `method` of `target`, returning the ID of the operation.
+    pub fn schedule<RT: Runtime>(
+        &mut self,
+        rt: &RT,
+        target: Address,
+        method: MethodNum,
+        params: &RawBytes,
+    ) -> Result<u64, ActorError> {
+        let id = self.next_id;
+        let ready_at = rt.curr_epoch().checked_add(self.delay).ok_or_else(|| {
+            actor_error!(illegal_state;
+                "operation delayed by {} epochs from {} overflows", self.delay, rt.curr_epoch())
+        })?;
+        let op = TimelockedOp {
+            target,
+            method,
+            params_hash: rt.hash_blake2b(params.bytes()),
+            ready_at,
+        };
+        self.ops
+            .update(rt.store(), |ops| {
+                ops.set(u64_key(id), op)?;
+                Ok(())
+            })
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to schedule operation")
+            })?;
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    /// Cancel a scheduled operation, provided the immediate caller is the admin.
+    pub fn cancel<RT: Runtime>(&mut self, rt: &RT, id: u64) -> Result<TimelockedOp, ActorError> {
+        let caller = rt.message().caller();
+        if rt.resolve_address(&self.admin) != Some(caller) {
+            return Err(actor_error!(forbidden;
+                "caller {} is not the timelock admin {}", caller, self.admin));
+        }
+        self.remove(rt.store(), id)
+    }
+
+    /// Remove a scheduled operation for execution, after checking that it is the one being
+    /// executed and that its delay has passed. The actor is expected to send the message next.
+    pub fn execute<RT: Runtime>(
+        &mut self,
+        rt: &RT,
+        id: u64,
+        target: &Address,
+        method: MethodNum,
+        params: &RawBytes,
+    ) -> Result<TimelockedOp, ActorError> {
+        let op = self
+            .get(rt.store(), id)
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to load operation")
+            })?
+            .ok_or_else(|| actor_error!(not_found; "no operation {} scheduled", id))?;
+
+        if &op.target != target
+            || op.method != method
+            || op.params_hash != rt.hash_blake2b(params.bytes())
+        {
+            return Err(actor_error!(illegal_argument;
+                "message doesn't match operation {} scheduled for method {} of {}", id, op.method, op.target));
+        }
+        let epoch = rt.curr_epoch();
+        if epoch < op.ready_at {
+            return Err(actor_error!(forbidden;
+                "operation {} can't be executed before epoch {}, now {}", id, op.ready_at, epoch));
+        }
+        self.remove(rt.store(), id)
+    }
+
+    fn remove<S: Blockstore>(&mut self, store: &S, id: u64) -> Result<TimelockedOp, ActorError> {
+        let removed = self
+            .ops
+            .modify(store, |ops| Ok(ops.delete(&u64_key(id))?))
+            .map_err(|e| {
+                e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to remove operation")
+            })?;
+        removed
+            .map(|(_, op)| op)
+            .ok_or_else(|| actor_error!(not_found; "no operation {} scheduled", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::{MockRuntime, ACCOUNT_ACTOR_CODE_ID};
+    use fil_actors_runtime::ActorError;
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+
+    use super::Timelock;
+
+    #[test]
+    fn schedule_cancel_and_execute() {
+        let admin = Address::new_id(100);
+        let target = Address::new_id(200);
+        let params = RawBytes::new(vec![1, 2, 3]);
+
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(101));
+        rt.set_epoch(5);
+        rt.in_call = true;
+        let mut timelock = Timelock::new(&*rt.store, admin, 10).unwrap();
+
+        let first = timelock.schedule(&rt, target, 2, &params).unwrap();
+        let second = timelock.schedule(&rt, target, 3, &params).unwrap();
+        assert_eq!(
+            timelock.get(&*rt.store, first).unwrap().unwrap().ready_at,
+            15
+        );
+
+        // Only the admin can cancel.
+        let err = timelock.cancel(&rt, second).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, admin);
+        timelock.cancel(&rt, second).unwrap();
+        let err = timelock
+            .execute(&rt, second, &target, 3, &params)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_NOT_FOUND);
+
+        rt.set_epoch(14);
+        let err = timelock
+            .execute(&rt, first, &target, 2, &params)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+
+        rt.set_epoch(15);
+        let other = RawBytes::new(vec![4]);
+        let err = timelock
+            .execute(&rt, first, &target, 2, &other)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let op = timelock.execute(&rt, first, &target, 2, &params).unwrap();
+        assert_eq!(op.method, 2);
+        assert!(timelock.get(&*rt.store, first).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_delays() {
+        let mut rt = MockRuntime::default();
+        let err = Timelock::new(&*rt.store, Address::new_id(100), -1).unwrap_err();
+        assert_eq!(
+            err.downcast::<ActorError>().unwrap().exit_code(),
+            ExitCode::USR_ILLEGAL_ARGUMENT
+        );
+
+        rt.set_epoch(1);
+        rt.in_call = true;
+        let mut timelock = Timelock::new(&*rt.store, Address::new_id(100), i64::MAX).unwrap();
+        let err = timelock
+            .schedule(&rt, Address::new_id(200), 2, &RawBytes::default())
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        assert_eq!(timelock.get(&*rt.store, 0).unwrap(), None);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer};
+
+/// A root state type that knows its own version, and how to decode and migrate
+/// all of its previous versions. Implemented with `versioned_state!`.
+pub trait StateVersion: Serialize + DeserializeOwned {
+    /// The version the type is serialized as.
+    const VERSION: u64;
+
+    /// Decode the state serialized as `version` from the sequence,
+    /// and migrate it to the current one.
+    fn decode_version<'de, A>(version: u64, seq: &mut A) -> Result<Self, A::Error>
+    where
+        A: SeqAccess<'de>;
+}
+
+/// Wrapper of the root state of an actor, serialized as `[version, state]`.
+///
+/// Decoding a state stored under an older version goes through the migration chain of `T`,
+/// so the actor always gets the current version, which is what it will be stored as next.
+///
+/// # Example
+/// ```
+/// use fvm_ipld_encoding::tuple::*;
+/// use fvm_ipld_encoding::{from_slice, to_vec};
+/// use primitives::{versioned_state, VersionedState};
+///
+/// #[derive(Serialize_tuple, Deserialize_tuple)]
+/// struct StateV0 {
+///     count: u32,
+/// }
+///
+/// #[derive(Serialize_tuple, Deserialize_tuple)]
+/// struct State {
+///     count: u64,
+///     label: String,
+/// }
+///
+/// impl From<StateV0> for State {
+///     fn from(v0: StateV0) -> Self {
+///         State { count: v0.count.into(), label: String::new() }
+///     }
+/// }
+///
+/// versioned_state! {
+///     State: 1 {
+///         0 => StateV0,
+///     }
+/// }
+///
+/// let old = to_vec(&(0u64, StateV0 { count: 5 })).unwrap();
+/// let state: VersionedState<State> = from_slice(&old).unwrap();
+/// assert_eq!(state.count, 5);
+///
+/// let (version, _): (u64, State) = from_slice(&to_vec(&state).unwrap()).unwrap();
+/// assert_eq!(version, 1);
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct VersionedState<T>(T);
+
+impl<T> VersionedState<T> {
+    pub fn new(state: T) -> Self {
+        Self(state)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for VersionedState<T> {
+    fn from(state: T) -> Self {
+        Self(state)
+    }
+}
+
+impl<T> Deref for VersionedState<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for VersionedState<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: StateVersion> Serialize for VersionedState<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (T::VERSION, &self.0).serialize(serializer)
+    }
+}
+
+impl<'de, T: StateVersion> Deserialize<'de> for VersionedState<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct VersionedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: StateVersion> Visitor<'de> for VersionedVisitor<T> {
+            type Value = VersionedState<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a [version, state] tuple")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let version: u64 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                if version > T::VERSION {
+                    return Err(de::Error::custom(format!(
+                        "state version {} is newer than the supported {}",
+                        version,
+                        T::VERSION
+                    )));
+                }
+                T::decode_version(version, &mut seq).map(VersionedState)
+            }
+        }
+
+        deserializer.deserialize_tuple(2, VersionedVisitor(PhantomData))
+    }
+}
+
+/// Read the state element of a `[version, state]` tuple as `T`.
+#[doc(hidden)]
+pub fn next_state<'de, A, T>(seq: &mut A) -> Result<T, A::Error>
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de>,
+{
+    seq.next_element()?
+        .ok_or_else(|| de::Error::invalid_length(1, &"a [version, state] tuple"))
+}
+
+/// Error for a version the state has no decoder for.
+#[doc(hidden)]
+pub fn unknown_version<E: de::Error>(version: u64) -> E {
+    E::custom(format!("unknown state version {version}"))
+}
+
+/// Implement `StateVersion` for the current state type, listing the previous versions
+/// in order. Each version must implement `From` the one before it, and the last
+/// previous version `From` for the current type, e.g. `StateV0 -> StateV1 -> State`.
+///
+/// ```ignore
+/// versioned_state! {
+///     State: 2 {
+///         0 => StateV0,
+///         1 => StateV1,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! versioned_state {
+    (
+        $current:ty : $current_version:literal {
+            $($version:literal => $typ:ty),* $(,)?
+        }
+    ) => {
+        impl $crate::StateVersion for $current {
+            const VERSION: u64 = $current_version;
+
+            fn decode_version<'de, A>(version: u64, seq: &mut A) -> Result<Self, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                if version == $current_version {
+                    return $crate::next_state(seq);
+                }
+                $crate::versioned_state!(@decode seq, version; $current; $($version => $typ),*);
+                Err($crate::unknown_version(version))
+            }
+        }
+    };
+
+    // Try the oldest remaining version, then recurse on the newer ones.
+    (@decode $seq:ident, $v:ident; $current:ty; $version:literal => $typ:ty $(, $rversion:literal => $rtyp:ty)*) => {
+        if $v == $version {
+            let state: $typ = $crate::next_state($seq)?;
+            return Ok($crate::versioned_state!(@migrate state; $($rtyp,)* $current));
+        }
+        $crate::versioned_state!(@decode $seq, $v; $current; $($rversion => $rtyp),*);
+    };
+    (@decode $seq:ident, $v:ident; $current:ty;) => {};
+
+    // Convert through each of the newer versions, in order.
+    (@migrate $state:expr; $next:ty $(, $rest:ty)*) => {
+        $crate::versioned_state!(@migrate <$next as From<_>>::from($state); $($rest),*)
+    };
+    (@migrate $state:expr;) => {
+        $state
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+
+    use crate::VersionedState;
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct StateV0 {
+        count: u32,
+    }
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct StateV1 {
+        count: u64,
+    }
+
+    #[derive(Serialize_tuple, Deserialize_tuple, PartialEq, Clone, Debug)]
+    struct State {
+        count: u64,
+        owners: Vec<u64>,
+    }
+
+    impl From<StateV0> for StateV1 {
+        fn from(v0: StateV0) -> Self {
+            StateV1 {
+                count: v0.count as u64 * 10,
+            }
+        }
+    }
+
+    impl From<StateV1> for State {
+        fn from(v1: StateV1) -> Self {
+            State {
+                count: v1.count + 1,
+                owners: Vec::new(),
+            }
+        }
+    }
+
+    versioned_state! {
+        State: 2 {
+            0 => StateV0,
+            1 => StateV1,
+        }
+    }
+
+    #[test]
+    fn migrates_through_the_chain() {
+        let v0 = to_vec(&(0u64, StateV0 { count: 1 })).unwrap();
+        let state: VersionedState<State> = from_slice(&v0).unwrap();
+        assert_eq!(state.count, 11);
+
+        let v1 = to_vec(&(1u64, StateV1 { count: 1 })).unwrap();
+        let state: VersionedState<State> = from_slice(&v1).unwrap();
+        assert_eq!(state.count, 2);
+    }
+
+    #[test]
+    fn roundtrip_current_version() {
+        let state = VersionedState::new(State {
+            count: 3,
+            owners: vec![100],
+        });
+        let bytes = to_vec(&state).unwrap();
+        assert_eq!(
+            from_slice::<(u64, State)>(&bytes).unwrap(),
+            (2, state.clone().into_inner())
+        );
+        assert_eq!(from_slice::<VersionedState<State>>(&bytes).unwrap(), state);
+    }
+
+    #[test]
+    fn rejects_future_versions() {
+        let bytes = to_vec(&(3u64, StateV1 { count: 1 })).unwrap();
+        assert!(from_slice::<VersionedState<State>>(&bytes).is_err());
+    }
+}
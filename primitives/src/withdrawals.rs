@@ -0,0 +1,237 @@
+use fil_actors_runtime::runtime::Runtime;
+use fil_actors_runtime::{ActorDowncast, ActorError};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::*;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::METHOD_SEND;
+use num_traits::Zero;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+
+use crate::{TCid, THamt};
+
+/// Funds owed by an actor to other addresses, to be embedded in its state.
+///
+/// Instead of pushing funds to recipients from inside business methods, where a
+/// failing recipient would make the whole method fail, the amounts are credited
+/// here and every recipient pulls their own funds with [`withdraw`].
+///
+/// # Example
+/// ```
+/// use primitives::Withdrawals;
+/// use fvm_ipld_blockstore::MemoryBlockstore;
+/// use fvm_shared::address::Address;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let store = MemoryBlockstore::new();
+/// let mut withdrawals = Withdrawals::new(&store).unwrap();
+///
+/// let addr = Address::new_id(100);
+/// withdrawals.credit(&store, &addr, &TokenAmount::from_atto(10)).unwrap();
+/// withdrawals.credit(&store, &addr, &TokenAmount::from_atto(5)).unwrap();
+///
+/// assert_eq!(TokenAmount::from_atto(15), withdrawals.credit_of(&store, &addr).unwrap());
+/// assert_eq!(&TokenAmount::from_atto(15), withdrawals.total());
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct Withdrawals {
+    credits: TCid<THamt<Address, TokenAmount>>,
+    total: TokenAmount,
+}
+
+impl Withdrawals {
+    pub fn new<S: Blockstore>(store: &S) -> anyhow::Result<Self> {
+        Ok(Self {
+            credits: TCid::new_hamt(store)?,
+            total: TokenAmount::zero(),
+        })
+    }
+
+    /// Sum of all the credits not yet withdrawn, which the actor has to keep in its balance.
+    pub fn total(&self) -> &TokenAmount {
+        &self.total
+    }
+
+    /// Add to the funds that can be withdrawn by an address.
+    ///
+    /// The address should be an ID address, as that's what the withdrawing caller will be.
+    pub fn credit<S: Blockstore>(
+        &mut self,
+        store: &S,
+        addr: &Address,
+        amount: &TokenAmount,
+    ) -> anyhow::Result<()> {
+        if amount.is_negative() {
+            return Err(anyhow::anyhow!("negative credit {} for {}", amount, addr));
+        }
+        if amount.is_zero() {
+            return Ok(());
+        }
+        self.credits.update(store, |credits| {
+            let key = BytesKey::from(addr.to_bytes());
+            let credit = credits.get(&key)?.cloned().unwrap_or_default();
+            credits.set(key, credit + amount)?;
+            Ok(())
+        })?;
+        self.total += amount;
+        Ok(())
+    }
+
+    /// The funds that can currently be withdrawn by an address.
+    pub fn credit_of<S: Blockstore>(
+        &self,
+        store: &S,
+        addr: &Address,
+    ) -> anyhow::Result<TokenAmount> {
+        let credits = self.credits.load(store)?;
+        let credit = credits.get(&BytesKey::from(addr.to_bytes()))?;
+        Ok(credit.cloned().unwrap_or_default())
+    }
+
+    /// Remove the credit of an address, returning its amount.
+    fn take<S: Blockstore>(&mut self, store: &S, addr: &Address) -> anyhow::Result<TokenAmount> {
+        let credit = self.credits.modify(store, |credits| {
+            let removed = credits.delete(&BytesKey::from(addr.to_bytes()))?;
+            Ok(removed.map(|(_, credit)| credit).unwrap_or_default())
+        })?;
+        self.total -= &credit;
+        Ok(credit)
+    }
+}
+
+/// Implementation of a `Withdraw` method, sending the immediate caller all of its
+/// credit in the `Withdrawals` selected from the actor state `T`.
+///
+/// Any caller is accepted; the ones without credit receive nothing.
+/// The credit is zeroed before the funds are sent, so a reentrant call can't withdraw twice.
+/// If the send fails the error is returned, which aborts the message and reverts the state,
+/// leaving the credit in place; a failing recipient only ever affects its own withdrawal.
+///
+/// Returns the amount withdrawn.
+pub fn withdraw<T, RT>(
+    rt: &mut RT,
+    withdrawals: impl FnOnce(&mut T) -> &mut Withdrawals,
+) -> Result<TokenAmount, ActorError>
+where
+    T: Serialize + DeserializeOwned,
+    RT: Runtime,
+{
+    rt.validate_immediate_caller_accept_any()?;
+    let caller = rt.message().caller();
+
+    let amount = rt.transaction(|st: &mut T, rt| {
+        withdrawals(st)
+            .take(rt.store(), &caller)
+            .map_err(|e| e.downcast_default(ExitCode::USR_ILLEGAL_STATE, "failed to take credit"))
+    })?;
+
+    if !amount.is_zero() {
+        rt.send(&caller, METHOD_SEND, None, amount.clone())
+            .map_err(|e| e.wrap(format!("failed to send withdrawal to {caller}")))?;
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use fil_actors_runtime::test_utils::{MockRuntime, ACCOUNT_ACTOR_CODE_ID};
+    use fil_actors_runtime::ActorError;
+    use fvm_ipld_encoding::tuple::*;
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::METHOD_SEND;
+
+    use super::{withdraw, Withdrawals};
+
+    #[derive(Serialize_tuple, Deserialize_tuple)]
+    struct State {
+        withdrawals: Withdrawals,
+    }
+
+    fn setup(credits: &[(Address, u64)]) -> MockRuntime {
+        let mut rt = MockRuntime::default();
+        let mut withdrawals = Withdrawals::new(rt.store.as_ref()).unwrap();
+        for (addr, amount) in credits {
+            withdrawals
+                .credit(rt.store.as_ref(), addr, &TokenAmount::from_atto(*amount))
+                .unwrap();
+        }
+        rt.set_balance(withdrawals.total().clone());
+        rt.replace_state(&State { withdrawals });
+        rt
+    }
+
+    fn call_withdraw(rt: &mut MockRuntime, caller: Address) -> Result<TokenAmount, ActorError> {
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, caller);
+        rt.expect_validate_caller_any();
+        rt.in_call = true;
+        let res = withdraw(rt, |st: &mut State| &mut st.withdrawals);
+        rt.in_call = false;
+        rt.verify();
+        res
+    }
+
+    #[test]
+    fn withdraw_sends_and_zeroes_credit() {
+        let (alice, bob) = (Address::new_id(100), Address::new_id(101));
+        let mut rt = setup(&[(alice, 10), (bob, 20)]);
+
+        rt.expect_send(
+            alice,
+            METHOD_SEND,
+            None,
+            TokenAmount::from_atto(10),
+            None,
+            ExitCode::OK,
+        );
+        let amount = call_withdraw(&mut rt, alice).unwrap();
+        assert_eq!(amount, TokenAmount::from_atto(10));
+
+        let st: State = rt.get_state();
+        let store = rt.store.as_ref();
+        assert!(st.withdrawals.credit_of(store, &alice).unwrap().is_zero());
+        assert_eq!(
+            st.withdrawals.credit_of(store, &bob).unwrap(),
+            TokenAmount::from_atto(20)
+        );
+        assert_eq!(st.withdrawals.total(), &TokenAmount::from_atto(20));
+
+        // Nothing left to withdraw.
+        let amount = call_withdraw(&mut rt, alice).unwrap();
+        assert!(amount.is_zero());
+    }
+
+    #[test]
+    fn failed_send_aborts() {
+        let alice = Address::new_id(100);
+        let mut rt = setup(&[(alice, 10)]);
+
+        rt.expect_send(
+            alice,
+            METHOD_SEND,
+            None,
+            TokenAmount::from_atto(10),
+            None,
+            ExitCode::USR_FORBIDDEN,
+        );
+        let err = call_withdraw(&mut rt, alice).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+    }
+
+    #[test]
+    fn negative_credit_rejected() {
+        let rt = MockRuntime::default();
+        let mut withdrawals = Withdrawals::new(rt.store.as_ref()).unwrap();
+        assert!(withdrawals
+            .credit(
+                rt.store.as_ref(),
+                &Address::new_id(100),
+                &TokenAmount::from_atto(-1)
+            )
+            .is_err());
+    }
+}
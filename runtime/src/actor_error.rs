@@ -1,13 +1,16 @@
 use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use serde::Serialize;
 use std::fmt::Display;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 
 use fvm_shared::error::ExitCode;
-use thiserror::Error;
+
+use crate::util::cbor::CborBlock;
 
 /// The error type returned by actor method calls.
-#[derive(Error, Debug, Clone, PartialEq, Eq)]
-#[error("ActorError(exit_code: {exit_code:?}, msg: {msg})")]
+#[derive(Debug, Clone)]
 pub struct ActorError {
     /// The exit code for this invocation.
     /// Codes less than `FIRST_USER_EXIT_CODE` are prohibited and will be overwritten by the VM.
@@ -16,17 +19,18 @@ pub struct ActorError {
     data: Option<IpldBlock>,
     /// Message for debugging purposes,
     msg: String,
+    /// The underlying error this one was created from, if any.
+    /// Only kept on host builds, where it is reported through `std::error::Error::source`
+    /// so that test failures show the full chain; it is shared to keep the error `Clone`.
+    #[cfg(not(target_arch = "wasm32"))]
+    source: Option<Arc<anyhow::Error>>,
 }
 
 impl ActorError {
     /// Creates a new ActorError. This method does not check that the code is in the
     /// range of valid actor abort codes.
     pub fn unchecked(code: ExitCode, msg: String) -> Self {
-        Self {
-            exit_code: code,
-            msg,
-            data: None,
-        }
+        Self::unchecked_with_data(code, msg, None)
     }
 
     pub fn unchecked_with_data(code: ExitCode, msg: String, data: Option<IpldBlock>) -> Self {
@@ -34,6 +38,8 @@ impl ActorError {
             exit_code: code,
             msg,
             data,
+            #[cfg(not(target_arch = "wasm32"))]
+            source: None,
         }
     }
 
@@ -51,75 +57,35 @@ impl ActorError {
             // Otherwise, pass it through.
             code => code,
         };
-        Self {
-            exit_code,
-            msg,
-            data,
-        }
+        Self::unchecked_with_data(exit_code, msg, data)
     }
 
     pub fn illegal_argument(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_ILLEGAL_ARGUMENT,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_ILLEGAL_ARGUMENT, msg)
     }
     pub fn not_found(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_NOT_FOUND,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_NOT_FOUND, msg)
     }
     pub fn forbidden(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_FORBIDDEN,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_FORBIDDEN, msg)
     }
     pub fn insufficient_funds(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_INSUFFICIENT_FUNDS,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_INSUFFICIENT_FUNDS, msg)
     }
     pub fn illegal_state(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_ILLEGAL_STATE,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_ILLEGAL_STATE, msg)
     }
     pub fn serialization(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_SERIALIZATION,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_SERIALIZATION, msg)
     }
     pub fn unhandled_message(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_UNHANDLED_MESSAGE,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_UNHANDLED_MESSAGE, msg)
     }
     pub fn unspecified(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_UNSPECIFIED,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_UNSPECIFIED, msg)
     }
     pub fn assertion_failed(msg: String) -> Self {
-        Self {
-            exit_code: ExitCode::USR_ASSERTION_FAILED,
-            msg,
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_ASSERTION_FAILED, msg)
     }
 
     /// Returns the exit code of the error.
@@ -137,21 +103,97 @@ impl ActorError {
         std::mem::take(&mut self.data)
     }
 
+    /// The optional associated data.
+    pub fn data(&self) -> Option<&IpldBlock> {
+        self.data.as_ref()
+    }
+
+    /// An error with a typed payload, aborting with its exit code and message.
+    pub fn typed<E: TypedError>(err: &E) -> Self {
+        match IpldBlock::serialize_cbor(err) {
+            Ok(data) => Self::unchecked_with_data(err.exit_code(), err.to_string(), data),
+            Err(e) => Self::serialization(format!("failed to serialize error {err}: {e}")),
+        }
+    }
+
+    /// The typed payload of an abort, e.g. one returned by `send`, if its exit code is one of
+    /// those of `E` and it carries a payload of that type.
+    pub fn to_typed<E: TypedError>(&self) -> Option<E> {
+        if !E::EXIT_CODES.contains(&self.exit_code) {
+            return None;
+        }
+        self.data.as_ref()?.deserialize().ok()
+    }
+
     /// Prefix error message with a string message.
     pub fn wrap(mut self, msg: impl AsRef<str>) -> Self {
         self.msg = format!("{}: {}", msg.as_ref(), self.msg);
         self
     }
+
+    /// Attaches the error this one was created from. The source is dropped on Wasm builds.
+    pub fn with_source<E: Into<anyhow::Error>>(self, source: E) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut this = self;
+            this.source = Some(Arc::new(source.into()));
+            this
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            drop(source);
+            self
+        }
+    }
+
+    /// Ends the call with the exit code and message of the error right away, for failures the
+    /// caller can't do anything sensible about, e.g. state that doesn't decode.
+    ///
+    /// In an actor this exits through the FVM, rather than reporting a panic as
+    /// `USR_ASSERTION_FAILED`; off chain, e.g. in tests with the `MockRuntime`, it panics
+    /// with the exit code and the message.
+    pub fn abort(self) -> ! {
+        #[cfg(all(feature = "fil-actor", target_arch = "wasm32"))]
+        fvm_sdk::vm::abort(self.exit_code.value(), Some(&self.msg));
+        #[cfg(not(all(feature = "fil-actor", target_arch = "wasm32")))]
+        panic!("aborted with exit code {}: {}", self.exit_code, self.msg)
+    }
+}
+
+impl Display for ActorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ActorError(exit_code: {:?}, msg: {})",
+            self.exit_code, self.msg
+        )
+    }
+}
+
+impl std::error::Error for ActorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(source) = &self.source {
+            let source: &(dyn std::error::Error + Send + Sync + 'static) = source.as_ref().as_ref();
+            return Some(source);
+        }
+        None
+    }
+}
+
+/// Errors are compared by their observable outcome; the source chain is ignored.
+impl PartialEq for ActorError {
+    fn eq(&self, other: &Self) -> bool {
+        self.exit_code == other.exit_code && self.data == other.data && self.msg == other.msg
+    }
 }
 
+impl Eq for ActorError {}
+
 /// Converts a raw encoding error into an ErrSerialization.
 impl From<fvm_ipld_encoding::Error> for ActorError {
     fn from(e: fvm_ipld_encoding::Error) -> Self {
-        Self {
-            exit_code: ExitCode::USR_SERIALIZATION,
-            msg: e.to_string(),
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_SERIALIZATION, e.to_string()).with_source(e)
     }
 }
 
@@ -160,11 +202,7 @@ impl From<fvm_ipld_encoding::Error> for ActorError {
 #[cfg(feature = "fil-actor")]
 impl From<fvm_sdk::error::ActorDeleteError> for ActorError {
     fn from(e: fvm_sdk::error::ActorDeleteError) -> Self {
-        Self {
-            exit_code: ExitCode::USR_ILLEGAL_ARGUMENT,
-            msg: e.to_string(),
-            data: None,
-        }
+        Self::unchecked(ExitCode::USR_ILLEGAL_ARGUMENT, e.to_string())
     }
 }
 
@@ -173,11 +211,7 @@ impl From<fvm_sdk::error::ActorDeleteError> for ActorError {
 #[cfg(feature = "fil-actor")]
 impl From<fvm_sdk::error::StateReadError> for ActorError {
     fn from(e: fvm_sdk::error::StateReadError) -> Self {
-        Self {
-            exit_code: ExitCode::USR_ILLEGAL_STATE,
-            data: None,
-            msg: e.to_string(),
-        }
+        Self::unchecked(ExitCode::USR_ILLEGAL_STATE, e.to_string())
     }
 }
 
@@ -186,17 +220,29 @@ impl From<fvm_sdk::error::StateReadError> for ActorError {
 #[cfg(feature = "fil-actor")]
 impl From<fvm_sdk::error::StateUpdateError> for ActorError {
     fn from(e: fvm_sdk::error::StateUpdateError) -> Self {
-        Self {
-            exit_code: match e {
-                fvm_sdk::error::StateUpdateError::ActorDeleted => ExitCode::USR_ILLEGAL_STATE,
-                fvm_sdk::error::StateUpdateError::ReadOnly => ExitCode::USR_READ_ONLY,
-            },
-            data: None,
-            msg: e.to_string(),
-        }
+        let exit_code = match e {
+            fvm_sdk::error::StateUpdateError::ActorDeleted => ExitCode::USR_ILLEGAL_STATE,
+            fvm_sdk::error::StateUpdateError::ReadOnly => ExitCode::USR_READ_ONLY,
+        };
+        Self::unchecked(exit_code, e.to_string())
     }
 }
 
+/// An error which an actor aborts with along with a payload, e.g.
+/// `QueueFull { metrics }`, so that its callers can decode it back from the
+/// abort with `ActorError::to_typed` and handle it, rather than parse the message.
+///
+/// Its exit codes should be registered along with the type in the exit code registry of the
+/// actor family, as `primitives` does for the IPC actors, so that no other error aborts with
+/// them and a payload is only ever decoded as the type it is.
+pub trait TypedError: Serialize + DeserializeOwned + Display {
+    /// All the exit codes the error can abort with.
+    const EXIT_CODES: &'static [ExitCode];
+
+    /// The exit code of this error, one of `EXIT_CODES`.
+    fn exit_code(&self) -> ExitCode;
+}
+
 /// Convenience macro for generating Actor Errors
 #[macro_export]
 macro_rules! actor_error {
@@ -269,22 +315,14 @@ pub trait AsActorError<T>: Sized {
 // Note: E should be std::error::Error, revert to this after anyhow:Error is no longer used.
 impl<T, E: Display> AsActorError<T> for Result<T, E> {
     fn exit_code(self, code: ExitCode) -> Result<T, ActorError> {
-        self.map_err(|err| ActorError {
-            exit_code: code,
-            msg: err.to_string(),
-            data: None,
-        })
+        self.map_err(|err| ActorError::unchecked(code, err.to_string()))
     }
 
     fn context_code<C>(self, code: ExitCode, context: C) -> Result<T, ActorError>
     where
         C: Display + 'static,
     {
-        self.map_err(|err| ActorError {
-            exit_code: code,
-            msg: format!("{context}: {err}"),
-            data: None,
-        })
+        self.map_err(|err| ActorError::unchecked(code, format!("{context}: {err}")))
     }
 
     fn with_context_code<C, F>(self, code: ExitCode, f: F) -> Result<T, ActorError>
@@ -292,32 +330,20 @@ impl<T, E: Display> AsActorError<T> for Result<T, E> {
         C: Display + 'static,
         F: FnOnce() -> C,
     {
-        self.map_err(|err| ActorError {
-            exit_code: code,
-            msg: format!("{}: {}", f(), err),
-            data: None,
-        })
+        self.map_err(|err| ActorError::unchecked(code, format!("{}: {}", f(), err)))
     }
 }
 
 impl<T> AsActorError<T> for Option<T> {
     fn exit_code(self, code: ExitCode) -> Result<T, ActorError> {
-        self.ok_or_else(|| ActorError {
-            exit_code: code,
-            msg: "None".to_string(),
-            data: None,
-        })
+        self.ok_or_else(|| ActorError::unchecked(code, "None".to_string()))
     }
 
     fn context_code<C>(self, code: ExitCode, context: C) -> Result<T, ActorError>
     where
         C: Display + 'static,
     {
-        self.ok_or_else(|| ActorError {
-            exit_code: code,
-            msg: context.to_string(),
-            data: None,
-        })
+        self.ok_or_else(|| ActorError::unchecked(code, context.to_string()))
     }
 
     fn with_context_code<C, F>(self, code: ExitCode, f: F) -> Result<T, ActorError>
@@ -325,11 +351,7 @@ impl<T> AsActorError<T> for Option<T> {
         C: Display + 'static,
         F: FnOnce() -> C,
     {
-        self.ok_or_else(|| ActorError {
-            exit_code: code,
-            msg: f().to_string(),
-            data: None,
-        })
+        self.ok_or_else(|| ActorError::unchecked(code, f().to_string()))
     }
 }
 
@@ -341,6 +363,41 @@ where
         ExitCode::USR_ASSERTION_FAILED,
         "return expected".to_string(),
     )?
-    .deserialize()
-    .exit_code(ExitCode::USR_SERIALIZATION)
+    .decode_cbor()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use anyhow::anyhow;
+    use fvm_shared::error::ExitCode;
+
+    use super::ActorError;
+
+    #[test]
+    fn source_is_kept() {
+        let err: ActorError = fvm_ipld_encoding::from_slice::<u64>(&[0xff])
+            .unwrap_err()
+            .into();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<fvm_ipld_encoding::Error>().is_some());
+
+        let cause = anyhow!("block not found").context("failed to load the state");
+        let err = ActorError::illegal_state("failed to update".into()).with_source(cause);
+        let mut chain = Vec::new();
+        let mut source = err.source();
+        while let Some(e) = source {
+            chain.push(e.to_string());
+            source = e.source();
+        }
+        assert_eq!(chain, ["failed to load the state", "block not found"]);
+
+        // The source isn't part of the comparison.
+        assert_eq!(err, ActorError::illegal_state("failed to update".into()));
+        assert!(ActorError::illegal_state("failed to update".into())
+            .source()
+            .is_none());
+    }
 }
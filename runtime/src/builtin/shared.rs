@@ -1,11 +1,19 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use fvm_shared::address::Address;
-use fvm_shared::{MethodNum, METHOD_SEND};
+use cid::Cid;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::{Address, Payload};
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::{ActorID, MethodNum, METHOD_SEND};
+use serde::Serialize;
 
+use crate::builtin::types::{
+    InitExec4Params, InitExecParams, InitExecReturn, INIT_EXEC4_METHOD_NUM, INIT_EXEC_METHOD_NUM,
+};
 use crate::runtime::Runtime;
-use crate::{actor_error, ActorError};
+use crate::{actor_error, deserialize_block, ActorError, INIT_ACTOR_ADDR};
 
 pub const HAMT_BIT_WIDTH: u32 = 5;
 
@@ -30,6 +38,68 @@ pub fn resolve_to_id_addr(rt: &mut impl Runtime, address: &Address) -> anyhow::R
     })
 }
 
+/// Create an actor of `code_cid` through the Init actor, constructed with
+/// `constructor_params` and funded with `value`, returning its addresses.
+pub fn init_exec(
+    rt: &impl Runtime,
+    code_cid: Cid,
+    constructor_params: &impl Serialize,
+    value: TokenAmount,
+) -> Result<InitExecReturn, ActorError> {
+    let params = InitExecParams {
+        code_cid,
+        constructor_params: RawBytes::serialize(constructor_params)?,
+    };
+    let ret = rt
+        .send(
+            &INIT_ACTOR_ADDR,
+            INIT_EXEC_METHOD_NUM,
+            IpldBlock::serialize_cbor(&params)?,
+            value,
+        )
+        .map_err(|e| e.wrap("failed to create actor through the init actor"))?;
+    deserialize_block(ret)
+}
+
+/// Like `init_exec`, also giving the new actor the delegated address with the `subaddress`
+/// in the namespace of the calling actor.
+///
+/// On chain the Init actor only accepts `Exec4` from the EAM, so this fails for any other
+/// caller; other actors create actors with delegated addresses by calling the EAM.
+pub fn init_exec4(
+    rt: &impl Runtime,
+    code_cid: Cid,
+    constructor_params: &impl Serialize,
+    subaddress: &[u8],
+    value: TokenAmount,
+) -> Result<InitExecReturn, ActorError> {
+    let params = InitExec4Params {
+        code_cid,
+        constructor_params: RawBytes::serialize(constructor_params)?,
+        subaddress: RawBytes::new(subaddress.to_vec()),
+    };
+    let ret = rt
+        .send(
+            &INIT_ACTOR_ADDR,
+            INIT_EXEC4_METHOD_NUM,
+            IpldBlock::serialize_cbor(&params)?,
+            value,
+        )
+        .map_err(|e| e.wrap("failed to create actor through the init actor"))?;
+    deserialize_block(ret)
+}
+
+/// The subaddress of a delegated (f4) address in the given namespace, e.g. the 20 byte
+/// Ethereum address of an f410 address, whose namespace is the EAM actor.
+pub fn delegated_subaddress(address: &Address, namespace: ActorID) -> Option<&[u8]> {
+    match address.payload() {
+        Payload::Delegated(delegated) if delegated.namespace() == namespace => {
+            Some(delegated.subaddress())
+        }
+        _ => None,
+    }
+}
+
 // The lowest FRC-42 method number.
 pub const FIRST_EXPORTED_METHOD_NUMBER: MethodNum = 1 << 24;
 
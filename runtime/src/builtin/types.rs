@@ -2,7 +2,9 @@ use cid::Cid;
 use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
 use fvm_ipld_encoding::RawBytes;
 use fvm_shared::address::Address;
-use fvm_shared::MethodNum;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::{actor_error, ActorError};
 
 /// Init actor Exec Params, see https://github.com/filecoin-project/builtin-actors/blob/master/actors/init/src/types.rs#L17
 #[derive(Serialize_tuple, Deserialize_tuple, Debug)]
@@ -11,8 +13,19 @@ pub struct InitExecParams {
     pub constructor_params: RawBytes,
 }
 
-/// Init actor Exec Params, see https://github.com/filecoin-project/builtin-actors/blob/master/actors/init/src/types.rs
-#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+/// Init actor Exec4 Params, see https://github.com/filecoin-project/builtin-actors/blob/master/actors/init/src/types.rs#L30
+///
+/// The new actor is also given the delegated address in the namespace of the caller with
+/// the `subaddress`. The Init actor only accepts `Exec4` from the EAM.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct InitExec4Params {
+    pub code_cid: Cid,
+    pub constructor_params: RawBytes,
+    pub subaddress: RawBytes,
+}
+
+/// Init actor Exec Return, see https://github.com/filecoin-project/builtin-actors/blob/master/actors/init/src/types.rs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
 pub struct InitExecReturn {
     /// ID based address for created actor
     pub id_address: Address,
@@ -29,5 +42,18 @@ impl Default for InitExecReturn {
     }
 }
 
+impl InitExecReturn {
+    /// The ID of the created actor, failing if the Init actor didn't return an ID address.
+    pub fn actor_id(&self) -> Result<ActorID, ActorError> {
+        self.id_address.id().map_err(|_| {
+            actor_error!(illegal_state;
+                "init actor returned non-ID address {}", self.id_address)
+        })
+    }
+}
+
 /// Init actor exec method number, see https://github.com/filecoin-project/builtin-actors/blob/fb759f87fcd5de0a98cb61966cd27f680df83364/actors/init/src/lib.rs#L32
 pub const INIT_EXEC_METHOD_NUM: MethodNum = 2;
+
+/// Init actor exec4 method number, see https://github.com/filecoin-project/builtin-actors/blob/master/actors/init/src/lib.rs
+pub const INIT_EXEC4_METHOD_NUM: MethodNum = 3;
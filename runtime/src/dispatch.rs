@@ -2,11 +2,16 @@ use castaway::cast;
 use std::marker::PhantomData;
 
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_shared::{MethodNum, METHOD_CONSTRUCTOR};
 use serde::{Deserialize, Serialize};
 
-use crate::ActorError;
+use crate::runtime::Runtime;
+use crate::util::cbor::{self, CborBlock};
+use crate::{ActorError, FIRST_EXPORTED_METHOD_NUMBER};
 
-/// Implement actor method dispatch:
+/// Implement actor method dispatch, along with `ActorCode::METHOD_TABLE`, and the standard
+/// `Version` method, which the `Methods` enum shouldn't declare. The other methods are called
+/// through `ActorCode::around_method`:
 ///
 /// ```ignore
 /// type Actor;
@@ -25,24 +30,131 @@ use crate::ActorError;
 #[macro_export]
 macro_rules! actor_dispatch {
     ($($method:ident => $func:ident,)*) => {
+        const METHOD_TABLE: &'static [$crate::runtime::MethodInfo] = &[
+            $($crate::runtime::MethodInfo {
+                number: Self::Methods::$method as $crate::fvm_shared::MethodNum,
+                name: stringify!($method),
+                handler: stringify!($func),
+            },)*
+            $crate::runtime::MethodInfo {
+                number: $crate::runtime::VERSION_METHOD,
+                name: "Version",
+                handler: "version",
+            },
+        ];
+
         fn invoke_method<RT>(
             rt: &mut RT,
-            method: MethodNum,
-            args: Option<fvm_ipld_encoding::ipld_block::IpldBlock>,
-        ) -> Result<Option<fvm_ipld_encoding::ipld_block::IpldBlock>, ActorError>
+            method: $crate::fvm_shared::MethodNum,
+            args: Option<$crate::fvm_ipld_encoding::ipld_block::IpldBlock>,
+        ) -> Result<Option<$crate::fvm_ipld_encoding::ipld_block::IpldBlock>, $crate::ActorError>
         where
-            RT: Runtime,
+            RT: $crate::runtime::Runtime,
             RT::Blockstore: Clone,
         {
-            restrict_internal_api(rt, method)?;
-            match FromPrimitive::from_u64(method) {
-                $(Some(Self::Methods::$method) => $crate::dispatch(rt, Self::$func, &args),)*
-                None => Err(actor_error!(unhandled_message; "invalid method: {}", method)),
+            $crate::restrict_internal_api(rt, method)?;
+            if method == $crate::runtime::VERSION_METHOD {
+                return $crate::dispatch(rt, <Self as $crate::runtime::ActorCode>::version, &args);
+            }
+            <Self as $crate::runtime::ActorCode>::around_method(rt, method, |rt| {
+                match $crate::num_traits::FromPrimitive::from_u64(method) {
+                    $(Some(Self::Methods::$method) => $crate::dispatch(rt, Self::$func, &args),)*
+                    None => {
+                        Err($crate::actor_error!(unhandled_message; "invalid method: {}", method))
+                    }
+                }
+            })
+        }
+    };
+}
+
+/// Declare the methods of an actor as a `#[repr(u64)]` enum, replacing the `num_derive` stanza.
+///
+/// Besides the enum, deriving `Clone`, `Copy`, `Debug`, `PartialEq` and `Eq`, it generates:
+/// * `FromPrimitive` and `ToPrimitive`, as used by `actor_dispatch!` and `ActorHandle`;
+/// * `From<Method> for MethodNum`;
+/// * `ALL_METHODS`, listing every method, e.g. to check that each is dispatched;
+/// * a compile time check that every method number is either the constructor's or in the
+///   FRC-42 range of exported methods, the others being reserved for built-in actors.
+///
+/// Duplicate method numbers are rejected by the compiler as duplicate discriminants.
+///
+/// ```ignore
+/// actor_methods! {
+///     /// The methods of the actor.
+///     pub enum Method {
+///         Constructor = METHOD_CONSTRUCTOR,
+///         Persist = frc42_dispatch::method_hash!("Persist"),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! actor_methods {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$vmeta:meta])* $variant:ident = $value:expr),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[repr(u64)]
+        $vis enum $name {
+            $($(#[$vmeta])* $variant = $value,)*
+        }
+
+        const _: () = $crate::check_method_numbers(&[$($name::$variant as u64),*]);
+
+        impl $name {
+            /// All the methods of the actor.
+            pub const ALL_METHODS: &'static [$name] = &[$($name::$variant),*];
+        }
+
+        impl From<$name> for $crate::fvm_shared::MethodNum {
+            fn from(method: $name) -> Self {
+                method as $crate::fvm_shared::MethodNum
+            }
+        }
+
+        impl $crate::num_traits::FromPrimitive for $name {
+            fn from_i64(n: i64) -> Option<Self> {
+                u64::try_from(n).ok().and_then(Self::from_u64)
+            }
+
+            fn from_u64(n: u64) -> Option<Self> {
+                match n {
+                    $(n if n == $name::$variant as u64 => Some($name::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl $crate::num_traits::ToPrimitive for $name {
+            fn to_i64(&self) -> Option<i64> {
+                i64::try_from(*self as u64).ok()
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                Some(*self as u64)
             }
         }
     };
 }
 
+/// Check that method numbers are either the constructor's or exported ones.
+/// Used by `actor_methods!` at compile time.
+#[doc(hidden)]
+pub const fn check_method_numbers(methods: &[MethodNum]) {
+    let mut i = 0;
+    while i < methods.len() {
+        assert!(
+            methods[i] == METHOD_CONSTRUCTOR || methods[i] >= FIRST_EXPORTED_METHOD_NUMBER,
+            "method number reserved for built-in actors"
+        );
+        i += 1;
+    }
+}
+
 pub trait Dispatch<'de, RT> {
     fn call(
         self,
@@ -87,7 +199,7 @@ fn maybe_into_block<T: Serialize>(v: T) -> Result<Option<IpldBlock>, ActorError>
     if cast!(&v, &()).is_ok() {
         Ok(None)
     } else {
-        Ok(IpldBlock::serialize_cbor(&v)?)
+        Ok(Some(IpldBlock::from_cbor(&v)?))
     }
 }
 
@@ -115,6 +227,7 @@ where
     F: FnOnce(&mut RT, A) -> Result<R, ActorError>,
     A: Deserialize<'de>,
     R: Serialize,
+    RT: Runtime,
 {
     fn call(
         self,
@@ -125,7 +238,16 @@ where
             None => Err(ActorError::illegal_argument(
                 "method expects arguments".into(),
             )),
-            Some(arg) => maybe_into_block((self.func)(rt, arg.deserialize()?)?),
+            Some(arg) => {
+                let policy = rt.policy();
+                cbor::check_limits(
+                    arg,
+                    policy.max_params_depth,
+                    policy.max_params_collection_len,
+                )
+                .map_err(|e| e.wrap("failed to deserialize method parameters"))?;
+                maybe_into_block((self.func)(rt, arg.decode_cbor()?)?)
+            }
         }
     }
 }
@@ -141,9 +263,8 @@ fn test_dispatch() {
         foo: String,
     }
 
-    trait Runtime {}
-    struct MockRuntime;
-    impl Runtime for MockRuntime {}
+    use crate::runtime::Runtime;
+    use crate::test_utils::MockRuntime;
 
     fn with_arg(_: &mut impl Runtime, foo: SomeArgs) -> Result<(), ActorError> {
         assert_eq!(foo.foo, "foo");
@@ -158,7 +279,7 @@ fn test_dispatch() {
         Ok(())
     }
 
-    let mut rt = MockRuntime;
+    let mut rt = MockRuntime::default();
     let arg = IpldBlock::serialize_cbor(&SomeArgs { foo: "foo".into() })
         .expect("failed to serialize arguments");
 
@@ -177,4 +298,105 @@ fn test_dispatch() {
     // Incorrect dispatch
     let _ = dispatch(&mut rt, with_arg, &None).expect_err("should have required an argument");
     let _ = dispatch(&mut rt, without_arg, &arg).expect_err("should have required an argument");
+
+    // Arguments nested beyond the policy limits
+    let mut data = vec![0x81; 1000];
+    data.push(0x00);
+    let arg = Some(IpldBlock {
+        codec: fvm_ipld_encoding::DAG_CBOR,
+        data,
+    });
+    let err = dispatch(&mut rt, with_arg, &arg).expect_err("should have rejected the nesting");
+    assert_eq!(
+        err.exit_code(),
+        fvm_shared::error::ExitCode::USR_SERIALIZATION
+    );
+
+    // The limits are those of the policy of the runtime.
+    rt.policy.max_params_collection_len = 0;
+    let arg = IpldBlock::serialize_cbor(&SomeArgs { foo: "foo".into() }).unwrap();
+    let err = dispatch(&mut rt, with_arg, &arg).expect_err("should have rejected the map");
+    assert_eq!(
+        err.exit_code(),
+        fvm_shared::error::ExitCode::USR_SERIALIZATION
+    );
+}
+
+#[test]
+fn test_actor_methods() {
+    use num_traits::{FromPrimitive, ToPrimitive};
+
+    actor_methods! {
+        enum Method {
+            Constructor = METHOD_CONSTRUCTOR,
+            Persist = FIRST_EXPORTED_METHOD_NUMBER + 1,
+        }
+    }
+
+    assert_eq!(Method::ALL_METHODS, &[Method::Constructor, Method::Persist]);
+    for method in Method::ALL_METHODS {
+        let num = MethodNum::from(*method);
+        assert_eq!(Method::from_u64(num), Some(*method));
+        assert_eq!(method.to_u64(), Some(num));
+    }
+    assert_eq!(Method::from_u64(2), None);
+    assert_eq!(Method::from_i64(-1), None);
+}
+
+#[test]
+#[should_panic(expected = "method number reserved for built-in actors")]
+fn test_reserved_method_numbers() {
+    check_method_numbers(&[METHOD_CONSTRUCTOR, 2]);
+}
+
+#[test]
+fn test_method_table() {
+    use crate::runtime::{ActorCode, MethodInfo, Runtime};
+
+    actor_methods! {
+        enum Method {
+            Constructor = METHOD_CONSTRUCTOR,
+            Persist = FIRST_EXPORTED_METHOD_NUMBER + 1,
+        }
+    }
+
+    struct Actor;
+
+    impl Actor {
+        fn constructor(_: &mut impl Runtime) -> Result<(), ActorError> {
+            Ok(())
+        }
+
+        fn persist(_: &mut impl Runtime) -> Result<(), ActorError> {
+            Ok(())
+        }
+    }
+
+    impl ActorCode for Actor {
+        type Methods = Method;
+        actor_dispatch! {
+            Constructor => constructor,
+            Persist => persist,
+        }
+    }
+
+    let persist = MethodInfo {
+        number: FIRST_EXPORTED_METHOD_NUMBER + 1,
+        name: "Persist",
+        handler: "persist",
+    };
+    // Along with the standard Version method.
+    assert_eq!(Actor::METHOD_TABLE.len(), Method::ALL_METHODS.len() + 1);
+    assert_eq!(
+        MethodInfo::lookup(Actor::METHOD_TABLE, FIRST_EXPORTED_METHOD_NUMBER + 1),
+        Some(&persist)
+    );
+    assert_eq!(MethodInfo::lookup(Actor::METHOD_TABLE, 2), None);
+    assert_eq!(
+        serde_json::to_string(&persist).unwrap(),
+        format!(
+            r#"{{"number":{},"name":"Persist","handler":"persist"}}"#,
+            FIRST_EXPORTED_METHOD_NUMBER + 1
+        )
+    );
 }
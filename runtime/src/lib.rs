@@ -6,7 +6,7 @@
 #[macro_use]
 extern crate lazy_static;
 // workaround for a compiler bug, see https://github.com/rust-lang/rust/issues/55779
-extern crate serde;
+pub extern crate serde;
 
 use builtin::HAMT_BIT_WIDTH;
 use cid::Cid;
@@ -17,7 +17,7 @@ pub use fvm_shared::BLOCKS_PER_EPOCH as EXPECTED_LEADERS_PER_EPOCH;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use unsigned_varint::decode::Error as UVarintError;
-pub use {fvm_ipld_amt, fvm_ipld_hamt};
+pub use {fvm_ipld_amt, fvm_ipld_encoding, fvm_ipld_hamt, fvm_shared, num_traits};
 
 pub use self::actor_error::*;
 pub use self::builtin::*;
@@ -30,7 +30,7 @@ pub mod runtime;
 pub mod util;
 
 mod dispatch;
-pub use dispatch::dispatch;
+pub use dispatch::{check_method_numbers, dispatch};
 
 #[cfg(feature = "test_utils")]
 pub mod test_utils;
@@ -43,6 +43,12 @@ macro_rules! wasm_trampoline {
             $crate::runtime::fvm::trampoline::<$target>(param)
         }
     };
+    ($target:ty, $configure:expr) => {
+        #[no_mangle]
+        pub extern "C" fn invoke(param: u32) -> u32 {
+            $crate::runtime::fvm::trampoline_with::<$target, _>(param, $configure)
+        }
+    };
 }
 
 /// Map type to be used within actors. The underlying type is a HAMT.
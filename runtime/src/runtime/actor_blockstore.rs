@@ -10,8 +10,9 @@ use crate::actor_error;
 
 /// A blockstore suitable for use within actors.
 ///
-/// Cloning simply clones a reference and does not copy the underlying blocks.
-#[derive(Debug, Clone)]
+/// Cloning simply clones a reference and does not copy the underlying blocks, and every
+/// instance, including the default one, is the same store.
+#[derive(Debug, Clone, Default)]
 pub struct ActorBlockstore;
 
 /// Implements a blockstore delegating to IPLD syscalls.
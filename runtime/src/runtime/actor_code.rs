@@ -1,15 +1,53 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::strict_bytes;
+use fvm_ipld_encoding::tuple::*;
 use fvm_shared::MethodNum;
+use serde::Serialize;
 
 use crate::{ActorError, Runtime};
 
+/// The FRC-42 number of the `Version` method, which `actor_dispatch!` dispatches to
+/// `ActorCode::version` for every actor.
+pub const VERSION_METHOD: MethodNum = frc42_dispatch::method_hash!("Version");
+
 /// Interface for invoking methods on an Actor
 pub trait ActorCode {
     type Methods;
+    /// The methods the actor dispatches, generated by `actor_dispatch!`, for symbolizing
+    /// method numbers in logs and in external tools such as gas profilers and explorers.
+    const METHOD_TABLE: &'static [MethodInfo] = &[];
+    /// The semver of the actor, e.g. `env!("CARGO_PKG_VERSION")`, reported by `version`.
+    const VERSION: &'static str = "";
+    /// The layout of the state of the actor, e.g. the `stringify!`-ed definitions of its types,
+    /// of which `version` reports the hash, so that tooling can tell whether two deployments
+    /// have the same state schema without comparing their code.
+    const STATE_SCHEMA: &'static str = "";
+
+    /// The standard `Version` method, telling what exactly is deployed. Any caller is accepted.
+    fn version<RT: Runtime>(rt: &mut RT) -> Result<VersionInfo, ActorError> {
+        rt.validate_immediate_caller_accept_any()?;
+        Ok(VersionInfo {
+            version: Self::VERSION.to_string(),
+            code_cid: rt.own_code_cid()?,
+            state_schema: rt.hash_blake2b(Self::STATE_SCHEMA.as_bytes()),
+        })
+    }
+
+    /// Wraps the dispatch by `actor_dispatch!` of every method but `Version`, e.g. to guard
+    /// some of them with a circuit breaker. By default, just makes the call.
+    fn around_method<RT: Runtime>(
+        rt: &mut RT,
+        _method: MethodNum,
+        call: impl FnOnce(&mut RT) -> Result<Option<IpldBlock>, ActorError>,
+    ) -> Result<Option<IpldBlock>, ActorError> {
+        call(rt)
+    }
+
     /// Invokes method with runtime on the actor's code. Method number will match one
     /// defined by the Actor, and parameters will be serialized and used in execution
     fn invoke_method<RT>(
@@ -24,3 +62,33 @@ pub trait ActorCode {
         RT: Runtime,
         RT::Blockstore: Blockstore + Clone;
 }
+
+/// The return value of the `Version` method.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
+pub struct VersionInfo {
+    /// The semver of the actor, as in `ActorCode::VERSION`.
+    pub version: String,
+    pub code_cid: Cid,
+    /// The blake2b-256 hash of `ActorCode::STATE_SCHEMA`.
+    #[serde(with = "strict_bytes")]
+    pub state_schema: [u8; 32],
+}
+
+/// An entry of `ActorCode::METHOD_TABLE`. Serializes as e.g.
+/// `{"number": 3844450837, "name": "Persist", "handler": "persist"}`, so tooling can dump
+/// the table of an actor as JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MethodInfo {
+    pub number: MethodNum,
+    /// The name of the method, as in the `Methods` enum.
+    pub name: &'static str,
+    /// The name of the function handling the method.
+    pub handler: &'static str,
+}
+
+impl MethodInfo {
+    /// Find a method in a table by its number.
+    pub fn lookup(table: &'static [MethodInfo], number: MethodNum) -> Option<&'static MethodInfo> {
+        table.iter().find(|m| m.number == number)
+    }
+}
@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use cid::Cid;
+use fvm_shared::address::Address;
+use fvm_shared::ActorID;
+
+/// Results of address resolution and code CID lookups, kept for the duration of an
+/// invocation so that e.g. validating many signers doesn't repeat the same syscalls.
+///
+/// Lookups that found nothing are cached too, so the cache has to be invalidated whenever an
+/// actor may have been created, i.e. after `create_actor` and after every send, as sending
+/// to a new key address creates its account actor.
+#[derive(Default, Debug)]
+pub struct AddressCache {
+    ids: RefCell<HashMap<Address, Option<Address>>>,
+    code_cids: RefCell<HashMap<ActorID, Option<Cid>>>,
+}
+
+impl AddressCache {
+    /// The ID address of `address`, from the cache or else from `resolve`.
+    pub fn resolve_address(
+        &self,
+        address: &Address,
+        resolve: impl FnOnce(&Address) -> Option<Address>,
+    ) -> Option<Address> {
+        if let Some(id) = self.ids.borrow().get(address) {
+            return *id;
+        }
+        let id = resolve(address);
+        self.ids.borrow_mut().insert(*address, id);
+        id
+    }
+
+    /// The code CID of the actor `id`, from the cache or else from `lookup`.
+    pub fn get_actor_code_cid(
+        &self,
+        id: ActorID,
+        lookup: impl FnOnce(ActorID) -> Option<Cid>,
+    ) -> Option<Cid> {
+        if let Some(code) = self.code_cids.borrow().get(&id) {
+            return *code;
+        }
+        let code = lookup(id);
+        self.code_cids.borrow_mut().insert(id, code);
+        code
+    }
+
+    /// Forget everything, e.g. after an actor has been created.
+    pub fn invalidate(&self) {
+        self.ids.borrow_mut().clear();
+        self.code_cids.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use cid::Cid;
+    use fvm_shared::address::Address;
+
+    use super::AddressCache;
+
+    #[test]
+    fn lookups_are_cached_until_invalidated() {
+        let cache = AddressCache::default();
+        let key = Address::new_secp256k1(&[1u8; 65]).unwrap();
+        let calls = Cell::new(0);
+        let resolve = |id: Option<u64>| {
+            let calls = &calls;
+            move |_: &Address| {
+                calls.set(calls.get() + 1);
+                id.map(Address::new_id)
+            }
+        };
+
+        // Not found yet, and that is cached as well.
+        assert_eq!(cache.resolve_address(&key, resolve(None)), None);
+        assert_eq!(cache.resolve_address(&key, resolve(Some(100))), None);
+        assert_eq!(calls.get(), 1);
+
+        // Once the account exists.
+        cache.invalidate();
+        assert_eq!(
+            cache.resolve_address(&key, resolve(Some(100))),
+            Some(Address::new_id(100))
+        );
+        assert_eq!(
+            cache.resolve_address(&key, resolve(None)),
+            Some(Address::new_id(100))
+        );
+        assert_eq!(calls.get(), 2);
+
+        assert_eq!(
+            cache.get_actor_code_cid(100, |_| Some(Cid::default())),
+            Some(Cid::default())
+        );
+        assert_eq!(
+            cache.get_actor_code_cid(100, |_| None),
+            Some(Cid::default())
+        );
+        assert_eq!(cache.get_actor_code_cid(101, |_| None), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt::{self, Display};
+
+/// A structured debug message, rendered as `label key=value ...`, with values containing
+/// spaces, `=` or quotes quoted, so that logs can be parsed back into fields.
+pub struct DebugEvent<'a> {
+    pub label: &'a str,
+    pub fields: &'a [(&'a str, &'a dyn Display)],
+}
+
+impl Display for DebugEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label)?;
+        for (key, value) in self.fields {
+            let value = value.to_string();
+            if value.is_empty()
+                || value.contains(|c: char| c.is_whitespace() || c == '=' || c == '"')
+            {
+                write!(f, " {key}={value:?}")?;
+            } else {
+                write!(f, " {key}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Forwards every log record to a sink, e.g. the FVM log syscall, prefixed with "[LEVEL] ".
+#[cfg(any(feature = "fil-actor", test))]
+pub(crate) struct Logger(pub fn(String));
+
+#[cfg(any(feature = "fil-actor", test))]
+impl log::Log for Logger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        // The log system doesn't call enabled() before this, but the logger is only
+        // installed when logging is enabled, and nothing disables it dynamically.
+        (self.0)(format!("[{}] {}", record.level(), record.args()));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(any(feature = "fil-actor", test))]
+impl Logger {
+    /// Installs the logger with every level enabled, unless a logger is already installed,
+    /// e.g. by an earlier invocation in the same instance.
+    pub(crate) fn install(&'static self) {
+        if log::set_logger(self).is_ok() {
+            log::set_max_level(log::LevelFilter::Trace);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::{DebugEvent, Logger};
+    use crate::runtime::Runtime;
+    use crate::test_utils::MockRuntime;
+
+    #[test]
+    fn key_value_formatting() {
+        let event = DebugEvent {
+            label: "checkpoint",
+            fields: &[
+                ("epoch", &10),
+                ("subnet", &"/r123/f0100"),
+                ("note", &"a=b c"),
+                ("empty", &""),
+            ],
+        };
+        assert_eq!(
+            event.to_string(),
+            r#"checkpoint epoch=10 subnet=/r123/f0100 note="a=b c" empty="""#
+        );
+    }
+
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn debug_events_reach_the_logger() {
+        static LOGGER: Logger = Logger(|msg| LOGGED.lock().unwrap().push(msg));
+        LOGGER.install();
+
+        let rt = MockRuntime::default();
+        rt.debug_event("checkpoint", &[("epoch", &10)]);
+        assert!(LOGGED
+            .lock()
+            .unwrap()
+            .contains(&"[DEBUG] checkpoint epoch=10".to_string()));
+    }
+}
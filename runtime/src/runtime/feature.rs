@@ -0,0 +1,44 @@
+use fvm_shared::version::NetworkVersion;
+
+/// Capabilities of the environment that depend on the network version, so that actors can
+/// check for them with `Runtime::supports` rather than comparing network versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Emitting actor events with `Runtime::emit_event`.
+    Events,
+    /// Upgrading the code of an actor in place.
+    Upgrades,
+    /// Sending messages that can't modify any state.
+    ReadOnlyCalls,
+}
+
+impl Feature {
+    /// The first network version supporting the feature.
+    pub const fn min_network_version(&self) -> NetworkVersion {
+        match self {
+            // Introduced along with the FEVM (Hygge).
+            Feature::Events | Feature::ReadOnlyCalls => NetworkVersion::V18,
+            // Actor upgrades came with FVM 4, which this runtime doesn't target yet.
+            Feature::Upgrades => NetworkVersion::new(22),
+        }
+    }
+
+    pub fn supported_at(&self, version: NetworkVersion) -> bool {
+        version >= self.min_network_version()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::version::NetworkVersion;
+
+    use super::Feature;
+
+    #[test]
+    fn features_by_network_version() {
+        assert!(!Feature::Events.supported_at(NetworkVersion::V17));
+        assert!(Feature::Events.supported_at(NetworkVersion::V18));
+        assert!(Feature::ReadOnlyCalls.supported_at(NetworkVersion::V20));
+        assert!(!Feature::Upgrades.supported_at(NetworkVersion::V20));
+    }
+}
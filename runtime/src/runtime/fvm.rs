@@ -7,20 +7,30 @@ use fvm_ipld_encoding::{to_vec, CborStore, DAG_CBOR};
 use fvm_sdk as fvm;
 use fvm_sdk::NO_DATA_BLOCK_ID;
 use fvm_shared::address::{Address, Protocol};
+use fvm_shared::chainid::ChainID;
 use fvm_shared::clock::ChainEpoch;
 use fvm_shared::crypto::signature::Signature;
 use fvm_shared::econ::TokenAmount;
 use fvm_shared::error::{ErrorNumber, ExitCode};
-use fvm_shared::sys::SendFlags;
+use fvm_shared::event::ActorEvent;
+use fvm_shared::sys::out::ipld::{IpldOpen, IpldStat};
+use fvm_shared::sys::{BlockId, SendFlags};
 use fvm_shared::version::NetworkVersion;
-use fvm_shared::{ActorID, MethodNum};
+use fvm_shared::{ActorID, MethodNum, MAX_CID_LEN};
 use num_traits::Zero;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::cbor::normalize_params;
 use crate::runtime::actor_blockstore::ActorBlockstore;
-use crate::runtime::{ActorCode, MessageInfo, Primitives};
-use crate::{actor_error, deserialize_block, ActorError, Runtime, Type};
+use crate::runtime::{
+    ActorCode, AddressCache, Logger, MessageInfo, MethodInfo, PanicReport, Policy, Primitives,
+    StateInvariants,
+};
+use crate::{
+    actor_error, delegated_subaddress, deserialize_block, ActorError, BytesReader, Runtime, Type,
+    EAM_ACTOR_ID,
+};
 
 pub const PUBKEY_ADDRESS_METHOD: u64 = 2;
 // The original method is `2`, but we have a custom account actor
@@ -44,6 +54,12 @@ pub struct FvmRuntime<B = ActorBlockstore> {
     in_transaction: bool,
     /// Indicates that the caller has been validated.
     caller_validated: bool,
+    /// The limits and parameters in effect for the actor.
+    policy: Policy,
+    /// Checked on the state by every transaction, if set.
+    state_invariants: Option<StateInvariants>,
+    /// Caches address resolution and code CID lookups during the invocation, if set.
+    address_cache: Option<AddressCache>,
 }
 
 impl Default for FvmRuntime {
@@ -52,11 +68,35 @@ impl Default for FvmRuntime {
             blockstore: ActorBlockstore,
             in_transaction: false,
             caller_validated: false,
+            policy: Policy::default(),
+            state_invariants: None,
+            address_cache: Some(AddressCache::default()),
         }
     }
 }
 
 impl<B> FvmRuntime<B> {
+    /// Use the limits and parameters of the policy, e.g. of the network the actor is deployed
+    /// to, instead of the defaults; see `trampoline_with` to set it for every invocation.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Make every address resolution and code CID lookup a syscall, rather than caching them
+    /// for the rest of the invocation. Set it for every invocation of an actor with
+    /// `trampoline_with(params, FvmRuntime::without_address_cache)`.
+    pub fn without_address_cache(mut self) -> Self {
+        self.address_cache = None;
+        self
+    }
+
+    fn invalidate_address_cache(&self) {
+        if let Some(cache) = &self.address_cache {
+            cache.invalidate();
+        }
+    }
+
     fn assert_not_validated(&mut self) -> Result<(), ActorError> {
         if self.caller_validated {
             return Err(actor_error!(
@@ -83,6 +123,14 @@ impl MessageInfo for FvmMessage {
     fn value_received(&self) -> TokenAmount {
         fvm::message::value_received()
     }
+
+    fn origin(&self) -> Address {
+        Address::new_id(fvm::message::origin())
+    }
+
+    fn gas_premium(&self) -> TokenAmount {
+        fvm::message::gas_premium()
+    }
 }
 
 impl<B> Runtime for FvmRuntime<B>
@@ -95,10 +143,18 @@ where
         fvm::network::version()
     }
 
+    fn chain_id(&self) -> ChainID {
+        fvm::network::chain_id()
+    }
+
     fn message(&self) -> &dyn MessageInfo {
         &FvmMessage
     }
 
+    fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
     fn curr_epoch(&self) -> ChainEpoch {
         fvm::network::curr_epoch()
     }
@@ -115,7 +171,11 @@ where
     {
         self.assert_not_validated()?;
         let caller_addr = self.message().caller();
-        if addresses.into_iter().any(|a| *a == caller_addr) {
+        // The caller is an ID address, so only the others need resolving, at a syscall each.
+        if addresses.into_iter().any(|a| {
+            *a == caller_addr
+                || (a.protocol() != Protocol::ID && self.resolve_address(a) == Some(caller_addr))
+        }) {
             self.caller_validated = true;
             Ok(())
         } else {
@@ -167,16 +227,65 @@ where
         }
     }
 
+    fn validate_immediate_caller_is_eth(&mut self, address: &[u8; 20]) -> Result<(), ActorError> {
+        self.assert_not_validated()?;
+        let caller_addr = self.message().caller();
+        let delegated = self.lookup_delegated_address(caller_addr.id().unwrap());
+        match delegated
+            .as_ref()
+            .and_then(|a| delegated_subaddress(a, EAM_ACTOR_ID))
+        {
+            Some(subaddress) if subaddress == address.as_slice() => {
+                self.caller_validated = true;
+                Ok(())
+            }
+            _ => Err(actor_error!(forbidden;
+                "caller {} is not the expected eth address", caller_addr)),
+        }
+    }
+
+    fn validate_immediate_caller_namespace(
+        &mut self,
+        namespace: ActorID,
+    ) -> Result<(), ActorError> {
+        self.assert_not_validated()?;
+        let caller_addr = self.message().caller();
+        let delegated = self.lookup_delegated_address(caller_addr.id().unwrap());
+        match delegated
+            .as_ref()
+            .and_then(|a| delegated_subaddress(a, namespace))
+        {
+            Some(_) => {
+                self.caller_validated = true;
+                Ok(())
+            }
+            None => Err(actor_error!(forbidden;
+                "caller {} has no delegated address in namespace {}", caller_addr, namespace)),
+        }
+    }
+
     fn current_balance(&self) -> TokenAmount {
         fvm::sself::current_balance()
     }
 
     fn resolve_address(&self, address: &Address) -> Option<Address> {
-        fvm::actor::resolve_address(address).map(Address::new_id)
+        let resolve = |a: &Address| fvm::actor::resolve_address(a).map(Address::new_id);
+        match &self.address_cache {
+            Some(cache) => cache.resolve_address(address, resolve),
+            None => resolve(address),
+        }
     }
 
     fn get_actor_code_cid(&self, id: &ActorID) -> Option<Cid> {
-        fvm::actor::get_actor_code_cid(&Address::new_id(*id))
+        let lookup = |id| fvm::actor::get_actor_code_cid(&Address::new_id(id));
+        match &self.address_cache {
+            Some(cache) => cache.get_actor_code_cid(*id, lookup),
+            None => lookup(*id),
+        }
+    }
+
+    fn lookup_delegated_address(&self, id: ActorID) -> Option<Address> {
+        fvm::actor::lookup_delegated_address(id)
     }
 
     fn create<T: Serialize>(&mut self, obj: &T) -> Result<(), ActorError> {
@@ -220,16 +329,41 @@ where
         self.in_transaction = false;
 
         let ret = result?;
+        if let Some(invariants) = &self.state_invariants {
+            invariants.check(&state)?;
+        }
         let new_root = ActorBlockstore.put_cbor(&state, Code::Blake2b256)
             .map_err(|e| actor_error!(illegal_argument; "failed to write actor state in transaction: {}", e.to_string()))?;
         fvm::sself::set_root(&new_root)?;
         Ok(ret)
     }
 
+    fn set_state_invariants(&mut self, invariants: StateInvariants) {
+        self.state_invariants = Some(invariants);
+    }
+
     fn store(&self) -> &B {
         &self.blockstore
     }
 
+    fn read_block(&self, cid: &Cid, max_size: u32) -> Result<IpldBlock, ActorError> {
+        let mut cid_buf = [0u8; MAX_CID_LEN];
+        cid.write_bytes(&mut cid_buf[..])
+            .map_err(|e| actor_error!(illegal_argument; "invalid CID {}: {}", cid, e))?;
+        let IpldOpen { id, codec, size } =
+            unsafe { fvm::sys::ipld::block_open(cid_buf.as_mut_ptr()) }.map_err(|e| match e {
+                ErrorNumber::NotFound => actor_error!(not_found; "block {} not found", cid),
+                _ => actor_error!(illegal_state; "failed to open block {}: {:?}", cid, e),
+            })?;
+        if size > max_size {
+            return Err(actor_error!(illegal_argument;
+                "block {} has {} bytes, more than the limit of {}", cid, size, max_size));
+        }
+        let data = fvm::ipld::get_block(id, Some(size))
+            .map_err(|e| actor_error!(illegal_state; "failed to read block {}: {:?}", cid, e))?;
+        Ok(IpldBlock { codec, data })
+    }
+
     fn send(
         &self,
         to: &Address,
@@ -240,7 +374,11 @@ where
         if self.in_transaction {
             return Err(actor_error!(assertion_failed; "send is not allowed during transaction"));
         }
-        match fvm::send::send(to, method, params, value, None, SendFlags::empty()) {
+        let params = normalize_params(params);
+        // Sending to a new key address creates its account actor.
+        let result = fvm::send::send(to, method, params, value, None, SendFlags::empty());
+        self.invalidate_address_cache();
+        match result {
             Ok(ret) => {
                 if ret.exit_code.is_success() {
                     Ok(ret.return_data)
@@ -292,7 +430,9 @@ where
                 actor_error!(assertion_failed; "create_actor is not allowed during transaction"),
             );
         }
-        fvm::actor::create_actor(actor_id, &code_id, None).map_err(|e| match e {
+        let result = fvm::actor::create_actor(actor_id, &code_id, None);
+        self.invalidate_address_cache();
+        result.map_err(|e| match e {
             ErrorNumber::IllegalArgument => {
                 ActorError::illegal_argument("failed to create actor".into())
             }
@@ -328,6 +468,11 @@ where
     fn base_fee(&self) -> TokenAmount {
         fvm::network::base_fee()
     }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ActorError> {
+        fvm::event::emit_event(event)
+            .map_err(|e| actor_error!(illegal_argument; "failed to emit event: {}", e))
+    }
 }
 
 impl<B> Primitives for FvmRuntime<B>
@@ -351,6 +496,56 @@ where
     }
 }
 
+/// Reads a block held by the FVM, such as the parameters of the message, piecewise,
+/// rather than copying all of it into memory at once.
+pub struct BlockReader {
+    id: BlockId,
+    codec: u64,
+    size: u32,
+}
+
+impl BlockReader {
+    /// Opens the block with the given ID, or returns `None` for `NO_DATA_BLOCK_ID`.
+    pub fn open(id: BlockId) -> Result<Option<Self>, ActorError> {
+        if id == NO_DATA_BLOCK_ID {
+            return Ok(None);
+        }
+        let IpldStat { codec, size } = unsafe { fvm::sys::ipld::block_stat(id) }
+            .map_err(|e| actor_error!(illegal_state; "failed to stat block {}: {}", id, e))?;
+        Ok(Some(Self { id, codec, size }))
+    }
+
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// Copies the whole block into memory.
+    pub fn to_block(&self) -> Result<IpldBlock, ActorError> {
+        let data = fvm::ipld::get_block(self.id, Some(self.size))
+            .map_err(|e| actor_error!(illegal_state; "failed to read block {}: {}", self.id, e))?;
+        Ok(IpldBlock {
+            codec: self.codec,
+            data,
+        })
+    }
+}
+
+impl BytesReader for BlockReader {
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<usize, ActorError> {
+        let len = (self.size.saturating_sub(offset) as usize).min(buf.len());
+        if len == 0 {
+            return Ok(0);
+        }
+        unsafe { fvm::sys::ipld::block_read(self.id, offset, buf.as_mut_ptr(), len as u32) }
+            .map_err(|e| actor_error!(illegal_state; "failed to read block {}: {}", self.id, e))?;
+        Ok(len)
+    }
+}
+
 /// A convenience function that built-in actors can delegate their execution to.
 ///
 /// The trampoline takes care of boilerplate:
@@ -363,23 +558,94 @@ where
 /// 5a. In case of error, aborts the execution with the emitted exit code, or
 /// 5b. In case of success, stores the return data as a block and returns the latter.
 pub fn trampoline<C: ActorCode>(params: u32) -> u32 {
+    trampoline_with::<C, _>(params, |rt| rt)
+}
+
+/// Same as `trampoline`, except that `configure` gets to adjust the runtime before the method
+/// is invoked, e.g. to set the policy of the network the actor is deployed to, or to disable
+/// the address cache with `FvmRuntime::without_address_cache`.
+///
+/// # Example
+/// ```ignore
+/// #[no_mangle]
+/// pub fn invoke(params: u32) -> u32 {
+///     trampoline_with::<Actor, _>(params, |rt| {
+///         rt.with_policy(Policy::for_network(Network::Calibration))
+///     })
+/// }
+///
+/// // Or, to make every address lookup a syscall:
+/// // trampoline_with::<Actor, _>(params, FvmRuntime::without_address_cache)
+/// ```
+pub fn trampoline_with<C, F>(params: u32, configure: F) -> u32
+where
+    C: ActorCode,
+    F: FnOnce(FvmRuntime) -> FvmRuntime,
+{
+    run(configure(FvmRuntime::default()), |rt, method| {
+        if fvm::debug::enabled() {
+            if let Some(m) = MethodInfo::lookup(C::METHOD_TABLE, method) {
+                log::debug!("invoking {} with {}", m.name, m.handler);
+            }
+        }
+        let params = fvm::message::params_raw(params).expect("params block invalid");
+        C::invoke_method(rt, method, params)
+    })
+}
+
+/// Same as `trampoline`, except that the parameters are left in the FVM and `invoke` gets a
+/// `BlockReader` over them instead, for methods taking payloads too large to copy around,
+/// e.g. Wasm modules or proofs.
+///
+/// # Example
+/// ```ignore
+/// #[no_mangle]
+/// pub fn invoke(params: u32) -> u32 {
+///     reader_trampoline(params, |rt, method, params| match method {
+///         UPLOAD_METHOD => upload(rt, params),
+///         _ => Actor::invoke_method(rt, method, params.map(|p| p.to_block()).transpose()?),
+///     })
+/// }
+/// ```
+pub fn reader_trampoline<F>(params: u32, invoke: F) -> u32
+where
+    F: FnOnce(
+        &mut FvmRuntime,
+        MethodNum,
+        Option<BlockReader>,
+    ) -> Result<Option<IpldBlock>, ActorError>,
+{
+    run(FvmRuntime::default(), |rt, method| {
+        let params = BlockReader::open(params).expect("params block invalid");
+        invoke(rt, method, params)
+    })
+}
+
+/// What the trampolines have in common, apart from getting the parameters.
+fn run<F>(mut rt: FvmRuntime, invoke: F) -> u32
+where
+    F: FnOnce(&mut FvmRuntime, MethodNum) -> Result<Option<IpldBlock>, ActorError>,
+{
     init_logging();
 
+    // Exit with the encoded report as the data, for the caller to decode.
     std::panic::set_hook(Box::new(|info| {
-        fvm::vm::abort(
+        let report = PanicReport::new(info.payload(), info.location());
+        fvm::vm::exit(
             ExitCode::USR_ASSERTION_FAILED.value(),
-            Some(&format!("{info}")),
+            IpldBlock::serialize_cbor(&report).ok().flatten(),
+            Some(&report.to_string()),
         )
     }));
 
     let method = fvm::message::method_number();
-    let params = fvm::message::params_raw(params).expect("params block invalid");
 
-    // Construct a new runtime.
-    let mut rt = FvmRuntime::default();
-    // Invoke the method, aborting if the actor returns an errored exit code.
-    let ret = C::invoke_method(&mut rt, method, params)
-        .unwrap_or_else(|err| fvm::vm::abort(err.exit_code().value(), Some(err.msg())));
+    // Invoke the method, aborting if the actor returns an errored exit code, along with the
+    // data of the error, if any, for the caller to decode.
+    let ret = invoke(&mut rt, method).unwrap_or_else(|mut err| match err.take_data() {
+        None => fvm::vm::abort(err.exit_code().value(), Some(err.msg())),
+        data => fvm::vm::exit(err.exit_code().value(), data, Some(err.msg())),
+    });
 
     // Abort with "assertion failed" if the actor failed to validate the caller somewhere.
     // We do this after handling the error, because the actor may have encountered an error before
@@ -409,24 +675,9 @@ pub fn trampoline<C: ActorCode>(params: u32) -> u32 {
 /// Note: this is similar to fvm::debug::init_logging() from the FVM SDK, but
 /// that doesn't work (at FVM SDK v2.2).
 fn init_logging() {
-    struct Logger;
-
-    impl log::Log for Logger {
-        fn enabled(&self, _: &log::Metadata) -> bool {
-            true
-        }
-
-        fn log(&self, record: &log::Record) {
-            // Note the log system won't automatically call enabled() before this,
-            // so it's canonical to check it here.
-            // But logging must have been enabled at initialisation time in order for
-            // the logger to be installed.
-            // There's currently no use for dynamically disabling logging, so just skip checking.
-            let msg = format!("[{}] {}", record.level(), record.args());
-            fvm::debug::log(msg);
-        }
-
-        fn flush(&self) {}
+    static LOGGER: Logger = Logger(fvm::debug::log);
+    if fvm::debug::enabled() {
+        LOGGER.install();
     }
 }
 
@@ -444,9 +695,8 @@ pub fn resolve_secp_bls(rt: &mut impl Runtime, addr: &Address) -> Result<Address
             )?;
             deserialize_block(ret)
         }
-        _ => Err(ActorError::illegal_argument(String::from(
-            "address type not compatible",
-        ))),
+        _ => Err(actor_error!(illegal_argument;
+            "expected a SECP, BLS or ID address, got {}", addr)),
     }
 }
 
@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use cid::Cid;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_shared::address::Address;
+use fvm_shared::chainid::ChainID;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::crypto::signature::Signature;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::event::ActorEvent;
+use fvm_shared::version::NetworkVersion;
+use fvm_shared::{ActorID, MethodNum};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::runtime::{MessageInfo, Policy, Primitives, Runtime, StateInvariants};
+use crate::{actor_error, ActorError, Type};
+
+/// A `Runtime` decorator counting the calls made through it by category, e.g. `"send"` or
+/// `"state"`, and logging a summary at debug level when it's dropped, i.e. when the method
+/// invoked with it exits.
+///
+/// Wrapping the runtime where the method is invoked profiles it without changing the actor.
+/// The counts can also be read directly with `counts`, e.g. in tests with `MockRuntime`.
+///
+/// The `transaction` callback gets the wrapper, so the calls inside it are counted too.
+/// To achieve that it runs outside of the inner runtime's transaction, with the wrapper
+/// rejecting in its place what the inner runtime would, i.e. sends, creating state, and
+/// creating and deleting actors, and the state is written back once it succeeds.
+pub struct InstrumentedRuntime<R> {
+    inner: R,
+    counts: RefCell<BTreeMap<&'static str, u64>>,
+    in_transaction: bool,
+}
+
+impl<R> InstrumentedRuntime<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            counts: Default::default(),
+            in_transaction: false,
+        }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The number of calls made so far, by category.
+    pub fn counts(&self) -> BTreeMap<&'static str, u64> {
+        self.counts.borrow().clone()
+    }
+
+    /// The counts on a single line, e.g. `send=1 state=2 validate=1`.
+    pub fn summary(&self) -> String {
+        let counts = self.counts.borrow();
+        let parts: Vec<String> = counts.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        parts.join(" ")
+    }
+
+    fn count(&self, category: &'static str) {
+        *self.counts.borrow_mut().entry(category).or_default() += 1;
+    }
+
+    fn assert_not_in_transaction(&self, op: &str) -> Result<(), ActorError> {
+        if self.in_transaction {
+            return Err(actor_error!(assertion_failed; "{} is not allowed during transaction", op));
+        }
+        Ok(())
+    }
+}
+
+impl<R> Drop for InstrumentedRuntime<R> {
+    fn drop(&mut self) {
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!("syscalls: {}", self.summary());
+        }
+    }
+}
+
+impl<R: Runtime> Primitives for InstrumentedRuntime<R> {
+    fn hash_blake2b(&self, data: &[u8]) -> [u8; 32] {
+        self.count("crypto");
+        self.inner.hash_blake2b(data)
+    }
+
+    fn verify_signature(
+        &self,
+        signature: &Signature,
+        signer: &Address,
+        plaintext: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        self.count("crypto");
+        self.inner.verify_signature(signature, signer, plaintext)
+    }
+}
+
+impl<R: Runtime> Runtime for InstrumentedRuntime<R> {
+    type Blockstore = R::Blockstore;
+
+    fn network_version(&self) -> NetworkVersion {
+        self.count("network");
+        self.inner.network_version()
+    }
+
+    fn chain_id(&self) -> ChainID {
+        self.count("network");
+        self.inner.chain_id()
+    }
+
+    fn message(&self) -> &dyn MessageInfo {
+        self.count("message");
+        self.inner.message()
+    }
+
+    fn policy(&self) -> &Policy {
+        self.inner.policy()
+    }
+
+    fn curr_epoch(&self) -> ChainEpoch {
+        self.count("network");
+        self.inner.curr_epoch()
+    }
+
+    fn validate_immediate_caller_accept_any(&mut self) -> Result<(), ActorError> {
+        self.count("validate");
+        self.inner.validate_immediate_caller_accept_any()
+    }
+
+    fn validate_immediate_caller_is<'a, I>(&mut self, addresses: I) -> Result<(), ActorError>
+    where
+        I: IntoIterator<Item = &'a Address>,
+    {
+        self.count("validate");
+        self.inner.validate_immediate_caller_is(addresses)
+    }
+
+    fn validate_immediate_caller_type<'a, I>(&mut self, types: I) -> Result<(), ActorError>
+    where
+        I: IntoIterator<Item = &'a Type>,
+    {
+        self.count("validate");
+        self.inner.validate_immediate_caller_type(types)
+    }
+
+    fn validate_immediate_caller_not_type<'a, I>(&mut self, types: I) -> Result<(), ActorError>
+    where
+        I: IntoIterator<Item = &'a Type>,
+    {
+        self.count("validate");
+        self.inner.validate_immediate_caller_not_type(types)
+    }
+
+    fn validate_immediate_caller_is_eth(&mut self, address: &[u8; 20]) -> Result<(), ActorError> {
+        self.count("validate");
+        self.inner.validate_immediate_caller_is_eth(address)
+    }
+
+    fn validate_immediate_caller_namespace(
+        &mut self,
+        namespace: ActorID,
+    ) -> Result<(), ActorError> {
+        self.count("validate");
+        self.inner.validate_immediate_caller_namespace(namespace)
+    }
+
+    fn current_balance(&self) -> TokenAmount {
+        self.count("balance");
+        self.inner.current_balance()
+    }
+
+    fn resolve_address(&self, address: &Address) -> Option<Address> {
+        self.count("address");
+        self.inner.resolve_address(address)
+    }
+
+    fn get_actor_code_cid(&self, id: &ActorID) -> Option<Cid> {
+        self.count("actor");
+        self.inner.get_actor_code_cid(id)
+    }
+
+    fn lookup_delegated_address(&self, id: ActorID) -> Option<Address> {
+        self.count("address");
+        self.inner.lookup_delegated_address(id)
+    }
+
+    fn create<T: Serialize>(&mut self, obj: &T) -> Result<(), ActorError> {
+        self.count("state");
+        self.assert_not_in_transaction("create")?;
+        self.inner.create(obj)
+    }
+
+    fn state<T: DeserializeOwned>(&self) -> Result<T, ActorError> {
+        self.count("state");
+        self.inner.state()
+    }
+
+    fn transaction<T, RT, F>(&mut self, f: F) -> Result<RT, ActorError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(&mut T, &mut Self) -> Result<RT, ActorError>,
+    {
+        self.count("state");
+        self.assert_not_in_transaction("transaction")?;
+        let mut state: T = self.inner.state()?;
+
+        self.in_transaction = true;
+        let result = f(&mut state, self);
+        self.in_transaction = false;
+
+        let ret = result?;
+        self.inner.transaction(|st: &mut T, _| {
+            *st = state;
+            Ok(())
+        })?;
+        Ok(ret)
+    }
+
+    fn set_state_invariants(&mut self, invariants: StateInvariants) {
+        self.inner.set_state_invariants(invariants)
+    }
+
+    fn store(&self) -> &Self::Blockstore {
+        self.count("store");
+        self.inner.store()
+    }
+
+    fn read_block(&self, cid: &Cid, max_size: u32) -> Result<IpldBlock, ActorError> {
+        self.count("store");
+        self.inner.read_block(cid, max_size)
+    }
+
+    fn send(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Option<IpldBlock>, ActorError> {
+        self.count("send");
+        self.assert_not_in_transaction("send")?;
+        self.inner.send(to, method, params, value)
+    }
+
+    fn new_actor_address(&mut self) -> Result<Address, ActorError> {
+        self.count("actor");
+        self.inner.new_actor_address()
+    }
+
+    fn create_actor(&mut self, code_id: Cid, address: ActorID) -> Result<(), ActorError> {
+        self.count("actor");
+        self.assert_not_in_transaction("create_actor")?;
+        self.inner.create_actor(code_id, address)
+    }
+
+    fn delete_actor(&mut self, beneficiary: &Address) -> Result<(), ActorError> {
+        self.count("actor");
+        self.assert_not_in_transaction("delete_actor")?;
+        self.inner.delete_actor(beneficiary)
+    }
+
+    fn resolve_builtin_actor_type(&self, code_id: &Cid) -> Option<Type> {
+        self.count("actor");
+        self.inner.resolve_builtin_actor_type(code_id)
+    }
+
+    fn get_code_cid_for_type(&self, typ: Type) -> Cid {
+        self.count("actor");
+        self.inner.get_code_cid_for_type(typ)
+    }
+
+    fn total_fil_circ_supply(&self) -> TokenAmount {
+        self.count("network");
+        self.inner.total_fil_circ_supply()
+    }
+
+    fn charge_gas(&mut self, name: &'static str, compute: i64) {
+        self.count("gas");
+        self.inner.charge_gas(name, compute)
+    }
+
+    fn base_fee(&self) -> TokenAmount {
+        self.count("network");
+        self.inner.base_fee()
+    }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ActorError> {
+        self.count("event");
+        self.inner.emit_event(event)
+    }
+}
@@ -0,0 +1,58 @@
+use std::rc::Rc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{actor_error, ActorError, MessageAccumulator};
+
+type Check = dyn Fn(&[u8], &MessageAccumulator) -> Result<(), ActorError>;
+
+/// Checks of the invariants of an actor state, which `transaction` runs on the mutated state
+/// before flushing it, failing the transaction with `USR_ILLEGAL_STATE` on any violation.
+///
+/// The state is checked in its encoded form, so the checks of a state type can be set on a
+/// runtime that doesn't know about it. Encoding and decoding it again costs gas, so the checks
+/// are meant for cheap, local invariants; the exhaustive ones belong in tests.
+///
+/// # Example
+/// ```ignore
+/// rt.set_state_invariants(StateInvariants::new(|st: &State, acc| {
+///     acc.require(st.total >= st.locked, "locked funds exceed the total");
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct StateInvariants {
+    check: Rc<Check>,
+}
+
+impl StateInvariants {
+    pub fn new<S, F>(check: F) -> Self
+    where
+        S: DeserializeOwned,
+        F: Fn(&S, &MessageAccumulator) + 'static,
+    {
+        Self {
+            check: Rc::new(move |bytes, acc| {
+                let state: S = fvm_ipld_encoding::from_slice(bytes).map_err(
+                    |e| actor_error!(serialization; "failed to decode state for invariants: {}", e),
+                )?;
+                check(&state, acc);
+                Ok(())
+            }),
+        }
+    }
+
+    /// Check a state, listing the violated invariants in the error.
+    pub fn check<S: Serialize>(&self, state: &S) -> Result<(), ActorError> {
+        let bytes = fvm_ipld_encoding::to_vec(state).map_err(
+            |e| actor_error!(serialization; "failed to encode state for invariants: {}", e),
+        )?;
+        let acc = MessageAccumulator::default();
+        (self.check)(&bytes, &acc)?;
+        if !acc.is_empty() {
+            return Err(actor_error!(illegal_state;
+                "state invariants violated: {}", acc.messages().join("; ")));
+        }
+        Ok(())
+    }
+}
@@ -4,10 +4,12 @@
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_shared::address::Address;
+use fvm_shared::chainid::ChainID;
 use fvm_shared::clock::ChainEpoch;
 use fvm_shared::consensus::ConsensusFault;
 use fvm_shared::crypto::signature::Signature;
 use fvm_shared::econ::TokenAmount;
+use fvm_shared::event::ActorEvent;
 use fvm_shared::sector::{
     AggregateSealVerifyProofAndInfos, ReplicaUpdateInfo, SealVerifyInfo, WindowPoStVerifyInfo,
 };
@@ -17,15 +19,33 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 pub use self::actor_code::*;
-use crate::{ActorError, Type};
+pub use self::address_cache::AddressCache;
+pub use self::debug::DebugEvent;
+#[cfg(any(feature = "fil-actor", test))]
+pub(crate) use self::debug::Logger;
+pub use self::feature::Feature;
+pub use self::instrumented::InstrumentedRuntime;
+pub use self::invariants::StateInvariants;
+pub use self::panic::{PanicClass, PanicReport, MAX_PANIC_MESSAGE_LEN};
+pub use self::policy::*;
+use crate::{actor_error, ActorError, Type};
 
 mod actor_code;
+mod address_cache;
+mod debug;
+mod feature;
+mod instrumented;
+mod invariants;
+mod panic;
+mod policy;
 
 #[cfg(feature = "fil-actor")]
 pub mod fvm;
 
 #[cfg(feature = "fil-actor")]
 mod actor_blockstore;
+#[cfg(feature = "fil-actor")]
+pub use actor_blockstore::ActorBlockstore;
 
 pub(crate) mod empty;
 
@@ -40,15 +60,29 @@ pub trait Runtime: Primitives {
     /// The network protocol version number at the current epoch.
     fn network_version(&self) -> NetworkVersion;
 
+    /// Whether the network supports a feature at the current epoch, e.g.
+    /// `rt.supports(Feature::Events)`, to branch on rather than on the network version.
+    fn supports(&self, feature: Feature) -> bool {
+        feature.supported_at(self.network_version())
+    }
+
+    /// The ID of the chain the actor is running on.
+    fn chain_id(&self) -> ChainID;
+
     /// Information related to the current message being executed.
     fn message(&self) -> &dyn MessageInfo;
 
+    /// The limits and parameters in effect for the actor.
+    fn policy(&self) -> &Policy;
+
     /// The current chain epoch number. The genesis block has epoch zero.
     fn curr_epoch(&self) -> ChainEpoch;
 
     /// Validates the caller against some predicate.
     /// Exported actor methods must invoke at least one caller validation before returning.
     fn validate_immediate_caller_accept_any(&mut self) -> Result<(), ActorError>;
+    /// The addresses may be of any protocol; they are resolved to ID addresses before being
+    /// compared with the caller.
     fn validate_immediate_caller_is<'a, I>(&mut self, addresses: I) -> Result<(), ActorError>
     where
         I: IntoIterator<Item = &'a Address>;
@@ -58,6 +92,13 @@ pub trait Runtime: Primitives {
     fn validate_immediate_caller_not_type<'a, I>(&mut self, types: I) -> Result<(), ActorError>
     where
         I: IntoIterator<Item = &'a Type>;
+    /// Validates that the caller's delegated address is the f410 address of the given
+    /// Ethereum address, i.e. that the caller is that EVM contract or account.
+    fn validate_immediate_caller_is_eth(&mut self, address: &[u8; 20]) -> Result<(), ActorError>;
+    /// Validates that the caller has a delegated address in the namespace of the given actor,
+    /// e.g. `EAM_ACTOR_ID` for any EVM contract or account.
+    fn validate_immediate_caller_namespace(&mut self, namespace: ActorID)
+        -> Result<(), ActorError>;
 
     /// The balance of the receiver.
     fn current_balance(&self) -> TokenAmount;
@@ -70,6 +111,19 @@ pub trait Runtime: Primitives {
     /// Look up the code ID at an actor address.
     fn get_actor_code_cid(&self, id: &ActorID) -> Option<Cid>;
 
+    /// The code ID of the receiving actor, i.e. of the code being executed.
+    fn own_code_cid(&self) -> Result<Cid, ActorError> {
+        let receiver = self.message().receiver();
+        receiver
+            .id()
+            .ok()
+            .and_then(|id| self.get_actor_code_cid(&id))
+            .ok_or_else(|| actor_error!(illegal_state; "no code CID for receiver {}", receiver))
+    }
+
+    /// Look up the delegated (f4) address of an actor, if it has one.
+    fn lookup_delegated_address(&self, id: ActorID) -> Option<Address>;
+
     /// Initializes the state object.
     /// This is only valid when the state has not yet been initialized.
     /// NOTE: we should also limit this to being invoked during the constructor method
@@ -84,15 +138,27 @@ pub trait Runtime: Primitives {
     ///
     /// During the call to `f`, execution is protected from side-effects, (including message send).
     ///
+    /// If state invariants are set, they are checked before the state is put back, and any
+    /// violation fails the transaction without changing the state.
+    ///
     /// Returns the result of `f`.
     fn transaction<T, RT, F>(&mut self, f: F) -> Result<RT, ActorError>
     where
         T: Serialize + DeserializeOwned,
         F: FnOnce(&mut T, &mut Self) -> Result<RT, ActorError>;
 
+    /// Sets the invariants checked by `transaction` on the state, replacing any set before,
+    /// so that it can be called on every invocation.
+    fn set_state_invariants(&mut self, invariants: StateInvariants);
+
     /// Returns reference to blockstore
     fn store(&self) -> &Self::Blockstore;
 
+    /// Reads a block along with its codec, for data the actor is handed by CID rather than
+    /// by value. Fails with `USR_ILLEGAL_ARGUMENT` if the block is larger than `max_size`
+    /// bytes, before reading it, and with `USR_NOT_FOUND` if the block isn't reachable.
+    fn read_block(&self, cid: &Cid, max_size: u32) -> Result<IpldBlock, ActorError>;
+
     /// Sends a message to another actor, returning the exit code and return value envelope.
     /// If the invoked method does not return successfully, its state changes
     /// (and that of any messages it sent in turn) will be rolled back.
@@ -143,7 +209,32 @@ pub trait Runtime: Primitives {
     /// `name` provides information about gas charging point
     fn charge_gas(&mut self, name: &'static str, compute: i64);
 
+    /// Charges one of the named amounts of the `GasSchedule` of the policy, e.g.
+    /// `rt.charge(rt.policy().gas.persist_entry)`.
+    /// With the `no-gas-charges` feature it doesn't charge anything.
+    fn charge(&mut self, charge: GasCharge) {
+        if cfg!(feature = "no-gas-charges") {
+            return;
+        }
+        self.charge_gas(charge.name, charge.compute)
+    }
+
     fn base_fee(&self) -> TokenAmount;
+
+    /// Emits an event denoting that something externally noteworthy has occurred.
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ActorError>;
+
+    /// Logs a structured debug message, e.g. `rt.debug_event("checkpoint", &[("epoch", &epoch)])`,
+    /// rendered as `label key=value ...` and unrelated to the events of `emit_event`.
+    ///
+    /// It reaches the FVM through the logger the trampoline installs when debugging is enabled
+    /// in the VM, and is compiled out of release builds, so it has no gas cost on production
+    /// networks.
+    fn debug_event(&self, label: &str, fields: &[(&str, &dyn std::fmt::Display)]) {
+        if cfg!(debug_assertions) {
+            log::debug!("{}", DebugEvent { label, fields });
+        }
+    }
 }
 
 /// Message information available to the actor about executing message.
@@ -157,6 +248,13 @@ pub trait MessageInfo {
     /// The value attached to the message being processed, implicitly
     /// added to current_balance() before method invocation.
     fn value_received(&self) -> TokenAmount;
+
+    /// The address of the account that sent the top level message. Always an ID-address.
+    fn origin(&self) -> Address;
+
+    /// The gas premium of the top level message, paid to the block producer for each unit
+    /// of gas used. The gas limit of the message is not exposed by the FVM.
+    fn gas_premium(&self) -> TokenAmount;
 }
 
 /// Pure functions implemented as primitives by the runtime.
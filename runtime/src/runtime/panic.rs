@@ -0,0 +1,158 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::panic::Location;
+
+use fvm_ipld_encoding::tuple::*;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+/// Messages longer than this many bytes are truncated, to keep the abort message bounded.
+pub const MAX_PANIC_MESSAGE_LEN: usize = 256;
+
+/// The kinds of panic worth telling apart when debugging an aborted actor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum PanicClass {
+    Other = 0,
+    IndexOutOfBounds = 1,
+    UnwrapNone = 2,
+    UnwrapErr = 3,
+    ArithmeticOverflow = 4,
+    DivideByZero = 5,
+}
+
+impl PanicClass {
+    /// Classify a panic by the message the standard library panics with.
+    pub fn of(message: &str) -> Self {
+        if message.starts_with("index out of bounds") || message.contains("out of range for slice")
+        {
+            PanicClass::IndexOutOfBounds
+        } else if message.starts_with("called `Option::unwrap()` on a `None` value") {
+            PanicClass::UnwrapNone
+        } else if message.starts_with("called `Result::unwrap()` on an `Err` value") {
+            PanicClass::UnwrapErr
+        } else if message.starts_with("attempt to") && message.ends_with("with overflow") {
+            PanicClass::ArithmeticOverflow
+        } else if message.starts_with("attempt to divide by zero")
+            || message.starts_with("attempt to calculate the remainder with a divisor of zero")
+        {
+            PanicClass::DivideByZero
+        } else {
+            PanicClass::Other
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PanicClass::Other => "panic",
+            PanicClass::IndexOutOfBounds => "index out of bounds",
+            PanicClass::UnwrapNone => "unwrap on None",
+            PanicClass::UnwrapErr => "unwrap on Err",
+            PanicClass::ArithmeticOverflow => "arithmetic overflow",
+            PanicClass::DivideByZero => "divide by zero",
+        }
+    }
+}
+
+/// What the panic hook installed by the trampoline knows about a panic: its class, a message
+/// truncated to `MAX_PANIC_MESSAGE_LEN` and where it happened.
+///
+/// It is displayed as the abort message, e.g. `[unwrap on None] called ... at src/lib.rs:10:5`.
+/// Its CBOR encoding, `[class, message, file, line, column]`, is the data of the abort.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
+pub struct PanicReport {
+    pub class: PanicClass,
+    pub message: String,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl PanicReport {
+    /// Build the report from the payload and location of a panic, as passed to the panic hook.
+    pub fn new(payload: &(dyn Any + Send), location: Option<&Location>) -> Self {
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.as_str()
+        } else {
+            "Box<dyn Any>"
+        };
+        Self {
+            class: PanicClass::of(message),
+            message: truncate(message, MAX_PANIC_MESSAGE_LEN).to_string(),
+            file: location.map(|l| l.file().to_string()).unwrap_or_default(),
+            line: location.map(Location::line).unwrap_or_default(),
+            column: location.map(Location::column).unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for PanicReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.class.as_str(), self.message)?;
+        if !self.file.is_empty() {
+            write!(f, " at {}:{}:{}", self.file, self.line, self.column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cut `s` to at most `max` bytes, on a character boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use fvm_ipld_encoding::{from_slice, to_vec};
+
+    use super::{PanicClass, PanicReport, MAX_PANIC_MESSAGE_LEN};
+
+    #[test]
+    fn classify_and_truncate() {
+        let location = Location::caller();
+        let report = PanicReport::new(
+            &"called `Option::unwrap()` on a `None` value",
+            Some(location),
+        );
+        assert_eq!(report.class, PanicClass::UnwrapNone);
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "[unwrap on None] called `Option::unwrap()` on a `None` value at {}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        );
+        assert_eq!(
+            from_slice::<PanicReport>(&to_vec(&report).unwrap()).unwrap(),
+            report
+        );
+
+        let message = "index out of bounds: the len is 1 but the index is 1".to_string();
+        assert_eq!(
+            PanicReport::new(&message, None).class,
+            PanicClass::IndexOutOfBounds
+        );
+        assert_eq!(
+            PanicClass::of("attempt to add with overflow"),
+            PanicClass::ArithmeticOverflow
+        );
+
+        let long = "é".repeat(MAX_PANIC_MESSAGE_LEN);
+        let report = PanicReport::new(&long, None);
+        assert_eq!(report.class, PanicClass::Other);
+        assert_eq!(report.message.len(), MAX_PANIC_MESSAGE_LEN);
+        assert_eq!(report.to_string(), format!("[panic] {}", report.message));
+    }
+}
@@ -0,0 +1,242 @@
+use fvm_shared::econ::TokenAmount;
+
+/// Maximum nesting of arrays and maps accepted in method parameters.
+pub const MAX_PARAMS_DEPTH: u32 = 64;
+
+/// Maximum number of elements of a single array or map accepted in method parameters.
+pub const MAX_PARAMS_COLLECTION_LEN: u64 = 1 << 16;
+
+/// Maximum number of cross messages in a single batch.
+pub const MAX_CROSS_MSGS_PER_BATCH: u64 = 1 << 10;
+
+/// Maximum size in bytes of the parameters of a cross message.
+pub const MAX_CROSS_MSG_PARAMS_SIZE: u64 = 1 << 16;
+
+/// Maximum depth of a subnet below the root that cross messages can be addressed to.
+pub const MAX_SUBNET_DEPTH: u64 = 16;
+
+/// Gas charged for writing an entry of a collection of the state.
+pub const PERSIST_ENTRY_GAS: i64 = 1_000;
+
+/// Gas charged for reading an entry of a collection of the state.
+pub const LOAD_ENTRY_GAS: i64 = 300;
+
+/// Gas charged for removing an entry of a collection of the state.
+pub const REMOVE_ENTRY_GAS: i64 = 500;
+
+/// Gas charged for each cross message processed.
+pub const CROSS_MSG_GAS: i64 = 2_000;
+
+/// An amount of gas charged under a name, as passed to `Runtime::charge`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasCharge {
+    pub name: &'static str,
+    pub compute: i64,
+}
+
+impl GasCharge {
+    pub const fn new(name: &'static str, compute: i64) -> Self {
+        Self { name, compute }
+    }
+}
+
+/// The gas charged by actors on top of the one the FVM charges, so that it's defined in one
+/// place and can be tuned on network upgrades rather than being hardcoded in the methods.
+///
+/// It is part of the `Policy`, so an actor on chain charges a schedule other than the default
+/// by setting it in the `configure` closure of `trampoline_with`, e.g.
+/// `rt.with_policy(Policy::default().with_gas_schedule(schedule))`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasSchedule {
+    pub persist_entry: GasCharge,
+    pub load_entry: GasCharge,
+    pub remove_entry: GasCharge,
+    pub cross_msg: GasCharge,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            persist_entry: GasCharge::new("persist_entry", PERSIST_ENTRY_GAS),
+            load_entry: GasCharge::new("load_entry", LOAD_ENTRY_GAS),
+            remove_entry: GasCharge::new("remove_entry", REMOVE_ENTRY_GAS),
+            cross_msg: GasCharge::new("cross_msg", CROSS_MSG_GAS),
+        }
+    }
+}
+
+/// The networks with a preset `Policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Calibration,
+    Devnet,
+}
+
+/// The economic parameters of subnets and cross messages, which differ per network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Economics {
+    /// Minimum collateral a validator has to stake to join a subnet.
+    pub min_validator_collateral: TokenAmount,
+    /// Fee paid for every cross message, on top of the gas.
+    pub cross_msg_fee: TokenAmount,
+    /// Reward paid to the validator submitting a checkpoint.
+    pub checkpoint_reward: TokenAmount,
+}
+
+impl Economics {
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Mainnet => Self {
+                min_validator_collateral: TokenAmount::from_whole(10),
+                cross_msg_fee: TokenAmount::from_nano(100),
+                checkpoint_reward: TokenAmount::from_nano(1_000_000),
+            },
+            Network::Calibration => Self {
+                min_validator_collateral: TokenAmount::from_whole(1),
+                cross_msg_fee: TokenAmount::from_nano(100),
+                checkpoint_reward: TokenAmount::from_nano(1_000_000),
+            },
+            // Cheap enough for local testing with a handful of validators.
+            Network::Devnet => Self {
+                min_validator_collateral: TokenAmount::from_nano(1_000_000),
+                cross_msg_fee: TokenAmount::from_atto(0),
+                checkpoint_reward: TokenAmount::from_atto(0),
+            },
+        }
+    }
+}
+
+impl Default for Economics {
+    fn default() -> Self {
+        Self::for_network(Network::Mainnet)
+    }
+}
+
+/// Tunable limits and parameters enforced by the runtime and the shared components.
+///
+/// Both runtimes return it from `Runtime::policy`: the `MockRuntime` in its public `policy`
+/// field, and the `FvmRuntime` the one set with `FvmRuntime::with_policy`, typically in the
+/// `configure` closure of `trampoline_with`, or the default (mainnet) policy otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// Maximum nesting of arrays and maps accepted in method parameters.
+    pub max_params_depth: u32,
+    /// Maximum number of elements of a single array or map accepted in method parameters.
+    pub max_params_collection_len: u64,
+    /// Maximum number of cross messages in a single batch.
+    pub max_cross_msgs_per_batch: u64,
+    /// Maximum size in bytes of the parameters of a cross message.
+    pub max_cross_msg_params_size: u64,
+    /// Maximum depth of a subnet below the root that cross messages can be addressed to.
+    pub max_subnet_depth: u64,
+    /// Gas charged by actors for their operations.
+    pub gas: GasSchedule,
+    /// Collateral, fees and rewards.
+    pub economics: Economics,
+}
+
+impl Policy {
+    /// The policy of a network, which can be adjusted with the `with_` methods, e.g.
+    /// `Policy::for_network(Network::Devnet).with_cross_msg_fee(fee)`.
+    pub fn for_network(network: Network) -> Self {
+        Self {
+            economics: Economics::for_network(network),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_min_validator_collateral(mut self, amount: TokenAmount) -> Self {
+        self.economics.min_validator_collateral = amount;
+        self
+    }
+
+    pub fn with_cross_msg_fee(mut self, amount: TokenAmount) -> Self {
+        self.economics.cross_msg_fee = amount;
+        self
+    }
+
+    pub fn with_checkpoint_reward(mut self, amount: TokenAmount) -> Self {
+        self.economics.checkpoint_reward = amount;
+        self
+    }
+
+    pub fn with_gas_schedule(mut self, gas: GasSchedule) -> Self {
+        self.gas = gas;
+        self
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_params_depth: MAX_PARAMS_DEPTH,
+            max_params_collection_len: MAX_PARAMS_COLLECTION_LEN,
+            max_cross_msgs_per_batch: MAX_CROSS_MSGS_PER_BATCH,
+            max_cross_msg_params_size: MAX_CROSS_MSG_PARAMS_SIZE,
+            max_subnet_depth: MAX_SUBNET_DEPTH,
+            gas: GasSchedule::default(),
+            economics: Economics::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::econ::TokenAmount;
+
+    use super::{Economics, GasCharge, GasSchedule, Network, Policy};
+
+    #[test]
+    fn network_presets() {
+        assert_eq!(Policy::default(), Policy::for_network(Network::Mainnet));
+        assert_eq!(
+            Economics::default(),
+            Economics::for_network(Network::Mainnet)
+        );
+
+        let mainnet = Economics::for_network(Network::Mainnet);
+        let calibration = Economics::for_network(Network::Calibration);
+        let devnet = Economics::for_network(Network::Devnet);
+        assert!(calibration.min_validator_collateral < mainnet.min_validator_collateral);
+        assert!(devnet.min_validator_collateral < calibration.min_validator_collateral);
+        assert!(devnet.cross_msg_fee.is_zero());
+        assert!(devnet.checkpoint_reward.is_zero());
+
+        // Only the economics differ between networks.
+        let policy = Policy::for_network(Network::Devnet);
+        assert_eq!(policy.economics, devnet);
+        assert_eq!(
+            Policy {
+                economics: mainnet,
+                ..policy
+            },
+            Policy::default()
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let policy = Policy::for_network(Network::Devnet)
+            .with_min_validator_collateral(TokenAmount::from_whole(2))
+            .with_cross_msg_fee(TokenAmount::from_atto(3))
+            .with_checkpoint_reward(TokenAmount::from_atto(4));
+        assert_eq!(
+            policy.economics,
+            Economics {
+                min_validator_collateral: TokenAmount::from_whole(2),
+                cross_msg_fee: TokenAmount::from_atto(3),
+                checkpoint_reward: TokenAmount::from_atto(4),
+            }
+        );
+        assert_eq!(policy.gas, Policy::default().gas);
+
+        let gas = GasSchedule {
+            persist_entry: GasCharge::new("persist_entry", 1),
+            ..Default::default()
+        };
+        let policy = policy.with_gas_schedule(gas);
+        assert_eq!(policy.gas.persist_entry.compute, 1);
+        assert_eq!(policy.economics.cross_msg_fee, TokenAmount::from_atto(3));
+    }
+}
@@ -4,32 +4,51 @@
 use core::fmt;
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
 
 use cid::multihash::{Code, Multihash as OtherMultihash};
 use cid::Cid;
+use fvm_ipld_blockstore::tracking::{BSStats, TrackingBlockstore};
 use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
 use fvm_ipld_encoding::de::DeserializeOwned;
 use fvm_ipld_encoding::ipld_block::IpldBlock;
-use fvm_ipld_encoding::CborStore;
+use fvm_ipld_encoding::{CborStore, RawBytes};
 use fvm_shared::address::{Address, Protocol};
+use fvm_shared::chainid::ChainID;
 use fvm_shared::clock::ChainEpoch;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use fvm_shared::commcid::{FIL_COMMITMENT_SEALED, FIL_COMMITMENT_UNSEALED};
 use fvm_shared::crypto::signature::Signature;
 use fvm_shared::econ::TokenAmount;
-use fvm_shared::error::ExitCode;
+use fvm_shared::error::{ErrorNumber, ExitCode};
+use fvm_shared::event::ActorEvent;
 use fvm_shared::version::NetworkVersion;
 use fvm_shared::{ActorID, MethodNum};
 
 use multihash::derive::Multihash;
 use multihash::MultihashDigest;
+use num_traits::ToPrimitive;
 
 use rand::prelude::*;
 
-use crate::runtime::{ActorCode, MessageInfo, Primitives, Runtime};
-use crate::{actor_error, ActorError, Type};
+use crate::builtin::types::{
+    InitExec4Params, InitExecParams, InitExecReturn, INIT_EXEC4_METHOD_NUM, INIT_EXEC_METHOD_NUM,
+};
+use crate::runtime::{
+    ActorCode, GasCharge, MessageInfo, Policy, Primitives, Runtime, StateInvariants,
+};
+use crate::util::cbor::{block_from_raw_bytes, normalize_params, CborBlock};
+use crate::util::{parse_fil, ActorHandle};
+use crate::{
+    actor_error, delegated_subaddress, ActorError, EventSchema, Type, EAM_ACTOR_ID, INIT_ACTOR_ADDR,
+};
+
+pub mod run;
+pub mod wasm_size;
 
 type Func = dyn Fn(&[u8]) -> [u8; 32];
 
@@ -90,13 +109,18 @@ pub struct MockRuntime<BS = MemoryBlockstore> {
     pub base_fee: TokenAmount,
     pub id_addresses: HashMap<Address, Address>,
     pub actor_code_cids: HashMap<Address, Cid>,
+    pub delegated_addresses: HashMap<ActorID, Address>,
     pub new_actor_addr: Option<Address>,
     pub receiver: Address,
     pub caller: Address,
     pub caller_type: Cid,
     pub value_received: TokenAmount,
+    pub origin: Address,
+    pub gas_premium: TokenAmount,
     pub hash_func: Box<Func>,
     pub network_version: NetworkVersion,
+    pub chain_id: ChainID,
+    pub policy: Policy,
 
     // Actor State
     pub state: Option<Cid>,
@@ -105,12 +129,223 @@ pub struct MockRuntime<BS = MemoryBlockstore> {
     // VM Impl
     pub in_call: bool,
     pub store: Rc<BS>,
+    /// Reads the counts of a tracked store, for `expect_max_ipld_ops`; see `new_tracked`.
+    pub store_stats: Option<Box<dyn Fn() -> BSStats>>,
     pub in_transaction: bool,
+    /// Checked on the state by every transaction, if set.
+    pub state_invariants: Option<StateInvariants>,
 
     // Expectations
     pub expectations: RefCell<Expectations>,
 
     pub circulating_supply: TokenAmount,
+
+    // Events
+    /// If set, the events each method may emit; emitting any other one fails the test.
+    pub declared_events: Option<HashMap<MethodNum, Vec<EventSchema>>>,
+    /// The method being called, if any.
+    pub method: Option<MethodNum>,
+
+    // Tracing
+    /// If set, every `call` appends its `CallTrace` to this file as a line of JSON.
+    pub trace_file: Option<PathBuf>,
+    /// Turns the parameters and return values in the traces into JSON.
+    /// By default they are rendered as hex encoded bytes.
+    pub trace_decoder: Option<Box<TraceDecoder>>,
+    /// The trace of the call in progress.
+    pub trace: RefCell<Option<CallTrace>>,
+    /// If set, every `call` appends its `MessageFixture` to this file as a line of JSON.
+    pub fixture_file: Option<PathBuf>,
+
+    // Persistence
+    /// If set, the root of the state is written to this file after every `call`, and whenever
+    /// the state is replaced or restored, so that the state in a `DiskBlockstore` can still be
+    /// found after a failing test.
+    pub head_file: Option<PathBuf>,
+
+    // Simulation
+    /// If set, caller validations, sends, actor creation and deletion, signature verifications,
+    /// gas charges and events are accepted as they come, rather than checked against
+    /// expectations, for running methods outside of tests; see `run`. Sends succeed without
+    /// returning anything, and signatures are valid.
+    pub simulate: bool,
+
+    // Syscall errors
+    /// The errors the next reads of the state root fail with, in order, as the `sself::root`
+    /// syscall would; see `MockRuntime::fail_state_read`.
+    pub state_read_errors: RefCell<VecDeque<ErrorNumber>>,
+    /// The errors the next writes of the state root fail with, in order, as the
+    /// `sself::set_root` syscall would; see `MockRuntime::fail_state_write`.
+    pub state_write_errors: RefCell<VecDeque<ErrorNumber>>,
+}
+
+/// A `Blockstore` keeping every block in a file of its own, named after its CID, so that the
+/// state of a test outlives it and can be inspected afterwards, e.g. with a
+/// `FetchingBlockstore` reading from the same directory. See `MockRuntime::on_disk`.
+pub struct DiskBlockstore {
+    dir: PathBuf,
+}
+
+impl DiskBlockstore {
+    /// Opens the store in `dir`, creating it if necessary. Blocks already in it are kept.
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Blockstore for DiskBlockstore {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.dir.join(k.to_string())) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        Ok(std::fs::write(self.dir.join(k.to_string()), block)?)
+    }
+}
+
+/// Decodes the parameters (if `is_return` is false) or return value of a method.
+pub type TraceDecoder = dyn Fn(MethodNum, bool, &IpldBlock) -> serde_json::Value;
+
+/// What happened during a `MockRuntime::call`, for diffing or visualization by external tools.
+///
+/// Traces can be read back with `read_all`, compared with `diff` to review how the behaviour
+/// of an actor changed, and turned into expectations with `Expectations::from_trace`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CallTrace {
+    pub method: MethodNum,
+    pub caller: String,
+    pub receiver: String,
+    pub value: String,
+    pub params: serde_json::Value,
+    pub validations: Vec<String>,
+    pub sends: Vec<SendTrace>,
+    pub gas_charges: Vec<GasChargeTrace>,
+    pub state_before: Option<String>,
+    pub state_after: Option<String>,
+    pub exit_code: u32,
+    pub ret: serde_json::Value,
+}
+
+/// The message a `MockRuntime::call` exercised, for replaying it on a devnet, e.g. with
+/// `lotus send --from <from> --method <method> --params-hex <params> <to> <value>`.
+///
+/// The addresses are the ones of the test, so they usually have to be mapped to actors
+/// deployed on the devnet before replaying.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MessageFixture {
+    pub from: String,
+    pub to: String,
+    pub method: MethodNum,
+    /// The CBOR encoded parameters in hex, empty if there are none.
+    pub params: String,
+    /// The value in FIL.
+    pub value: String,
+    /// The exit code the test got, to check the replay against.
+    pub exit_code: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SendTrace {
+    pub to: String,
+    pub method: MethodNum,
+    pub params: serde_json::Value,
+    pub value: String,
+    pub exit_code: u32,
+    /// What the send returned.
+    #[serde(default)]
+    pub ret: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GasChargeTrace {
+    pub name: String,
+    pub value: i64,
+}
+
+impl CallTrace {
+    /// Read the traces written by `MockRuntime::trace_to`, in the order of the calls.
+    pub fn read_all(path: impl AsRef<Path>) -> anyhow::Result<Vec<CallTrace>> {
+        let content = std::fs::read_to_string(path)?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// The differences from another trace of the same call, usually recorded before a change,
+    /// as `path: old -> new` lines, e.g. `sends[0].value: "1" -> "2"`. Empty if they're the same.
+    pub fn diff(&self, new: &CallTrace) -> Vec<String> {
+        let mut diffs = Vec::new();
+        let old = serde_json::to_value(self).expect("failed to serialize trace");
+        let new = serde_json::to_value(new).expect("failed to serialize trace");
+        diff_json("", &old, &new, &mut diffs);
+        diffs
+    }
+}
+
+/// The differences between two runs of a test, call by call, as with `CallTrace::diff`,
+/// e.g. to print for review when re-recording its traces after an intended change.
+pub fn diff_traces(old: &[CallTrace], new: &[CallTrace]) -> Vec<String> {
+    let mut diffs = Vec::new();
+    for i in 0..old.len().max(new.len()) {
+        match (old.get(i), new.get(i)) {
+            (Some(old), Some(new)) => {
+                diffs.extend(old.diff(new).into_iter().map(|d| format!("call {i}: {d}")))
+            }
+            (Some(old), None) => diffs.push(format!("call {i}: method {} removed", old.method)),
+            (None, Some(new)) => diffs.push(format!("call {i}: method {} added", new.method)),
+            (None, None) => unreachable!(),
+        }
+    }
+    diffs
+}
+
+fn diff_json(path: &str, old: &serde_json::Value, new: &serde_json::Value, out: &mut Vec<String>) {
+    use serde_json::Value;
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: std::collections::BTreeSet<_> = a.keys().chain(b.keys()).collect();
+            for k in keys {
+                let path = if path.is_empty() {
+                    k.to_string()
+                } else {
+                    format!("{path}.{k}")
+                };
+                let (a, b) = (a.get(k), b.get(k));
+                diff_json(
+                    &path,
+                    a.unwrap_or(&Value::Null),
+                    b.unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let (a, b) = (a.get(i), b.get(i));
+                let path = format!("{path}[{i}]");
+                diff_json(
+                    &path,
+                    a.unwrap_or(&Value::Null),
+                    b.unwrap_or(&Value::Null),
+                    out,
+                );
+            }
+        }
+        _ if old != new => out.push(format!("{path}: {old} -> {new}")),
+        _ => {}
+    }
 }
 
 impl<BS> MockRuntime<BS> {
@@ -121,82 +356,432 @@ impl<BS> MockRuntime<BS> {
             base_fee: Default::default(),
             id_addresses: Default::default(),
             actor_code_cids: Default::default(),
+            delegated_addresses: Default::default(),
             new_actor_addr: Default::default(),
             receiver: Address::new_id(0),
             caller: Address::new_id(0),
             caller_type: Default::default(),
             value_received: Default::default(),
+            origin: Address::new_id(0),
+            gas_premium: Default::default(),
             hash_func: Box::new(blake2b_256),
             network_version: NetworkVersion::V0,
+            chain_id: ChainID::from(0),
+            policy: Default::default(),
             state: Default::default(),
             balance: Default::default(),
             in_call: Default::default(),
             store: Rc::new(store),
+            store_stats: None,
             in_transaction: Default::default(),
+            state_invariants: None,
             expectations: Default::default(),
             circulating_supply: Default::default(),
+            declared_events: None,
+            method: None,
+            trace_file: None,
+            trace_decoder: None,
+            trace: Default::default(),
+            fixture_file: None,
+            head_file: None,
+            simulate: false,
+            state_read_errors: Default::default(),
+            state_write_errors: Default::default(),
+        }
+    }
+
+    /// Read the state root, failing like `FvmRuntime` would if a read error was injected.
+    fn sself_root(&self) -> Result<(), ActorError> {
+        match self.state_read_errors.borrow_mut().pop_front() {
+            None => Ok(()),
+            Some(ErrorNumber::IllegalOperation) => {
+                Err(actor_error!(illegal_state; "actor has been deleted"))
+            }
+            Some(e) => panic!("unexpected error from `self::root` syscall: {e}"),
+        }
+    }
+
+    /// Write the state root, failing like `FvmRuntime` would if a write error was injected.
+    fn sself_set_root(&self) -> Result<(), ActorError> {
+        match self.state_write_errors.borrow_mut().pop_front() {
+            None => Ok(()),
+            Some(ErrorNumber::IllegalOperation) => {
+                Err(actor_error!(illegal_state; "actor has been deleted"))
+            }
+            Some(ErrorNumber::ReadOnly) => Err(ActorError::unchecked(
+                ExitCode::USR_READ_ONLY,
+                "current execution context is read-only".into(),
+            )),
+            Some(e) => panic!("unexpected error from `self::set_root` syscall: {e}"),
+        }
+    }
+
+    /// When simulating, expect whatever the actor is about to do.
+    fn expect_in_simulation(&self, f: impl FnOnce(&mut Expectations)) {
+        if self.simulate {
+            f(&mut self.expectations.borrow_mut())
         }
     }
 }
 
-#[derive(Default)]
+/// What the caller of a method accepting any caller is expected to satisfy.
+pub type CallerPredicate = dyn Fn(&Address) -> bool;
+
+#[derive(Default, Clone)]
 pub struct Expectations {
     pub expect_validate_caller_any: bool,
+    /// What the caller has to satisfy when validating any; see
+    /// `MockRuntime::expect_validate_caller_any_with`.
+    pub expect_validate_caller_any_with: Option<Rc<CallerPredicate>>,
     pub expect_validate_caller_addr: Option<Vec<Address>>,
     pub expect_validate_caller_type: Option<Vec<Cid>>,
     pub expect_validate_caller_not_type: Option<Vec<Cid>>,
+    pub expect_validate_caller_eth: Option<[u8; 20]>,
+    pub expect_validate_caller_namespace: Option<ActorID>,
     pub expect_sends: VecDeque<ExpectedMessage>,
     pub expect_create_actor: Option<ExpectCreateActor>,
     pub expect_delete_actor: Option<Address>,
     pub expect_verify_sigs: VecDeque<ExpectedVerifySig>,
-    pub expect_gas_charge: VecDeque<i64>,
+    pub expect_gas_charge: VecDeque<ExpectGasCharge>,
+    pub expect_emitted_events: VecDeque<ActorEvent>,
+    pub expect_state_transition: Option<ExpectStateTransition>,
+    pub expect_create_state: Option<Rc<StateCreation>>,
+
+    /// The label given to the expectations being added; see `MockRuntime::expect_group`.
+    pub group: Option<String>,
+    /// The labels of the pending expectations of each kind, in the order they are to be met.
+    pub groups: BTreeMap<ExpectationKind, VecDeque<Option<String>>>,
+
+    /// The most blocks each call may read and write; see `MockRuntime::expect_max_ipld_ops`.
+    pub max_ipld_ops: Option<(usize, usize)>,
+    /// The most bytes each call may write; see `MockRuntime::expect_max_state_bytes`.
+    pub max_state_bytes: Option<usize>,
+    /// The blockstore operations of each call made since the last `verify`.
+    pub ipld_stats: Vec<(MethodNum, BSStats)>,
+}
+
+/// The kinds of mock expectations, for grouping them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExpectationKind {
+    ValidateCallerAny,
+    ValidateCallerAddr,
+    ValidateCallerType,
+    ValidateCallerNotType,
+    ValidateCallerEth,
+    ValidateCallerNamespace,
+    Send,
+    CreateActor,
+    DeleteActor,
+    VerifySig,
+    GasCharge,
+    EmittedEvent,
+    StateTransition,
+    CreateState,
+}
+
+impl ExpectationKind {
+    const ALL: [ExpectationKind; 14] = [
+        Self::ValidateCallerAny,
+        Self::ValidateCallerAddr,
+        Self::ValidateCallerType,
+        Self::ValidateCallerNotType,
+        Self::ValidateCallerEth,
+        Self::ValidateCallerNamespace,
+        Self::Send,
+        Self::CreateActor,
+        Self::DeleteActor,
+        Self::VerifySig,
+        Self::GasCharge,
+        Self::EmittedEvent,
+        Self::StateTransition,
+        Self::CreateState,
+    ];
+
+    /// Whether expectations of this kind queue up, rather than replace each other.
+    fn is_queued(&self) -> bool {
+        matches!(
+            self,
+            Self::Send | Self::VerifySig | Self::GasCharge | Self::EmittedEvent
+        )
+    }
 }
 
 impl Expectations {
+    /// Expectations for the sends and gas charges of a recorded call, so that they can be
+    /// re-recorded with `MockRuntime::trace_to` when the behaviour of the actor changes
+    /// intentionally, rather than edited by hand. Caller validations, which are set up along
+    /// with the caller, aren't included.
+    ///
+    /// Parameters and return values are taken to be CBOR blocks, as made by
+    /// `IpldBlock::from_cbor`, so the trace must be recorded without a `trace_decoder`.
+    ///
+    /// ```ignore
+    /// let traces = CallTrace::read_all("tests/traces/submit.jsonl")?;
+    /// *rt.expectations.get_mut() = Expectations::from_trace(&traces[0])?;
+    /// rt.call::<Actor>(Method::Submit as u64, params)?;
+    /// rt.verify();
+    /// ```
+    pub fn from_trace(trace: &CallTrace) -> anyhow::Result<Self> {
+        let block = |v: &serde_json::Value| -> anyhow::Result<Option<IpldBlock>> {
+            match v {
+                serde_json::Value::Null => Ok(None),
+                serde_json::Value::String(data) => {
+                    Ok(block_from_raw_bytes(RawBytes::new(hex::decode(data)?)))
+                }
+                other => Err(anyhow::anyhow!("expected a hex encoded block, got {other}")),
+            }
+        };
+
+        let mut expectations = Expectations::default();
+        for send in &trace.sends {
+            expectations.expect_sends.push_back(ExpectedMessage {
+                to: Address::from_str(&send.to)?,
+                method: send.method,
+                params: block(&send.params)?,
+                value: parse_fil(&send.value)?,
+                send_return: block(&send.ret)?,
+                exit_code: ExitCode::new(send.exit_code),
+            });
+            expectations.label(ExpectationKind::Send);
+        }
+        for charge in &trace.gas_charges {
+            expectations.expect_gas_charge.push_back(ExpectGasCharge {
+                name: Some(charge.name.clone()),
+                value: charge.value,
+            });
+            expectations.label(ExpectationKind::GasCharge);
+        }
+        Ok(expectations)
+    }
+
     fn reset(&mut self) {
         *self = Default::default();
     }
 
+    /// Label the expectation of the given kind just added with the current group.
+    fn label(&mut self, kind: ExpectationKind) {
+        let labels = self.groups.entry(kind).or_default();
+        if !kind.is_queued() {
+            labels.clear();
+        }
+        labels.push_back(self.group.clone());
+    }
+
+    /// Drop the label of the queued expectation of the given kind just met.
+    fn met(&mut self, kind: ExpectationKind) {
+        if let Some(labels) = self.groups.get_mut(&kind) {
+            labels.pop_front();
+        }
+    }
+
+    /// Number of expectations of the given kind not met yet.
+    fn pending(&self, kind: ExpectationKind) -> usize {
+        use ExpectationKind::*;
+        match kind {
+            ValidateCallerAny => self.expect_validate_caller_any as usize,
+            ValidateCallerAddr => self.expect_validate_caller_addr.is_some() as usize,
+            ValidateCallerType => self.expect_validate_caller_type.is_some() as usize,
+            ValidateCallerNotType => self.expect_validate_caller_not_type.is_some() as usize,
+            ValidateCallerEth => self.expect_validate_caller_eth.is_some() as usize,
+            ValidateCallerNamespace => self.expect_validate_caller_namespace.is_some() as usize,
+            Send => self.expect_sends.len(),
+            CreateActor => self.expect_create_actor.is_some() as usize,
+            DeleteActor => self.expect_delete_actor.is_some() as usize,
+            VerifySig => self.expect_verify_sigs.len(),
+            GasCharge => self.expect_gas_charge.len(),
+            EmittedEvent => self.expect_emitted_events.len(),
+            StateTransition => self.expect_state_transition.is_some() as usize,
+            CreateState => self.expect_create_state.is_some() as usize,
+        }
+    }
+
+    /// The labels of the pending expectations of the given kind.
+    fn pending_labels(&self, kind: ExpectationKind) -> impl Iterator<Item = Option<&str>> {
+        let pending = self.pending(kind);
+        let labels = self.groups.get(&kind).into_iter().flatten();
+        labels.map(Option::as_deref).take(pending)
+    }
+
+    /// Describe the group of the first pending expectation of the given kind, if it has one.
+    fn group_of(&self, kind: ExpectationKind) -> String {
+        match self.pending_labels(kind).next().flatten() {
+            Some(label) => format!(" (group \"{label}\")"),
+            None => String::new(),
+        }
+    }
+
+    fn verify_group(&self, label: &str) {
+        let unmet: Vec<String> = ExpectationKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let n = self
+                    .pending_labels(*kind)
+                    .filter(|l| *l == Some(label))
+                    .count();
+                (n > 0).then(|| format!("{n} {kind:?}"))
+            })
+            .collect();
+        assert!(
+            unmet.is_empty(),
+            "expectations of group \"{label}\" not met: {}",
+            unmet.join(", ")
+        );
+    }
+
+    fn reset_group(&mut self, label: &str) {
+        use ExpectationKind::*;
+        for kind in ExpectationKind::ALL {
+            let Some(labels) = self.groups.get_mut(&kind) else {
+                continue;
+            };
+            let in_group: Vec<bool> = labels.iter().map(|l| l.as_deref() == Some(label)).collect();
+            labels.retain(|l| l.as_deref() != Some(label));
+            if !in_group.contains(&true) {
+                continue;
+            }
+            match kind {
+                ValidateCallerAny => {
+                    self.expect_validate_caller_any = false;
+                    self.expect_validate_caller_any_with = None;
+                }
+                ValidateCallerAddr => self.expect_validate_caller_addr = None,
+                ValidateCallerType => self.expect_validate_caller_type = None,
+                ValidateCallerNotType => self.expect_validate_caller_not_type = None,
+                ValidateCallerEth => self.expect_validate_caller_eth = None,
+                ValidateCallerNamespace => self.expect_validate_caller_namespace = None,
+                Send => retain_ungrouped(&mut self.expect_sends, &in_group),
+                CreateActor => self.expect_create_actor = None,
+                DeleteActor => self.expect_delete_actor = None,
+                VerifySig => retain_ungrouped(&mut self.expect_verify_sigs, &in_group),
+                GasCharge => retain_ungrouped(&mut self.expect_gas_charge, &in_group),
+                EmittedEvent => retain_ungrouped(&mut self.expect_emitted_events, &in_group),
+                StateTransition => self.expect_state_transition = None,
+                CreateState => self.expect_create_state = None,
+            }
+        }
+    }
+
     fn verify(&mut self) {
         assert!(
             !self.expect_validate_caller_any,
-            "expected ValidateCallerAny, not received"
+            "expected ValidateCallerAny, not received{}",
+            self.group_of(ExpectationKind::ValidateCallerAny)
         );
         assert!(
             self.expect_validate_caller_addr.is_none(),
-            "expected ValidateCallerAddr {:?}, not received",
-            self.expect_validate_caller_addr
+            "expected ValidateCallerAddr {:?}, not received{}",
+            self.expect_validate_caller_addr,
+            self.group_of(ExpectationKind::ValidateCallerAddr)
         );
         assert!(
             self.expect_validate_caller_type.is_none(),
-            "expected ValidateCallerType {:?}, not received",
-            self.expect_validate_caller_type
+            "expected ValidateCallerType {:?}, not received{}",
+            self.expect_validate_caller_type,
+            self.group_of(ExpectationKind::ValidateCallerType)
+        );
+        assert!(
+            self.expect_validate_caller_not_type.is_none(),
+            "expected ValidateCallerNotType {:?}, not received{}",
+            self.expect_validate_caller_not_type,
+            self.group_of(ExpectationKind::ValidateCallerNotType)
+        );
+        assert!(
+            self.expect_validate_caller_eth.is_none(),
+            "expected ValidateCallerEth {:?}, not received{}",
+            self.expect_validate_caller_eth,
+            self.group_of(ExpectationKind::ValidateCallerEth)
+        );
+        assert!(
+            self.expect_validate_caller_namespace.is_none(),
+            "expected ValidateCallerNamespace {:?}, not received{}",
+            self.expect_validate_caller_namespace,
+            self.group_of(ExpectationKind::ValidateCallerNamespace)
         );
         assert!(
             self.expect_sends.is_empty(),
-            "expected all message to be send, unsent messages {:?}",
-            self.expect_sends
+            "expected all message to be send, unsent messages {:?}{}",
+            self.expect_sends,
+            self.group_of(ExpectationKind::Send)
         );
         assert!(
             self.expect_create_actor.is_none(),
-            "expected actor to be created, uncreated actor: {:?}",
-            self.expect_create_actor
+            "expected actor to be created, uncreated actor: {:?}{}",
+            self.expect_create_actor,
+            self.group_of(ExpectationKind::CreateActor)
         );
         assert!(
             self.expect_delete_actor.is_none(),
-            "expected actor to be deleted: {:?}",
-            self.expect_delete_actor
+            "expected actor to be deleted: {:?}{}",
+            self.expect_delete_actor,
+            self.group_of(ExpectationKind::DeleteActor)
         );
         assert!(
             self.expect_verify_sigs.is_empty(),
-            "expect_verify_sigs: {:?}, not received",
-            self.expect_verify_sigs
+            "expect_verify_sigs: {:?}, not received{}",
+            self.expect_verify_sigs,
+            self.group_of(ExpectationKind::VerifySig)
         );
         assert!(
             self.expect_gas_charge.is_empty(),
-            "expect_gas_charge {:?}, not received",
-            self.expect_gas_charge
+            "expect_gas_charge {:?}, not received{}",
+            self.expect_gas_charge,
+            self.group_of(ExpectationKind::GasCharge)
+        );
+        assert!(
+            self.expect_emitted_events.is_empty(),
+            "expect_emitted_events {:?}, not received{}",
+            self.expect_emitted_events,
+            self.group_of(ExpectationKind::EmittedEvent)
+        );
+        let group = self.group_of(ExpectationKind::StateTransition);
+        if let Some(transition) = self.expect_state_transition.take() {
+            match (transition.before, transition.after) {
+                (Some(Some(before)), Some(Some(after))) => (transition.check)(&before, &after),
+                // There is no transition to check without a state on either side of the call.
+                (Some(_), Some(_)) => {}
+                _ => panic!("expected a call for the state transition, not received{group}"),
+            }
+        }
+        assert!(
+            self.expect_create_state.is_none(),
+            "expected the state to be created, not received{}",
+            self.group_of(ExpectationKind::CreateState)
         );
+        for (method, stats) in std::mem::take(&mut self.ipld_stats) {
+            if let Some((reads, writes)) = self.max_ipld_ops {
+                assert!(
+                    stats.r <= reads && stats.w <= writes,
+                    "call to method {method} made {} reads and {} writes, expected at most {reads} and {writes}",
+                    stats.r,
+                    stats.w
+                );
+            }
+            if let Some(bytes) = self.max_state_bytes {
+                assert!(
+                    stats.bw <= bytes,
+                    "call to method {method} wrote {} bytes, expected at most {bytes}",
+                    stats.bw
+                );
+            }
+        }
+    }
+}
+
+/// Keep the elements of a queue of expectations not in the group being reset.
+fn retain_ungrouped<T>(queue: &mut VecDeque<T>, in_group: &[bool]) {
+    let mut in_group = in_group.iter();
+    queue.retain(|_| !in_group.next().copied().unwrap_or_default());
+}
+
+impl MockRuntime<DiskBlockstore> {
+    /// A runtime keeping its blocks in `dir`, and the root of the state in `dir/HEAD`,
+    /// for debugging a test by inspecting the state it left behind.
+    pub fn on_disk(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let store = DiskBlockstore::new(dir)?;
+        let head_file = store.dir().join("HEAD");
+        let mut rt = Self::new(store);
+        rt.head_file = Some(head_file);
+        Ok(rt)
     }
 }
 
@@ -208,20 +793,37 @@ impl Default for MockRuntime {
             base_fee: Default::default(),
             id_addresses: Default::default(),
             actor_code_cids: Default::default(),
+            delegated_addresses: Default::default(),
             new_actor_addr: Default::default(),
             receiver: Address::new_id(0),
             caller: Address::new_id(0),
             caller_type: Default::default(),
             value_received: Default::default(),
+            origin: Address::new_id(0),
+            gas_premium: Default::default(),
             hash_func: Box::new(blake2b_256),
             network_version: NetworkVersion::V0,
+            chain_id: ChainID::from(0),
+            policy: Default::default(),
             state: Default::default(),
             balance: Default::default(),
             in_call: Default::default(),
             store: Default::default(),
+            store_stats: None,
             in_transaction: Default::default(),
+            state_invariants: None,
             expectations: Default::default(),
             circulating_supply: Default::default(),
+            declared_events: None,
+            method: None,
+            trace_file: None,
+            trace_decoder: None,
+            trace: Default::default(),
+            fixture_file: None,
+            head_file: None,
+            simulate: false,
+            state_read_errors: Default::default(),
+            state_write_errors: Default::default(),
         }
     }
 }
@@ -232,6 +834,49 @@ pub struct ExpectCreateActor {
     pub actor_id: ActorID,
 }
 
+#[derive(Clone, Debug)]
+pub struct ExpectGasCharge {
+    /// The pattern the name of the charge must match, where `*` stands for any characters,
+    /// or `None` to accept any name.
+    pub name: Option<String>,
+    pub value: i64,
+}
+
+impl ExpectGasCharge {
+    fn matches(&self, name: &str, value: i64) -> bool {
+        let name_matches = match &self.name {
+            Some(pattern) => glob_match(pattern, name),
+            None => true,
+        };
+        self.value == value && name_matches
+    }
+}
+
+impl fmt::Display for ExpectGasCharge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name:?} of {}", self.value),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// Whether `s` matches `pattern`, where `*` matches any, possibly empty, run of characters.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix, rest)) => {
+            let Some(s) = s.strip_prefix(prefix) else {
+                return false;
+            };
+            s.char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(s.len()))
+                .any(|i| glob_match(rest, &s[i..]))
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ExpectedMessage {
     pub to: Address,
@@ -252,6 +897,43 @@ pub struct ExpectedVerifySig {
     pub result: Result<(), anyhow::Error>,
 }
 
+impl Clone for ExpectedVerifySig {
+    fn clone(&self) -> Self {
+        Self {
+            sig: self.sig.clone(),
+            signer: self.signer,
+            plaintext: self.plaintext.clone(),
+            // Errors aren't cloneable, only their message is kept.
+            result: match &self.result {
+                Ok(()) => Ok(()),
+                Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+            },
+        }
+    }
+}
+
+/// A point to return to with `MockRuntime::restore`, taken by `MockRuntime::snapshot`.
+pub struct Snapshot {
+    state: Option<Cid>,
+    balance: TokenAmount,
+    expectations: Expectations,
+}
+
+type StateCheck = dyn Fn(&[u8], &[u8]);
+
+/// The check of the state given to `create`, set up by `expect_create_state`.
+pub type StateCreation = dyn Fn(&[u8]);
+
+/// The check of the state before and after a call, set up by `expect_state_transition`.
+/// The states are captured as encoded bytes, if the actor has any, and decoded when the check
+/// runs.
+#[derive(Clone)]
+pub struct ExpectStateTransition {
+    check: Rc<StateCheck>,
+    before: Option<Option<Vec<u8>>>,
+    after: Option<Option<Vec<u8>>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ExpectRandomness {}
 
@@ -286,6 +968,147 @@ pub fn expect_abort<T: fmt::Debug>(exit_code: ExitCode, res: Result<T, ActorErro
     expect_abort_contains_message(exit_code, "", res);
 }
 
+/// Call a method of `A` expecting it to fail with `code`, and assert that it left the state
+/// root, the content of the state block and the balance of the actor as they were, returning
+/// the error for further checks of its message.
+///
+/// The mock reverts the state root of a failed call like the FVM does, but not the value sent
+/// before the failure, so a changed balance means that the method sent funds before it checked
+/// everything that could make it fail.
+pub fn assert_call_fails_without_state_change<A: ActorCode, BS: Blockstore>(
+    rt: &mut MockRuntime<BS>,
+    method: MethodNum,
+    params: Option<IpldBlock>,
+    code: ExitCode,
+) -> ActorError {
+    let state = rt.state;
+    let block = |rt: &MockRuntime<BS>| state.map(|c| rt.store.get(&c).unwrap());
+    let before = block(rt);
+    let balance = rt.balance.borrow().clone();
+
+    let err = rt
+        .call::<A>(method, params)
+        .expect_err(&format!("expected method {method} to fail with {code}"));
+    assert_eq!(
+        err.exit_code(),
+        code,
+        "method {method} failed with {}, expected {code}: {}",
+        err.exit_code(),
+        err.msg()
+    );
+    assert_eq!(rt.state, state, "method {method} changed the state root");
+    assert_eq!(block(rt), before, "method {method} changed the state block");
+    assert_eq!(
+        *rt.balance.borrow(),
+        balance,
+        "method {method} changed the balance"
+    );
+    err
+}
+
+/// A case of `assert_all_exit_codes!`: how to prepare a fresh runtime, the parameters
+/// to call the method with, and the exit code it should return.
+pub struct ExitCodeCase {
+    pub setup: Box<dyn Fn(&mut MockRuntime)>,
+    pub params: Option<IpldBlock>,
+    pub expected: ExitCode,
+}
+
+/// Call a method of `A` on a fresh runtime for each case, asserting the exit code it returns,
+/// then fail if any of the `declared` exit codes, the ones the method can return, wasn't
+/// reached by a case. Expectations are verified for the successful calls only, as an aborted
+/// call stops short of the ones set up for the rest of the method.
+pub fn check_exit_codes<A: ActorCode>(
+    method: MethodNum,
+    declared: &[(&str, ExitCode)],
+    cases: Vec<ExitCodeCase>,
+) {
+    let mut reached = Vec::new();
+    for (i, case) in cases.into_iter().enumerate() {
+        let mut rt = MockRuntime::default();
+        (case.setup)(&mut rt);
+        let code = match rt.call::<A>(method, case.params) {
+            Ok(_) => ExitCode::OK,
+            Err(e) => e.exit_code(),
+        };
+        assert_eq!(
+            code, case.expected,
+            "case {i} of method {method} exited with {code}, expected {}",
+            case.expected
+        );
+        if code.is_success() {
+            rt.verify();
+        }
+        reached.push(code);
+    }
+
+    let unreached: Vec<String> = declared
+        .iter()
+        .filter(|(_, code)| !reached.contains(code))
+        .map(|(name, code)| format!("{name} ({code})"))
+        .collect();
+    assert!(
+        unreached.is_empty(),
+        "exit codes of method {method} not reached by any case: {}",
+        unreached.join(", ")
+    );
+}
+
+/// Drive a table of `(setup, params, expected exit code)` cases through `MockRuntime`
+/// for a method of an actor, and fail on the declared exit codes no case reached.
+///
+/// ```ignore
+/// assert_all_exit_codes!(Actor, Method::Join as MethodNum,
+///     declared: [ExitCode::USR_FORBIDDEN, USR_SUBNET_NOT_ACTIVE],
+///     cases: [
+///         (|rt: &mut MockRuntime| { ... }, params, ExitCode::OK),
+///         (|rt: &mut MockRuntime| { ... }, params, ExitCode::USR_FORBIDDEN),
+///         (|rt: &mut MockRuntime| { ... }, params, USR_SUBNET_NOT_ACTIVE),
+///     ]
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_all_exit_codes {
+    (
+        $actor:ty, $method:expr,
+        declared: [$($declared:expr),* $(,)?],
+        cases: [$(($setup:expr, $params:expr, $expected:expr)),* $(,)?] $(,)?
+    ) => {
+        $crate::test_utils::check_exit_codes::<$actor>(
+            $method,
+            &[$((stringify!($declared), $declared)),*],
+            vec![$($crate::test_utils::ExitCodeCase {
+                setup: Box::new($setup),
+                params: $params,
+                expected: $expected,
+            }),*],
+        )
+    };
+}
+
+impl<BS: Blockstore + 'static> MockRuntime<TrackingBlockstore<BS>> {
+    /// A runtime whose store counts the blocks and bytes each call reads and writes.
+    pub fn new_tracked(store: BS) -> Self {
+        let mut rt = Self::new(TrackingBlockstore::new(store));
+        let store = rt.store.clone();
+        rt.store_stats = Some(Box::new(move || *store.stats.borrow()));
+        rt
+    }
+
+    /// Expect every call to read at most `reads` blocks and write at most `writes` blocks,
+    /// e.g. to codify the complexity budget of hot methods. It's checked by `verify`, on the
+    /// calls made since the previous one, and holds until the expectations are reset.
+    pub fn expect_max_ipld_ops(&mut self, reads: usize, writes: usize) {
+        self.expectations.get_mut().max_ipld_ops = Some((reads, writes));
+    }
+
+    /// Expect every call to write at most `bytes` bytes to the store, checked like
+    /// `expect_max_ipld_ops`.
+    pub fn expect_max_state_bytes(&mut self, bytes: usize) {
+        self.expectations.get_mut().max_state_bytes = Some(bytes);
+    }
+}
+
 impl<BS: Blockstore> MockRuntime<BS> {
     ///// Runtime access for tests /////
 
@@ -295,6 +1118,20 @@ impl<BS: Blockstore> MockRuntime<BS> {
 
     pub fn replace_state<T: Serialize>(&mut self, obj: &T) {
         self.state = Some(self.store_put(obj));
+        self.write_head();
+    }
+
+    /// Make the next read of the state root by `create`, `state` or `transaction` fail with
+    /// the error of the `sself::root` syscall, e.g. `IllegalOperation` for a deleted actor.
+    /// Errors the syscall can't return panic, as they do in the SDK.
+    pub fn fail_state_read(&self, err: ErrorNumber) {
+        self.state_read_errors.borrow_mut().push_back(err);
+    }
+
+    /// Make the next write of the state root by `create` or the commit of a `transaction` fail
+    /// with the error of the `sself::set_root` syscall, e.g. `ReadOnly` in a read-only call.
+    pub fn fail_state_write(&self, err: ErrorNumber) {
+        self.state_write_errors.borrow_mut().push_back(err);
     }
 
     pub fn set_balance(&mut self, amount: TokenAmount) {
@@ -319,10 +1156,19 @@ impl<BS: Blockstore> MockRuntime<BS> {
         self.actor_code_cids.insert(address, code_id);
     }
 
+    pub fn set_delegated_address(&mut self, id: ActorID, address: Address) {
+        self.delegated_addresses.insert(id, address);
+    }
+
     pub fn set_address_actor_type(&mut self, address: Address, actor_type: Cid) {
         self.actor_code_cids.insert(address, actor_type);
     }
 
+    fn caller_delegated_address(&self) -> Option<Address> {
+        let id = self.caller.id().ok()?;
+        self.lookup_delegated_address(id)
+    }
+
     pub fn get_id_address(&self, address: &Address) -> Option<Address> {
         if address.protocol() == Protocol::ID {
             return Some(*address);
@@ -345,16 +1191,140 @@ impl<BS: Blockstore> MockRuntime<BS> {
         params: Option<IpldBlock>,
     ) -> Result<Option<IpldBlock>, ActorError> {
         self.in_call = true;
+        self.method = Some(method_num);
         let prev_state = self.state;
+        let prev_stats = self.store_stats.as_ref().map(|stats| stats());
+        self.capture_state(|t| &mut t.before);
+        let fixture = self.fixture_file.is_some().then(|| MessageFixture {
+            from: self.caller.to_string(),
+            to: self.receiver.to_string(),
+            method: method_num,
+            params: params
+                .as_ref()
+                .map(|p| hex::encode(&p.data))
+                .unwrap_or_default(),
+            value: self.value_received.to_string(),
+            exit_code: 0,
+        });
+        if self.trace_file.is_some() {
+            *self.trace.get_mut() = Some(CallTrace {
+                method: method_num,
+                caller: self.caller.to_string(),
+                receiver: self.receiver.to_string(),
+                value: self.value_received.to_string(),
+                params: self.trace_block(method_num, false, params.as_ref()),
+                state_before: self.state.map(|c| c.to_string()),
+                ..Default::default()
+            });
+        }
         let res = A::invoke_method(self, method_num, params);
 
         if res.is_err() {
             self.state = prev_state;
         }
         self.in_call = false;
+        self.method = None;
+        if let (Some(stats), Some(prev_stats)) = (&self.store_stats, prev_stats) {
+            let stats = stats();
+            self.expectations.get_mut().ipld_stats.push((
+                method_num,
+                BSStats {
+                    r: stats.r - prev_stats.r,
+                    w: stats.w - prev_stats.w,
+                    br: stats.br - prev_stats.br,
+                    bw: stats.bw - prev_stats.bw,
+                },
+            ));
+        }
+        self.capture_state(|t| &mut t.after);
+        self.write_head();
+        self.write_trace(method_num, &res);
+        if let Some(fixture) = fixture {
+            self.write_fixture(fixture, &res);
+        }
         res
     }
 
+    /// Enables writing the message of every `call` to a file as a `MessageFixture`, as JSON lines.
+    pub fn fixtures_to(&mut self, path: impl Into<PathBuf>) {
+        self.fixture_file = Some(path.into());
+    }
+
+    fn write_fixture(
+        &self,
+        mut fixture: MessageFixture,
+        res: &Result<Option<IpldBlock>, ActorError>,
+    ) {
+        let Some(path) = &self.fixture_file else {
+            return;
+        };
+        fixture.exit_code = match res {
+            Ok(_) => ExitCode::OK.value(),
+            Err(e) => e.exit_code().value(),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("failed to open fixture file");
+        let line = serde_json::to_string(&fixture).expect("failed to serialize fixture");
+        writeln!(file, "{line}").expect("failed to write fixture");
+    }
+
+    fn write_head(&self) {
+        if let (Some(path), Some(state)) = (&self.head_file, &self.state) {
+            std::fs::write(path, state.to_string()).expect("failed to write state root");
+        }
+    }
+
+    /// Enables writing a `CallTrace` of every `call` to a file, as JSON lines.
+    pub fn trace_to(&mut self, path: impl Into<PathBuf>) {
+        self.trace_file = Some(path.into());
+    }
+
+    fn trace_block(
+        &self,
+        method: MethodNum,
+        is_return: bool,
+        block: Option<&IpldBlock>,
+    ) -> serde_json::Value {
+        match (block, &self.trace_decoder) {
+            (None, _) => serde_json::Value::Null,
+            (Some(block), Some(decode)) => decode(method, is_return, block),
+            (Some(block), None) => serde_json::Value::String(hex::encode(&block.data)),
+        }
+    }
+
+    fn record_trace(&self, f: impl FnOnce(&mut CallTrace)) {
+        if let Some(trace) = self.trace.borrow_mut().as_mut() {
+            f(trace)
+        }
+    }
+
+    fn write_trace(&mut self, method: MethodNum, res: &Result<Option<IpldBlock>, ActorError>) {
+        let (Some(path), Some(mut trace)) = (&self.trace_file, self.trace.get_mut().take()) else {
+            return;
+        };
+        trace.state_after = self.state.map(|c| c.to_string());
+        match res {
+            Ok(ret) => {
+                trace.exit_code = ExitCode::OK.value();
+                trace.ret = self.trace_block(method, true, ret.as_ref());
+            }
+            Err(e) => {
+                trace.exit_code = e.exit_code().value();
+                trace.ret = serde_json::Value::String(e.msg().to_string());
+            }
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("failed to open trace file");
+        let line = serde_json::to_string(&trace).expect("failed to serialize trace");
+        writeln!(file, "{line}").expect("failed to write trace");
+    }
+
     /// Method to use when we need to call something in the test that requires interacting
     /// with the runtime in a read-only fashion, but it's not an actor invocation.
     pub fn call_fn<F, T>(&mut self, f: F) -> anyhow::Result<T>
@@ -377,46 +1347,116 @@ impl<BS: Blockstore> MockRuntime<BS> {
         self.expectations.borrow_mut().reset();
     }
 
+    /// Label the expectations added by `f`, e.g. with the phase of a scenario they belong to,
+    /// so that `verify` tells which group an unmet expectation is from, and the group can be
+    /// verified or reset on its own with `verify_group` and `reset_group`.
+    pub fn expect_group(&mut self, label: &str, f: impl FnOnce(&mut Self)) {
+        let outer = self.expectations.get_mut().group.replace(label.to_string());
+        f(self);
+        self.expectations.get_mut().group = outer;
+    }
+
+    /// Verifies that the expectations of a group have been met, regardless of the others.
+    pub fn verify_group(&mut self, label: &str) {
+        self.expectations.get_mut().verify_group(label)
+    }
+
+    /// Clears the pending expectations of a group, leaving the others.
+    pub fn reset_group(&mut self, label: &str) {
+        self.expectations.get_mut().reset_group(label)
+    }
+
+    /// Capture the state root, balance and pending expectations, so that several scenarios
+    /// can be run from the same setup by restoring it before each of them.
+    pub fn snapshot(&self) -> Snapshot {
+        assert!(!self.in_call, "snapshot taken during a call");
+        Snapshot {
+            state: self.state,
+            balance: self.balance.borrow().clone(),
+            expectations: self.expectations.borrow().clone(),
+        }
+    }
+
+    /// Revert to a snapshot. The blockstore is content addressed, so the restored state root
+    /// still resolves; anything written since is just unreachable.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        assert!(!self.in_call, "snapshot restored during a call");
+        self.state = snapshot.state;
+        self.write_head();
+        *self.balance.get_mut() = snapshot.balance.clone();
+        *self.expectations.get_mut() = snapshot.expectations.clone();
+    }
+
     ///// Mock expectations /////
 
     #[allow(dead_code)]
     pub fn expect_validate_caller_addr(&mut self, addr: Vec<Address>) {
         assert!(!addr.is_empty(), "addrs must be non-empty");
-        self.expectations.get_mut().expect_validate_caller_addr = Some(addr);
+        let expectations = self.expectations.get_mut();
+        expectations.expect_validate_caller_addr = Some(addr);
+        expectations.label(ExpectationKind::ValidateCallerAddr);
     }
 
     #[allow(dead_code)]
     pub fn expect_verify_signature(&self, exp: ExpectedVerifySig) {
-        self.expectations
-            .borrow_mut()
-            .expect_verify_sigs
-            .push_back(exp);
+        let mut expectations = self.expectations.borrow_mut();
+        expectations.expect_verify_sigs.push_back(exp);
+        expectations.label(ExpectationKind::VerifySig);
     }
 
     #[allow(dead_code)]
     pub fn expect_validate_caller_type(&mut self, types: Vec<Cid>) {
         assert!(!types.is_empty(), "addrs must be non-empty");
-        self.expectations.borrow_mut().expect_validate_caller_type = Some(types);
+        let expectations = self.expectations.get_mut();
+        expectations.expect_validate_caller_type = Some(types);
+        expectations.label(ExpectationKind::ValidateCallerType);
     }
 
     #[allow(dead_code)]
     pub fn expect_validate_caller_not_type(&mut self, types: Vec<Cid>) {
-        // we add type as an expectation to ensure that we did the type check
-        // and then perform the explicit "not_type" check in the validate of
-        // the MockRuntime
-        self.expectations
-            .borrow_mut()
-            .expect_validate_caller_not_type = Some(types);
+        assert!(!types.is_empty(), "types must be non-empty");
+        let expectations = self.expectations.get_mut();
+        expectations.expect_validate_caller_not_type = Some(types);
+        expectations.label(ExpectationKind::ValidateCallerNotType);
+    }
+
+    #[allow(dead_code)]
+    pub fn expect_validate_caller_eth(&mut self, address: [u8; 20]) {
+        let expectations = self.expectations.get_mut();
+        expectations.expect_validate_caller_eth = Some(address);
+        expectations.label(ExpectationKind::ValidateCallerEth);
+    }
+
+    #[allow(dead_code)]
+    pub fn expect_validate_caller_namespace(&mut self, namespace: ActorID) {
+        let expectations = self.expectations.get_mut();
+        expectations.expect_validate_caller_namespace = Some(namespace);
+        expectations.label(ExpectationKind::ValidateCallerNamespace);
     }
 
     #[allow(dead_code)]
     pub fn expect_validate_caller_any(&self) {
-        self.expectations.borrow_mut().expect_validate_caller_any = true;
+        let mut expectations = self.expectations.borrow_mut();
+        expectations.expect_validate_caller_any = true;
+        expectations.label(ExpectationKind::ValidateCallerAny);
+    }
+
+    /// Expect the method to accept any caller, like `expect_validate_caller_any`, and assert
+    /// that the caller it is called by satisfies a predicate nonetheless, e.g. that a public
+    /// method is reached through a particular actor in the test.
+    #[allow(dead_code)]
+    pub fn expect_validate_caller_any_with(&self, predicate: impl Fn(&Address) -> bool + 'static) {
+        self.expect_validate_caller_any();
+        self.expectations
+            .borrow_mut()
+            .expect_validate_caller_any_with = Some(Rc::new(predicate));
     }
 
     #[allow(dead_code)]
     pub fn expect_delete_actor(&mut self, beneficiary: Address) {
-        self.expectations.borrow_mut().expect_delete_actor = Some(beneficiary);
+        let expectations = self.expectations.get_mut();
+        expectations.expect_delete_actor = Some(beneficiary);
+        expectations.label(ExpectationKind::DeleteActor);
     }
 
     #[allow(dead_code)]
@@ -429,28 +1469,130 @@ impl<BS: Blockstore> MockRuntime<BS> {
         send_return: Option<IpldBlock>,
         exit_code: ExitCode,
     ) {
-        self.expectations
-            .borrow_mut()
-            .expect_sends
-            .push_back(ExpectedMessage {
-                to,
-                method,
-                params,
-                value,
-                send_return,
-                exit_code,
-            })
+        let expectations = self.expectations.get_mut();
+        expectations.expect_sends.push_back(ExpectedMessage {
+            to,
+            method,
+            params,
+            value,
+            send_return,
+            exit_code,
+        });
+        expectations.label(ExpectationKind::Send);
     }
 
+    /// Expect a call to a method of the actor behind `to`, as made by `ActorHandle::send`,
+    /// with `params` and returning `ret`, both encoded as CBOR, so that tests name the method
+    /// and give the values rather than a method number and encoded blocks.
+    ///
+    /// Calls without parameters or return value, or which fail, are expected with `expect_send`
+    /// and `ActorHandle::method_num`.
     #[allow(dead_code)]
-    pub fn expect_create_actor(&mut self, code_id: Cid, actor_id: ActorID) {
-        let a = ExpectCreateActor { code_id, actor_id };
-        self.expectations.borrow_mut().expect_create_actor = Some(a);
+    pub fn expect_actor_call<A: ActorCode>(
+        &mut self,
+        to: &ActorHandle<A>,
+        method: A::Methods,
+        params: &impl Serialize,
+        value: TokenAmount,
+        ret: &impl Serialize,
+    ) where
+        A::Methods: ToPrimitive,
+    {
+        self.expect_send(
+            *to.address(),
+            ActorHandle::<A>::method_num(method),
+            Some(IpldBlock::from_cbor(params).unwrap()),
+            value,
+            Some(IpldBlock::from_cbor(ret).unwrap()),
+            ExitCode::OK,
+        );
     }
 
+    /// Expect a send creating an actor of `code_cid` through the Init actor, as made by
+    /// `init_exec`, returning `ret`.
+    ///
+    /// The addresses in `ret` are registered right away, so that they resolve to the new actor
+    /// in the rest of the test, as they would once the actor has been created.
     #[allow(dead_code)]
-    pub fn set_received(&mut self, amount: TokenAmount) {
-        self.value_received = amount;
+    pub fn expect_init_exec(
+        &mut self,
+        code_cid: Cid,
+        constructor_params: &impl Serialize,
+        value: TokenAmount,
+        ret: InitExecReturn,
+    ) {
+        let params = InitExecParams {
+            code_cid,
+            constructor_params: RawBytes::serialize(constructor_params).unwrap(),
+        };
+        self.expect_exec(INIT_EXEC_METHOD_NUM, &params, code_cid, value, ret);
+    }
+
+    /// Like `expect_init_exec`, for `init_exec4`, also registering the delegated address
+    /// with the `subaddress` in the namespace of the receiver.
+    #[allow(dead_code)]
+    pub fn expect_init_exec4(
+        &mut self,
+        code_cid: Cid,
+        constructor_params: &impl Serialize,
+        subaddress: &[u8],
+        value: TokenAmount,
+        ret: InitExecReturn,
+    ) {
+        let params = InitExec4Params {
+            code_cid,
+            constructor_params: RawBytes::serialize(constructor_params).unwrap(),
+            subaddress: RawBytes::new(subaddress.to_vec()),
+        };
+        let namespace = self.receiver.id().expect("receiver must be an ID address");
+        let delegated = Address::new_delegated(namespace, subaddress).unwrap();
+        self.delegated_addresses
+            .insert(ret.actor_id().unwrap(), delegated);
+        self.add_id_address(delegated, ret.id_address);
+        self.expect_exec(INIT_EXEC4_METHOD_NUM, &params, code_cid, value, ret);
+    }
+
+    fn expect_exec(
+        &mut self,
+        method: MethodNum,
+        params: &impl Serialize,
+        code_cid: Cid,
+        value: TokenAmount,
+        ret: InitExecReturn,
+    ) {
+        self.add_id_address(ret.robust_address, ret.id_address);
+        self.set_address_actor_type(ret.id_address, code_cid);
+        self.expect_send(
+            INIT_ACTOR_ADDR,
+            method,
+            Some(IpldBlock::from_cbor(params).unwrap()),
+            value,
+            Some(IpldBlock::from_cbor(&ret).unwrap()),
+            ExitCode::OK,
+        );
+    }
+
+    #[allow(dead_code)]
+    pub fn expect_create_actor(&mut self, code_id: Cid, actor_id: ActorID) {
+        let a = ExpectCreateActor { code_id, actor_id };
+        let expectations = self.expectations.get_mut();
+        expectations.expect_create_actor = Some(a);
+        expectations.label(ExpectationKind::CreateActor);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_received(&mut self, amount: TokenAmount) {
+        self.value_received = amount;
+    }
+
+    #[allow(dead_code)]
+    pub fn set_origin(&mut self, origin: Address) {
+        self.origin = origin;
+    }
+
+    #[allow(dead_code)]
+    pub fn set_gas_premium(&mut self, gas_premium: TokenAmount) {
+        self.gas_premium = gas_premium;
     }
 
     #[allow(dead_code)]
@@ -468,16 +1610,105 @@ impl<BS: Blockstore> MockRuntime<BS> {
         self.epoch = epoch;
     }
 
+    /// Expect a charge of `value` gas, under any name.
     #[allow(dead_code)]
     pub fn expect_gas_charge(&mut self, value: i64) {
-        self.expectations
-            .borrow_mut()
-            .expect_gas_charge
-            .push_back(value);
+        self.push_gas_charge(ExpectGasCharge { name: None, value });
+    }
+
+    /// Expect a charge of `value` gas under a name matching `name`, in which `*` stands for
+    /// any characters, e.g. `"persist_*"`.
+    pub fn expect_named_gas_charge(&mut self, name: &str, value: i64) {
+        self.push_gas_charge(ExpectGasCharge {
+            name: Some(name.into()),
+            value,
+        });
+    }
+
+    /// Expect a charge from the gas schedule, e.g. `rt.policy().gas.persist_entry`.
+    pub fn expect_charge(&mut self, charge: GasCharge) {
+        self.expect_named_gas_charge(charge.name, charge.compute);
+    }
+
+    fn push_gas_charge(&mut self, charge: ExpectGasCharge) {
+        let expectations = self.expectations.get_mut();
+        expectations.expect_gas_charge.push_back(charge);
+        expectations.label(ExpectationKind::GasCharge);
+    }
+
+    /// Declare the events a method may emit, enabling the check of all emitted events.
+    pub fn declare_events(&mut self, method: MethodNum, events: Vec<EventSchema>) {
+        self.declared_events
+            .get_or_insert_with(HashMap::new)
+            .insert(method, events);
+    }
+
+    /// Capture the state before and after the next `call`, and pass both to `check` on `verify`,
+    /// to assert how the call changed the state.
+    ///
+    /// The check is skipped if the actor has no state before or after the call, e.g. for a
+    /// constructor, whose state is checked with `expect_create_state` instead.
+    #[allow(dead_code)]
+    pub fn expect_state_transition<S, F>(&mut self, check: F)
+    where
+        S: DeserializeOwned,
+        F: Fn(S, S) + 'static,
+    {
+        let decode = |bytes: &[u8]| -> S {
+            fvm_ipld_encoding::from_slice(bytes).expect("failed to decode state")
+        };
+        let expectations = self.expectations.get_mut();
+        expectations.expect_state_transition = Some(ExpectStateTransition {
+            check: Rc::new(move |before, after| check(decode(before), decode(after))),
+            before: None,
+            after: None,
+        });
+        expectations.label(ExpectationKind::StateTransition);
+    }
+
+    /// Expect `create` to be called once, e.g. by a constructor, and pass the state it is
+    /// called with to `check`, to assert on the initial state directly.
+    pub fn expect_create_state<S, F>(&mut self, check: F)
+    where
+        S: DeserializeOwned,
+        F: Fn(S) + 'static,
+    {
+        let expectations = self.expectations.get_mut();
+        expectations.expect_create_state = Some(Rc::new(move |bytes| {
+            check(fvm_ipld_encoding::from_slice(bytes).expect("failed to decode created state"))
+        }));
+        expectations.label(ExpectationKind::CreateState);
+    }
+
+    #[allow(dead_code)]
+    pub fn expect_emitted_event(&mut self, event: ActorEvent) {
+        let expectations = self.expectations.get_mut();
+        expectations.expect_emitted_events.push_back(event);
+        expectations.label(ExpectationKind::EmittedEvent);
     }
 
     ///// Private helpers /////
 
+    /// Record the current state, if any, in the pending state transition, unless already
+    /// recorded.
+    fn capture_state(
+        &self,
+        slot: impl Fn(&mut ExpectStateTransition) -> &mut Option<Option<Vec<u8>>>,
+    ) {
+        let mut expectations = self.expectations.borrow_mut();
+        if let Some(transition) = expectations.expect_state_transition.as_mut() {
+            let slot = slot(transition);
+            if slot.is_none() {
+                *slot = Some(self.state.map(|cid| {
+                    self.store
+                        .get(&cid)
+                        .unwrap()
+                        .expect("state not in the store")
+                }));
+            }
+        }
+    }
+
     fn require_in_call(&self) {
         assert!(
             self.in_call,
@@ -504,6 +1735,12 @@ impl<BS> MessageInfo for MockRuntime<BS> {
     fn value_received(&self) -> TokenAmount {
         self.value_received.clone()
     }
+    fn origin(&self) -> Address {
+        self.origin
+    }
+    fn gas_premium(&self) -> TokenAmount {
+        self.gas_premium.clone()
+    }
 }
 
 impl<BS: Blockstore> Runtime for MockRuntime<BS> {
@@ -513,11 +1750,19 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         self.network_version
     }
 
+    fn chain_id(&self) -> ChainID {
+        self.chain_id
+    }
+
     fn message(&self) -> &dyn MessageInfo {
         self.require_in_call();
         self
     }
 
+    fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
     fn curr_epoch(&self) -> ChainEpoch {
         self.require_in_call();
         self.epoch
@@ -525,11 +1770,25 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
 
     fn validate_immediate_caller_accept_any(&mut self) -> Result<(), ActorError> {
         self.require_in_call();
+        self.expect_in_simulation(|e| e.expect_validate_caller_any = true);
         assert!(
             self.expectations.borrow_mut().expect_validate_caller_any,
             "unexpected validate-caller-any"
         );
         self.expectations.borrow_mut().expect_validate_caller_any = false;
+        let predicate = self
+            .expectations
+            .borrow_mut()
+            .expect_validate_caller_any_with
+            .take();
+        if let Some(predicate) = predicate {
+            let caller = self.message().caller();
+            assert!(
+                predicate(&caller),
+                "caller {caller} validated as any does not satisfy the expected predicate"
+            );
+        }
+        self.record_trace(|t| t.validations.push("any".into()));
         Ok(())
     }
 
@@ -540,6 +1799,8 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         self.require_in_call();
 
         let addrs: Vec<Address> = addresses.into_iter().cloned().collect();
+        self.record_trace(|t| t.validations.push(format!("is {addrs:?}")));
+        self.expect_in_simulation(|e| e.expect_validate_caller_addr = Some(addrs.clone()));
 
         let mut expectations = self.expectations.borrow_mut();
         assert!(
@@ -554,13 +1815,16 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
             addrs, &expectations.expect_validate_caller_addr
         );
 
-        for expected in &addrs {
-            if self.message().caller() == *expected {
-                expectations.expect_validate_caller_addr = None;
-                return Ok(());
-            }
-        }
+        // The caller may be configured with a non-ID address, which the real one never is.
+        let caller = self.message().caller();
+        let resolved = self.resolve_address(&caller);
         expectations.expect_validate_caller_addr = None;
+        if addrs
+            .iter()
+            .any(|a| *a == caller || (resolved.is_some() && self.resolve_address(a) == resolved))
+        {
+            return Ok(());
+        }
         Err(actor_error!(forbidden;
                 "caller address {:?} forbidden, allowed: {:?}",
                 self.message().caller(), &addrs
@@ -572,14 +1836,6 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         I: IntoIterator<Item = &'a Type>,
     {
         self.require_in_call();
-        assert!(
-            self.expectations
-                .borrow_mut()
-                .expect_validate_caller_type
-                .is_some(),
-            "unexpected validate caller code"
-        );
-
         let find_by_type = |typ| {
             (*ACTOR_TYPES)
                 .iter()
@@ -588,6 +1844,15 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
                 .unwrap()
         };
         let types: Vec<Cid> = types.into_iter().map(find_by_type).collect();
+        self.expect_in_simulation(|e| e.expect_validate_caller_type = Some(types.clone()));
+        assert!(
+            self.expectations
+                .borrow_mut()
+                .expect_validate_caller_type
+                .is_some(),
+            "unexpected validate caller code"
+        );
+        self.record_trace(|t| t.validations.push(format!("type {types:?}")));
         let expected_caller_type = self
             .expectations
             .borrow_mut()
@@ -618,16 +1883,6 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         I: IntoIterator<Item = &'a Type>,
     {
         self.require_in_call();
-
-        // still requires the caller type to be set otherwise we cannot check against not type
-        assert!(
-            self.expectations
-                .borrow_mut()
-                .expect_validate_caller_not_type
-                .is_some(),
-            "unexpected validate caller code"
-        );
-
         let find_by_type = |typ| {
             (*ACTOR_TYPES)
                 .iter()
@@ -636,26 +1891,88 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
                 .unwrap()
         };
         let types: Vec<Cid> = types.into_iter().map(find_by_type).collect();
-
-        let expect_validate_caller_not_type = self
+        self.expect_in_simulation(|e| e.expect_validate_caller_not_type = Some(types.clone()));
+        assert!(
+            self.expectations
+                .borrow_mut()
+                .expect_validate_caller_not_type
+                .is_some(),
+            "unexpected validate caller not code"
+        );
+        self.record_trace(|t| t.validations.push(format!("not type {types:?}")));
+        let expected_not_type = self
             .expectations
             .borrow_mut()
             .expect_validate_caller_not_type
-            .clone()
+            .take()
             .unwrap();
+        assert_eq!(
+            &types, &expected_not_type,
+            "unexpected validate caller not code {types:?}, expected {expected_not_type:?}"
+        );
 
-        let mut r = Ok(());
-        for unexpected in &types {
-            if !expect_validate_caller_not_type.contains(unexpected) {
-                r = Err(actor_error!(forbidden; "caller type {:?} not expected", unexpected));
-                break;
-            }
+        if types.contains(&self.caller_type) {
+            return Err(
+                actor_error!(forbidden; "caller type {:?} forbidden, forbidden types: {:?}",
+                    self.caller_type, types),
+            );
         }
+        Ok(())
+    }
 
-        self.expectations
+    fn validate_immediate_caller_is_eth(&mut self, address: &[u8; 20]) -> Result<(), ActorError> {
+        self.require_in_call();
+        self.record_trace(|t| t.validations.push(format!("eth {address:?}")));
+        self.expect_in_simulation(|e| e.expect_validate_caller_eth = Some(*address));
+        let expected = self
+            .expectations
             .borrow_mut()
-            .expect_validate_caller_not_type = None;
-        r
+            .expect_validate_caller_eth
+            .take()
+            .expect("unexpected validate caller eth");
+        assert_eq!(
+            address, &expected,
+            "unexpected validate caller eth {address:?}, expected {expected:?}"
+        );
+
+        match self
+            .caller_delegated_address()
+            .as_ref()
+            .and_then(|a| delegated_subaddress(a, EAM_ACTOR_ID))
+        {
+            Some(subaddress) if subaddress == address.as_slice() => Ok(()),
+            _ => Err(actor_error!(forbidden;
+                "caller {} is not the expected eth address", self.caller)),
+        }
+    }
+
+    fn validate_immediate_caller_namespace(
+        &mut self,
+        namespace: ActorID,
+    ) -> Result<(), ActorError> {
+        self.require_in_call();
+        self.record_trace(|t| t.validations.push(format!("namespace {namespace}")));
+        self.expect_in_simulation(|e| e.expect_validate_caller_namespace = Some(namespace));
+        let expected = self
+            .expectations
+            .borrow_mut()
+            .expect_validate_caller_namespace
+            .take()
+            .expect("unexpected validate caller namespace");
+        assert_eq!(
+            namespace, expected,
+            "unexpected validate caller namespace {namespace}, expected {expected}"
+        );
+
+        match self
+            .caller_delegated_address()
+            .as_ref()
+            .and_then(|a| delegated_subaddress(a, namespace))
+        {
+            Some(_) => Ok(()),
+            None => Err(actor_error!(forbidden;
+                "caller {} has no delegated address in namespace {}", self.caller, namespace)),
+        }
     }
 
     fn current_balance(&self) -> TokenAmount {
@@ -676,15 +1993,33 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         self.actor_code_cids.get(&Address::new_id(*id)).cloned()
     }
 
+    fn lookup_delegated_address(&self, id: ActorID) -> Option<Address> {
+        self.require_in_call();
+        self.delegated_addresses.get(&id).copied()
+    }
+
     fn create<T: Serialize>(&mut self, obj: &T) -> Result<(), ActorError> {
+        self.sself_root()?;
         if self.state.is_some() {
             return Err(actor_error!(illegal_state; "state already constructed"));
         }
-        self.state = Some(self.store_put(obj));
+        let root = self.store_put(obj);
+        self.sself_set_root()?;
+        self.state = Some(root);
+
+        let check = {
+            let mut expectations = self.expectations.borrow_mut();
+            expectations.met(ExpectationKind::CreateState);
+            expectations.expect_create_state.take()
+        };
+        if let Some(check) = check {
+            check(&fvm_ipld_encoding::to_vec(obj).unwrap());
+        }
         Ok(())
     }
 
     fn state<T: DeserializeOwned>(&self) -> Result<T, ActorError> {
+        self.sself_root()?;
         Ok(self.store_get(self.state.as_ref().unwrap()))
     }
 
@@ -696,20 +2031,49 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         if self.in_transaction {
             return Err(actor_error!(assertion_failed; "nested transaction"));
         }
-        let mut read_only = self.state()?;
+        self.sself_root()
+            .map_err(|_| actor_error!(illegal_argument; "failed to get actor root state CID"))?;
+        let mut read_only = self.store_get(self.state.as_ref().unwrap());
         self.in_transaction = true;
-        let ret = f(&mut read_only, self);
-        if ret.is_ok() {
-            self.state = Some(self.store_put(&read_only));
-        }
+        let ret = f(&mut read_only, self).and_then(|ret| {
+            if let Some(invariants) = &self.state_invariants {
+                invariants.check(&read_only)?;
+            }
+            Ok(ret)
+        });
         self.in_transaction = false;
-        ret
+        let ret = ret?;
+        let root = self.store_put(&read_only);
+        self.sself_set_root()?;
+        self.state = Some(root);
+        Ok(ret)
+    }
+
+    fn set_state_invariants(&mut self, invariants: StateInvariants) {
+        self.state_invariants = Some(invariants);
     }
 
     fn store(&self) -> &Rc<BS> {
         &self.store
     }
 
+    fn read_block(&self, cid: &Cid, max_size: u32) -> Result<IpldBlock, ActorError> {
+        self.require_in_call();
+        let data = self
+            .store
+            .get(cid)
+            .map_err(|e| actor_error!(illegal_state; "failed to read block {}: {}", cid, e))?
+            .ok_or_else(|| actor_error!(not_found; "block {} not found", cid))?;
+        if data.len() > max_size as usize {
+            return Err(actor_error!(illegal_argument;
+                "block {} has {} bytes, more than the limit of {}", cid, data.len(), max_size));
+        }
+        Ok(IpldBlock {
+            codec: cid.codec(),
+            data,
+        })
+    }
+
     fn send(
         &self,
         to: &Address,
@@ -721,24 +2085,45 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         if self.in_transaction {
             return Err(actor_error!(assertion_failed; "side-effect within transaction"));
         }
+        let params = normalize_params(params);
+        self.expect_in_simulation(|e| {
+            e.expect_sends.push_back(ExpectedMessage {
+                to: *to,
+                method,
+                params: params.clone(),
+                value: value.clone(),
+                send_return: None,
+                exit_code: ExitCode::OK,
+            })
+        });
 
         assert!(
             !self.expectations.borrow_mut().expect_sends.is_empty(),
             "unexpected message to: {to:?} method: {method:?}, value: {value:?}, params: {params:?}"
         );
 
-        let expected_msg = self
-            .expectations
-            .borrow_mut()
-            .expect_sends
-            .pop_front()
-            .unwrap();
+        let expected_msg = {
+            let mut expectations = self.expectations.borrow_mut();
+            expectations.met(ExpectationKind::Send);
+            expectations.expect_sends.pop_front().unwrap()
+        };
 
         assert_eq!(expected_msg.to, *to);
         assert_eq!(expected_msg.method, method);
         assert_eq!(expected_msg.params, params);
         assert_eq!(expected_msg.value, value);
 
+        self.record_trace(|t| {
+            t.sends.push(SendTrace {
+                to: to.to_string(),
+                method,
+                params: self.trace_block(method, false, params.as_ref()),
+                value: value.to_string(),
+                exit_code: expected_msg.exit_code.value(),
+                ret: self.trace_block(method, true, expected_msg.send_return.as_ref()),
+            })
+        });
+
         {
             let mut balance = self.balance.borrow_mut();
             if value > *balance {
@@ -755,9 +2140,10 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
 
         match expected_msg.exit_code {
             ExitCode::OK => Ok(expected_msg.send_return),
-            x => Err(ActorError::unchecked(
+            x => Err(ActorError::unchecked_with_data(
                 x,
                 "Expected message Fail".to_string(),
+                expected_msg.send_return,
             )),
         }
     }
@@ -777,6 +2163,9 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         if self.in_transaction {
             return Err(actor_error!(assertion_failed; "side-effect within transaction"));
         }
+        self.expect_in_simulation(|e| {
+            e.expect_create_actor = Some(ExpectCreateActor { code_id, actor_id })
+        });
         let expect_create_actor = self
             .expectations
             .borrow_mut()
@@ -793,6 +2182,7 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         if self.in_transaction {
             return Err(actor_error!(assertion_failed; "side-effect within transaction"));
         }
+        self.expect_in_simulation(|e| e.expect_delete_actor = Some(*addr));
         let exp_act = self.expectations.borrow_mut().expect_delete_actor.take();
         if exp_act.is_none() {
             panic!("unexpected call to delete actor: {addr}");
@@ -825,22 +2215,55 @@ impl<BS: Blockstore> Runtime for MockRuntime<BS> {
         self.circulating_supply.clone()
     }
 
-    fn charge_gas(&mut self, _: &'static str, value: i64) {
+    fn charge_gas(&mut self, name: &'static str, value: i64) {
+        self.record_trace(|t| {
+            t.gas_charges.push(GasChargeTrace {
+                name: name.into(),
+                value,
+            })
+        });
+        self.expect_in_simulation(|e| {
+            e.expect_gas_charge.push_back(ExpectGasCharge {
+                name: Some(name.into()),
+                value,
+            })
+        });
         let mut exs = self.expectations.borrow_mut();
         assert!(
             !exs.expect_gas_charge.is_empty(),
-            "unexpected gas charge {value:?}"
+            "unexpected gas charge {name:?} of {value}"
         );
         let expected = exs.expect_gas_charge.pop_front().unwrap();
-        assert_eq!(
-            expected, value,
-            "expected gas charge {expected:?}, actual {value:?}"
+        exs.met(ExpectationKind::GasCharge);
+        assert!(
+            expected.matches(name, value),
+            "expected gas charge {expected}, actual {name:?} of {value}"
         );
     }
 
     fn base_fee(&self) -> TokenAmount {
         self.base_fee.clone()
     }
+
+    fn emit_event(&self, event: &ActorEvent) -> Result<(), ActorError> {
+        if let Some(declared) = &self.declared_events {
+            let method = self.method.expect("event emitted outside of a call");
+            let schemas = declared.get(&method).map(Vec::as_slice).unwrap_or_default();
+            assert!(
+                schemas.iter().any(|schema| schema.matches(event)),
+                "event not declared for method {method}: {event:?}"
+            );
+        }
+        self.expect_in_simulation(|e| e.expect_emitted_events.push_back(event.clone()));
+        let expected = {
+            let mut expectations = self.expectations.borrow_mut();
+            expectations.met(ExpectationKind::EmittedEvent);
+            expectations.expect_emitted_events.pop_front()
+        }
+        .unwrap_or_else(|| panic!("unexpected event emitted: {event:?}"));
+        assert_eq!(&expected, event, "unexpected event emitted");
+        Ok(())
+    }
 }
 
 impl<BS> Primitives for MockRuntime<BS> {
@@ -850,6 +2273,14 @@ impl<BS> Primitives for MockRuntime<BS> {
         signer: &Address,
         plaintext: &[u8],
     ) -> anyhow::Result<()> {
+        self.expect_in_simulation(|e| {
+            e.expect_verify_sigs.push_back(ExpectedVerifySig {
+                sig: signature.clone(),
+                signer: *signer,
+                plaintext: plaintext.to_vec(),
+                result: Ok(()),
+            })
+        });
         if self.expectations.borrow_mut().expect_verify_sigs.is_empty() {
             panic!(
                 "Unexpected signature verification sig: {:?}, signer: {}, plaintext: {}",
@@ -858,11 +2289,11 @@ impl<BS> Primitives for MockRuntime<BS> {
                 hex::encode(plaintext)
             );
         }
-        let exp = self
-            .expectations
-            .borrow_mut()
-            .expect_verify_sigs
-            .pop_front();
+        let exp = {
+            let mut expectations = self.expectations.borrow_mut();
+            expectations.met(ExpectationKind::VerifySig);
+            expectations.expect_verify_sigs.pop_front()
+        };
         if let Some(exp) = exp {
             if exp.sig != *signature || exp.signer != *signer || &exp.plaintext[..] != plaintext {
                 panic!(
@@ -943,3 +2374,991 @@ pub fn new_bls_addr(s: u8) -> Address {
     rng.fill_bytes(&mut key);
     Address::new_bls(&key).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::Address;
+    use fvm_shared::error::ExitCode;
+    use fvm_shared::METHOD_SEND;
+    use num_traits::Zero;
+
+    use super::*;
+    use crate::runtime::InstrumentedRuntime;
+    use crate::{EventBuilder, EventValueType};
+
+    struct TracedActor;
+
+    impl ActorCode for TracedActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            _: MethodNum,
+            params: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            rt.validate_immediate_caller_accept_any()?;
+            rt.charge_gas("traced", 10);
+            Ok(params)
+        }
+    }
+
+    #[test]
+    fn call_trace() {
+        let path = std::env::temp_dir().join(format!("call-trace-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut rt = MockRuntime::default();
+        rt.trace_to(&path);
+        rt.expect_validate_caller_any();
+        rt.expect_gas_charge(10);
+        let params = Some(IpldBlock::from_cbor(&1u64).unwrap());
+        rt.call::<TracedActor>(2, params).unwrap();
+        rt.verify();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let trace: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(trace["method"], 2);
+        assert_eq!(trace["params"], "01");
+        assert_eq!(trace["ret"], "01");
+        assert_eq!(trace["validations"], serde_json::json!(["any"]));
+        assert_eq!(trace["gas_charges"][0]["value"], 10);
+        assert_eq!(trace["exit_code"], 0);
+    }
+
+    #[test]
+    fn validate_caller_any_with_predicate() {
+        let mut rt = MockRuntime {
+            caller: Address::new_id(100),
+            ..Default::default()
+        };
+        rt.expect_validate_caller_any_with(|caller| caller.id() == Ok(100));
+        rt.expect_gas_charge(10);
+        rt.call::<TracedActor>(2, None).unwrap();
+        rt.verify();
+        assert!(rt
+            .expectations
+            .borrow()
+            .expect_validate_caller_any_with
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "caller f0101 validated as any does not satisfy the expected predicate"
+    )]
+    fn validate_caller_any_with_failing_predicate() {
+        let mut rt = MockRuntime {
+            caller: Address::new_id(101),
+            ..Default::default()
+        };
+        rt.expect_validate_caller_any_with(|caller| caller.id() == Ok(100));
+        rt.expect_gas_charge(10);
+        let _ = rt.call::<TracedActor>(2, None);
+    }
+
+    #[test]
+    fn expectations_from_trace() {
+        let path = std::env::temp_dir().join(format!("rerecord-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut rt = MockRuntime::default();
+        rt.trace_to(&path);
+        rt.expect_validate_caller_any();
+        rt.expect_gas_charge(10);
+        rt.call::<TracedActor>(2, None).unwrap();
+        rt.verify();
+
+        let traces = CallTrace::read_all(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(traces.len(), 1);
+
+        let mut rt = MockRuntime::default();
+        *rt.expectations.get_mut() = Expectations::from_trace(&traces[0]).unwrap();
+        rt.expect_validate_caller_any();
+        rt.call::<TracedActor>(2, None).unwrap();
+        rt.verify();
+
+        let mut trace = traces[0].clone();
+        trace.sends.push(SendTrace {
+            to: "f0101".into(),
+            method: 3,
+            params: "01".into(),
+            value: "1.5".into(),
+            exit_code: 0,
+            ret: serde_json::Value::Null,
+        });
+        let expectations = Expectations::from_trace(&trace).unwrap();
+        let send = &expectations.expect_sends[0];
+        assert_eq!(send.to, Address::new_id(101));
+        assert_eq!(send.params, Some(IpldBlock::from_cbor(&1u64).unwrap()));
+        assert_eq!(
+            send.value,
+            TokenAmount::from_atto(1_500_000_000_000_000_000u64)
+        );
+        assert_eq!(send.send_return, None);
+        assert_eq!(send.exit_code, ExitCode::OK);
+
+        let mut changed = trace.clone();
+        changed.sends[0].value = "2".into();
+        changed.gas_charges.clear();
+        assert_eq!(
+            trace.diff(&changed),
+            vec![
+                "gas_charges[0]: {\"name\":\"traced\",\"value\":10} -> null",
+                "sends[0].value: \"1.5\" -> \"2\"",
+            ]
+        );
+        assert!(trace.diff(&trace).is_empty());
+        assert_eq!(
+            diff_traces(&[trace.clone()], &[trace.clone(), changed]),
+            vec!["call 1: method 2 added"]
+        );
+
+        trace.sends[0].params = serde_json::json!({"decoded": 1});
+        assert!(Expectations::from_trace(&trace).is_err());
+    }
+
+    #[test]
+    fn state_on_disk() {
+        let dir = std::env::temp_dir().join(format!("mock-state-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut rt = MockRuntime::on_disk(&dir).unwrap();
+        rt.replace_state(&(1u64, "one".to_string()));
+        rt.expect_validate_caller_any();
+        rt.expect_gas_charge(10);
+        rt.call::<TracedActor>(2, None).unwrap();
+        rt.verify();
+        let head = rt.state.unwrap();
+        drop(rt);
+
+        // The state is still there for anyone reading the directory.
+        let root = std::fs::read_to_string(dir.join("HEAD")).unwrap();
+        assert_eq!(root, head.to_string());
+        let store = DiskBlockstore::new(&dir).unwrap();
+        let state: (u64, String) = store.get_cbor(&head).unwrap().unwrap();
+        assert_eq!(state, (1, "one".to_string()));
+        assert_eq!(store.get(&Cid::default()).unwrap(), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn version_method() {
+        use crate::runtime::{VersionInfo, VERSION_METHOD};
+        use crate::{actor_dispatch, actor_methods};
+
+        actor_methods! {
+            enum Method {
+                Constructor = fvm_shared::METHOD_CONSTRUCTOR,
+            }
+        }
+
+        struct VersionedActor;
+
+        impl VersionedActor {
+            fn constructor(_: &mut impl Runtime) -> Result<(), ActorError> {
+                Ok(())
+            }
+        }
+
+        impl ActorCode for VersionedActor {
+            type Methods = Method;
+            const VERSION: &'static str = "1.2.3";
+            const STATE_SCHEMA: &'static str = "struct State { count: u64 }";
+            actor_dispatch! {
+                Constructor => constructor,
+            }
+        }
+
+        let code = make_builtin(b"fil/test/versioned");
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(101));
+        rt.actor_code_cids.insert(rt.receiver, code);
+        rt.expect_validate_caller_any();
+        let ret = rt.call::<VersionedActor>(VERSION_METHOD, None).unwrap();
+        rt.verify();
+
+        let info: VersionInfo = crate::cbor::decode_block(&ret).unwrap();
+        assert_eq!(info.version, "1.2.3");
+        assert_eq!(info.code_cid, code);
+        assert_eq!(
+            VersionedActor::METHOD_TABLE.len(),
+            Method::ALL_METHODS.len() + 1
+        );
+        assert_eq!(
+            info.state_schema,
+            blake2b_256(VersionedActor::STATE_SCHEMA.as_bytes())
+        );
+    }
+
+    #[test]
+    fn charge_from_gas_schedule() {
+        let mut rt = MockRuntime::default();
+        if !cfg!(feature = "no-gas-charges") {
+            rt.expect_gas_charge(crate::runtime::PERSIST_ENTRY_GAS);
+        }
+        rt.charge(rt.policy().gas.persist_entry);
+        rt.verify();
+    }
+
+    #[test]
+    fn named_gas_charges() {
+        let mut rt = MockRuntime::default();
+        rt.expect_named_gas_charge("persist_entry", 1);
+        rt.expect_named_gas_charge("persist_*", 2);
+        rt.expect_named_gas_charge("*_entry", 3);
+        rt.expect_gas_charge(4);
+        rt.charge_gas("persist_entry", 1);
+        rt.charge_gas("persist_entry", 2);
+        rt.charge_gas("load_entry", 3);
+        rt.charge_gas("anything", 4);
+        rt.verify();
+
+        assert!(super::glob_match("a*b*c", "abbc"));
+        assert!(!super::glob_match("a*b", "abc"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "expected gas charge \"load_entry\" of 300, actual \"remove_entry\" of 300"
+    )]
+    fn gas_charge_name_mismatch() {
+        let mut rt = MockRuntime::default();
+        rt.expect_named_gas_charge("load_entry", 300);
+        rt.charge_gas("remove_entry", 300);
+    }
+
+    /// Writes as many blocks as its method number, of one byte each.
+    struct StoringActor;
+
+    impl ActorCode for StoringActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            method: MethodNum,
+            _: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            for i in 0..method {
+                rt.store().put_cbor(&i, Code::Blake2b256).unwrap();
+            }
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn ipld_budgets() {
+        let mut rt = MockRuntime::new_tracked(MemoryBlockstore::new());
+        rt.expect_max_ipld_ops(0, 2);
+        rt.expect_max_state_bytes(2);
+        rt.call::<StoringActor>(1, None).unwrap();
+        rt.call::<StoringActor>(2, None).unwrap();
+        rt.verify();
+
+        rt.call::<StoringActor>(3, None).unwrap();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rt.verify()));
+        let msg = *res.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            msg,
+            "call to method 3 made 0 reads and 3 writes, expected at most 0 and 2"
+        );
+
+        rt.reset();
+        rt.call::<StoringActor>(3, None).unwrap();
+        rt.verify();
+    }
+
+    #[test]
+    fn message_fixtures() {
+        let path = std::env::temp_dir().join(format!("fixtures-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut rt = MockRuntime::default();
+        rt.fixtures_to(&path);
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(100));
+        rt.value_received = TokenAmount::from_nano(1_500_000_000);
+        rt.expect_validate_caller_any();
+        rt.expect_gas_charge(10);
+        let params = Some(IpldBlock::from_cbor(&1u64).unwrap());
+        rt.call::<TracedActor>(2, params).unwrap();
+        rt.verify();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let fixture: MessageFixture = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(
+            fixture,
+            MessageFixture {
+                from: "f0100".into(),
+                to: rt.receiver.to_string(),
+                method: 2,
+                params: "01".into(),
+                value: "1.5".into(),
+                exit_code: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_caller_addr_resolved() {
+        let key = Address::new_secp256k1(&[1; 65]).unwrap();
+        let mut rt = MockRuntime::default();
+        rt.add_id_address(key, Address::new_id(100));
+        rt.in_call = true;
+
+        // The caller is given by its ID and validated against its key, and the other way around.
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(100));
+        rt.expect_validate_caller_addr(vec![key]);
+        rt.validate_immediate_caller_is([key].iter()).unwrap();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, key);
+        rt.expect_validate_caller_addr(vec![Address::new_id(100)]);
+        rt.validate_immediate_caller_is([Address::new_id(100)].iter())
+            .unwrap();
+
+        // Unresolvable addresses only match themselves.
+        let other = Address::new_secp256k1(&[2; 65]).unwrap();
+        let third = Address::new_secp256k1(&[3; 65]).unwrap();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, other);
+        rt.expect_validate_caller_addr(vec![other]);
+        rt.validate_immediate_caller_is([other].iter()).unwrap();
+        rt.expect_validate_caller_addr(vec![third]);
+        let err = rt.validate_immediate_caller_is([third].iter()).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        rt.verify();
+    }
+
+    #[test]
+    fn validate_caller_not_type() {
+        let mut rt = MockRuntime::default();
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(100));
+        rt.in_call = true;
+
+        rt.expect_validate_caller_not_type(vec![*MULTISIG_ACTOR_CODE_ID]);
+        rt.validate_immediate_caller_not_type(&[Type::Multisig])
+            .unwrap();
+        rt.verify();
+
+        rt.expect_validate_caller_not_type(vec![*MULTISIG_ACTOR_CODE_ID, *ACCOUNT_ACTOR_CODE_ID]);
+        let err = rt
+            .validate_immediate_caller_not_type(&[Type::Multisig, Type::Account])
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        rt.verify();
+    }
+
+    #[test]
+    fn validate_caller_delegated() {
+        let eth = [0xaa; 20];
+        let mut rt = MockRuntime::default();
+        rt.set_caller(make_builtin(b"fil/test/evm"), Address::new_id(100));
+        rt.set_delegated_address(100, Address::new_delegated(EAM_ACTOR_ID, &eth).unwrap());
+        rt.in_call = true;
+
+        rt.expect_validate_caller_eth(eth);
+        rt.validate_immediate_caller_is_eth(&eth).unwrap();
+        rt.expect_validate_caller_namespace(EAM_ACTOR_ID);
+        rt.validate_immediate_caller_namespace(EAM_ACTOR_ID)
+            .unwrap();
+        rt.verify();
+
+        rt.expect_validate_caller_eth([0xbb; 20]);
+        let err = rt
+            .validate_immediate_caller_is_eth(&[0xbb; 20])
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+
+        // An ID-only caller has no delegated address.
+        rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(101));
+        rt.expect_validate_caller_namespace(EAM_ACTOR_ID);
+        let err = rt
+            .validate_immediate_caller_namespace(EAM_ACTOR_ID)
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_FORBIDDEN);
+        rt.verify();
+    }
+
+    #[test]
+    fn state_syscall_errors() {
+        let mut rt = MockRuntime::default();
+        rt.fail_state_write(ErrorNumber::ReadOnly);
+        let err = rt.create(&1u64).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_READ_ONLY);
+        assert!(rt.state.is_none());
+        rt.create(&1u64).unwrap();
+
+        rt.fail_state_read(ErrorNumber::IllegalOperation);
+        let err = rt.state::<u64>().unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+
+        let increment = |st: &mut u64, _: &mut MockRuntime| {
+            *st += 1;
+            Ok(())
+        };
+        rt.fail_state_read(ErrorNumber::IllegalOperation);
+        let err = rt.transaction(increment).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        rt.fail_state_write(ErrorNumber::IllegalOperation);
+        let err = rt.transaction(increment).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        assert_eq!(rt.state::<u64>().unwrap(), 1);
+
+        rt.transaction(increment).unwrap();
+        assert_eq!(rt.state::<u64>().unwrap(), 2);
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&1u64);
+        rt.set_balance(TokenAmount::from_atto(10));
+        rt.expect_validate_caller_any();
+        let snapshot = rt.snapshot();
+
+        rt.replace_state(&2u64);
+        rt.add_balance(TokenAmount::from_atto(5));
+        rt.reset();
+        assert_eq!(rt.get_state::<u64>(), 2);
+
+        rt.restore(&snapshot);
+        assert_eq!(rt.get_state::<u64>(), 1);
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(10));
+        assert!(rt.expectations.borrow().expect_validate_caller_any);
+
+        // The same snapshot can be restored again.
+        rt.reset();
+        rt.restore(&snapshot);
+        assert!(rt.expectations.borrow().expect_validate_caller_any);
+        rt.reset();
+    }
+
+    #[test]
+    fn send_empty_params_as_no_block() {
+        let mut rt = MockRuntime::default();
+        let to = Address::new_id(100);
+        rt.expect_send(to, 2, None, TokenAmount::zero(), None, ExitCode::OK);
+
+        rt.in_call = true;
+        let empty = IpldBlock {
+            codec: fvm_ipld_encoding::CBOR,
+            data: vec![],
+        };
+        rt.send(&to, 2, Some(empty), TokenAmount::zero()).unwrap();
+        rt.verify();
+    }
+
+    #[test]
+    fn init_exec() {
+        let mut rt = MockRuntime {
+            receiver: Address::new_id(10),
+            ..Default::default()
+        };
+        rt.set_balance(TokenAmount::from_atto(5));
+        let code = *ACCOUNT_ACTOR_CODE_ID;
+        let child = |id| InitExecReturn {
+            id_address: Address::new_id(id),
+            robust_address: Address::new_actor(&id.to_be_bytes()),
+        };
+        rt.expect_init_exec(code, &(1u64,), TokenAmount::from_atto(5), child(100));
+        rt.expect_init_exec4(code, &(2u64,), &[7; 20], TokenAmount::zero(), child(101));
+
+        rt.in_call = true;
+        let ret = crate::init_exec(&rt, code, &(1u64,), TokenAmount::from_atto(5)).unwrap();
+        assert_eq!(ret, child(100));
+        let ret = crate::init_exec4(&rt, code, &(2u64,), &[7; 20], TokenAmount::zero()).unwrap();
+        assert_eq!(ret.actor_id().unwrap(), 101);
+        rt.verify();
+
+        let delegated = Address::new_delegated(10, &[7; 20]).unwrap();
+        assert_eq!(
+            rt.resolve_address(&child(100).robust_address),
+            Some(Address::new_id(100))
+        );
+        assert_eq!(rt.resolve_address(&delegated), Some(Address::new_id(101)));
+        assert_eq!(rt.lookup_delegated_address(101), Some(delegated));
+        assert_eq!(rt.get_actor_code_cid(&101), Some(code));
+
+        let robust = InitExecReturn {
+            id_address: child(102).robust_address,
+            ..child(102)
+        };
+        assert_eq!(
+            robust.actor_id().unwrap_err().exit_code(),
+            ExitCode::USR_ILLEGAL_STATE
+        );
+    }
+
+    #[derive(num_derive::ToPrimitive)]
+    enum GatewayMethod {
+        Fund = 3,
+    }
+
+    struct Gateway;
+
+    impl ActorCode for Gateway {
+        type Methods = GatewayMethod;
+
+        fn invoke_method<RT>(
+            _: &mut RT,
+            _: MethodNum,
+            _: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn actor_calls() {
+        let mut rt = MockRuntime::default();
+        rt.set_balance(TokenAmount::from_atto(10));
+        let gateway: ActorHandle<Gateway> = ActorHandle::assume_interface(Address::new_id(64));
+        rt.expect_actor_call(
+            &gateway,
+            GatewayMethod::Fund,
+            &("/r314", 7u64),
+            TokenAmount::from_atto(10),
+            &1u64,
+        );
+
+        let ret = rt
+            .call_fn(|rt| {
+                let params = Some(IpldBlock::from_cbor(&("/r314", 7u64))?);
+                let ret =
+                    gateway.send(rt, GatewayMethod::Fund, params, TokenAmount::from_atto(10))?;
+                Ok(crate::deserialize_block::<u64>(ret)?)
+            })
+            .unwrap();
+        assert_eq!(ret, 1);
+        rt.verify();
+    }
+
+    #[test]
+    fn expectation_groups() {
+        let mut rt = MockRuntime::default();
+        rt.set_balance(TokenAmount::from_atto(10));
+        let to = Address::new_id(100);
+        rt.expect_group("submission", |rt| {
+            rt.expect_validate_caller_any();
+            rt.expect_send(to, 2, None, TokenAmount::zero(), None, ExitCode::OK);
+        });
+        rt.expect_send(to, 3, None, TokenAmount::zero(), None, ExitCode::OK);
+        rt.expect_group("reward", |rt| {
+            rt.expect_send(to, 4, None, TokenAmount::zero(), None, ExitCode::OK);
+        });
+
+        rt.in_call = true;
+        rt.validate_immediate_caller_accept_any().unwrap();
+        rt.send(&to, 2, None, TokenAmount::zero()).unwrap();
+        rt.verify_group("submission");
+
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            rt.verify_group("reward");
+        }))
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<String>().unwrap(),
+            "expectations of group \"reward\" not met: 1 Send"
+        );
+
+        // The ungrouped send is kept, and is now the first to be met.
+        rt.reset_group("reward");
+        rt.verify_group("reward");
+        rt.send(&to, 3, None, TokenAmount::zero()).unwrap();
+        rt.verify();
+
+        rt.expect_group("cleanup", |rt| rt.expect_delete_actor(to));
+        let err =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rt.verify())).unwrap_err();
+        assert!(err
+            .downcast_ref::<String>()
+            .unwrap()
+            .ends_with("(group \"cleanup\")"));
+        rt.reset();
+    }
+
+    #[test]
+    fn transaction_checks_state_invariants() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&1u64);
+        rt.set_state_invariants(StateInvariants::new(|st: &u64, acc| {
+            acc.require(*st < 10, format!("state {st} not below 10"));
+        }));
+        rt.in_call = true;
+
+        rt.transaction(|st: &mut u64, _| {
+            *st = 9;
+            Ok(())
+        })
+        .unwrap();
+        let err = rt
+            .transaction(|st: &mut u64, _| {
+                *st = 10;
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_STATE);
+        assert_eq!(
+            err.msg(),
+            "state invariants violated: state 10 not below 10"
+        );
+        assert_eq!(rt.get_state::<u64>(), 9);
+    }
+
+    #[test]
+    fn read_block_with_limit() {
+        let mut rt = MockRuntime::default();
+        let data = vec![7u8; 100];
+        let cid = rt
+            .store
+            .put(
+                Code::Blake2b256,
+                &fvm_ipld_blockstore::Block::new(0x55, &data),
+            )
+            .unwrap();
+        rt.in_call = true;
+
+        let block = rt.read_block(&cid, 100).unwrap();
+        assert_eq!(block.codec, 0x55);
+        assert_eq!(block.data, data);
+
+        let err = rt.read_block(&cid, 99).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let missing = Cid::new_v1(0x55, Code::Blake2b256.digest(b"missing"));
+        let err = rt.read_block(&missing, 100).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_NOT_FOUND);
+    }
+
+    struct GuardedActor;
+
+    impl ActorCode for GuardedActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            _: MethodNum,
+            params: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            rt.validate_immediate_caller_is(&[Address::new_id(100)])?;
+            let amount: u64 = crate::cbor::decode_block(&params)?;
+            if amount == 0 {
+                return Err(actor_error!(illegal_argument; "zero amount"));
+            }
+            Ok(None)
+        }
+    }
+
+    fn guarded_caller(caller: u64) -> impl Fn(&mut MockRuntime) {
+        move |rt: &mut MockRuntime| {
+            rt.set_caller(*ACCOUNT_ACTOR_CODE_ID, Address::new_id(caller));
+            rt.expect_validate_caller_addr(vec![Address::new_id(100)]);
+        }
+    }
+
+    #[test]
+    fn all_exit_codes_reached() {
+        let amount = |n: u64| Some(IpldBlock::from_cbor(&n).unwrap());
+        assert_all_exit_codes!(GuardedActor, 2,
+            declared: [ExitCode::USR_FORBIDDEN, ExitCode::USR_ILLEGAL_ARGUMENT],
+            cases: [
+                (guarded_caller(100), amount(1), ExitCode::OK),
+                (guarded_caller(101), None, ExitCode::USR_FORBIDDEN),
+                (guarded_caller(100), amount(0), ExitCode::USR_ILLEGAL_ARGUMENT),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not reached by any case: ExitCode::USR_ILLEGAL_ARGUMENT (16)")]
+    fn unreached_exit_code() {
+        assert_all_exit_codes!(GuardedActor, 2,
+            declared: [ExitCode::USR_FORBIDDEN, ExitCode::USR_ILLEGAL_ARGUMENT],
+            cases: [
+                (guarded_caller(101), None, ExitCode::USR_FORBIDDEN),
+            ]
+        );
+    }
+
+    #[test]
+    fn instrumented_runtime_counts() {
+        let mut rt = InstrumentedRuntime::new(MockRuntime::default());
+        rt.inner_mut().replace_state(&1u64);
+        rt.inner_mut().in_call = true;
+        rt.inner().expect_validate_caller_any();
+        rt.inner_mut().expect_gas_charge(10);
+        let event = EventBuilder::new().typ("bumped").build();
+        rt.inner_mut().expect_emitted_event(event.clone());
+
+        TracedActor::invoke_method(&mut rt, 2, None).unwrap();
+        rt.transaction(|st: &mut u64, rt| {
+            *st += 1;
+            let err = rt
+                .send(&Address::new_id(100), 0, None, TokenAmount::zero())
+                .unwrap_err();
+            assert_eq!(err.exit_code(), ExitCode::USR_ASSERTION_FAILED);
+            let err = rt.create(&0u64).unwrap_err();
+            assert_eq!(err.exit_code(), ExitCode::USR_ASSERTION_FAILED);
+            // Allowed by the inner runtime, so by the wrapper too.
+            rt.emit_event(&event)?;
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(rt.inner().get_state::<u64>(), 2);
+        assert_eq!(rt.summary(), "event=1 gas=1 send=1 state=2 validate=1");
+        rt.inner_mut().verify();
+    }
+
+    struct EventActor;
+
+    impl ActorCode for EventActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            _: MethodNum,
+            params: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            rt.validate_immediate_caller_accept_any()?;
+            let key: String = crate::cbor::decode_block(&params)?;
+            let event = EventBuilder::new()
+                .typ("updated")
+                .field(&key, &1u64)?
+                .build();
+            rt.emit_event(&event)?;
+            Ok(None)
+        }
+    }
+
+    fn emit_with_key(rt: &mut MockRuntime, key: &str) {
+        let event = EventBuilder::new()
+            .typ("updated")
+            .field(key, &1u64)
+            .unwrap()
+            .build();
+        rt.expect_validate_caller_any();
+        rt.expect_emitted_event(event);
+        let params = Some(IpldBlock::from_cbor(&key).unwrap());
+        rt.call::<EventActor>(2, params).unwrap();
+    }
+
+    #[test]
+    fn declared_event() {
+        let mut rt = MockRuntime::default();
+        rt.declare_events(
+            2,
+            vec![EventSchema {
+                typ: "updated",
+                fields: &[("version", EventValueType::Uint)],
+            }],
+        );
+        emit_with_key(&mut rt, "version");
+        rt.verify();
+    }
+
+    #[test]
+    #[should_panic(expected = "event not declared for method 2")]
+    fn undeclared_event() {
+        let mut rt = MockRuntime::default();
+        rt.declare_events(
+            2,
+            vec![EventSchema {
+                typ: "updated",
+                fields: &[("version", EventValueType::Uint)],
+            }],
+        );
+        emit_with_key(&mut rt, "height");
+    }
+
+    #[test]
+    #[should_panic(expected = "event not declared for method 2")]
+    fn event_with_undeclared_value_type() {
+        let mut rt = MockRuntime::default();
+        rt.declare_events(
+            2,
+            vec![EventSchema {
+                typ: "updated",
+                fields: &[("version", EventValueType::String)],
+            }],
+        );
+        emit_with_key(&mut rt, "version");
+    }
+
+    #[test]
+    fn event_value_types() {
+        fn event<T: Serialize>(value: &T) -> ActorEvent {
+            EventBuilder::new()
+                .typ("updated")
+                .field("value", value)
+                .unwrap()
+                .build()
+        }
+        let cases = [
+            (event(&1u64), EventValueType::Uint),
+            (event(&-1i64), EventValueType::Int),
+            (event(&Address::new_id(1)), EventValueType::Bytes),
+            (event(&"a"), EventValueType::String),
+            (event(&true), EventValueType::Bool),
+            (event(&vec![1u64]), EventValueType::Array),
+            (event(&BTreeMap::from([(1u64, 1u64)])), EventValueType::Map),
+            (event(&Cid::default()), EventValueType::Cid),
+        ];
+        for (event, typ) in &cases {
+            for (_, other) in &cases {
+                let schema = EventSchema {
+                    typ: "updated",
+                    fields: vec![("value", *other)].leak(),
+                };
+                let expected =
+                    typ == other || (*typ == EventValueType::Uint && *other == EventValueType::Int);
+                assert_eq!(schema.matches(event), expected, "{typ:?} as {other:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn create_state() {
+        let mut rt = MockRuntime::default();
+        rt.expect_create_state(|st: (u64, String)| {
+            assert_eq!(st, (0, "new".to_string()));
+        });
+        rt.call_fn(|rt| Ok(rt.create(&(0u64, "new"))?)).unwrap();
+        rt.verify();
+        assert!(rt.call_fn(|rt| Ok(rt.create(&(1u64, "again"))?)).is_err());
+
+        let mut rt = MockRuntime::default();
+        rt.expect_create_state(|_: (u64, String)| {});
+        let err =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| rt.verify())).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<String>().unwrap(),
+            "expected the state to be created, not received"
+        );
+    }
+
+    #[test]
+    fn state_transition() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&(1u64, "a".to_string()));
+        rt.expect_state_transition(|before: (u64, String), after: (u64, String)| {
+            assert_eq!(after.0, before.0 + 1);
+            assert_eq!(after.1, before.1);
+        });
+
+        rt.expect_validate_caller_any();
+        rt.call::<CountingActor>(2, None).unwrap();
+        // Only the first call after the expectation is captured.
+        rt.expect_validate_caller_any();
+        rt.call::<CountingActor>(2, None).unwrap();
+        rt.verify();
+
+        // Without a state before the call there is nothing to compare.
+        let mut rt = MockRuntime::default();
+        rt.expect_state_transition(|_: (u64, String), _: (u64, String)| unreachable!());
+        rt.expect_validate_caller_any();
+        rt.call::<CountingActor>(CONSTRUCT, None).unwrap();
+        rt.verify();
+    }
+
+    #[test]
+    fn fails_without_state_change() {
+        let mut rt = MockRuntime::default();
+        rt.replace_state(&(1u64, "a".to_string()));
+        rt.set_balance(TokenAmount::from_atto(10));
+
+        // The state committed before the failure is reverted.
+        rt.expect_validate_caller_any();
+        let err = assert_call_fails_without_state_change::<CountingActor, _>(
+            &mut rt,
+            FAIL_AFTER_COMMIT,
+            None,
+            ExitCode::USR_ILLEGAL_ARGUMENT,
+        );
+        assert_eq!(err.msg(), "failed after commit");
+        assert_eq!(rt.get_state::<(u64, String)>().0, 1);
+
+        // Unlike the value sent before it.
+        rt.expect_validate_caller_any();
+        rt.expect_send(
+            Address::new_id(100),
+            METHOD_SEND,
+            None,
+            TokenAmount::from_atto(1),
+            None,
+            ExitCode::OK,
+        );
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            assert_call_fails_without_state_change::<CountingActor, _>(
+                &mut rt,
+                FAIL_AFTER_SEND,
+                None,
+                ExitCode::USR_ILLEGAL_ARGUMENT,
+            )
+        }));
+        let msg = *res.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.contains("method 4 changed the balance"), "{msg}");
+    }
+
+    const FAIL_AFTER_COMMIT: MethodNum = 3;
+    const FAIL_AFTER_SEND: MethodNum = 4;
+    const CONSTRUCT: MethodNum = 5;
+
+    struct CountingActor;
+
+    impl ActorCode for CountingActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            method: MethodNum,
+            _: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            rt.validate_immediate_caller_accept_any()?;
+            if method == CONSTRUCT {
+                rt.create(&(0u64, String::new()))?;
+                return Ok(None);
+            }
+            rt.transaction(|st: &mut (u64, String), _| {
+                st.0 += 1;
+                Ok(())
+            })?;
+            match method {
+                FAIL_AFTER_COMMIT => Err(actor_error!(illegal_argument; "failed after commit")),
+                FAIL_AFTER_SEND => {
+                    rt.send(
+                        &Address::new_id(100),
+                        METHOD_SEND,
+                        None,
+                        TokenAmount::from_atto(1),
+                    )?;
+                    Err(actor_error!(illegal_argument; "failed after send"))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Running a single method of an actor on a state loaded from a CAR file, with a `MockRuntime`
+//! in `simulate` mode, for a fast edit and run loop without a devnet.
+//!
+//! The runtime can only run actor code linked into it, so every actor crate builds its own
+//! `fvm-utils-run`, e.g. as `src/bin/fvm-utils-run.rs` behind a feature:
+//!
+//! ```ignore
+//! fn main() {
+//!     fil_actors_runtime::test_utils::run::main::<Actor, State>(|_rt| {});
+//! }
+//! ```
+//!
+//! and runs it with e.g.
+//! `cargo run --features run --bin fvm-utils-run -- --car state.car --method 2 --params p.cbor`.
+
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::{CborStore, DAG_CBOR};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::MethodNum;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{CallTrace, MockRuntime};
+use crate::runtime::{ActorCode, PanicReport};
+use crate::util::parse_fil;
+
+pub const USAGE: &str = "usage: fvm-utils-run --car <state.car> --method <number> \
+    [--params <params.cbor>] [--caller <address>] [--receiver <address>] \
+    [--value <FIL>] [--balance <FIL>] [--epoch <epoch>]";
+
+/// What to run, from the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunArgs {
+    /// A CARv1 file whose first root is the state of the actor.
+    pub car: PathBuf,
+    pub method: MethodNum,
+    /// A file with the CBOR encoded parameters, if the method takes any.
+    pub params: Option<PathBuf>,
+    pub caller: Address,
+    pub receiver: Address,
+    pub value: TokenAmount,
+    pub balance: TokenAmount,
+    pub epoch: ChainEpoch,
+}
+
+impl RunArgs {
+    /// Parse the arguments, without the name of the program; see `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut car = None;
+        let mut method = None;
+        let mut run = RunArgs {
+            car: PathBuf::new(),
+            method: 0,
+            params: None,
+            caller: Address::new_id(100),
+            receiver: Address::new_id(1000),
+            value: TokenAmount::default(),
+            balance: TokenAmount::default(),
+            epoch: 0,
+        };
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("missing value of {flag}"))?;
+            let address = || Address::from_str(&value).with_context(|| format!("{flag} {value}"));
+            match flag.as_str() {
+                "--car" => car = Some(PathBuf::from(&value)),
+                "--method" => method = Some(value.parse().context("--method")?),
+                "--params" => run.params = Some(PathBuf::from(&value)),
+                "--caller" => run.caller = address()?,
+                "--receiver" => run.receiver = address()?,
+                "--value" => run.value = parse_fil(&value)?,
+                "--balance" => run.balance = parse_fil(&value)?,
+                "--epoch" => run.epoch = value.parse().context("--epoch")?,
+                other => bail!("unknown argument {other}"),
+            }
+        }
+        run.car = car.ok_or_else(|| anyhow!("--car is required"))?;
+        run.method = method.ok_or_else(|| anyhow!("--method is required"))?;
+        Ok(run)
+    }
+}
+
+/// The outcome of a method run with `run_method`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunReport {
+    /// The trace of the call, with the return value, or the error message if it failed.
+    pub trace: CallTrace,
+    /// The lines of the debug output of the state removed (`-`) and added (`+`) by the call.
+    pub state_diff: Vec<String>,
+}
+
+impl RunReport {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::new(self.trace.exit_code)
+    }
+
+    /// The gas charged explicitly by the actor, on top of that of the syscalls.
+    pub fn gas_total(&self) -> i64 {
+        self.trace.gas_charges.iter().map(|c| c.value).sum()
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let t = &self.trace;
+        writeln!(f, "exit code: {}", t.exit_code)?;
+        writeln!(f, "return: {}", t.ret)?;
+        writeln!(f, "validations: {}", t.validations.join(", "))?;
+        for send in &t.sends {
+            writeln!(
+                f,
+                "send: {} method {} value {} params {}",
+                send.to, send.method, send.value, send.params
+            )?;
+        }
+        writeln!(
+            f,
+            "state: {} -> {}",
+            t.state_before.as_deref().unwrap_or("none"),
+            t.state_after.as_deref().unwrap_or("none")
+        )?;
+        for line in &self.state_diff {
+            writeln!(f, "  {line}")?;
+        }
+        writeln!(f, "gas: {}", self.gas_total())?;
+        for charge in &t.gas_charges {
+            writeln!(f, "  {}: {}", charge.name, charge.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a method of actor `A`, whose state is of type `S`, as described by `args`.
+///
+/// `setup` can adjust the runtime before the call, e.g. to set a `trace_decoder` so that the
+/// parameters and return value are shown decoded rather than as hex.
+///
+/// A state that doesn't decode as `S` and a call that panics, e.g. on an expectation the
+/// runtime asserts, are errors rather than panics.
+pub fn run_method<A, S>(
+    args: &RunArgs,
+    setup: impl FnOnce(&mut MockRuntime),
+) -> anyhow::Result<RunReport>
+where
+    A: ActorCode,
+    S: DeserializeOwned + fmt::Debug,
+{
+    let mut rt = MockRuntime {
+        receiver: args.receiver,
+        caller: args.caller,
+        value_received: args.value.clone(),
+        epoch: args.epoch,
+        simulate: true,
+        ..Default::default()
+    };
+    rt.set_balance(args.balance.clone());
+
+    let car = std::fs::read(&args.car).with_context(|| format!("reading {:?}", args.car))?;
+    let roots = read_car(&car, rt.store.as_ref())?;
+    rt.state = Some(
+        *roots
+            .first()
+            .ok_or_else(|| anyhow!("the CAR file has no root"))?,
+    );
+    let params = match &args.params {
+        Some(path) => Some(IpldBlock {
+            codec: DAG_CBOR,
+            data: std::fs::read(path).with_context(|| format!("reading {path:?}"))?,
+        }),
+        None => None,
+    };
+
+    let trace_path =
+        std::env::temp_dir().join(format!("fvm-utils-run-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&trace_path);
+    rt.trace_to(&trace_path);
+    setup(&mut rt);
+
+    let before: S = load_state(&rt).context("state before the call")?;
+    let call = catch_unwind(AssertUnwindSafe(|| rt.call::<A>(args.method, params)));
+    let traces = CallTrace::read_all(&trace_path);
+    let _ = std::fs::remove_file(&trace_path);
+    if let Err(payload) = call {
+        bail!("the call panicked: {}", PanicReport::new(&*payload, None));
+    }
+    let after: S = load_state(&rt).context("state after the call")?;
+
+    let trace = traces?
+        .pop()
+        .ok_or_else(|| anyhow!("the call left no trace"))?;
+    Ok(RunReport {
+        trace,
+        state_diff: diff_lines(&format!("{before:#?}"), &format!("{after:#?}")),
+    })
+}
+
+fn load_state<S: DeserializeOwned>(rt: &MockRuntime) -> anyhow::Result<S> {
+    let root = rt.state.ok_or_else(|| anyhow!("the actor has no state"))?;
+    rt.store
+        .get_cbor(&root)
+        .with_context(|| format!("decoding state {root} as {}", std::any::type_name::<S>()))?
+        .ok_or_else(|| anyhow!("state {root} is not in the store"))
+}
+
+/// Run the method given on the command line and print its report, exiting with 1 if it
+/// failed and 2 if it couldn't be run.
+pub fn main<A, S>(setup: impl FnOnce(&mut MockRuntime))
+where
+    A: ActorCode,
+    S: DeserializeOwned + fmt::Debug,
+{
+    let args = match RunArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    match run_method::<A, S>(&args, setup) {
+        Ok(report) => {
+            print!("{report}");
+            if !report.exit_code().is_success() {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(2);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CarHeader {
+    roots: Vec<Cid>,
+    version: u64,
+}
+
+/// Put the blocks of a CARv1 file into `store`, returning its roots.
+pub fn read_car(car: &[u8], store: &impl Blockstore) -> anyhow::Result<Vec<Cid>> {
+    let (header, mut rest) = car_section(car)?;
+    let header: CarHeader = fvm_ipld_encoding::from_slice(header).context("CAR header")?;
+    if header.version != 1 {
+        bail!("unsupported CAR version {}", header.version);
+    }
+    while !rest.is_empty() {
+        let (section, next) = car_section(rest)?;
+        let mut cursor = std::io::Cursor::new(section);
+        let cid = Cid::read_bytes(&mut cursor).context("CAR block")?;
+        store.put_keyed(&cid, &section[cursor.position() as usize..])?;
+        rest = next;
+    }
+    Ok(header.roots)
+}
+
+/// A CARv1 file of the given blocks, e.g. to save the state of a test for `fvm-utils-run`.
+pub fn write_car(
+    roots: &[Cid],
+    blocks: impl IntoIterator<Item = (Cid, Vec<u8>)>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut car = Vec::new();
+    let header = CarHeader {
+        roots: roots.to_vec(),
+        version: 1,
+    };
+    push_section(&mut car, &fvm_ipld_encoding::to_vec(&header)?);
+    for (cid, data) in blocks {
+        let mut section = cid.to_bytes();
+        section.extend_from_slice(&data);
+        push_section(&mut car, &section);
+    }
+    Ok(car)
+}
+
+/// Split off a section prefixed with its length as a varint.
+fn car_section(bytes: &[u8]) -> anyhow::Result<(&[u8], &[u8])> {
+    let (len, rest) = unsigned_varint::decode::u64(bytes)
+        .map_err(|e| anyhow!("invalid CAR section length: {e}"))?;
+    let len = usize::try_from(len)?;
+    if rest.len() < len {
+        bail!("truncated CAR section of {len} bytes");
+    }
+    Ok(rest.split_at(len))
+}
+
+fn push_section(car: &mut Vec<u8>, section: &[u8]) {
+    let mut len = unsigned_varint::encode::u64_buffer();
+    car.extend_from_slice(unsigned_varint::encode::u64(section.len() as u64, &mut len));
+    car.extend_from_slice(section);
+}
+
+/// The lines removed from `old` and added in `new`, as `- line` and `+ line`, in order.
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    // Length of the longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut diff) = (0, 0, Vec::new());
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("- {}", a[i].trim()));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", b[j].trim()));
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use cid::multihash::Code;
+    use fvm_ipld_blockstore::{Blockstore, MemoryBlockstore};
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::{to_vec, CborStore};
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::{MethodNum, METHOD_SEND};
+
+    use super::{diff_lines, read_car, run_method, write_car, RunArgs};
+    use crate::runtime::{ActorCode, Runtime};
+    use crate::ActorError;
+
+    struct CountingActor;
+
+    impl ActorCode for CountingActor {
+        type Methods = ();
+
+        fn invoke_method<RT>(
+            rt: &mut RT,
+            method: MethodNum,
+            params: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            rt.validate_immediate_caller_is(&[Address::new_id(100)])?;
+            assert_ne!(method, 3, "method 3 is broken");
+            rt.charge_gas("counting", 7);
+            rt.transaction(|st: &mut (u64, String), _| {
+                st.0 += 1;
+                Ok(())
+            })?;
+            rt.send(
+                &Address::new_id(101),
+                METHOD_SEND,
+                None,
+                TokenAmount::from_atto(1),
+            )?;
+            Ok(params)
+        }
+    }
+
+    #[test]
+    fn runs_method_on_car() {
+        let store = MemoryBlockstore::new();
+        let state = (41u64, "counter".to_string());
+        let root = store.put_cbor(&state, Code::Blake2b256).unwrap();
+        let car = write_car(&[root], [(root, to_vec(&state).unwrap())]).unwrap();
+
+        let copy = MemoryBlockstore::new();
+        assert_eq!(read_car(&car, &copy).unwrap(), vec![root]);
+        assert_eq!(copy.get_cbor::<(u64, String)>(&root).unwrap(), Some(state));
+
+        let dir = std::env::temp_dir();
+        let car_path = dir.join(format!("run-state-{}.car", std::process::id()));
+        let params_path = dir.join(format!("run-params-{}.cbor", std::process::id()));
+        std::fs::write(&car_path, &car).unwrap();
+        std::fs::write(&params_path, to_vec(&5u64).unwrap()).unwrap();
+        let args = |extra: &[&str]| {
+            let mut args = vec![
+                "--car".to_string(),
+                car_path.to_str().unwrap().to_string(),
+                "--method".to_string(),
+                "2".to_string(),
+            ];
+            args.extend(extra.iter().map(|s| s.to_string()));
+            RunArgs::parse(args).unwrap()
+        };
+
+        let params = params_path.to_str().unwrap();
+        let report = run_method::<CountingActor, (u64, String)>(
+            &args(&["--params", params, "--balance", "1"]),
+            |_| {},
+        )
+        .unwrap();
+        assert!(report.exit_code().is_success(), "{report}");
+        assert_eq!(report.trace.ret, "05");
+        assert_eq!(report.trace.sends[0].to, "f0101");
+        assert_eq!(report.gas_total(), 7);
+        assert_eq!(report.state_diff, vec!["- 41,", "+ 42,"]);
+
+        // Validations are still enforced.
+        let report =
+            run_method::<CountingActor, (u64, String)>(&args(&["--caller", "f0102"]), |_| {})
+                .unwrap();
+        assert_eq!(
+            report.exit_code(),
+            fvm_shared::error::ExitCode::USR_FORBIDDEN
+        );
+        assert!(report.state_diff.is_empty());
+
+        // Failures to run are errors.
+        let err = run_method::<CountingActor, u64>(&args(&[]), |_| {}).unwrap_err();
+        assert!(format!("{err:#}").starts_with("state before the call: decoding state"));
+        let mut broken = args(&[]);
+        broken.method = 3;
+        let err = run_method::<CountingActor, (u64, String)>(&broken, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("method 3 is broken"), "{err}");
+
+        let _ = std::fs::remove_file(&car_path);
+        let _ = std::fs::remove_file(&params_path);
+        assert!(RunArgs::parse(["--car".to_string(), "x".to_string()]).is_err());
+        assert_eq!(diff_lines("a\nb\nc", "a\nc\nd"), vec!["- b", "+ d"]);
+    }
+}
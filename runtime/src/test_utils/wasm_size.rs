@@ -0,0 +1,470 @@
+//! Size of an actor's Wasm binary, by section and by function, checked against a budget kept
+//! next to the actor, as every byte of the binary costs gas when the actor is deployed.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// Set to rewrite the budget with the current sizes rather than check them.
+pub const UPDATE_BUDGET_VAR: &str = "UPDATE_WASM_SIZE_BUDGET";
+
+/// Number of functions listed in a report, from the largest.
+pub const LARGEST_FUNCTIONS: usize = 10;
+
+/// Sizes of a Wasm binary, in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmSizeReport {
+    pub total: usize,
+    /// Size of the payload of each section, e.g. `code` or `custom:name`, in binary order.
+    pub sections: Vec<(String, usize)>,
+    /// The largest function bodies with their names, from the name section or the exports,
+    /// or else `func[index]`.
+    pub largest_functions: Vec<(String, usize)>,
+}
+
+/// The most a binary may weigh in total and in the given sections, as checked in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmSizeBudget {
+    pub total: usize,
+    #[serde(default)]
+    pub sections: BTreeMap<String, usize>,
+}
+
+impl WasmSizeReport {
+    pub fn parse(wasm: &[u8]) -> anyhow::Result<Self> {
+        let mut r = Reader {
+            bytes: wasm,
+            pos: 0,
+        };
+        if r.take(4)? != b"\0asm" {
+            bail!("not a Wasm binary");
+        }
+        r.take(4)?;
+
+        let mut sections = Vec::new();
+        let mut imported_functions = 0;
+        let mut bodies = Vec::new();
+        let mut names = BTreeMap::new();
+        let mut exports = BTreeMap::new();
+        while !r.done() {
+            let id = r.byte()?;
+            let len = r.leb()? as usize;
+            let mut payload = Reader {
+                bytes: r.take(len)?,
+                pos: 0,
+            };
+            let name = match id {
+                0 => format!("custom:{}", payload.name()?),
+                _ => SECTION_NAMES
+                    .get(id as usize)
+                    .ok_or_else(|| anyhow!("unknown section {id}"))?
+                    .to_string(),
+            };
+            match name.as_str() {
+                "import" => imported_functions = count_imported_functions(&mut payload)?,
+                "export" => exports = read_exported_functions(&mut payload)?,
+                "code" => {
+                    for _ in 0..payload.leb()? {
+                        let size = payload.leb()? as usize;
+                        payload.take(size)?;
+                        bodies.push(size);
+                    }
+                }
+                "custom:name" => names = read_function_names(&mut payload)?,
+                _ => {}
+            }
+            sections.push((name, len));
+        }
+
+        let mut largest_functions: Vec<(String, usize)> = bodies
+            .into_iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let index = imported_functions + i as u32;
+                let name = names
+                    .get(&index)
+                    .or_else(|| exports.get(&index))
+                    .cloned()
+                    .unwrap_or_else(|| format!("func[{index}]"));
+                (name, size)
+            })
+            .collect();
+        largest_functions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        largest_functions.truncate(LARGEST_FUNCTIONS);
+
+        Ok(Self {
+            total: wasm.len(),
+            sections,
+            largest_functions,
+        })
+    }
+
+    /// Size of the payload of a section, summing the custom sections of the same name.
+    pub fn section(&self, name: &str) -> usize {
+        self.sections
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, size)| size)
+            .sum()
+    }
+
+    /// A budget of exactly the current sizes, for the total and the sections other than the
+    /// custom ones, which tooling may strip.
+    pub fn to_budget(&self) -> WasmSizeBudget {
+        WasmSizeBudget {
+            total: self.total,
+            sections: self
+                .sections
+                .iter()
+                .filter(|(name, _)| !name.starts_with("custom:"))
+                .map(|(name, _)| (name.clone(), self.section(name)))
+                .collect(),
+        }
+    }
+
+    /// Every size over the budget, e.g. `"code: 1200 bytes, budget 1000"`.
+    pub fn over_budget(&self, budget: &WasmSizeBudget) -> Vec<String> {
+        let mut over = Vec::new();
+        if self.total > budget.total {
+            over.push(format!(
+                "total: {} bytes, budget {}",
+                self.total, budget.total
+            ));
+        }
+        for (name, max) in &budget.sections {
+            let size = self.section(name);
+            if size > *max {
+                over.push(format!("{name}: {size} bytes, budget {max}"));
+            }
+        }
+        over
+    }
+}
+
+impl fmt::Display for WasmSizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<32} {:>10}", "total", self.total)?;
+        for (name, size) in &self.sections {
+            writeln!(f, "  {:<30} {:>10}", name, size)?;
+        }
+        writeln!(f, "largest functions")?;
+        for (name, size) in &self.largest_functions {
+            writeln!(f, "  {:<30} {:>10}", name, size)?;
+        }
+        Ok(())
+    }
+}
+
+/// What `check_wasm_size` did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasmSizeCheck {
+    /// There was no binary to check.
+    Skipped,
+    /// The binary is within the budget.
+    Checked(WasmSizeReport),
+    /// The budget was rewritten with the sizes of the binary.
+    Updated(WasmSizeReport),
+}
+
+/// Report the size of `wasm` and fail if it is over the budget stored as JSON at
+/// `budget_path`, or if there is no budget there. The budget is written instead if
+/// `UPDATE_WASM_SIZE_BUDGET` is set, e.g. the first time or after a deliberate increase.
+///
+/// Skips the check without a binary, i.e. when the Wasm build was skipped or the target
+/// toolchain isn't installed.
+pub fn check_wasm_size(
+    wasm: Option<&[u8]>,
+    budget_path: impl AsRef<Path>,
+) -> anyhow::Result<WasmSizeCheck> {
+    let Some(wasm) = wasm else {
+        return Ok(WasmSizeCheck::Skipped);
+    };
+    let path = budget_path.as_ref();
+    let report = WasmSizeReport::parse(wasm).context("failed to parse Wasm binary")?;
+
+    if std::env::var_os(UPDATE_BUDGET_VAR).is_some() {
+        let json = serde_json::to_string_pretty(&report.to_budget())?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("failed to write Wasm size budget {}", path.display()))?;
+        return Ok(WasmSizeCheck::Updated(report));
+    }
+    if !path.exists() {
+        bail!(
+            "no Wasm size budget at {}\nset {} to write one",
+            path.display(),
+            UPDATE_BUDGET_VAR
+        );
+    }
+    let json = std::fs::read(path)
+        .with_context(|| format!("failed to read Wasm size budget {}", path.display()))?;
+    let budget: WasmSizeBudget = serde_json::from_slice(&json)
+        .with_context(|| format!("failed to parse Wasm size budget {}", path.display()))?;
+    let over = report.over_budget(&budget);
+    if !over.is_empty() {
+        bail!(
+            "Wasm binary over the budget in {}:\n{}\n{}set {} to accept the new sizes",
+            path.display(),
+            over.join("\n"),
+            report,
+            UPDATE_BUDGET_VAR
+        );
+    }
+    Ok(WasmSizeCheck::Checked(report))
+}
+
+/// Define a test reporting the size of an actor's Wasm binary and checking it against the
+/// budget in a JSON file, relative to the crate of the test.
+///
+/// ```ignore
+/// include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+///
+/// wasm_size_report!(WASM_BINARY, "wasm_size_budget.json");
+/// ```
+#[macro_export]
+macro_rules! wasm_size_report {
+    ($wasm:expr, $budget:literal) => {
+        #[test]
+        fn wasm_size_report() {
+            let check = $crate::test_utils::wasm_size::check_wasm_size(
+                $wasm,
+                concat!(env!("CARGO_MANIFEST_DIR"), "/", $budget),
+            )
+            .unwrap_or_else(|e| panic!("{e:#}"));
+            if check == $crate::test_utils::wasm_size::WasmSizeCheck::Skipped {
+                eprintln!(
+                    "no compacted Wasm binary, not checking it against {}",
+                    $budget
+                );
+            }
+        }
+    };
+}
+
+const SECTION_NAMES: [&str; 13] = [
+    "custom",
+    "type",
+    "import",
+    "function",
+    "table",
+    "memory",
+    "global",
+    "export",
+    "start",
+    "element",
+    "code",
+    "data",
+    "datacount",
+];
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow!("unexpected end of Wasm binary"))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// An unsigned LEB128 integer of up to 32 bits.
+    fn leb(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let b = self.byte()?;
+            value |= ((b & 0x7f) as u32) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("invalid LEB128 integer")
+    }
+
+    fn name(&mut self) -> anyhow::Result<String> {
+        let len = self.leb()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn limits(&mut self) -> anyhow::Result<()> {
+        let flags = self.byte()?;
+        self.leb()?;
+        if flags & 1 != 0 {
+            self.leb()?;
+        }
+        Ok(())
+    }
+}
+
+fn count_imported_functions(r: &mut Reader) -> anyhow::Result<u32> {
+    let mut functions = 0;
+    for _ in 0..r.leb()? {
+        r.name()?;
+        r.name()?;
+        match r.byte()? {
+            0 => {
+                r.leb()?;
+                functions += 1;
+            }
+            1 => {
+                r.byte()?;
+                r.limits()?;
+            }
+            2 => r.limits()?,
+            3 => {
+                r.take(2)?;
+            }
+            kind => bail!("unknown import kind {kind}"),
+        }
+    }
+    Ok(functions)
+}
+
+fn read_exported_functions(r: &mut Reader) -> anyhow::Result<BTreeMap<u32, String>> {
+    let mut names = BTreeMap::new();
+    for _ in 0..r.leb()? {
+        let name = r.name()?;
+        let kind = r.byte()?;
+        let index = r.leb()?;
+        if kind == 0 {
+            names.entry(index).or_insert(name);
+        }
+    }
+    Ok(names)
+}
+
+/// The function names subsection of the name section.
+fn read_function_names(r: &mut Reader) -> anyhow::Result<BTreeMap<u32, String>> {
+    let mut names = BTreeMap::new();
+    while !r.done() {
+        let id = r.byte()?;
+        let len = r.leb()? as usize;
+        let mut sub = Reader {
+            bytes: r.take(len)?,
+            pos: 0,
+        };
+        if id == 1 {
+            for _ in 0..sub.leb()? {
+                let index = sub.leb()?;
+                names.insert(index, sub.name()?);
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_wasm_size, WasmSizeBudget, WasmSizeCheck, WasmSizeReport};
+
+    /// A module importing one function and defining two, the second of which is exported
+    /// as `invoke` and named `run` in the name section.
+    fn module() -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        let mut section = |id: u8, payload: &[u8]| {
+            wasm.push(id);
+            wasm.push(payload.len() as u8);
+            wasm.extend_from_slice(payload);
+        };
+        // type: () -> ()
+        section(1, &[1, 0x60, 0, 0]);
+        // import: env.log as a function of type 0
+        section(2, &[1, 3, b'e', b'n', b'v', 3, b'l', b'o', b'g', 0, 0]);
+        section(3, &[2, 0, 0]);
+        // export: invoke = function 2
+        section(7, &[1, 6, b'i', b'n', b'v', b'o', b'k', b'e', 0, 2]);
+        // code: bodies of 2 and 4 bytes
+        section(10, &[2, 2, 0, 0x0b, 4, 0, 0x01, 0x01, 0x0b]);
+        // name: function 2 is `run`
+        section(
+            0,
+            &[4, b'n', b'a', b'm', b'e', 1, 6, 1, 2, 3, b'r', b'u', b'n'],
+        );
+        wasm
+    }
+
+    #[test]
+    fn report_and_budget() {
+        let wasm = module();
+        let report = WasmSizeReport::parse(&wasm).unwrap();
+        assert_eq!(report.total, wasm.len());
+        let names: Vec<_> = report.sections.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "type",
+                "import",
+                "function",
+                "export",
+                "code",
+                "custom:name"
+            ]
+        );
+        assert_eq!(report.section("code"), 9);
+        assert_eq!(
+            report.largest_functions,
+            vec![("run".to_string(), 4), ("func[1]".to_string(), 2)]
+        );
+
+        let budget = report.to_budget();
+        assert!(!budget.sections.contains_key("custom:name"));
+        assert!(report.over_budget(&budget).is_empty());
+        let tight = WasmSizeBudget {
+            total: wasm.len() - 1,
+            sections: [("code".to_string(), 8)].into_iter().collect(),
+        };
+        assert_eq!(
+            report.over_budget(&tight),
+            vec![
+                format!("total: {} bytes, budget {}", wasm.len(), wasm.len() - 1),
+                "code: 9 bytes, budget 8".to_string(),
+            ]
+        );
+        assert!(WasmSizeReport::parse(b"\0asm\x01\0\0\0\x0a\x05").is_err());
+    }
+
+    #[test]
+    fn check_against_budget_file() {
+        let wasm = module();
+        let report = WasmSizeReport::parse(&wasm).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("wasm_size_budget_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            check_wasm_size(None, &path).unwrap(),
+            WasmSizeCheck::Skipped
+        );
+        let err = check_wasm_size(Some(&wasm), &path).unwrap_err();
+        assert!(err.to_string().starts_with("no Wasm size budget"), "{err}");
+
+        let mut budget = report.to_budget();
+        std::fs::write(&path, serde_json::to_string(&budget).unwrap()).unwrap();
+        assert_eq!(
+            check_wasm_size(Some(&wasm), &path).unwrap(),
+            WasmSizeCheck::Checked(report)
+        );
+
+        budget.total -= 1;
+        std::fs::write(&path, serde_json::to_string(&budget).unwrap()).unwrap();
+        let err = check_wasm_size(Some(&wasm), &path).unwrap_err();
+        assert!(err.to_string().contains("over the budget"), "{err}");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
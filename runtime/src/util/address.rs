@@ -0,0 +1,66 @@
+use fvm_shared::address::{Address, Protocol};
+use fvm_shared::ActorID;
+
+use crate::runtime::Runtime;
+use crate::{actor_error, ActorError};
+
+/// Checked conversions of addresses, failing with an `ActorError` that names the address.
+///
+/// An address of the wrong protocol is `USR_ILLEGAL_ARGUMENT`,
+/// one that can't be resolved to an actor is `USR_NOT_FOUND`.
+pub trait AddressExt {
+    /// The actor ID of an ID address.
+    fn expect_id(&self) -> Result<ActorID, ActorError>;
+
+    /// The actor ID of an ID address, or of the actor the address resolves to.
+    fn as_id_or_resolve(&self, rt: &impl Runtime) -> Result<ActorID, ActorError>;
+
+    /// Check that the address is of the given protocol.
+    fn require_protocol(&self, protocol: Protocol) -> Result<(), ActorError>;
+}
+
+impl AddressExt for Address {
+    fn expect_id(&self) -> Result<ActorID, ActorError> {
+        self.id()
+            .map_err(|_| actor_error!(illegal_argument; "expected an ID address, got {}", self))
+    }
+
+    fn as_id_or_resolve(&self, rt: &impl Runtime) -> Result<ActorID, ActorError> {
+        if let Ok(id) = self.id() {
+            return Ok(id);
+        }
+        rt.resolve_address(self)
+            .ok_or_else(|| actor_error!(not_found; "failed to resolve address {} to an ID", self))?
+            .expect_id()
+    }
+
+    fn require_protocol(&self, protocol: Protocol) -> Result<(), ActorError> {
+        if self.protocol() != protocol {
+            return Err(actor_error!(illegal_argument;
+                "expected a {:?} address, got {}", protocol, self));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::{Address, Protocol};
+    use fvm_shared::error::ExitCode;
+
+    use super::AddressExt;
+
+    #[test]
+    fn checked_protocols() {
+        let id = Address::new_id(100);
+        let secp = Address::new_secp256k1(&[1u8; 65]).unwrap();
+
+        assert_eq!(id.expect_id().unwrap(), 100);
+        let err = secp.expect_id().unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        secp.require_protocol(Protocol::Secp256k1).unwrap();
+        let err = id.require_protocol(Protocol::BLS).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+    }
+}
@@ -0,0 +1,222 @@
+use fvm_ipld_encoding::tuple::*;
+use fvm_shared::error::ExitCode;
+
+use crate::{actor_error, ActorError};
+
+/// The exit code of an item of a batch that failed, by its index in the input.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailCode {
+    pub idx: u32,
+    pub code: ExitCode,
+}
+
+/// The outcome of a batch operation where items may fail individually without failing the
+/// whole batch, as returned by the batch methods of the builtin actors.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq, Eq, Default)]
+pub struct BatchReturn {
+    /// Number of items that succeeded.
+    pub success_count: u32,
+    /// The items that failed, in input order.
+    pub fail_codes: Vec<FailCode>,
+}
+
+impl BatchReturn {
+    pub const fn empty() -> Self {
+        Self {
+            success_count: 0,
+            fail_codes: Vec::new(),
+        }
+    }
+
+    /// A batch of `n` items that all succeeded.
+    pub const fn ok(n: u32) -> Self {
+        Self {
+            success_count: n,
+            fail_codes: Vec::new(),
+        }
+    }
+
+    /// Number of items in the batch.
+    pub fn size(&self) -> usize {
+        self.success_count as usize + self.fail_codes.len()
+    }
+
+    pub fn all_ok(&self) -> bool {
+        self.fail_codes.is_empty()
+    }
+
+    /// Checks that the failed items are in input order and within the batch, as they are when
+    /// built by `BatchReturnGen`, but not necessarily when decoded from a return value.
+    pub fn validate(&self) -> Result<(), ActorError> {
+        let mut next = 0;
+        for fail in &self.fail_codes {
+            let idx = fail.idx as usize;
+            if idx < next || idx >= self.size() {
+                return Err(actor_error!(illegal_state;
+                    "batch of {} items has a failure at index {} out of order or range",
+                    self.size(), fail.idx));
+            }
+            next = idx + 1;
+        }
+        Ok(())
+    }
+
+    /// The exit code of every item, `OK` for the successful ones, in input order.
+    pub fn codes(&self) -> Result<Vec<ExitCode>, ActorError> {
+        self.validate()?;
+        let mut codes = vec![ExitCode::OK; self.size()];
+        for fail in &self.fail_codes {
+            codes[fail.idx as usize] = fail.code;
+        }
+        Ok(codes)
+    }
+
+    /// The input items that succeeded, given the input of the batch.
+    pub fn successes<T: Clone>(&self, items: &[T]) -> Result<Vec<T>, ActorError> {
+        self.validate()?;
+        if items.len() != self.size() {
+            return Err(actor_error!(illegal_argument;
+                "{} items don't match the batch of {}", items.len(), self.size()));
+        }
+        let mut fails = self.fail_codes.iter().peekable();
+        Ok(items
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| match fails.peek() {
+                Some(fail) if fail.idx as usize == *i => {
+                    fails.next();
+                    false
+                }
+                _ => true,
+            })
+            .map(|(_, item)| item.clone())
+            .collect())
+    }
+}
+
+/// Builds a `BatchReturn` as the items of a batch are processed in order.
+#[derive(Debug)]
+pub struct BatchReturnGen {
+    success_count: u32,
+    fail_codes: Vec<FailCode>,
+    expect_count: usize,
+}
+
+impl BatchReturnGen {
+    /// Start a batch of `expect_count` items.
+    pub fn new(expect_count: usize) -> Self {
+        Self {
+            success_count: 0,
+            fail_codes: Vec::new(),
+            expect_count,
+        }
+    }
+
+    pub fn add_success(&mut self) -> &mut Self {
+        self.success_count += 1;
+        self
+    }
+
+    pub fn add_fail(&mut self, code: ExitCode) -> &mut Self {
+        let idx = self.success_count + self.fail_codes.len() as u32;
+        self.fail_codes.push(FailCode { idx, code });
+        self
+    }
+
+    /// Finish the batch, which must have had an outcome added for every item.
+    pub fn gen(&self) -> BatchReturn {
+        let size = self.success_count as usize + self.fail_codes.len();
+        assert_eq!(
+            size, self.expect_count,
+            "batch of {} items has {} outcomes",
+            self.expect_count, size
+        );
+        BatchReturn {
+            success_count: self.success_count,
+            fail_codes: self.fail_codes.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::error::ExitCode;
+
+    use super::{BatchReturn, BatchReturnGen, FailCode};
+
+    #[test]
+    fn generate_batch_return() {
+        let ret = BatchReturnGen::new(4)
+            .add_success()
+            .add_fail(ExitCode::USR_NOT_FOUND)
+            .add_success()
+            .add_fail(ExitCode::USR_FORBIDDEN)
+            .gen();
+
+        assert_eq!(ret.success_count, 2);
+        assert_eq!(
+            ret.fail_codes,
+            vec![
+                FailCode {
+                    idx: 1,
+                    code: ExitCode::USR_NOT_FOUND
+                },
+                FailCode {
+                    idx: 3,
+                    code: ExitCode::USR_FORBIDDEN
+                },
+            ]
+        );
+        assert!(!ret.all_ok());
+        assert_eq!(
+            ret.codes().unwrap(),
+            vec![
+                ExitCode::OK,
+                ExitCode::USR_NOT_FOUND,
+                ExitCode::OK,
+                ExitCode::USR_FORBIDDEN
+            ]
+        );
+        assert_eq!(
+            ret.successes(&["a", "b", "c", "d"]).unwrap(),
+            vec!["a", "c"]
+        );
+        assert!(ret.successes(&["a", "b"]).is_err());
+
+        let bytes = fvm_ipld_encoding::to_vec(&ret).unwrap();
+        assert_eq!(
+            fvm_ipld_encoding::from_slice::<BatchReturn>(&bytes).unwrap(),
+            ret
+        );
+
+        assert!(BatchReturn::ok(3).all_ok());
+        assert_eq!(BatchReturn::empty().size(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_decoded_batches() {
+        let fail = |idx| FailCode {
+            idx,
+            code: ExitCode::USR_NOT_FOUND,
+        };
+        for fail_codes in [vec![fail(2)], vec![fail(1), fail(0)], vec![fail(u32::MAX)]] {
+            let ret = BatchReturn {
+                success_count: 1,
+                fail_codes,
+            };
+            let bytes = fvm_ipld_encoding::to_vec(&ret).unwrap();
+            let ret: BatchReturn = fvm_ipld_encoding::from_slice(&bytes).unwrap();
+            assert_eq!(
+                ret.codes().unwrap_err().exit_code(),
+                ExitCode::USR_ILLEGAL_STATE
+            );
+            assert!(ret.successes(&[(); 3]).is_err());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "batch of 2 items has 1 outcomes")]
+    fn missing_outcomes() {
+        BatchReturnGen::new(2).add_success().gen();
+    }
+}
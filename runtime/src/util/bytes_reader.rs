@@ -0,0 +1,127 @@
+use std::io;
+
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+
+use crate::ActorError;
+
+/// A source of bytes that can be read piecewise, e.g. a block still held by the FVM,
+/// so that large payloads such as Wasm modules or proofs can be processed, or decoded,
+/// without first being copied into a buffer of their own.
+pub trait BytesReader {
+    /// The total number of bytes.
+    fn size(&self) -> u32;
+
+    /// Reads bytes starting at `offset` into `buf`, returning how many were read,
+    /// which is less than the length of `buf` only at the end.
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<usize, ActorError>;
+
+    /// Calls `f` with consecutive chunks of at most `chunk_len` bytes, reusing one buffer.
+    fn for_each_chunk<F>(&self, chunk_len: usize, mut f: F) -> Result<(), ActorError>
+    where
+        F: FnMut(&[u8]) -> Result<(), ActorError>,
+    {
+        let mut buf = vec![0u8; chunk_len.min(self.size() as usize)];
+        let mut offset = 0;
+        while offset < self.size() {
+            let n = self.read_at(offset, &mut buf)?;
+            if n == 0 {
+                return Err(ActorError::illegal_state(format!(
+                    "unexpected end of bytes at {} of {}",
+                    offset,
+                    self.size()
+                )));
+            }
+            f(&buf[..n])?;
+            offset += n as u32;
+        }
+        Ok(())
+    }
+
+    /// The bytes as an `io::Read`, e.g. to decode them with `cbor::deserialize_reader`.
+    fn reader(&self) -> Reader<'_, Self> {
+        Reader {
+            bytes: self,
+            offset: 0,
+        }
+    }
+}
+
+impl BytesReader for [u8] {
+    fn size(&self) -> u32 {
+        self.len() as u32
+    }
+
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<usize, ActorError> {
+        let rest = self.get(offset as usize..).unwrap_or_default();
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        Ok(n)
+    }
+}
+
+/// Reads the data of the block, which is already in memory.
+impl BytesReader for IpldBlock {
+    fn size(&self) -> u32 {
+        self.data.as_slice().size()
+    }
+
+    fn read_at(&self, offset: u32, buf: &mut [u8]) -> Result<usize, ActorError> {
+        self.data.as_slice().read_at(offset, buf)
+    }
+}
+
+/// An `io::Read` over a `BytesReader`.
+pub struct Reader<'a, R: ?Sized> {
+    bytes: &'a R,
+    offset: u32,
+}
+
+impl<R: BytesReader + ?Sized> io::Read for Reader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self
+            .bytes
+            .read_at(self.offset, buf)
+            .map_err(|e| io::Error::other(e.msg().to_string()))?;
+        self.offset += n as u32;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::CBOR;
+
+    use super::BytesReader;
+
+    #[test]
+    fn read_in_chunks() {
+        let block = IpldBlock {
+            codec: CBOR,
+            data: (0..=255u8).collect(),
+        };
+
+        let mut chunks = Vec::new();
+        block
+            .for_each_chunk(100, |chunk| {
+                chunks.push(chunk.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![100, 100, 56]
+        );
+        assert_eq!(chunks.concat(), block.data);
+
+        let mut buf = [0u8; 10];
+        assert_eq!(block.read_at(250, &mut buf).unwrap(), 6);
+        assert_eq!(block.read_at(300, &mut buf).unwrap(), 0);
+
+        let mut read = Vec::new();
+        block.reader().read_to_end(&mut read).unwrap();
+        assert_eq!(read, block.data);
+    }
+}
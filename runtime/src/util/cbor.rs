@@ -1,6 +1,11 @@
-use fvm_ipld_encoding::{to_vec, RawBytes};
+use std::io;
+
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_ipld_encoding::{to_vec, RawBytes, CBOR, DAG_CBOR};
 use serde::{de, ser};
 
+use crate::runtime::Policy;
+use crate::util::BytesReader;
 use crate::ActorError;
 
 /// Serializes a structure as a CBOR vector of bytes, returning a serialization error on failure.
@@ -28,7 +33,459 @@ pub fn deserialize<O: de::DeserializeOwned>(v: &RawBytes, desc: &str) -> Result<
         .map_err(|e| ActorError::serialization(format!("failed to deserialize {desc}: {e}")))
 }
 
-/// Deserialises CBOR-encoded bytes as a method parameters object.
-pub fn deserialize_params<O: de::DeserializeOwned>(params: &RawBytes) -> Result<O, ActorError> {
+/// Deserialises CBOR-encoded bytes read from a `BytesReader` as a structure, e.g. straight
+/// from a `BlockReader`, returning a serialization error on failure.
+/// Like `deserialize_params`, the bytes are first checked against the limits of the `Policy`,
+/// reading only the headers of their items.
+/// `desc` is a noun phrase for the object being deserialized, included in any error message.
+pub fn deserialize_reader<O, R>(bytes: &R, desc: &str, policy: &Policy) -> Result<O, ActorError>
+where
+    O: de::DeserializeOwned,
+    R: BytesReader + ?Sized,
+{
+    check_limits(
+        bytes,
+        policy.max_params_depth,
+        policy.max_params_collection_len,
+    )
+    .map_err(|e| e.wrap(format!("failed to deserialize {desc}")))?;
+    fvm_ipld_encoding::from_reader(io::BufReader::new(bytes.reader()))
+        .map_err(|e| ActorError::serialization(format!("failed to deserialize {desc}: {e}")))
+}
+
+/// Deserialises CBOR-encoded bytes as a method parameters object, after checking that
+/// they don't exceed the nesting and collection size limits of the `Policy`,
+/// which should be that of the runtime, i.e. `rt.policy()`.
+pub fn deserialize_params<O: de::DeserializeOwned>(
+    params: &RawBytes,
+    policy: &Policy,
+) -> Result<O, ActorError> {
+    check_limits(
+        params.bytes(),
+        policy.max_params_depth,
+        policy.max_params_collection_len,
+    )
+    .map_err(|e| e.wrap("failed to deserialize method parameters"))?;
     deserialize(params, "method parameters")
 }
+
+/// Conversions between values and CBOR encoded `IpldBlock`s.
+pub trait CborBlock: Sized {
+    /// Encodes a value as a CBOR block, returning a serialization error on failure.
+    /// Same as `IpldBlock::serialize_cbor`, the links in it aren't reachable by the FVM.
+    fn from_cbor<T: ser::Serialize + ?Sized>(value: &T) -> Result<Self, ActorError>;
+
+    /// Decodes the block, returning a serialization error if it isn't CBOR or DAG-CBOR,
+    /// or can't be decoded as `T`.
+    fn decode_cbor<'de, T: de::Deserialize<'de>>(&'de self) -> Result<T, ActorError>;
+}
+
+impl CborBlock for IpldBlock {
+    fn from_cbor<T: ser::Serialize + ?Sized>(value: &T) -> Result<Self, ActorError> {
+        Ok(IpldBlock {
+            codec: CBOR,
+            data: serialize_vec(value, "block")?,
+        })
+    }
+
+    fn decode_cbor<'de, T: de::Deserialize<'de>>(&'de self) -> Result<T, ActorError> {
+        if self.codec != DAG_CBOR && self.codec != CBOR {
+            return Err(ActorError::serialization(format!(
+                "expected a CBOR block, got codec {:#x}",
+                self.codec
+            )));
+        }
+        self.deserialize().map_err(|e| {
+            ActorError::serialization(format!("failed to decode block: {e}")).with_source(e)
+        })
+    }
+}
+
+/// Decodes a block that must be present, e.g. the parameters of a method that takes some.
+pub fn decode_block<'de, T: de::Deserialize<'de>>(
+    block: &'de Option<IpldBlock>,
+) -> Result<T, ActorError> {
+    block
+        .as_ref()
+        .ok_or_else(|| ActorError::illegal_argument("expected a block, got none".into()))?
+        .decode_cbor()
+}
+
+/// Decodes a block that may be absent, e.g. the return value of a method.
+pub fn decode_optional_block<'de, T: de::Deserialize<'de>>(
+    block: &'de Option<IpldBlock>,
+) -> Result<Option<T>, ActorError> {
+    block.as_ref().map(|b| b.decode_cbor()).transpose()
+}
+
+/// Converts CBOR encoded bytes to a block, where empty bytes mean no block.
+pub fn block_from_raw_bytes(bytes: RawBytes) -> Option<IpldBlock> {
+    if bytes.is_empty() {
+        None
+    } else {
+        Some(IpldBlock {
+            codec: CBOR,
+            data: bytes.into(),
+        })
+    }
+}
+
+/// The parameters of a send as they reach the receiver: a block without data, e.g. from
+/// converting empty `RawBytes` by hand, is sent as no block at all, as the FVM would reject
+/// it as CBOR anyway. Both `FvmRuntime` and `MockRuntime` apply it, so that actors behave the
+/// same under test and on chain.
+pub fn normalize_params(params: Option<IpldBlock>) -> Option<IpldBlock> {
+    params.filter(|p| !p.data.is_empty())
+}
+
+/// Converts a CBOR block to bytes, where no block means empty bytes.
+pub fn block_to_raw_bytes(block: Option<IpldBlock>) -> Result<RawBytes, ActorError> {
+    match block {
+        None => Ok(RawBytes::default()),
+        Some(b) if b.codec == DAG_CBOR || b.codec == CBOR => Ok(RawBytes::new(b.data)),
+        Some(b) => Err(ActorError::serialization(format!(
+            "expected a CBOR block, got codec {:#x}",
+            b.codec
+        ))),
+    }
+}
+
+/// Scans CBOR-encoded bytes without decoding them, failing with a serialization error
+/// if arrays and maps are nested deeper than `max_depth`, or any of them has more
+/// than `max_collection_len` elements.
+///
+/// The scan is iterative, so unlike decoding it can't exhaust the stack on hostile input.
+/// Indefinite length items, which are not allowed in DAG-CBOR, are rejected.
+pub fn check_limits<R: BytesReader + ?Sized>(
+    bytes: &R,
+    max_depth: u32,
+    max_collection_len: u64,
+) -> Result<(), ActorError> {
+    let mut pos = 0usize;
+    // Number of items still expected in each open array or map, outermost first.
+    // The first entry stands for the single top level item.
+    let mut pending: Vec<u64> = vec![1];
+
+    while let Some(remaining) = pending.last_mut() {
+        if *remaining == 0 {
+            pending.pop();
+            continue;
+        }
+        *remaining -= 1;
+
+        let (major, arg) = read_header(bytes, &mut pos)?;
+        match major {
+            // Byte and text strings: skip the content.
+            2 | 3 => {
+                pos = usize::try_from(arg)
+                    .ok()
+                    .and_then(|len| pos.checked_add(len))
+                    .filter(|end| *end <= bytes.size() as usize)
+                    .ok_or_else(|| ActorError::serialization("unexpected end of input".into()))?;
+            }
+            // Arrays and maps.
+            4 | 5 => {
+                if arg > max_collection_len {
+                    return Err(ActorError::serialization(format!(
+                        "collection of {arg} elements exceeds the limit of {max_collection_len}"
+                    )));
+                }
+                if pending.len() > max_depth as usize {
+                    return Err(ActorError::serialization(format!(
+                        "nesting exceeds the limit of {max_depth}"
+                    )));
+                }
+                // A map has a key and a value per element.
+                let items = if major == 5 {
+                    arg.checked_mul(2)
+                } else {
+                    Some(arg)
+                };
+                pending.push(items.ok_or_else(|| {
+                    ActorError::serialization(format!("map of {arg} elements is too large"))
+                })?);
+            }
+            // A tag applies to the item following it.
+            6 => *remaining += 1,
+            // Integers, simple values and floats have no content beyond the header.
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Checks that CBOR-encoded bytes are in the canonical form produced by our encoder, so that
+/// the same logical value always has the same bytes, and thus the same CID.
+/// Fails with a serialization error on:
+/// - indefinite length items,
+/// - integers and lengths not encoded in the fewest bytes possible,
+/// - map keys that are duplicated or not sorted by their encoded bytes, as in RFC 7049,
+/// - trailing bytes after the top level item.
+pub fn check_canonical(bytes: &[u8]) -> Result<(), ActorError> {
+    struct Frame {
+        // Items left to read.
+        remaining: u64,
+        // For maps, the position the current key started at, and the span of the previous one.
+        map: Option<(usize, Option<(usize, usize)>)>,
+    }
+
+    let mut pos = 0usize;
+    let mut stack = vec![Frame {
+        remaining: 1,
+        map: None,
+    }];
+
+    while let Some(frame) = stack.last_mut() {
+        if let Some((key_start, prev_key)) = &mut frame.map {
+            if frame.remaining % 2 == 1 {
+                // A key was just read, the value is next.
+                let key = &bytes[*key_start..pos];
+                if let Some((start, end)) = prev_key {
+                    if key <= &bytes[*start..*end] {
+                        return Err(ActorError::serialization(
+                            "map keys are duplicated or not in canonical order".into(),
+                        ));
+                    }
+                }
+                *prev_key = Some((*key_start, pos));
+            } else {
+                *key_start = pos;
+            }
+        }
+        if frame.remaining == 0 {
+            stack.pop();
+            continue;
+        }
+        frame.remaining -= 1;
+
+        let start = pos;
+        let (major, arg) = read_header(bytes, &mut pos)?;
+        let size = pos - start - 1;
+        let minimal = match size {
+            0 => true,
+            1 => arg >= 24,
+            2 => arg > u8::MAX as u64,
+            4 => arg > u16::MAX as u64,
+            _ => arg > u32::MAX as u64,
+        };
+        // Floats use the additional information for their precision, not for a length.
+        if major != 7 && !minimal {
+            return Err(ActorError::serialization(format!(
+                "non-minimal encoding of {arg} at offset {start}"
+            )));
+        }
+        match major {
+            2 | 3 => {
+                pos = usize::try_from(arg)
+                    .ok()
+                    .and_then(|len| pos.checked_add(len))
+                    .filter(|end| *end <= bytes.len())
+                    .ok_or_else(|| ActorError::serialization("unexpected end of input".into()))?;
+            }
+            4 => stack.push(Frame {
+                remaining: arg,
+                map: None,
+            }),
+            5 => stack.push(Frame {
+                remaining: arg.checked_mul(2).ok_or_else(|| {
+                    ActorError::serialization(format!("map of {arg} entries is too large"))
+                })?,
+                map: Some((pos, None)),
+            }),
+            6 => frame.remaining += 1,
+            _ => {}
+        }
+    }
+
+    if pos != bytes.len() {
+        return Err(ActorError::serialization(format!(
+            "{} trailing bytes after the top level item",
+            bytes.len() - pos
+        )));
+    }
+    Ok(())
+}
+
+/// Re-encodes CBOR bytes as `T`, giving their canonical form, for input that
+/// may come from encoders other than ours rather than being rejected.
+pub fn canonical_cbor<T>(bytes: &[u8]) -> Result<Vec<u8>, ActorError>
+where
+    T: de::DeserializeOwned + ser::Serialize,
+{
+    let value: T = fvm_ipld_encoding::from_slice(bytes)
+        .map_err(|e| ActorError::serialization(format!("failed to decode CBOR: {e}")))?;
+    serialize_vec(&value, "canonical CBOR")
+}
+
+/// Reads the header of a CBOR item, returning its major type and argument.
+fn read_header<R: BytesReader + ?Sized>(
+    bytes: &R,
+    pos: &mut usize,
+) -> Result<(u8, u64), ActorError> {
+    let eof = || ActorError::serialization("unexpected end of input".into());
+    let read = |pos: usize, buf: &mut [u8]| -> Result<(), ActorError> {
+        let offset = u32::try_from(pos).map_err(|_| eof())?;
+        if bytes.read_at(offset, buf)? < buf.len() {
+            return Err(eof());
+        }
+        Ok(())
+    };
+
+    let mut initial = [0u8];
+    read(*pos, &mut initial)?;
+    let initial = initial[0];
+    *pos += 1;
+
+    let major = initial >> 5;
+    let size = match initial & 0x1f {
+        info @ 0..=23 => return Ok((major, info as u64)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        info => {
+            return Err(ActorError::serialization(format!(
+                "unsupported additional information {info} for major type {major}"
+            )))
+        }
+    };
+
+    let mut buf = [0u8; 8];
+    let arg = &mut buf[..size];
+    read(*pos, arg)?;
+    *pos += size;
+    Ok((
+        major,
+        arg.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use fvm_ipld_encoding::{to_vec, RawBytes};
+    use fvm_shared::error::ExitCode;
+
+    use super::*;
+
+    #[test]
+    fn accepts_params_within_limits() {
+        let params = RawBytes::new(to_vec(&(vec![1u64, 2, 3], "foo", vec![vec![0u8; 4]])).unwrap());
+        let (nums, name, nested): (Vec<u64>, String, Vec<Vec<u8>>) =
+            deserialize_params(&params, &Policy::default()).unwrap();
+        assert_eq!(nums, vec![1, 2, 3]);
+        assert_eq!(name, "foo");
+        assert_eq!(nested, vec![vec![0u8; 4]]);
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        // 1000 nested single element arrays, without ever decoding them.
+        let mut bytes = vec![0x81; 1000];
+        bytes.push(0x00);
+        let err = check_limits(&bytes[..], MAX_DEPTH, 10).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+
+        let err =
+            deserialize_params::<Vec<u64>>(&RawBytes::new(bytes), &Policy::default()).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+    }
+
+    #[test]
+    fn rejects_large_collections() {
+        let policy = Policy {
+            max_params_collection_len: 2,
+            ..Default::default()
+        };
+        let params = RawBytes::new(to_vec(&vec![1u64, 2, 3]).unwrap());
+        let err = deserialize_params::<Vec<u64>>(&params, &policy).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+
+        // A map header claiming more entries than there are items to count.
+        let map = [0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        let err = check_limits(&map[..], MAX_DEPTH, u64::MAX).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        // A byte string claiming 10 bytes of content, with 2 present.
+        assert!(check_limits(&[0x4a, 0x00, 0x00][..], MAX_DEPTH, 10).is_err());
+        // An indefinite length array.
+        assert!(check_limits(&[0x9f, 0xff][..], MAX_DEPTH, 10).is_err());
+    }
+
+    #[test]
+    fn block_codec_is_checked() {
+        let block = IpldBlock::from_cbor(&(1u64, "foo")).unwrap();
+        assert_eq!(block.codec, CBOR);
+        let decoded: (u64, String) = block.decode_cbor().unwrap();
+        assert_eq!(decoded, (1, "foo".to_string()));
+
+        let raw = IpldBlock {
+            codec: fvm_ipld_encoding::IPLD_RAW,
+            data: block.data.clone(),
+        };
+        let err = raw.decode_cbor::<(u64, String)>().unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+        assert!(block_to_raw_bytes(Some(raw)).is_err());
+
+        let err = decode_block::<u64>(&None).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert_eq!(decode_optional_block::<u64>(&None).unwrap(), None);
+
+        let bytes = block_to_raw_bytes(Some(block)).unwrap();
+        assert_eq!(
+            block_from_raw_bytes(bytes.clone()).unwrap().data,
+            bytes.to_vec()
+        );
+        assert_eq!(block_from_raw_bytes(RawBytes::default()), None);
+    }
+
+    #[test]
+    fn deserialize_from_reader() {
+        let block = IpldBlock::from_cbor(&(RawBytes::new(vec![7u8; 1000]), "foo")).unwrap();
+        let policy = Policy::default();
+        let (bytes, name): (RawBytes, String) =
+            deserialize_reader(&block, "payload", &policy).unwrap();
+        assert_eq!(bytes.to_vec(), vec![7u8; 1000]);
+        assert_eq!(name, "foo");
+
+        let err =
+            deserialize_reader::<(RawBytes, String), _>(&block.data[..10], "payload", &policy)
+                .unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+
+        // Nested beyond the limits, which decoding would find out only after recursing.
+        let mut nested = vec![0x81; 1000];
+        nested.push(0x00);
+        let err = deserialize_reader::<Vec<u64>, _>(&nested[..], "payload", &policy).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::USR_SERIALIZATION);
+    }
+
+    #[test]
+    fn canonical_encoding() {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("bb".to_string(), 1u64);
+        map.insert("a".to_string(), 300u64);
+        map.insert("c".to_string(), 2u64);
+        let bytes = to_vec(&(vec![map], "foo", 1_000_000u64)).unwrap();
+        check_canonical(&bytes).unwrap();
+
+        // 1 encoded with a one byte argument.
+        assert!(check_canonical(&[0x18, 0x01]).is_err());
+        // An indefinite length array.
+        assert!(check_canonical(&[0x9f, 0xff]).is_err());
+        // {"b": 1, "a": 2}, out of order.
+        let unsorted = [0xa2, 0x61, b'b', 0x01, 0x61, b'a', 0x02];
+        assert!(check_canonical(&unsorted).is_err());
+        // {"a": 1, "a": 2}, duplicated.
+        assert!(check_canonical(&[0xa2, 0x61, b'a', 0x01, 0x61, b'a', 0x02]).is_err());
+        // Trailing bytes.
+        assert!(check_canonical(&[0x01, 0x02]).is_err());
+
+        let fixed = canonical_cbor::<std::collections::BTreeMap<String, u64>>(&unsorted).unwrap();
+        check_canonical(&fixed).unwrap();
+    }
+
+    const MAX_DEPTH: u32 = 32;
+}
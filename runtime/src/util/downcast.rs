@@ -25,6 +25,7 @@ impl ActorDowncast for anyhow::Error {
             Ok(actor_error) => actor_error.wrap(msg),
             Err(other) => {
                 ActorError::unchecked(default_exit_code, format!("{}: {}", msg.as_ref(), other))
+                    .with_source(other)
             }
         }
     }
@@ -42,6 +43,7 @@ impl ActorDowncast for AmtError {
             AmtError::Dynamic(e) => e.downcast_default(default_exit_code, msg),
             other => {
                 ActorError::unchecked(default_exit_code, format!("{}: {}", msg.as_ref(), other))
+                    .with_source(other)
             }
         }
     }
@@ -59,6 +61,7 @@ impl ActorDowncast for HamtError {
             HamtError::Dynamic(e) => e.downcast_default(default_exit_code, msg),
             other => {
                 ActorError::unchecked(default_exit_code, format!("{}: {}", msg.as_ref(), other))
+                    .with_source(other)
             }
         }
     }
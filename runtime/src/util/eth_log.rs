@@ -0,0 +1,308 @@
+use cid::multihash::{Code, MultihashDigest};
+use fvm_ipld_encoding::IPLD_RAW;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::Sign;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::event::{ActorEvent, Entry, Flags};
+
+use crate::{actor_error, delegated_subaddress, ActorError, EAM_ACTOR_ID};
+
+/// The most topics a log can have, the signature included, as with the EVM's `LOG4`.
+const MAX_TOPICS: usize = 4;
+
+/// The Keccak-256 digest of the data, as used for event signatures and indexed dynamic values.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let digest = Code::Keccak256.digest(data);
+    digest
+        .digest()
+        .try_into()
+        .expect("keccak-256 digests are 32 bytes")
+}
+
+/// A value of an event parameter, encoded per the Solidity ABI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AbiValue {
+    /// A static value, e.g. `uint256`, `address`, `bool` or `bytes32`, as one word.
+    Word([u8; 32]),
+    /// A dynamic value, i.e. `bytes` or `string`, as its unpadded bytes.
+    Dynamic(Vec<u8>),
+}
+
+impl AbiValue {
+    /// The topic of the value as an indexed parameter: the word itself, or the digest of
+    /// a dynamic value, which can then only be searched for, not recovered.
+    fn topic(&self) -> [u8; 32] {
+        match self {
+            AbiValue::Word(word) => *word,
+            AbiValue::Dynamic(bytes) => keccak256(bytes),
+        }
+    }
+}
+
+/// Types which can be event parameters in an Ethereum log.
+pub trait AbiEncode {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError>;
+}
+
+impl AbiEncode for bool {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        (*self as u64).abi_encode()
+    }
+}
+
+impl AbiEncode for u64 {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        Ok(AbiValue::Word(left_pad(&self.to_be_bytes())))
+    }
+}
+
+impl AbiEncode for u128 {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        Ok(AbiValue::Word(left_pad(&self.to_be_bytes())))
+    }
+}
+
+/// As a `bytes32`.
+impl AbiEncode for [u8; 32] {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        Ok(AbiValue::Word(*self))
+    }
+}
+
+/// As a `uint256` of atto, failing for negative amounts.
+impl AbiEncode for TokenAmount {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        let (sign, bytes) = self.atto().to_bytes_be();
+        if sign == Sign::Minus || bytes.len() > 32 {
+            return Err(actor_error!(illegal_argument; "amount {} is not a uint256", self));
+        }
+        Ok(AbiValue::Word(left_pad(&bytes)))
+    }
+}
+
+/// As an `address`: the masked ID address `0xff00..00<id>` of an ID address, or the
+/// Ethereum address of an `f410` address, failing for addresses with no Ethereum equivalent.
+impl AbiEncode for Address {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        let mut eth = [0u8; 20];
+        if let Ok(id) = self.id() {
+            eth[0] = 0xff;
+            eth[12..].copy_from_slice(&id.to_be_bytes());
+        } else {
+            match delegated_subaddress(self, EAM_ACTOR_ID) {
+                Some(subaddress) if subaddress.len() == 20 => eth.copy_from_slice(subaddress),
+                _ => {
+                    return Err(actor_error!(illegal_argument;
+                        "address {} has no Ethereum equivalent", self));
+                }
+            }
+        }
+        Ok(AbiValue::Word(left_pad(&eth)))
+    }
+}
+
+/// As a `string`.
+impl AbiEncode for str {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        Ok(AbiValue::Dynamic(self.as_bytes().to_vec()))
+    }
+}
+
+/// As a `string`.
+impl AbiEncode for String {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        self.as_str().abi_encode()
+    }
+}
+
+/// As `bytes`.
+impl AbiEncode for [u8] {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        Ok(AbiValue::Dynamic(self.to_vec()))
+    }
+}
+
+/// As `bytes`.
+impl AbiEncode for Vec<u8> {
+    fn abi_encode(&self) -> Result<AbiValue, ActorError> {
+        self.as_slice().abi_encode()
+    }
+}
+
+/// Helper to build an event in the shape of an Ethereum log, next to or instead of one built
+/// by `EventBuilder`, so that Ethereum tooling reading logs through the FEVM can index it.
+///
+/// Like the logs of the EVM actor, its topics are raw entries `t1` to `t4`, the first of
+/// which is the Keccak-256 digest of the event signature, and its non-indexed parameters
+/// are ABI encoded into a raw entry `d`.
+///
+/// # Example
+/// ```
+/// use fil_actors_runtime::{keccak256, EthLogBuilder};
+/// use fvm_shared::address::Address;
+/// use fvm_shared::econ::TokenAmount;
+///
+/// let event = EthLogBuilder::new("Transfer(address,address,uint256)")
+///     .indexed(&Address::new_id(100))
+///     .unwrap()
+///     .indexed(&Address::new_id(101))
+///     .unwrap()
+///     .data(&TokenAmount::from_atto(5))
+///     .unwrap()
+///     .build();
+///
+/// assert_eq!(event.entries[0].key, "t1");
+/// assert_eq!(event.entries[0].value, keccak256(b"Transfer(address,address,uint256)"));
+/// assert_eq!(event.entries[3].key, "d");
+/// ```
+pub struct EthLogBuilder {
+    topics: Vec<[u8; 32]>,
+    data: Vec<AbiValue>,
+}
+
+impl EthLogBuilder {
+    /// Starts a log of the event with this signature, e.g. `Transfer(address,address,uint256)`.
+    pub fn new(signature: &str) -> Self {
+        Self {
+            topics: vec![keccak256(signature.as_bytes())],
+            data: Vec::new(),
+        }
+    }
+
+    /// Adds an indexed parameter as the next topic, of which there can be three.
+    pub fn indexed<T: AbiEncode + ?Sized>(mut self, value: &T) -> Result<Self, ActorError> {
+        if self.topics.len() == MAX_TOPICS {
+            return Err(actor_error!(illegal_argument;
+                "a log can have at most {} indexed parameters", MAX_TOPICS - 1));
+        }
+        self.topics.push(value.abi_encode()?.topic());
+        Ok(self)
+    }
+
+    /// Adds a non-indexed parameter to the data.
+    pub fn data<T: AbiEncode + ?Sized>(mut self, value: &T) -> Result<Self, ActorError> {
+        self.data.push(value.abi_encode()?);
+        Ok(self)
+    }
+
+    /// Returns the built event.
+    pub fn build(self) -> ActorEvent {
+        let mut entries: Vec<Entry> = self
+            .topics
+            .iter()
+            .enumerate()
+            .map(|(i, topic)| raw_entry(format!("t{}", i + 1), topic.to_vec()))
+            .collect();
+        if !self.data.is_empty() {
+            entries.push(raw_entry("d".to_string(), encode_data(&self.data)));
+        }
+        entries.into()
+    }
+}
+
+/// The ABI encoding of a tuple of the values: a word per value, which for dynamic values is
+/// the offset of their length and padded bytes after the last word.
+fn encode_data(values: &[AbiValue]) -> Vec<u8> {
+    let mut head = Vec::new();
+    let mut tail = Vec::new();
+    for value in values {
+        match value {
+            AbiValue::Word(word) => head.extend_from_slice(word),
+            AbiValue::Dynamic(bytes) => {
+                let offset = (values.len() * 32 + tail.len()) as u64;
+                head.extend_from_slice(&left_pad(&offset.to_be_bytes()));
+                tail.extend_from_slice(&left_pad(&(bytes.len() as u64).to_be_bytes()));
+                tail.extend_from_slice(bytes);
+                tail.resize(tail.len() + (32 - bytes.len() % 32) % 32, 0);
+            }
+        }
+    }
+    head.extend(tail);
+    head
+}
+
+fn left_pad(bytes: &[u8]) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(bytes);
+    word
+}
+
+fn raw_entry(key: String, value: Vec<u8>) -> Entry {
+    Entry {
+        flags: Flags::FLAG_INDEXED_ALL,
+        key,
+        codec: IPLD_RAW,
+        value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::address::Address;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{keccak256, EthLogBuilder};
+    use crate::EAM_ACTOR_ID;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn encodes_like_solidity() {
+        assert_eq!(
+            hex(&keccak256(b"Transfer(address,address,uint256)")),
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+
+        let eth = [0x11u8; 20];
+        let event = EthLogBuilder::new("Noted(address,string,uint64)")
+            .indexed(&Address::new_delegated(EAM_ACTOR_ID, &eth).unwrap())
+            .unwrap()
+            .indexed("topic")
+            .unwrap()
+            .data("hello")
+            .unwrap()
+            .data(&7u64)
+            .unwrap()
+            .build();
+
+        let keys: Vec<&str> = event.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["t1", "t2", "t3", "d"]);
+        assert_eq!(hex(&event.entries[1].value), format!("{:0>64}", hex(&eth)));
+        assert_eq!(event.entries[2].value, keccak256(b"topic"));
+        assert_eq!(
+            hex(&event.entries[3].value),
+            [
+                format!("{:0>64}", "40"),
+                format!("{:0>64}", "7"),
+                format!("{:0>64}", "5"),
+                format!("{:0<64}", hex(b"hello")),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn rejects_what_solidity_cannot_express() {
+        let secp = Address::new_secp256k1(&[1u8; 65]).unwrap();
+        let err = EthLogBuilder::new("E(address)")
+            .indexed(&secp)
+            .err()
+            .unwrap();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let err = EthLogBuilder::new("E(uint256)")
+            .data(&TokenAmount::from_atto(-1))
+            .err()
+            .unwrap();
+        assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let mut log = EthLogBuilder::new("E(uint64,uint64,uint64)");
+        for i in 0..3u64 {
+            log = log.indexed(&i).unwrap();
+        }
+        assert!(log.indexed(&3u64).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use fvm_ipld_encoding::to_vec;
+use fvm_shared::event::{ActorEvent, Entry, Flags};
+use serde::Serialize;
+
+use crate::ActorError;
+
+/// Codec of event values, which per FIP-0049 must be `IPLD_RAW`.
+/// Values are CBOR encoded before being stored as raw bytes.
+const IPLD_RAW: u64 = 0x55;
+
+/// Key of the entry conventionally carrying the type of an event.
+pub const EVENT_TYPE_KEY: &str = "$type";
+
+/// Helper to build an `ActorEvent` entry by entry.
+///
+/// # Example
+/// ```
+/// use fil_actors_runtime::EventBuilder;
+///
+/// let event = EventBuilder::new()
+///     .typ("config-updated")
+///     .field_indexed("version", &1u64)
+///     .unwrap()
+///     .build();
+/// assert_eq!(event.entries.len(), 2);
+/// ```
+#[derive(Default)]
+pub struct EventBuilder {
+    entries: Vec<Entry>,
+}
+
+impl EventBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the type of the event, as an indexed entry under `$type`.
+    pub fn typ(mut self, typ: &str) -> Self {
+        self.push(
+            EVENT_TYPE_KEY,
+            to_vec(typ).unwrap(),
+            Flags::FLAG_INDEXED_ALL,
+        );
+        self
+    }
+
+    /// Adds a field that is indexed by both key and value.
+    pub fn field_indexed<T: Serialize + ?Sized>(
+        self,
+        name: &str,
+        value: &T,
+    ) -> Result<Self, ActorError> {
+        self.field_with_flags(name, value, Flags::FLAG_INDEXED_ALL)
+    }
+
+    /// Adds a field that is not indexed.
+    pub fn field<T: Serialize + ?Sized>(self, name: &str, value: &T) -> Result<Self, ActorError> {
+        self.field_with_flags(name, value, Flags::empty())
+    }
+
+    /// Returns the built event.
+    pub fn build(self) -> ActorEvent {
+        self.entries.into()
+    }
+
+    fn field_with_flags<T: Serialize + ?Sized>(
+        mut self,
+        name: &str,
+        value: &T,
+        flags: Flags,
+    ) -> Result<Self, ActorError> {
+        let value = to_vec(value).map_err(|e| {
+            ActorError::serialization(format!("failed to serialize event field {name}: {e}"))
+        })?;
+        self.push(name, value, flags);
+        Ok(self)
+    }
+
+    fn push(&mut self, key: &str, value: Vec<u8>, flags: Flags) {
+        self.entries.push(Entry {
+            flags,
+            key: key.to_string(),
+            codec: IPLD_RAW,
+            value,
+        });
+    }
+}
+
+/// The shape of an event a method may emit: its `$type`, and the keys and value types of its
+/// other entries in order, so that tests can check emitted events against what the indexer
+/// expects.
+///
+/// # Example
+/// ```
+/// use fil_actors_runtime::{EventBuilder, EventSchema, EventValueType};
+///
+/// let schema = EventSchema {
+///     typ: "config-updated",
+///     fields: &[("version", EventValueType::Uint)],
+/// };
+/// let event = EventBuilder::new()
+///     .typ("config-updated")
+///     .field_indexed("version", &1u64)
+///     .unwrap()
+///     .build();
+/// assert!(schema.matches(&event));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventSchema {
+    pub typ: &'static str,
+    pub fields: &'static [(&'static str, EventValueType)],
+}
+
+impl EventSchema {
+    /// Whether the event has this type and exactly these fields, with values of these types.
+    pub fn matches(&self, event: &ActorEvent) -> bool {
+        let typ = to_vec(self.typ).unwrap();
+        let mut has_type = false;
+        let mut fields = Vec::new();
+        for entry in &event.entries {
+            if entry.key == EVENT_TYPE_KEY {
+                has_type = entry.value == typ;
+            } else {
+                fields.push(entry);
+            }
+        }
+        has_type
+            && fields.len() == self.fields.len()
+            && fields
+                .iter()
+                .zip(self.fields)
+                .all(|(entry, (key, typ))| entry.key == *key && typ.matches(&entry.value))
+    }
+}
+
+/// The type of the CBOR encoded value of an event entry, as told by its major type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventValueType {
+    /// An unsigned integer.
+    Uint,
+    /// An integer of either sign.
+    Int,
+    /// A byte string, e.g. an `Address` or a `TokenAmount`.
+    Bytes,
+    /// A text string.
+    String,
+    Bool,
+    Array,
+    Map,
+    /// A CID, i.e. a byte string under tag 42.
+    Cid,
+}
+
+impl EventValueType {
+    fn matches(&self, value: &[u8]) -> bool {
+        let Some(head) = value.first() else {
+            return false;
+        };
+        let major = head >> 5;
+        match self {
+            EventValueType::Uint => major == 0,
+            EventValueType::Int => major <= 1,
+            EventValueType::Bytes => major == 2,
+            EventValueType::String => major == 3,
+            EventValueType::Bool => *head == 0xf4 || *head == 0xf5,
+            EventValueType::Array => major == 4,
+            EventValueType::Map => major == 5,
+            EventValueType::Cid => value.starts_with(&[0xd8, 42]),
+        }
+    }
+}
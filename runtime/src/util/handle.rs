@@ -0,0 +1,118 @@
+use std::marker::PhantomData;
+
+use fvm_ipld_encoding::ipld_block::IpldBlock;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::MethodNum;
+use num_traits::ToPrimitive;
+
+use crate::runtime::{ActorCode, Runtime};
+use crate::ActorError;
+
+/// The address of an actor along with the actor code it is known to run, so that only the
+/// methods of that actor, its `ActorCode::Methods`, can be sent to it.
+///
+/// Nothing on chain ties an address to an actor, so a handle is only obtained by
+/// calling `assume_interface`, which is where the assumption is made explicit.
+/// It serializes as the bare address, and can be kept in the state.
+///
+/// # Example
+/// ```ignore
+/// let gateway: ActorHandle<gateway::Actor> = ActorHandle::assume_interface(st.gateway);
+/// gateway.send(rt, gateway::Method::Commit, params, TokenAmount::zero())?;
+/// ```
+pub struct ActorHandle<A> {
+    address: Address,
+    _actor: PhantomData<A>,
+}
+
+impl<A: ActorCode> ActorHandle<A>
+where
+    A::Methods: ToPrimitive,
+{
+    /// Assume the actor at `address` runs the code of `A`, without checking it.
+    pub fn assume_interface(address: Address) -> Self {
+        Self {
+            address,
+            _actor: PhantomData,
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// The number of a method of `A`.
+    pub fn method_num(method: A::Methods) -> MethodNum {
+        method
+            .to_u64()
+            .expect("method numbers are representable as u64")
+    }
+
+    /// Send a message to a method of the actor.
+    pub fn send<RT: Runtime>(
+        &self,
+        rt: &RT,
+        method: A::Methods,
+        params: Option<IpldBlock>,
+        value: TokenAmount,
+    ) -> Result<Option<IpldBlock>, ActorError> {
+        rt.send(&self.address, Self::method_num(method), params, value)
+    }
+}
+
+// Serializes exactly as its address, and deserializes as one, the assumption having been
+// made when it was stored.
+crate::phantom_wrapper!(ActorHandle<A> { address: Address, _actor });
+
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_blockstore::Blockstore;
+    use fvm_ipld_encoding::ipld_block::IpldBlock;
+    use fvm_ipld_encoding::{from_slice, to_vec};
+    use fvm_shared::address::Address;
+    use fvm_shared::MethodNum;
+    use num_derive::ToPrimitive;
+
+    use super::ActorHandle;
+    use crate::runtime::{ActorCode, Runtime};
+    use crate::ActorError;
+
+    #[derive(ToPrimitive)]
+    #[repr(u64)]
+    enum Method {
+        Commit = 7,
+    }
+
+    struct Gateway;
+
+    impl ActorCode for Gateway {
+        type Methods = Method;
+
+        fn invoke_method<RT>(
+            _: &mut RT,
+            _: MethodNum,
+            _: Option<IpldBlock>,
+        ) -> Result<Option<IpldBlock>, ActorError>
+        where
+            RT: Runtime,
+            RT::Blockstore: Blockstore + Clone,
+        {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn handle_methods_and_encoding() {
+        assert_eq!(ActorHandle::<Gateway>::method_num(Method::Commit), 7);
+
+        let handle: ActorHandle<Gateway> = ActorHandle::assume_interface(Address::new_id(100));
+        let bytes = to_vec(&handle).unwrap();
+        assert_eq!(bytes, to_vec(&Address::new_id(100)).unwrap());
+        assert_eq!(from_slice::<ActorHandle<Gateway>>(&bytes).unwrap(), handle);
+        assert_eq!(
+            format!("{:?}", handle.clone()),
+            format!("ActorHandle<{}>(f0100)", std::any::type_name::<Gateway>())
+        );
+    }
+}
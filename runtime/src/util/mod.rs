@@ -1,15 +1,30 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub use self::address::AddressExt;
+pub use self::batch_return::{BatchReturn, BatchReturnGen, FailCode};
+pub use self::bytes_reader::{BytesReader, Reader};
 pub use self::downcast::*;
+pub use self::eth_log::{keccak256, AbiEncode, AbiValue, EthLogBuilder};
+pub use self::events::*;
+pub use self::handle::ActorHandle;
 pub use self::message_accumulator::MessageAccumulator;
 pub use self::multimap::*;
 pub use self::set::Set;
 pub use self::set_multimap::SetMultimap;
+pub use self::token::{format_atto, format_fil, parse_fil};
 
+mod address;
+mod batch_return;
+mod bytes_reader;
 pub mod cbor;
 mod downcast;
+mod eth_log;
+mod events;
+mod handle;
 mod message_accumulator;
 mod multimap;
+mod phantom;
 mod set;
 mod set_multimap;
+mod token;
@@ -0,0 +1,69 @@
+/// Implement `Clone`, `PartialEq`, `Eq`, `Debug`, `Serialize` and `Deserialize` for a struct
+/// wrapping a single value along with a `PhantomData` marker of its type parameter, such as
+/// `ActorHandle`, without requiring anything of the marker type, as the derives would.
+///
+/// It compares and encodes exactly as the wrapped value, which is shown by `Debug` through its
+/// `Display`, after the name of the marker type.
+///
+/// ```ignore
+/// pub struct ActorHandle<A> {
+///     address: Address,
+///     _actor: PhantomData<A>,
+/// }
+///
+/// phantom_wrapper!(ActorHandle<A> { address: Address, _actor });
+/// ```
+#[macro_export]
+macro_rules! phantom_wrapper {
+    ($name:ident<$param:ident> { $field:ident: $ty:ty, $marker:ident }) => {
+        impl<$param> Clone for $name<$param> {
+            fn clone(&self) -> Self {
+                Self {
+                    $field: self.$field.clone(),
+                    $marker: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        impl<$param> PartialEq for $name<$param> {
+            fn eq(&self, other: &Self) -> bool {
+                self.$field == other.$field
+            }
+        }
+
+        impl<$param> Eq for $name<$param> {}
+
+        impl<$param> ::std::fmt::Debug for $name<$param> {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(
+                    f,
+                    "{}<{}>({})",
+                    stringify!($name),
+                    ::std::any::type_name::<$param>(),
+                    self.$field
+                )
+            }
+        }
+
+        impl<$param> $crate::serde::Serialize for $name<$param> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::serde::Serializer,
+            {
+                $crate::serde::Serialize::serialize(&self.$field, serializer)
+            }
+        }
+
+        impl<'de, $param> $crate::serde::Deserialize<'de> for $name<$param> {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::serde::Deserializer<'de>,
+            {
+                Ok(Self {
+                    $field: <$ty as $crate::serde::Deserialize>::deserialize(deserializer)?,
+                    $marker: ::std::marker::PhantomData,
+                })
+            }
+        }
+    };
+}
@@ -0,0 +1,86 @@
+use fvm_shared::bigint::BigInt;
+use fvm_shared::econ::TokenAmount;
+
+use crate::{actor_error, ActorError};
+
+/// Parse an amount of FIL written in decimal, e.g. `"1.5"`, `"-0.25"` or `"2 FIL"`.
+///
+/// At most 18 decimals are accepted, the precision of `TokenAmount`; the excess is an error
+/// rather than being rounded away.
+pub fn parse_fil(s: &str) -> Result<TokenAmount, ActorError> {
+    let invalid = || actor_error!(illegal_argument; "invalid FIL amount {:?}", s);
+
+    let trimmed = s.trim();
+    let number = trimmed.strip_suffix("FIL").unwrap_or(trimmed).trim_end();
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, number),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(invalid());
+    }
+    if digits.ends_with('.') {
+        return Err(invalid());
+    }
+    let decimals = TokenAmount::DECIMALS;
+    if fraction.len() > decimals {
+        return Err(actor_error!(illegal_argument;
+            "FIL amount {:?} has more than {} decimals", s, decimals));
+    }
+
+    let atto = format!("{whole}{fraction:0<decimals$}");
+    let atto: BigInt = atto.parse().map_err(|_| invalid())?;
+    Ok(TokenAmount::from_atto(if negative { -atto } else { atto }))
+}
+
+/// Format an amount in FIL with all its significant decimals, e.g. `1.5 FIL`.
+pub fn format_fil(amount: &TokenAmount) -> String {
+    format!("{amount} FIL")
+}
+
+/// Format an amount in attoFIL, e.g. `1500000000000000000 attoFIL`.
+pub fn format_atto(amount: &TokenAmount) -> String {
+    format!("{} attoFIL", amount.atto())
+}
+
+#[cfg(test)]
+mod tests {
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::ExitCode;
+
+    use super::{format_atto, format_fil, parse_fil};
+
+    #[test]
+    fn parse_and_format() {
+        let amount = parse_fil("1.5").unwrap();
+        assert_eq!(amount, TokenAmount::from_atto(1_500_000_000_000_000_000u64));
+        assert_eq!(format_fil(&amount), "1.5 FIL");
+        assert_eq!(format_atto(&amount), "1500000000000000000 attoFIL");
+        assert_eq!(parse_fil(&format_fil(&amount)).unwrap(), amount);
+
+        assert_eq!(parse_fil("2 FIL").unwrap(), TokenAmount::from_whole(2));
+        assert_eq!(
+            parse_fil("-0.000000000000000001").unwrap(),
+            TokenAmount::from_atto(-1)
+        );
+        assert_eq!(
+            format_fil(&TokenAmount::from_atto(-1)),
+            "-0.000000000000000001 FIL"
+        );
+
+        for invalid in [
+            "",
+            "1.",
+            ".5",
+            "1.2.3",
+            "1e18",
+            "--1",
+            "0.0000000000000000001",
+        ] {
+            let err = parse_fil(invalid).unwrap_err();
+            assert_eq!(err.exit_code(), ExitCode::USR_ILLEGAL_ARGUMENT, "{invalid}");
+        }
+    }
+}
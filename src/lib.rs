@@ -1,2 +1,4 @@
 pub use fil_actors_runtime as runtime;
 pub use primitives;
+
+pub mod prelude;
@@ -0,0 +1,39 @@
+//! The items most actors need, for a single import in their crates:
+//!
+//! ```
+//! use fvm_utils::prelude::*;
+//!
+//! #[derive(Serialize_tuple, Deserialize_tuple)]
+//! pub struct State {
+//!     pub owner: Address,
+//!     pub balances: TCid<THamt<Address, TokenAmount>>,
+//! }
+//! ```
+//!
+//! Everything is re-exported from the versions the runtime and the primitives are built with,
+//! so the encoding of an actor can't end up derived with one version of `fvm_ipld_encoding`
+//! and decoded by the runtime with another. That includes `serde_tuple`, which the tuple
+//! derives expand to paths of. The `serde` derives refer to their crate by name, so it
+//! still has to be a dependency of the actor crate.
+
+pub use cid::Cid;
+pub use fil_actors_runtime::num_traits::FromPrimitive;
+pub use fil_actors_runtime::runtime::{ActorCode, Primitives, Runtime};
+pub use fil_actors_runtime::{
+    actor_dispatch, actor_error, actor_methods, restrict_internal_api, ActorDowncast, ActorError,
+    AsActorError,
+};
+pub use fvm_ipld_blockstore::Blockstore;
+pub use fvm_ipld_encoding;
+pub use fvm_ipld_encoding::ipld_block::IpldBlock;
+pub use fvm_ipld_encoding::serde::{Deserialize, Serialize};
+pub use fvm_ipld_encoding::tuple::*;
+pub use fvm_ipld_encoding::{CborStore, RawBytes};
+pub use fvm_ipld_hamt::BytesKey;
+pub use fvm_shared::address::Address;
+pub use fvm_shared::clock::ChainEpoch;
+pub use fvm_shared::econ::TokenAmount;
+pub use fvm_shared::error::ExitCode;
+pub use fvm_shared::{ActorID, MethodNum, METHOD_CONSTRUCTOR};
+
+pub use primitives::{TAmt, TCid, THamt, TLink};